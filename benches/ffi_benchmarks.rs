@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use rust_go_ffi::{add_numbers, cleanup, initialize};
+use rust_go_ffi::{add_numbers, add_numbers_batch, cleanup, initialize};
 use semver::Version;
 use std::time::Duration;
 
@@ -29,6 +29,31 @@ fn bench_add_numbers(c: &mut Criterion) {
     teardown();
 }
 
+fn bench_add_numbers_batch(c: &mut Criterion) {
+    setup();
+
+    let mut group = c.benchmark_group("add_numbers_batch");
+    group.measurement_time(Duration::from_secs(10));
+
+    for size in [10, 1_000, 100_000].iter() {
+        let pairs: Vec<(i32, i32)> = (0..*size).map(|i| (i, i)).collect();
+        group.bench_with_input(BenchmarkId::new("batch", size), &pairs, |b, pairs| {
+            b.iter(|| add_numbers_batch(black_box(pairs)).unwrap());
+        });
+        group.bench_with_input(BenchmarkId::new("loop", size), &pairs, |b, pairs| {
+            b.iter(|| {
+                pairs
+                    .iter()
+                    .map(|&(a, b)| add_numbers(black_box(a), black_box(b)).unwrap())
+                    .collect::<Vec<_>>()
+            });
+        });
+    }
+
+    group.finish();
+    teardown();
+}
+
 fn bench_initialization(c: &mut Criterion) {
     let mut group = c.benchmark_group("initialization");
     group.measurement_time(Duration::from_secs(5));
@@ -49,6 +74,6 @@ criterion_group! {
         .with_plots() // Enable plot generation
         .sample_size(50)
         .measurement_time(Duration::from_secs(30));
-    targets = bench_add_numbers, bench_initialization
+    targets = bench_add_numbers, bench_add_numbers_batch, bench_initialization
 }
 criterion_main!(benches);
@@ -1,47 +1,1382 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::env;
-use std::path::PathBuf;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const LIBRARY_PATH: &str = "go_lib";
-const INPUT_HEADER: &str = "go_lib/go_lib.h";
+const STATIC_LIBRARY_PATH: &str = "go_lib/static";
+/// Optional build settings at the crate root
+const FFI_TOML: &str = "ffi.toml";
+const COMPAT_MATRIX: &str = "compat.toml";
+const SEH_GUARD_SOURCE: &str = "shim/seh_guard.c";
+/// Directory of `.proto` files for the protobuf call bridge, for both sides
+const PROTO_DIR_ENV: &str = "RUST_GO_FFI_PROTO_DIR";
+/// Package the generated Go protobuf types go into
+const PROTO_GO_PACKAGE: &str = "go_lib/pb";
+/// Path of the `go` binary to build with, instead of the one on `PATH`
+const GO_ENV: &str = "GO";
+const GO_DOWNLOAD_URL: &str = "https://go.dev/dl/";
+/// Exports the crate calls itself at fixed points of the library's
+/// lifecycle, which get no generated wrapper
+const LIFECYCLE_EXPORTS: &[&str] = &["GoInit", "GoShutdown", "TakeThreadGoError"];
+/// Environment variables that change what `go build` produces
+const GO_BUILD_ENV: &[&str] = &["CC", "CGO_CFLAGS", "CGO_LDFLAGS", "GOFLAGS"];
+/// Directory of a prebuilt Go library to link instead of building one
+const PREBUILT_DIR_ENV: &str = "RUST_GO_FFI_PREBUILT_DIR";
+/// Go build tags turned on by Cargo features, for Go code that pulls in
+/// third-party modules only those features need
+const FEATURE_TAGS: &[(&str, &str)] = &[
+    ("CARGO_FEATURE_BINARY_BRIDGE", "binary_bridge"),
+    ("CARGO_FEATURE_PROTO_BRIDGE", "proto_bridge"),
+];
+const LICENSE_FILES: &[&str] = &["LICENSE", "LICENSE.txt", "LICENSE.md", "COPYING", "LICENCE"];
+
+/// Build settings from `ffi.toml`; everything is optional
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FfiToml {
+    bindgen: BindgenToml,
+    go: GoToml,
+    output: OutputToml,
+}
+
+/// Which items of the header bindgen generates bindings for, as regexes,
+/// and what to call them
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct BindgenToml {
+    allowlist_functions: Vec<String>,
+    allowlist_types: Vec<String>,
+    allowlist_vars: Vec<String>,
+    blocklist_functions: Vec<String>,
+    blocklist_types: Vec<String>,
+    /// Name in the header to name in the bindings
+    rename: BTreeMap<String, String>,
+}
+
+/// Extra `go build` settings
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct GoToml {
+    tags: Vec<String>,
+    /// Appended to the linker flags that stamp the build info
+    ldflags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct OutputToml {
+    /// File name of the library without extension or `lib` prefix
+    name: String,
+}
+
+impl Default for OutputToml {
+    fn default() -> Self {
+        Self {
+            name: "go_lib".to_string(),
+        }
+    }
+}
+
+/// Renames items of the bindings as `[bindgen.rename]` says
+#[derive(Debug)]
+struct Renames(BTreeMap<String, String>);
+
+impl bindgen::callbacks::ParseCallbacks for Renames {
+    fn item_name(&self, original_item_name: &str) -> Option<String> {
+        self.0.get(original_item_name).cloned()
+    }
+}
+
+#[derive(Deserialize)]
+struct CompatFile {
+    #[serde(default)]
+    entry: Vec<CompatEntry>,
+}
+
+/// One object of `go list -m -json all` output
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GoListModule {
+    path: String,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    main: bool,
+    #[serde(default)]
+    dir: Option<PathBuf>,
+    #[serde(default)]
+    replace: Option<Box<GoListModule>>,
+}
+
+#[derive(Deserialize)]
+struct CompatEntry {
+    #[serde(rename = "crate")]
+    crate_req: String,
+    dll: String,
+    abi: u32,
+}
 
 fn main() {
     // Instruct Cargo when to re-run this build script.
-    println!("cargo:rerun-if-changed=build.py");
     println!("cargo:rerun-if-changed=go_lib/go_lib.go");
-    println!("cargo:rerun-if-changed=go_lib/go_lib.h");
+    println!("cargo:rerun-if-changed=go_lib/bridge_binary.go");
+    println!("cargo:rerun-if-changed=go_lib/bridge_binary_off.go");
+    println!("cargo:rerun-if-changed=go_lib/bridge_proto.go");
+    println!("cargo:rerun-if-changed=go_lib/bridge_proto_off.go");
+    println!("cargo:rerun-if-changed=go_lib/go.mod");
+    println!("cargo:rerun-if-changed=go_lib/go.sum");
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", SEH_GUARD_SOURCE);
+    println!("cargo:rerun-if-changed={}", COMPAT_MATRIX);
+    println!("cargo:rerun-if-changed={}", FFI_TOML);
+    println!("cargo:rerun-if-env-changed={}", GO_ENV);
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    for var in GO_BUILD_ENV {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
 
-    // Execute the Python build script.
-    // Adjust "python" to "python3" if needed.
-    let status = Command::new("python")
-        .arg("build.py")
-        .status()
-        .expect("Failed to execute build.py");
-    if !status.success() {
-        panic!(
-            "build.py failed with exit status: {}",
-            status.code().unwrap_or(-1)
-        );
+    let static_link = env::var_os("CARGO_FEATURE_STATIC").is_some();
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let (goos, goarch) = go_target(&target_os, &target_arch);
+    let ffi = read_ffi_toml();
+    let name = ffi.output.name.as_str();
+    let dll_name = dll_file_name(name, &target_os, &target_arch);
+    // The loader and the `embedded` feature look for the same file name.
+    println!("cargo:rustc-env=GO_LIB_NAME={}", name);
+    println!("cargo:rustc-env=GO_LIB_DLL_NAME={}", dll_name);
+    // Release assets for the `installer-remote` feature are named after it.
+    println!(
+        "cargo:rustc-env=GO_LIB_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+
+    // MSVC looks for `name.lib`, every other toolchain for `libname.a`.
+    let archive_name = if target_env == "msvc" {
+        format!("{}.lib", name)
+    } else {
+        format!("lib{}.a", name)
+    };
+    let output = if static_link {
+        format!("static/{}", archive_name)
+    } else {
+        dll_name.clone()
+    };
+    // `go build` names the header after its output file.
+    let header = Path::new(LIBRARY_PATH).join(&output).with_extension("h");
+    println!("cargo:rerun-if-changed={}", header.display());
+
+    let go = match prebuilt_dir() {
+        Some(dir) => {
+            let mut files = vec![Path::new(LIBRARY_PATH).join(&output), header.clone()];
+            // The linker needs the import library of a Windows DLL.
+            if goos == "windows" && !static_link {
+                files.push(Path::new(LIBRARY_PATH).join(format!("{}.lib", name)));
+            }
+            link_prebuilt(&dir, &files, goos, static_link);
+            if !static_link {
+                copy_to_target_dirs(&dll_name);
+            }
+            None
+        }
+        None => {
+            let go = go_binary();
+            match go_toolchain(&go) {
+                Some(version) => {
+                    println!("Building {} with {}", output, version);
+                    build_go_library(&go, &version, goos, goarch, &output, static_link, &ffi);
+                    if !static_link {
+                        copy_to_target_dirs(&dll_name);
+                    }
+                }
+                None => {
+                    use_prebuilt(&go, &Path::new(LIBRARY_PATH).join(&output));
+                    if !static_link {
+                        copy_to_target_dirs(&dll_name);
+                    }
+                }
+            }
+            Some(go)
+        }
+    };
+
+    // The manifest `checksum::verify` checks installed and loaded copies
+    // against; a c-archive is linked in and never loaded from disk.
+    let dll_digest = if static_link {
+        String::new()
+    } else {
+        let dll = fs::read(Path::new(LIBRARY_PATH).join(&dll_name)).expect("Couldn't read DLL!");
+        sha256_hex(&dll)
+    };
+    println!("cargo:rustc-env=GO_LIB_DLL_SHA256={}", dll_digest);
+
+    if static_link {
+        println!("cargo:rustc-link-search=native={}", STATIC_LIBRARY_PATH);
+        println!("cargo:rustc-link-lib=static={}", name);
+        // System libraries the Go runtime depends on.
+        let system_libs: &[&str] = match target_os.as_str() {
+            "windows" => &["ws2_32", "winmm", "ntdll", "userenv"],
+            "macos" => &["resolv"],
+            _ => &["pthread", "dl"],
+        };
+        for lib in system_libs {
+            println!("cargo:rustc-link-lib=dylib={}", lib);
+        }
+    } else {
+        // Link configuration: Tell Cargo where to find the native library.
+        println!("cargo:rustc-link-search=native={}", LIBRARY_PATH);
+        // Windows links against the import library, which keeps the plain
+        // name; elsewhere the linker looks for `lib<name>.so` or `.dylib`
+        // and needs the architecture suffix of the file itself.
+        let link_name = if goos == "windows" {
+            name.to_string()
+        } else {
+            Path::new(&dll_name)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.strip_prefix("lib"))
+                .expect("shared library name without lib prefix")
+                .to_string()
+        };
+        println!("cargo:rustc-link-lib=dylib={}", link_name);
     }
 
-    // Link configuration: Tell Cargo where to find the native library.
-    println!("cargo:rustc-link-search=native={}", LIBRARY_PATH);
-    // The library name here should match the actual library name without any prefix or extension.
-    // For example, if your DLL is named "go_lib.dll", then use "go_lib".
-    println!("cargo:rustc-link-lib=dylib=go_lib");
+    // `go_build_stamped` stamps the same hash into the Go side for `abi_check`.
+    let fingerprint = abi_fingerprint(&fs::read_to_string(&header).expect("Couldn't read header!"));
+    println!(
+        "cargo:rustc-env=GO_LIB_ABI_FINGERPRINT={:016x}",
+        fingerprint
+    );
 
     // Generate Rust bindings to the provided header using bindgen.
-    let bindings = bindgen::Builder::default()
+    let mut builder = bindgen::Builder::default()
         .rust_target("1.81".parse().unwrap())
-        .header(INPUT_HEADER)
-        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
-        .generate()
-        .expect("Unable to generate bindings");
+        .header(header.to_string_lossy())
+        // Use the crate's newtypes for cgo's platform-sized scalars.
+        .blocklist_type("GoInt|GoUint|GoFloat64")
+        .raw_line("use crate::types::{GoFloat64, GoInt, GoUint};")
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()));
+    let options = ffi.bindgen;
+    for pattern in &options.allowlist_functions {
+        builder = builder.allowlist_function(pattern);
+    }
+    for pattern in &options.allowlist_types {
+        builder = builder.allowlist_type(pattern);
+    }
+    for pattern in &options.allowlist_vars {
+        builder = builder.allowlist_var(pattern);
+    }
+    for pattern in &options.blocklist_functions {
+        builder = builder.blocklist_function(pattern);
+    }
+    for pattern in &options.blocklist_types {
+        builder = builder.blocklist_type(pattern);
+    }
+    if !options.rename.is_empty() {
+        builder = builder.parse_callbacks(Box::new(Renames(options.rename)));
+    }
+    let bindings = builder.generate().expect("Unable to generate bindings");
 
     // Write the bindings to the $OUT_DIR/bindings.rs file.
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
+
+    generate_compat_matrix(&out_path);
+    generate_build_info(go.as_deref(), &out_path);
+    generate_wrappers(&header, &out_path);
+
+    #[cfg(feature = "guarded")]
+    build_seh_guard(&target_os, &target_env);
+
+    #[cfg(feature = "proto-bridge")]
+    generate_protos(&out_path);
+}
+
+/// Generates prost types for the `.proto` files the Go side is built from.
+///
+/// An empty or missing directory yields an empty `proto` module, so protoc
+/// is only needed once there is something to compile.
+#[cfg(feature = "proto-bridge")]
+fn generate_protos(out_path: &Path) {
+    println!("cargo:rerun-if-env-changed={}", PROTO_DIR_ENV);
+    let dir = env::var(PROTO_DIR_ENV).unwrap_or_else(|_| "proto".to_string());
+    println!("cargo:rerun-if-changed={}", dir);
+
+    let mut protos: Vec<PathBuf> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "proto"))
+                .collect()
+        })
+        .unwrap_or_default();
+    protos.sort();
+
+    if protos.is_empty() {
+        fs::write(out_path.join("proto.rs"), "").expect("Couldn't write proto module!");
+        return;
+    }
+    prost_build::Config::new()
+        .include_file("proto.rs")
+        .compile_protos(&protos, &[&dir])
+        .expect("Failed to compile .proto files");
+}
+
+/// Compiles the `__try`/`__except` shim the `guarded` feature calls through.
+///
+/// Only MSVC understands structured exception handling in C; other targets
+/// get a warning and unguarded calls.
+#[cfg(feature = "guarded")]
+fn build_seh_guard(target_os: &str, target_env: &str) {
+    if target_os != "windows" || target_env != "msvc" {
+        println!(
+            "cargo:warning=The guarded feature needs a Windows MSVC target, calls into the DLL are not guarded"
+        );
+        return;
+    }
+    cc::Build::new()
+        .file(SEH_GUARD_SOURCE)
+        .compile("rgf_seh_guard");
+}
+
+/// The `go` binary: `GO` if set, else whatever `PATH` finds
+fn go_binary() -> String {
+    env::var(GO_ENV)
+        .ok()
+        .filter(|go| !go.is_empty())
+        .unwrap_or_else(|| "go".to_string())
+}
+
+/// `go version` output, or `None` if there is no working toolchain
+fn go_toolchain(go: &str) -> Option<String> {
+    Command::new(go)
+        .arg("version")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+}
+
+/// Falls back to a library built earlier when there is no Go toolchain,
+/// and stops the build with instructions when there is none either
+fn use_prebuilt(go: &str, library: &Path) {
+    let advice = format!(
+        "Install Go from {} with a C compiler for cgo (gcc, clang or MinGW-w64), make sure `go version` works, or point {} at the go binary. To build without Go, point {} at a prebuilt library",
+        GO_DOWNLOAD_URL, GO_ENV, PREBUILT_DIR_ENV
+    );
+    println!(
+        "cargo:warning=The Go toolchain ({}) was not found, so the Go library can't be built",
+        go
+    );
+    println!("cargo:warning={}", advice);
+    if library.exists() {
+        println!(
+            "cargo:warning=Using the existing {}, which may be out of date with go_lib.go",
+            library.display()
+        );
+        return;
+    }
+    panic!(
+        "No Go toolchain and no prebuilt {} to use instead. {}",
+        library.display(),
+        advice
+    );
+}
+
+/// Where to take a prebuilt Go library from instead of building it:
+/// `RUST_GO_FFI_PREBUILT_DIR`, or with the `prebuilt` feature the library
+/// directory itself
+fn prebuilt_dir() -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed={}", PREBUILT_DIR_ENV);
+    env::var_os(PREBUILT_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("CARGO_FEATURE_PREBUILT").map(|_| PathBuf::from(LIBRARY_PATH)))
+}
+
+/// Copies the prebuilt `files` from `dir`, laid out like the library
+/// directory, into place without running Go.
+///
+/// The first file is the library. It has to be one the target can link, and
+/// every file with a `<file>.sha256` next to it has to match that digest, so
+/// a missing or wrong artifact stops the build here rather than at link or
+/// load time.
+fn link_prebuilt(dir: &Path, files: &[PathBuf], goos: &str, static_link: bool) {
+    println!("cargo:rerun-if-changed={}", dir.display());
+    for file in files {
+        let relative = file.strip_prefix(LIBRARY_PATH).unwrap_or(file);
+        let source = dir.join(relative);
+        let contents = fs::read(&source).unwrap_or_else(|e| {
+            panic!(
+                "The prebuilt library directory {} has no {}: {}. It needs {}, laid out like {}/ after a build",
+                dir.display(),
+                relative.display(),
+                e,
+                files
+                    .iter()
+                    .map(|f| f.strip_prefix(LIBRARY_PATH).unwrap_or(f).display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                LIBRARY_PATH
+            )
+        });
+
+        let mut sidecar = source.clone().into_os_string();
+        sidecar.push(".sha256");
+        if let Ok(expected) = fs::read_to_string(&sidecar) {
+            let expected = expected
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            let found = sha256_hex(&contents);
+            if found != expected {
+                panic!(
+                    "SHA-256 of {} is {}, but {} expects {}",
+                    source.display(),
+                    found,
+                    Path::new(&sidecar).display(),
+                    expected
+                );
+            }
+        }
+
+        if source != *file {
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent).expect("Couldn't create the output directory");
+            }
+            fs::write(file, &contents)
+                .unwrap_or_else(|e| panic!("Failed to copy {}: {}", source.display(), e));
+        }
+    }
+
+    let library = fs::read(&files[0]).expect("Couldn't read the prebuilt library");
+    if !is_library_for(&library, goos, static_link) {
+        panic!(
+            "{} is not a {} for {}; was it built for another target?",
+            files[0].display(),
+            if static_link {
+                "C archive"
+            } else {
+                "shared library"
+            },
+            goos
+        );
+    }
+}
+
+/// Whether `contents` start like a library `goos` can link: an `ar` archive
+/// with `static`, else the platform's shared library format
+fn is_library_for(contents: &[u8], goos: &str, static_link: bool) -> bool {
+    if static_link {
+        return contents.starts_with(b"!<arch>\n");
+    }
+    match goos {
+        "windows" => contents.starts_with(b"MZ"),
+        // 64-bit, 32-bit and universal Mach-O
+        "darwin" => [
+            [0xcf, 0xfa, 0xed, 0xfe],
+            [0xce, 0xfa, 0xed, 0xfe],
+            [0xca, 0xfe, 0xba, 0xbe],
+        ]
+        .iter()
+        .any(|magic| contents.starts_with(magic)),
+        _ => contents.starts_with(b"\x7fELF"),
+    }
+}
+
+/// Hex SHA-256 of `contents`
+fn sha256_hex(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+/// Runs `command`, stopping the build with `what` and `hint` if it fails
+fn run(command: &mut Command, what: &str, hint: &str) {
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => panic!("{} failed with {}. {}", what, status, hint),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => panic!(
+            "{} failed: {:?} was not found. {}",
+            what,
+            command.get_program(),
+            hint
+        ),
+        Err(e) => panic!("{} failed: {}. {}", what, e, hint),
+    }
+}
+
+/// A `go` command run in the library directory for GOOS/GOARCH.
+///
+/// cgo is off by default when cross-compiling, so it is forced on; the
+/// matching C cross compiler must be provided through `CC`.
+fn go_command(go: &str, goos: &str, goarch: &str) -> Command {
+    let mut command = Command::new(go);
+    command
+        .current_dir(LIBRARY_PATH)
+        .env("GOOS", goos)
+        .env("GOARCH", goarch)
+        .env("CGO_ENABLED", "1");
+    command
+}
+
+/// Builds the shared library, or with `static_link` the C archive, into
+/// `output` under the library directory.
+///
+/// When the Go sources, the toolchain `version` and everything else that
+/// goes into the build match the last one, the files it left are copied
+/// back from `OUT_DIR` instead of running `go build` again.
+fn build_go_library(
+    go: &str,
+    version: &str,
+    goos: &str,
+    goarch: &str,
+    output: &str,
+    static_link: bool,
+    ffi: &FfiToml,
+) {
+    let library_dir = Path::new(LIBRARY_PATH);
+    if !library_dir.join("go.mod").exists() {
+        run(
+            go_command(go, goos, goarch).args(["mod", "init", "go_lib"]),
+            "go mod init",
+            "",
+        );
+    }
+    if env::var_os("CARGO_FEATURE_PROTO_BRIDGE").is_some() {
+        generate_go_protos();
+    }
+    // Only the feature tags import third-party modules, and resolving them
+    // needs the network, so the build never does it on its own.
+    let tags = feature_tags();
+    if !tags.is_empty() && !library_dir.join("go.sum").exists() {
+        panic!(
+            "go_lib/go.sum is missing, so go build can't verify the modules the build tags {} import. Run `go mod tidy` in go_lib/ once with network access and commit go.sum",
+            tags.join(",")
+        );
+    }
+
+    let mut artifacts = vec![
+        library_dir.join(output),
+        library_dir.join(output).with_extension("h"),
+    ];
+    if goos == "windows" && !static_link {
+        artifacts.push(library_dir.join(format!("{}.lib", ffi.output.name)));
+    }
+    let key = go_build_key(version, goos, goarch, output, &ffi.go);
+    let cache_dir = PathBuf::from(env::var("OUT_DIR").unwrap()).join("go_build");
+    if restore_go_build(&cache_dir, &key, &artifacts) {
+        println!("Go sources unchanged, reusing the cached {}", output);
+        return;
+    }
+
+    if let Some(parent) = library_dir.join(output).parent() {
+        fs::create_dir_all(parent).expect("Couldn't create the output directory");
+    }
+    let buildmode = if static_link {
+        "-buildmode=c-archive"
+    } else {
+        "-buildmode=c-shared"
+    };
+    go_build_stamped(go, goos, goarch, buildmode, output, &ffi.go);
+    if !library_dir.join(output).exists() {
+        panic!("go build succeeded but left no {}", output);
+    }
+
+    // Import libraries are only needed to link against a Windows DLL.
+    if goos == "windows" && !static_link {
+        generate_import_library(output, &ffi.output.name);
+    }
+    store_go_build(&cache_dir, &key, &artifacts);
+}
+
+/// Hex SHA-256 over everything that decides what `go build` produces: the
+/// toolchain, the target, the stamped commit and time, the `ffi.toml` and
+/// process build settings and every Go source, `go.mod` and `go.sum` in the
+/// library directory
+fn go_build_key(version: &str, goos: &str, goarch: &str, output: &str, options: &GoToml) -> String {
+    let mut key = format!(
+        "{}\n{}/{}\n{}\n{:?}\n{:?}\n{}\n{}\n",
+        version,
+        goos,
+        goarch,
+        output,
+        go_tags(options),
+        options.ldflags,
+        git_commit(),
+        env::var("SOURCE_DATE_EPOCH").unwrap_or_default()
+    );
+    for var in GO_BUILD_ENV {
+        let _ = writeln!(key, "{}={}", var, env::var(var).unwrap_or_default());
+    }
+
+    let mut sources = Vec::new();
+    let mut dirs = vec![PathBuf::from(LIBRARY_PATH)];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "go")
+                || path
+                    .file_name()
+                    .is_some_and(|name| name == "go.mod" || name == "go.sum")
+            {
+                sources.push(path);
+            }
+        }
+    }
+    sources.sort();
+
+    let mut bytes = key.into_bytes();
+    for source in sources {
+        let contents = fs::read(&source)
+            .unwrap_or_else(|e| panic!("Couldn't read {}: {}", source.display(), e));
+        bytes.extend_from_slice(source.to_string_lossy().as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(sha256_hex(&contents).as_bytes());
+        bytes.push(b'\n');
+    }
+    sha256_hex(&bytes)
+}
+
+/// Copies the `artifacts` of the cached build back into place if it was made
+/// with `key`
+fn restore_go_build(cache_dir: &Path, key: &str, artifacts: &[PathBuf]) -> bool {
+    if fs::read_to_string(cache_dir.join("key")).ok().as_deref() != Some(key) {
+        return false;
+    }
+    let cached: Vec<PathBuf> = artifacts
+        .iter()
+        .map(|artifact| cache_dir.join(artifact.file_name().unwrap()))
+        .collect();
+    if !cached.iter().all(|path| path.is_file()) {
+        return false;
+    }
+    for (cached, artifact) in cached.iter().zip(artifacts) {
+        if let Some(parent) = artifact.parent() {
+            fs::create_dir_all(parent).expect("Couldn't create the output directory");
+        }
+        fs::copy(cached, artifact)
+            .unwrap_or_else(|e| panic!("Failed to restore {}: {}", artifact.display(), e));
+    }
+    true
+}
+
+/// Keeps a copy of the `artifacts` of the build made with `key`, replacing
+/// the previous one.
+///
+/// The key is written last, so an interrupted copy is never restored.
+fn store_go_build(cache_dir: &Path, key: &str, artifacts: &[PathBuf]) {
+    let _ = fs::remove_dir_all(cache_dir);
+    fs::create_dir_all(cache_dir).expect("Couldn't create the Go build cache");
+    for artifact in artifacts {
+        fs::copy(artifact, cache_dir.join(artifact.file_name().unwrap()))
+            .unwrap_or_else(|e| panic!("Failed to cache {}: {}", artifact.display(), e));
+    }
+    fs::write(cache_dir.join("key"), key).expect("Couldn't write the Go build cache key");
+}
+
+/// Runs `go build` and stamps the header fingerprint into the result.
+///
+/// The header only exists after a build, so the code is built twice; the
+/// second build reuses the cache and only links again, and `-X` doesn't
+/// change the header.
+fn go_build_stamped(
+    go: &str,
+    goos: &str,
+    goarch: &str,
+    buildmode: &str,
+    output: &str,
+    options: &GoToml,
+) {
+    let build = |fingerprint: &str| {
+        let mut command = go_command(go, goos, goarch);
+        command
+            .args(["build", buildmode, "-buildvcs=false"])
+            .arg(go_ldflags(fingerprint, &options.ldflags));
+        let tags = go_tags(options);
+        if !tags.is_empty() {
+            command.arg(format!("-tags={}", tags.join(",")));
+        }
+        run(
+            command.args(["-o", output, "."]),
+            "go build",
+            "cgo needs a C compiler: install gcc, clang or MinGW-w64, or set CC, e.g. to a cross compiler for the target",
+        );
+    };
+    build("");
+    let header = Path::new(LIBRARY_PATH).join(output).with_extension("h");
+    let header = fs::read_to_string(&header).expect("go build left no header");
+    build(&format!("{:016x}", abi_fingerprint(&header)));
+}
+
+/// Build tags of the enabled features
+fn feature_tags() -> Vec<&'static str> {
+    FEATURE_TAGS
+        .iter()
+        .filter(|(feature, _)| env::var_os(feature).is_some())
+        .map(|&(_, tag)| tag)
+        .collect()
+}
+
+/// The `ffi.toml` build tags followed by those of the enabled features
+fn go_tags(options: &GoToml) -> Vec<String> {
+    let mut tags = options.tags.clone();
+    tags.extend(feature_tags().into_iter().map(String::from));
+    tags
+}
+
+/// Linker flags that stamp the commit and build time reported by the
+/// `GetBuildInfo` export, and the header fingerprint reported by
+/// `GetABIFingerprint`, followed by the `extra` flags of `ffi.toml`.
+///
+/// `SOURCE_DATE_EPOCH` overrides the time for reproducible builds; outside
+/// a git checkout the commit stays empty.
+fn go_ldflags(abi_fingerprint: &str, extra: &[String]) -> String {
+    let epoch = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+    let mut flags = format!(
+        "-ldflags=-X main.buildCommit={} -X main.buildTime={} -X main.abiFingerprint={}",
+        git_commit(),
+        utc_timestamp(epoch),
+        abi_fingerprint
+    );
+    for flag in extra {
+        flags.push(' ');
+        flags.push_str(flag);
+    }
+    flags
+}
+
+/// The checked out commit, empty outside a git checkout
+fn git_commit() -> String {
+    Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+/// `seconds` since the epoch as `YYYY-MM-DDTHH:MM:SSZ`
+fn utc_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+    // Civil date from days since 1970-01-01, after Howard Hinnant.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Generates Go types for the `.proto` files of the protobuf call bridge
+/// into `go_lib/pb`.
+///
+/// Nothing is needed when the directory is missing or empty; otherwise
+/// `protoc` and `protoc-gen-go` must be on `PATH`.
+fn generate_go_protos() {
+    let dir = PathBuf::from(env::var(PROTO_DIR_ENV).unwrap_or_else(|_| "proto".to_string()));
+    let mut protos: Vec<String> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "proto"))
+                .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    if protos.is_empty() {
+        return;
+    }
+    protos.sort();
+
+    let out_dir = Path::new(LIBRARY_PATH).join("pb");
+    fs::create_dir_all(&out_dir).expect("Couldn't create go_lib/pb");
+    let mut command = Command::new("protoc");
+    command
+        .arg(format!("-I{}", dir.display()))
+        .arg(format!("--go_out={}", out_dir.display()))
+        .arg("--go_opt=paths=source_relative");
+    for proto in &protos {
+        command.arg(format!("--go_opt=M{}={}", proto, PROTO_GO_PACKAGE));
+    }
+    command.args(&protos);
+    run(
+        &mut command,
+        "protoc",
+        "Install protoc and protoc-gen-go (`go install google.golang.org/protobuf/cmd/protoc-gen-go@latest`) and put them on PATH",
+    );
+}
+
+/// Generates `<name>.def` from the DLL's exports with `dumpbin` and the
+/// import library `<name>.lib` from it with `dlltool`.
+///
+/// The library keeps its fixed name so the linker flags do not depend on the
+/// architecture; the DLL name recorded in it does.
+fn generate_import_library(dll_name: &str, name: &str) {
+    let def_file = format!("{}.def", name);
+    let output = Command::new("dumpbin")
+        .args(["/exports", dll_name])
+        .current_dir(LIBRARY_PATH)
+        .output()
+        .unwrap_or_else(|e| {
+            panic!(
+                "dumpbin failed: {}. It comes with the MSVC build tools; run the build from a Developer Command Prompt",
+                e
+            )
+        });
+    if !output.status.success() {
+        panic!("dumpbin failed with {}", output.status);
+    }
+    fs::write(
+        Path::new(LIBRARY_PATH).join(&def_file),
+        def_content(&String::from_utf8_lossy(&output.stdout)),
+    )
+    .unwrap_or_else(|e| panic!("Couldn't write {}: {}", def_file, e));
+
+    run(
+        Command::new("dlltool")
+            .args([
+                "-d",
+                &def_file,
+                "-D",
+                dll_name,
+                "-l",
+                &format!("{}.lib", name),
+            ])
+            .current_dir(LIBRARY_PATH),
+        "dlltool",
+        "dlltool comes with MinGW-w64 binutils; put it on PATH",
+    );
+}
+
+/// `.def` file listing the exports in `dumpbin /exports` output: the fourth
+/// column of the lines that start with an ordinal
+fn def_content(dumpbin_output: &str) -> String {
+    let mut lines = vec!["EXPORTS"];
+    for line in dumpbin_output.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() > 3 && parts[0].bytes().all(|b| b.is_ascii_digit()) {
+            lines.push(parts[3]);
+        }
+    }
+    lines.join("\n")
+}
+
+/// Copies the DLL next to the binaries and tests of this build, where the
+/// loader finds it
+fn copy_to_target_dirs(dll_name: &str) {
+    // OUT_DIR is <profile dir>/build/<package>-<hash>/out.
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let Some(profile_dir) = out_dir.ancestors().nth(3) else {
+        return;
+    };
+    let source = Path::new(LIBRARY_PATH).join(dll_name);
+    for dir in [profile_dir.join("deps"), profile_dir.to_path_buf()] {
+        fs::create_dir_all(&dir).expect("Couldn't create the target directory");
+        // A test binary still running may hold the old copy for a moment.
+        let mut attempt = 1;
+        while let Err(e) = fs::copy(&source, dir.join(dll_name)) {
+            if attempt == 5 {
+                panic!(
+                    "Failed to copy {} to {}: {}",
+                    source.display(),
+                    dir.display(),
+                    e
+                );
+            }
+            thread::sleep(Duration::from_millis(100 * attempt));
+            attempt += 1;
+        }
+    }
+}
+
+/// 64-bit FNV-1a hash of the cgo header, skipping `#line` directives, which
+/// carry build paths. The Go side reports the same hash from `GetABIFingerprint`.
+fn abi_fingerprint(header: &str) -> u64 {
+    header
+        .lines()
+        .filter(|line| !line.starts_with("#line"))
+        .flat_map(|line| line.bytes().chain([b'\n']))
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Maps the cargo target to the GOOS/GOARCH pair `go build` expects.
+fn go_target(os: &str, arch: &str) -> (&'static str, &'static str) {
+    let goos = match os {
+        "windows" => "windows",
+        "linux" => "linux",
+        "macos" => "darwin",
+        other => panic!("Unsupported target OS for the Go library: {}", other),
+    };
+    let goarch = match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        other => panic!(
+            "Unsupported target architecture for the Go library: {}",
+            other
+        ),
+    };
+    (goos, goarch)
+}
+
+/// Name of the shared library `name` built for `os` and `arch`, in the
+/// form the platform's linker looks for: `name.dll`, `libname.so` or
+/// `libname.dylib`.
+///
+/// x86_64 keeps the unsuffixed name existing installs use.
+fn dll_file_name(name: &str, os: &str, arch: &str) -> String {
+    let stem = match arch {
+        "x86_64" => name.to_string(),
+        other => format!("{}_{}", name, other),
+    };
+    match os {
+        "windows" => format!("{}.dll", stem),
+        "macos" => format!("lib{}.dylib", stem),
+        _ => format!("lib{}.so", stem),
+    }
+}
+
+/// Reads `ffi.toml`, or the defaults if there is none
+fn read_ffi_toml() -> FfiToml {
+    let source = match fs::read_to_string(FFI_TOML) {
+        Ok(source) => source,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return FfiToml::default(),
+        Err(e) => panic!("Failed to read {}: {}", FFI_TOML, e),
+    };
+    let ffi: FfiToml =
+        toml::from_str(&source).unwrap_or_else(|e| panic!("Invalid {}: {}", FFI_TOML, e));
+    let name = &ffi.output.name;
+    if name.is_empty()
+        || !name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+    {
+        panic!(
+            "Invalid {}: output.name {:?} must be a non-empty file name of letters, digits, `_` and `-`",
+            FFI_TOML, name
+        );
+    }
+    ffi
+}
+
+/// A function prototype of the cgo header
+#[derive(Debug, PartialEq)]
+struct HeaderExport {
+    name: String,
+    /// C return type, `void` for none
+    ret: String,
+    /// C type and name of each parameter
+    params: Vec<(String, String)>,
+}
+
+/// The `extern` prototypes of a cgo header.
+///
+/// cgo writes one per line, e.g. `extern GoInt AddNumbers(GoInt a, GoInt b);`,
+/// with `__declspec(dllexport)` after `extern` on Windows.
+fn parse_header_exports(header: &str) -> Vec<HeaderExport> {
+    // `char *name` declares a `char*` called `name`.
+    fn split_declaration(declaration: &str) -> Option<(String, String)> {
+        let (c_type, name) = declaration.trim().rsplit_once(char::is_whitespace)?;
+        let stars = name.len() - name.trim_start_matches('*').len();
+        let c_type = format!("{}{}", c_type.trim(), &name[..stars]);
+        Some((c_type.replace(" *", "*"), name[stars..].to_string()))
+    }
+
+    header
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().strip_prefix("extern ")?.strip_suffix(");")?;
+            let line = line
+                .trim_start()
+                .strip_prefix("__declspec(dllexport)")
+                .unwrap_or(line);
+            let (head, params) = line.split_once('(')?;
+            let (ret, name) = split_declaration(head)?;
+            let params = match params.trim() {
+                "" | "void" => Vec::new(),
+                params => params
+                    .split(',')
+                    .map(split_declaration)
+                    .collect::<Option<_>>()?,
+            };
+            Some(HeaderExport { name, ret, params })
+        })
+        .collect()
+}
+
+/// The Rust type callers see for a C type of the header, and the type it
+/// has in the bindings, if a generated wrapper can convert it
+fn header_type(c_type: &str, pointer_width: &str) -> Option<(&'static str, &'static str)> {
+    let wide = pointer_width == "64";
+    Some(match c_type.trim_start_matches("const ") {
+        "GoInt8" | "signed char" => ("i8", "i8"),
+        "GoUint8" | "unsigned char" => ("u8", "u8"),
+        "GoInt16" | "short" => ("i16", "i16"),
+        "GoUint16" | "unsigned short" => ("u16", "u16"),
+        "GoInt32" | "int" => ("i32", "i32"),
+        "GoUint32" | "unsigned int" => ("u32", "u32"),
+        "GoInt64" | "long long" => ("i64", "i64"),
+        "GoUint64" | "unsigned long long" => ("u64", "u64"),
+        "GoFloat32" | "float" => ("f32", "f32"),
+        "double" => ("f64", "f64"),
+        "_Bool" | "bool" => ("bool", "bool"),
+        // The crate's newtypes, sized after the pointer width like cgo's
+        "GoInt" if wide => ("i64", "crate::types::GoInt"),
+        "GoInt" => ("i32", "crate::types::GoInt"),
+        "GoUint" if wide => ("u64", "crate::types::GoUint"),
+        "GoUint" => ("u32", "crate::types::GoUint"),
+        "GoFloat64" => ("f64", "crate::types::GoFloat64"),
+        "Int128" => ("i128", "crate::ffi::Int128"),
+        _ => return None,
+    })
+}
+
+/// `AddNumbers` as `add_numbers` and `GetDLLVersion` as `get_dll_version`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if previous.is_ascii_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_ascii_uppercase() && next_is_lower)
+            {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    // Parameter names may be Rust keywords, e.g. `in`.
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "static", "struct", "trait", "type", "unsafe", "use", "where",
+        "while", "yield",
+    ];
+    if KEYWORDS.contains(&snake.as_str()) {
+        snake.insert_str(0, "r#");
+    }
+    snake
+}
+
+/// Generates a safe wrapper for every export of the header whose arguments
+/// and result can be converted: scalars, `void` and a `char*` result.
+///
+/// `$OUT_DIR/wrappers.rs` holds the `go_header_fn!` invocations the crate
+/// re-exports from `ffi`, and `$OUT_DIR/header_symbols.rs` the symbols they
+/// call through. Exports the crate manages itself and ones that take or
+/// return pointers or structs are listed at the top of `wrappers.rs` instead.
+fn generate_wrappers(header: &Path, out_path: &Path) {
+    let pointer_width = env::var("CARGO_CFG_TARGET_POINTER_WIDTH").unwrap_or_default();
+    let source = fs::read_to_string(header).expect("Couldn't read header!");
+
+    let mut wrappers = format!("// Generated by build.rs from {}.\n\n", header.display());
+    let mut fields = String::new();
+    let mut linked = String::new();
+    let mut lazy = String::new();
+    let mut exports = String::new();
+    for export in parse_header_exports(&source) {
+        let name = &export.name;
+        let rust_name = snake_case(name);
+        let declaration = format!(
+            "{} {}({})",
+            export.ret,
+            name,
+            export
+                .params
+                .iter()
+                .map(|(c_type, param)| format!("{} {}", c_type, param))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if LIFECYCLE_EXPORTS.contains(&name.as_str()) {
+            writeln!(
+                wrappers,
+                "// Not wrapped, called by the crate: {}",
+                declaration
+            )
+            .unwrap();
+            continue;
+        }
+
+        let params: Option<Vec<_>> = export
+            .params
+            .iter()
+            .map(|(c_type, param)| {
+                let (rust, raw) = header_type(c_type, &pointer_width)?;
+                Some((snake_case(param), rust, raw))
+            })
+            .collect();
+        // The Rust side of the result, and its type in the symbol's signature
+        let ret = match export.ret.as_str() {
+            "void" => Some((String::new(), String::new())),
+            "char*" => Some((
+                " -> GoStr".to_string(),
+                " -> *mut std::os::raw::c_char".to_string(),
+            )),
+            c_type => header_type(c_type, &pointer_width)
+                .map(|(rust, raw)| (format!(" -> {} as {}", rust, raw), format!(" -> {}", raw))),
+        };
+        let (Some(params), Some((ret, raw_ret))) = (params, ret) else {
+            writeln!(
+                wrappers,
+                "// Not wrapped, unsupported types: {}",
+                declaration
+            )
+            .unwrap();
+            continue;
+        };
+
+        let fn_type = format!(
+            "unsafe extern \"C\" fn({}){}",
+            params
+                .iter()
+                .map(|(_, _, raw)| *raw)
+                .collect::<Vec<_>>()
+                .join(", "),
+            raw_ret
+        );
+        writeln!(fields, "    pub {}: Symbol<{}>,", rust_name, fn_type).unwrap();
+        writeln!(
+            linked,
+            "            {}: Symbol::linked({:?}, crate::ffi::{}),",
+            rust_name, name, name
+        )
+        .unwrap();
+        writeln!(lazy, "            {}: Symbol::lazy({:?}),", rust_name, name).unwrap();
+        write!(exports, "{:?}, ", name).unwrap();
+        writeln!(
+            wrappers,
+            "\ngo_header_fn! {{\n    /// Calls `{}`, declared as `{}` in the header\n    pub fn {}({}){} => {}\n}}",
+            name,
+            declaration,
+            rust_name,
+            params
+                .iter()
+                .map(|(param, rust, raw)| format!("{}: {} as {}", param, rust, raw))
+                .collect::<Vec<_>>()
+                .join(", "),
+            ret,
+            name
+        )
+        .unwrap();
+    }
+
+    let symbols = format!(
+        "/// The exports behind the wrappers generated from the header\n\
+         pub(crate) struct HeaderSymbols {{\n{}}}\n\n\
+         impl HeaderSymbols {{\n\
+         \x20   /// Every export behind the generated wrappers\n\
+         \x20   pub const EXPORTS: &'static [&'static str] = &[{}];\n\n\
+         \x20   #[allow(dead_code)]\n\
+         \x20   pub fn linked() -> Self {{\n        Self {{\n{}        }}\n    }}\n\n\
+         \x20   pub fn lazy() -> Self {{\n        Self {{\n{}        }}\n    }}\n}}\n",
+        fields,
+        exports.trim_end_matches(", "),
+        linked,
+        lazy
+    );
+    fs::write(out_path.join("wrappers.rs"), wrappers).expect("Couldn't write wrappers!");
+    fs::write(out_path.join("header_symbols.rs"), symbols).expect("Couldn't write header symbols!");
+}
+
+/// Turns `compat.toml` into a static table at $OUT_DIR/compat_matrix.rs.
+fn generate_compat_matrix(out_path: &Path) {
+    let source = fs::read_to_string(COMPAT_MATRIX).expect("Failed to read compat.toml");
+    let matrix: CompatFile = toml::from_str(&source).expect("Invalid compat.toml");
+
+    let mut code = String::from("pub(crate) static COMPAT_MATRIX: &[CompatEntry] = &[\n");
+    for entry in &matrix.entry {
+        for req in [&entry.crate_req, &entry.dll] {
+            if semver::VersionReq::parse(req).is_err() {
+                panic!("Invalid version requirement in compat.toml: {}", req);
+            }
+        }
+        writeln!(
+            code,
+            "    CompatEntry {{ crate_req: {:?}, dll_req: {:?}, abi: {} }},",
+            entry.crate_req, entry.dll, entry.abi
+        )
+        .unwrap();
+    }
+    code.push_str("];\n");
+
+    fs::write(out_path.join("compat_matrix.rs"), code).expect("Couldn't write compat matrix!");
+}
+
+/// Embeds the Go toolchain version and module list at $OUT_DIR/build_info.rs.
+///
+/// Missing Go tooling only produces a warning; the crate then reports an
+/// empty module list. A prebuilt library, with no `go`, reports one silently.
+fn generate_build_info(go: Option<&str>, out_path: &Path) {
+    let go_version = go
+        .and_then(|go| {
+            Command::new(go)
+                .arg("env")
+                .arg("GOVERSION")
+                .current_dir(LIBRARY_PATH)
+                .output()
+                .ok()
+        })
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default();
+
+    let modules = match go.map(|go| {
+        Command::new(go)
+            .args(["list", "-m", "-json", "all"])
+            .current_dir(LIBRARY_PATH)
+            .output()
+    }) {
+        None => Vec::new(),
+        Some(Ok(output)) if output.status.success() => {
+            // The output is a stream of JSON objects rather than an array.
+            serde_json::Deserializer::from_slice(&output.stdout)
+                .into_iter::<GoListModule>()
+                .collect::<Result<Vec<_>, _>>()
+                .expect("Invalid `go list -m -json` output")
+        }
+        Some(Ok(output)) => {
+            println!(
+                "cargo:warning=go list failed, build info will be empty: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            Vec::new()
+        }
+        Some(Err(e)) => {
+            println!(
+                "cargo:warning=Could not run go list, build info will be empty: {}",
+                e
+            );
+            Vec::new()
+        }
+    };
+
+    let mut code = String::from("pub(crate) static GO_MODULES: &[GoModule] = &[\n");
+    let mut sbom = Vec::new();
+    for module in &modules {
+        // A replaced module ships the replacement's code and license.
+        let source = module.replace.as_deref().unwrap_or(module);
+        let (license, license_file) = source
+            .dir
+            .as_deref()
+            .and_then(find_license)
+            .map_or((None, None), |(id, file)| (id, Some(file)));
+        writeln!(
+            code,
+            "    GoModule {{ path: {:?}, version: {:?}, main: {}, replaced_by: {:?}, license: {:?}, license_file: {:?} }},",
+            module.path,
+            module.version,
+            module.main,
+            module
+                .replace
+                .as_ref()
+                .map(|r| format!("{}@{}", r.path, r.version.as_deref().unwrap_or(""))),
+            license,
+            license_file,
+        )
+        .unwrap();
+        sbom.push(serde_json::json!({
+            "path": module.path,
+            "version": module.version,
+            "main": module.main,
+            "replaced_by": module.replace.as_ref().map(|r| &r.path),
+            "license": license,
+            "license_file": license_file,
+        }));
+    }
+    code.push_str("];\n");
+
+    let sbom = serde_json::json!({
+        "go_version": go_version,
+        "crate": env::var("CARGO_PKG_NAME").unwrap_or_default(),
+        "crate_version": env::var("CARGO_PKG_VERSION").unwrap_or_default(),
+        "modules": sbom,
+    });
+    writeln!(
+        code,
+        "pub(crate) static GO_VERSION: &str = {:?};",
+        go_version
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "pub(crate) static SBOM_JSON: &str = {:?};",
+        sbom.to_string()
+    )
+    .unwrap();
+
+    fs::write(out_path.join("build_info.rs"), code).expect("Couldn't write build info!");
+}
+
+/// Finds the license file in a module directory and guesses its SPDX id
+fn find_license(dir: &Path) -> Option<(Option<&'static str>, String)> {
+    let name = LICENSE_FILES.iter().find(|name| dir.join(name).is_file())?;
+    let text = fs::read_to_string(dir.join(name)).unwrap_or_default();
+    Some((detect_license(&text), name.to_string()))
+}
+
+fn detect_license(text: &str) -> Option<&'static str> {
+    let known: &[(&str, &str)] = &[
+        ("Apache License", "Apache-2.0"),
+        ("Mozilla Public License", "MPL-2.0"),
+        ("GNU LESSER GENERAL PUBLIC LICENSE", "LGPL-3.0"),
+        ("GNU GENERAL PUBLIC LICENSE", "GPL-3.0"),
+        ("Permission is hereby granted, free of charge", "MIT"),
+        ("Neither the name of", "BSD-3-Clause"),
+        (
+            "Redistribution and use in source and binary forms",
+            "BSD-2-Clause",
+        ),
+        ("This is free and unencumbered software", "Unlicense"),
+        ("Permission to use, copy, modify, and/or distribute", "ISC"),
+    ];
+    known
+        .iter()
+        .find(|(marker, _)| text.contains(marker))
+        .map(|(_, id)| *id)
 }
@@ -48,7 +48,7 @@ fn main() {
 
     unsafe {
         print!("Adding numbers (5 + 3)... ");
-        let result = rust_go_ffi::AddNumbers(5, 3);
+        let result = rust_go_ffi::AddNumbers(5.into(), 3.into());
         println!("{} = {} {}", "result".blue().bold(), result, "✅".green());
     }
 
@@ -7,7 +7,7 @@ use colored::*;
 use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
 use metrics_exporter_prometheus::PrometheusBuilder;
 use metrics_util::MetricKindMask;
-use rust_go_ffi::{add_numbers, initialize, verify_dll};
+use rust_go_ffi::{add_numbers, initialize, shutdown, verify_dll};
 use semver::Version;
 
 fn setup_metrics() {
@@ -33,7 +33,7 @@ fn setup_metrics() {
     );
 }
 
-fn run_ffi_operations() {
+fn run_ffi_operations(running: &AtomicBool) {
     let start = std::time::Instant::now();
 
     // Initialize FFI
@@ -53,10 +53,13 @@ fn run_ffi_operations() {
 
     println!("{}", "\nRunning FFI operations...".cyan().bold());
     for i in 0..total_operations {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
         print!("Operation {}/{}: ", i + 1, total_operations);
 
         let op_start = std::time::Instant::now();
-        match add_numbers(i as i32, (i * 2) as i32) {
+        match add_numbers(i, i * 2) {
             Ok(result) => {
                 success_count += 1;
                 counter!("ffi_calls_total", "operation" => "add_numbers").increment(1);
@@ -93,38 +96,24 @@ fn run_ffi_operations() {
     );
 }
 
-fn cleanup_and_exit() {
+fn shutdown_gracefully() {
     println!("\n{}", "Shutting down gracefully...".yellow());
-    // Add small delay for any pending operations
-    thread::sleep(Duration::from_millis(200));
+    // Lets the Go side stop its goroutines before the library is released.
+    if let Err(e) = shutdown(Duration::from_secs(5)) {
+        eprintln!("Go shutdown failed: {}", e);
+    }
     println!("{}", "Goodbye! 👋".green().bold());
-    // Ensure we exit with success status
-    std::process::exit(0);
 }
 
 fn main() {
-    // Windows-specific: Set console control handler
-    #[cfg(windows)]
-    unsafe {
-        use winapi::um::consoleapi::SetConsoleCtrlHandler;
-
-        extern "system" fn handler(_: u32) -> i32 {
-            cleanup_and_exit();
-            0
-        }
-
-        SetConsoleCtrlHandler(Some(handler), 1);
-    }
-
     println!("{}", "FFI Metrics Example".bold().blue());
     println!("{}", "=================".blue());
 
-    // Set up Ctrl+C handler
+    // Ctrl+C only asks main to stop; it shuts down once no call is running.
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
     ctrlc::set_handler(move || {
         r.store(false, Ordering::SeqCst);
-        cleanup_and_exit();
     })
     .expect("Error setting Ctrl-C handler");
 
@@ -134,7 +123,7 @@ fn main() {
         "Metrics server started at http://127.0.0.1:9000".yellow()
     );
 
-    run_ffi_operations();
+    run_ffi_operations(&running);
 
     println!("\n{}", "Keeping metrics server alive...".cyan());
     println!("Press Ctrl+C to exit");
@@ -143,5 +132,5 @@ fn main() {
         thread::sleep(Duration::from_secs(1));
     }
 
-    cleanup_and_exit();
+    shutdown_gracefully();
 }
@@ -0,0 +1,47 @@
+//go:build binary_bridge
+
+package main
+
+import "C"
+import (
+	"bytes"
+	"unsafe"
+
+	"github.com/fxamacker/cbor/v2"
+	"github.com/vmihailenco/msgpack/v5"
+)
+
+// msgpackUnmarshal decodes MessagePack, reading struct field names from json
+// tags like the other codecs.
+func msgpackUnmarshal(data []byte, v any) error {
+	dec := msgpack.NewDecoder(bytes.NewReader(data))
+	dec.SetCustomStructTag("json")
+	return dec.Decode(v)
+}
+
+// msgpackMarshal encodes MessagePack, naming struct fields after json tags.
+func msgpackMarshal(v any) ([]byte, error) {
+	var buf bytes.Buffer
+	enc := msgpack.NewEncoder(&buf)
+	enc.SetCustomStructTag("json")
+	err := enc.Encode(v)
+	return buf.Bytes(), err
+}
+
+// CallMsgpack runs a bridge function with a MessagePack payload; see
+// callBridge.
+//
+//export CallMsgpack
+func CallMsgpack(function *C.char, payload *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	return callBridge(function, payload, length, outLen, msgpackUnmarshal, msgpackMarshal)
+}
+
+// CallCBOR runs a bridge function with a CBOR payload; see callBridge. CBOR
+// falls back to json tags for struct field names on its own.
+//
+//export CallCBOR
+func CallCBOR(function *C.char, payload *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	return callBridge(function, payload, length, outLen, cbor.Unmarshal, cbor.Marshal)
+}
@@ -0,0 +1,32 @@
+//go:build !binary_bridge
+
+package main
+
+import "C"
+import (
+	"errors"
+	"unsafe"
+)
+
+// errNoBinaryBridge is reported by the binary codecs of a library built
+// without the binary_bridge tag, which build.rs sets for the Cargo feature
+// binary-bridge. The exports stay so every build has the same header.
+var errNoBinaryBridge = errors.New("go_lib was built without MessagePack and CBOR support; enable the binary-bridge feature")
+
+// CallMsgpack fails with errNoBinaryBridge.
+//
+//export CallMsgpack
+func CallMsgpack(function *C.char, payload *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	setLastError(errNoBinaryBridge)
+	return nil
+}
+
+// CallCBOR fails with errNoBinaryBridge.
+//
+//export CallCBOR
+func CallCBOR(function *C.char, payload *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	setLastError(errNoBinaryBridge)
+	return nil
+}
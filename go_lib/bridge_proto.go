@@ -0,0 +1,53 @@
+//go:build proto_bridge
+
+package main
+
+import "C"
+import (
+	"fmt"
+	"unsafe"
+
+	"go_lib/pb"
+
+	"google.golang.org/protobuf/proto"
+)
+
+// Negate takes and returns the messages of proto/bridge.proto so CallProto
+// can reach it; the other codecs see them as {"value": n}.
+func init() {
+	bridgeHandlers["Negate"] = func(decode func(any) error) (any, error) {
+		request := &pb.NegateRequest{}
+		if err := decode(request); err != nil {
+			return nil, err
+		}
+		return &pb.NegateResponse{Value: -request.Value}, nil
+	}
+}
+
+// protoUnmarshal decodes protobuf into v, which must be a generated message.
+func protoUnmarshal(data []byte, v any) error {
+	message, ok := v.(proto.Message)
+	if !ok {
+		return fmt.Errorf("%T is not a protobuf message", v)
+	}
+	return proto.Unmarshal(data, message)
+}
+
+// protoMarshal encodes a generated protobuf message.
+func protoMarshal(v any) ([]byte, error) {
+	message, ok := v.(proto.Message)
+	if !ok {
+		return nil, fmt.Errorf("%T is not a protobuf message", v)
+	}
+	return proto.Marshal(message)
+}
+
+// CallProto runs a bridge function with a protobuf payload; see callBridge.
+// Handlers reached this way must decode into and return generated messages,
+// e.g. from the go_lib/pb package build.rs generates from proto/.
+//
+//export CallProto
+func CallProto(function *C.char, payload *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	return callBridge(function, payload, length, outLen, protoUnmarshal, protoMarshal)
+}
@@ -0,0 +1,23 @@
+//go:build !proto_bridge
+
+package main
+
+import "C"
+import (
+	"errors"
+	"unsafe"
+)
+
+// errNoProtoBridge is reported by CallProto in a library built without the
+// proto_bridge tag, which build.rs sets for the Cargo feature proto-bridge.
+// The export stays so every build has the same header.
+var errNoProtoBridge = errors.New("go_lib was built without protobuf support; enable the proto-bridge feature")
+
+// CallProto fails with errNoProtoBridge.
+//
+//export CallProto
+func CallProto(function *C.char, payload *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	setLastError(errNoProtoBridge)
+	return nil
+}
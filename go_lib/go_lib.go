@@ -1,25 +1,1244 @@
 package main
 
+/*
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+#ifdef _WIN32
+#include <windows.h>
+#else
+#include <pthread.h>
+#endif
+
+// current_thread_id identifies the calling OS thread the way the Rust side
+// does.
+static inline unsigned long long current_thread_id(void) {
+#ifdef _WIN32
+	return GetCurrentThreadId();
+#else
+	return (unsigned long long)(uintptr_t)pthread_self();
+#endif
+}
+
+// Point is passed by value and by pointer; the Rust side mirrors its layout.
+typedef struct {
+	long long x;
+	long long y;
+} Point;
+
+// Int128 carries a 128-bit integer as two halves; C has no portable 128-bit
+// type.
+typedef struct {
+	unsigned long long lo;
+	long long hi;
+} Int128;
+
+// RuntimeStats is filled in by ReadRuntimeStats.
+typedef struct {
+	unsigned long long heap_bytes;
+	unsigned long long heap_objects;
+	unsigned long long goroutines;
+	unsigned long long gc_cycles;
+	unsigned long long gc_pause_total_ns;
+} RuntimeStats;
+
+// rust_callback is a function the Rust side registered. It returns 0 on
+// success.
+typedef int (*rust_callback)(void *context, const unsigned char *payload, long long length);
+
+// Go cannot call C function pointers directly.
+static inline int call_rust_callback(rust_callback fn, void *context, const unsigned char *payload, long long length) {
+	return fn(context, payload, length);
+}
+*/
 import "C"
 import (
+	"context"
+	"encoding/base64"
+	"encoding/binary"
+	"encoding/json"
 	"fmt"
+	"hash/crc32"
+	"io"
+	"log"
+	"log/slog"
+	"math"
+	"math/bits"
+	"os"
+	"runtime"
+	"runtime/debug"
+	"strconv"
+	"strings"
+	"sync"
+	"sync/atomic"
+	"time"
+	"unsafe"
+)
+
+// initConfig holds the configuration string passed to GoInit.
+var initConfig string
+
+// buildCommit and buildTime are set by build.rs with -ldflags -X, which
+// builds with -buildvcs=false so a checkout git can't read doesn't fail it.
+var (
+	buildCommit string
+	buildTime   string
 )
 
+// abiFingerprint is the hex FNV-1a hash of go_lib.h that build.rs stamps in
+// with -ldflags -X after generating the header.
+var abiFingerprint string
+
+// threadID identifies the OS thread running the current export. cgo runs an
+// export on the thread that called it, so this is the caller's thread.
+func threadID() uint64 {
+	return uint64(C.current_thread_id())
+}
+
+// lastError holds, per calling thread, the message of the most recent failure
+// reported through a sentinel return value, until the Rust side collects it.
+// Like errno, concurrent callers never see each other's errors.
+var lastError struct {
+	sync.Mutex
+	messages map[uint64]string
+}
+
+// setLastError records err for TakeThreadGoError. Exports that can fail call
+// it before returning their sentinel value.
+func setLastError(err error) {
+	thread := threadID()
+	lastError.Lock()
+	defer lastError.Unlock()
+	if lastError.messages == nil {
+		lastError.messages = make(map[uint64]string)
+	}
+	lastError.messages[thread] = err.Error()
+}
+
+// TakeThreadGoError returns and clears the error recorded by the last failing
+// call made on thread, or NULL if there is none. thread is the id of the OS
+// thread that made the call (pthread_self or GetCurrentThreadId). The caller
+// owns the string and releases it with FreeGoString.
+//
+//export TakeThreadGoError
+func TakeThreadGoError(thread C.ulonglong) *C.char {
+	lastError.Lock()
+	defer lastError.Unlock()
+	message, ok := lastError.messages[uint64(thread)]
+	if !ok {
+		return nil
+	}
+	delete(lastError.messages, uint64(thread))
+	return C.CString(message)
+}
+
+// goPanic is a panic recovered by guard.
+type goPanic struct {
+	message string
+	stack   string
+}
+
+// lastPanic holds, per calling thread, a panic recovered by guard until
+// TakeThreadGoPanic collects it.
+var lastPanic struct {
+	sync.Mutex
+	panics map[uint64]goPanic
+}
+
+// guard recovers a panic in an export so it does not abort the process. Every
+// export but TakeThreadGoError and TakeThreadGoPanic, which hand the results
+// out, defers it first; after a panic the export returns its zero value and
+// the Rust side picks up the details through TakeThreadGoPanic for the thread
+// that made the call.
+func guard() {
+	if r := recover(); r != nil {
+		p := goPanic{message: fmt.Sprint(r), stack: string(debug.Stack())}
+		thread := threadID()
+		lastPanic.Lock()
+		defer lastPanic.Unlock()
+		if lastPanic.panics == nil {
+			lastPanic.panics = make(map[uint64]goPanic)
+		}
+		lastPanic.panics[thread] = p
+	}
+}
+
+// guardBackground recovers a panic in a goroutine an export started. No call
+// is waiting for it, so it is logged instead of handed to TakeThreadGoPanic.
+func guardBackground() {
+	if r := recover(); r != nil {
+		slog.Error("background goroutine panicked", "target", "go::panic",
+			"panic", fmt.Sprint(r), "stack", string(debug.Stack()))
+	}
+}
+
+// TakeThreadGoPanic reports and clears the panic recovered in the last call
+// made on thread, the id of the OS thread that made it (pthread_self or
+// GetCurrentThreadId). It returns 0 if there is none; otherwise it returns 1
+// and stores the message and stack trace in the out parameters, to be
+// released with FreeGoString.
+//
+//export TakeThreadGoPanic
+func TakeThreadGoPanic(thread C.ulonglong, message, stack **C.char) C.int {
+	lastPanic.Lock()
+	defer lastPanic.Unlock()
+	p, ok := lastPanic.panics[uint64(thread)]
+	if !ok {
+		return 0
+	}
+	delete(lastPanic.panics, uint64(thread))
+	*message = C.CString(p.message)
+	*stack = C.CString(p.stack)
+	return 1
+}
+
+// FreeGoString releases a string handed out by this library.
+//
+//export FreeGoString
+func FreeGoString(s *C.char) {
+	defer guard()
+	C.free(unsafe.Pointer(s))
+}
+
 //export GetDLLVersion
 func GetDLLVersion() C.longlong {
+	defer guard()
 	// Version format: major * 10000 + minor * 100 + patch
 	// For version 0.1.0 this returns 100
 	return C.longlong(100) // represents 0.1.0
 }
 
+// Capability bits reported by NegotiateVersion. They mirror Capabilities on
+// the Rust side; never reuse a bit for something else.
+const (
+	capStrings      = 1 << 0 // Go-allocated strings released with FreeGoString
+	capBytes        = 1 << 1 // borrowed byte buffers and Go-allocated results
+	capCallbacks    = 1 << 2 // RegisterCallback and UnregisterCallback
+	capEvents       = 1 << 3 // events through the "event" callback
+	capStreams      = 1 << 4 // chunked results through NextChunk
+	capJSONBridge   = 1 << 5 // CallJSON and CallJSONBatch
+	capLogBridge    = 1 << 6 // SetLogLevel
+	capRuntimeStats = 1 << 7 // ReadRuntimeStats, SetGCPercent, SetMemoryLimit
+)
+
+// capabilities is what this build of the library supports.
+const capabilities = capStrings | capBytes | capCallbacks | capEvents | capStreams |
+	capJSONBridge | capLogBridge | capRuntimeStats
+
+// NegotiateVersion replaces GetDLLVersion for callers that know about it.
+// The Rust side sends the range of versions it accepts, encoded like
+// GetDLLVersion, with maxVersion exclusive; the library replies with its own
+// version and stores its capability bits in caps. There is only one version
+// to offer today, so the range is only logged; the caller decides whether
+// the reply is acceptable.
+//
+//export NegotiateVersion
+func NegotiateVersion(minVersion, maxVersion C.longlong, caps *C.ulonglong) C.longlong {
+	defer guard()
+	slog.Debug("negotiating version", "target", "go::lifecycle", "min", int64(minVersion), "max", int64(maxVersion))
+	if caps != nil {
+		*caps = C.ulonglong(capabilities)
+	}
+	return GetDLLVersion()
+}
+
+// GoInit is called once by the Rust side after the version check and before
+// any other export. A non-zero return aborts initialization.
+//
+//export GoInit
+func GoInit(config *C.char) C.int {
+	defer guard()
+	initConfig = C.GoString(config)
+	slog.Debug("initialized", "target", "go::lifecycle", "config", initConfig)
+	return 0
+}
+
+// background tracks goroutines that outlive the export that started them, so
+// GoShutdown can stop them. Each shutdown starts a new generation.
+var background struct {
+	sync.Mutex
+	ctx    context.Context
+	cancel context.CancelFunc
+	wg     *sync.WaitGroup
+}
+
+// goBackground runs f on a goroutine that GoShutdown waits for. f must return
+// soon after ctx is done.
+func goBackground(f func(ctx context.Context)) {
+	background.Lock()
+	if background.ctx == nil {
+		background.ctx, background.cancel = context.WithCancel(context.Background())
+		background.wg = &sync.WaitGroup{}
+	}
+	ctx, wg := background.ctx, background.wg
+	wg.Add(1)
+	background.Unlock()
+	go func() {
+		defer wg.Done()
+		defer guardBackground()
+		f(ctx)
+	}()
+}
+
+// GoShutdown releases Go-side resources before the library is unloaded: it
+// stops background goroutines and waits for them. It must return within
+// deadlineMs milliseconds; a non-zero return is reported as a shutdown
+// failure, 1 for a non-positive deadline and 2 when goroutines were still
+// running at the deadline.
+//
+//export GoShutdown
+func GoShutdown(deadlineMs C.longlong) C.int {
+	defer guard()
+	if deadlineMs <= 0 {
+		return 1
+	}
+	background.Lock()
+	cancel, wg := background.cancel, background.wg
+	background.ctx, background.cancel, background.wg = nil, nil, nil
+	background.Unlock()
+	initConfig = ""
+	if cancel == nil {
+		return 0
+	}
+	cancel()
+
+	done := make(chan struct{})
+	go func() {
+		wg.Wait()
+		close(done)
+	}()
+	select {
+	case <-done:
+		return 0
+	case <-time.After(time.Duration(deadlineMs) * time.Millisecond):
+		slog.Warn("background goroutines still running at the shutdown deadline",
+			"target", "go::lifecycle", "deadline_ms", int64(deadlineMs))
+		return 2
+	}
+}
+
+// GoRuntimeVersion returns the version of the Go runtime inside the library.
+// The caller owns the string and releases it with FreeGoString.
+//
+//export GoRuntimeVersion
+func GoRuntimeVersion() *C.char {
+	defer guard()
+	return C.CString(runtime.Version())
+}
+
+// GetABIFingerprint returns the hash of the header this library was built
+// with, or 0 if the build didn't stamp one. The Rust side compares it with
+// the hash of the header its bindings were generated from.
+//
+//export GetABIFingerprint
+func GetABIFingerprint() C.ulonglong {
+	defer guard()
+	fingerprint, err := strconv.ParseUint(abiFingerprint, 16, 64)
+	if err != nil {
+		return 0
+	}
+	return C.ulonglong(fingerprint)
+}
+
+// GetBuildInfo returns the JSON encoded toolchain version, module version,
+// commit and build time of the library, so a deployment can be traced back
+// to its source. Unknown fields are empty. The caller releases the string
+// with FreeGoString.
+//
+//export GetBuildInfo
+func GetBuildInfo() *C.char {
+	defer guard()
+	info := map[string]string{
+		"go_version":     runtime.Version(),
+		"module_version": "(devel)",
+		"commit":         buildCommit,
+		"build_time":     buildTime,
+	}
+	if build, ok := debug.ReadBuildInfo(); ok {
+		if build.Main.Version != "" {
+			info["module_version"] = build.Main.Version
+		}
+		for _, setting := range build.Settings {
+			if setting.Key == "vcs.revision" && info["commit"] == "" {
+				info["commit"] = setting.Value
+			}
+		}
+	}
+	encoded, err := json.Marshal(info)
+	if err != nil {
+		setLastError(err)
+		return nil
+	}
+	return C.CString(string(encoded))
+}
+
+// Ping echoes nonce back through a goroutine, so a successful round trip
+// shows the Go scheduler is running and not just that the export is callable.
+//
+//export Ping
+func Ping(nonce C.longlong) C.longlong {
+	defer guard()
+	echo := make(chan C.longlong, 1)
+	go func() { echo <- nonce }()
+	return <-echo
+}
+
+// ReadRuntimeStats fills out with the live heap, the goroutine count and the
+// garbage collector's totals. Like runtime.ReadMemStats it stops the world
+// briefly, so the Rust side polls it every few seconds at most.
+//
+//export ReadRuntimeStats
+func ReadRuntimeStats(out *C.RuntimeStats) {
+	defer guard()
+	var m runtime.MemStats
+	runtime.ReadMemStats(&m)
+	out.heap_bytes = C.ulonglong(m.HeapAlloc)
+	out.heap_objects = C.ulonglong(m.HeapObjects)
+	out.goroutines = C.ulonglong(runtime.NumGoroutine())
+	out.gc_cycles = C.ulonglong(m.NumGC)
+	out.gc_pause_total_ns = C.ulonglong(m.PauseTotalNs)
+}
+
+// SetGCPercent sets the heap growth that triggers a collection, as
+// debug.SetGCPercent does, and returns the previous setting. A negative
+// percentage turns the collector off.
+//
+//export SetGCPercent
+func SetGCPercent(percent C.int) C.int {
+	defer guard()
+	return C.int(debug.SetGCPercent(int(percent)))
+}
+
+// SetMemoryLimit sets the runtime's soft memory limit in bytes, as
+// debug.SetMemoryLimit does, and returns the previous one. A negative limit
+// leaves it unchanged, so the Rust side can read it.
+//
+//export SetMemoryLimit
+func SetMemoryLimit(limit C.longlong) C.longlong {
+	defer guard()
+	return C.longlong(debug.SetMemoryLimit(int64(limit)))
+}
+
+// goBytes views a buffer owned by the caller without copying it. It must not
+// be retained after the export returns.
+func goBytes(data *C.uchar, length C.longlong) []byte {
+	if data == nil || length <= 0 {
+		return nil
+	}
+	return unsafe.Slice((*byte)(unsafe.Pointer(data)), int(length))
+}
+
+// Crc32 returns the IEEE CRC-32 of the caller's buffer.
+//
+//export Crc32
+func Crc32(data *C.uchar, length C.longlong) C.uint {
+	defer guard()
+	return C.uint(crc32.ChecksumIEEE(goBytes(data, length)))
+}
+
+// UpperBytes upper-cases the ASCII letters of the caller's buffer in place.
+//
+//export UpperBytes
+func UpperBytes(data *C.uchar, length C.longlong) {
+	defer guard()
+	buf := goBytes(data, length)
+	for i, c := range buf {
+		if 'a' <= c && c <= 'z' {
+			buf[i] = c - 'a' + 'A'
+		}
+	}
+}
+
+// DecodeBase64 decodes standard base64 into a buffer allocated with malloc
+// and stores its length in outLen. The caller releases it with FreeGoBytes.
+// On malformed input it records the error and returns NULL.
+//
+//export DecodeBase64
+func DecodeBase64(data *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	decoded, err := base64.StdEncoding.DecodeString(string(goBytes(data, length)))
+	if err != nil {
+		setLastError(err)
+		return nil
+	}
+	*outLen = C.longlong(len(decoded))
+	return C.CBytes(decoded)
+}
+
+// ScalePoint returns p with both coordinates multiplied by factor.
+//
+//export ScalePoint
+func ScalePoint(p C.Point, factor C.longlong) C.Point {
+	defer guard()
+	return C.Point{x: p.x * factor, y: p.y * factor}
+}
+
+// TranslatePoint moves the caller's point by (dx, dy) in place.
+//
+//export TranslatePoint
+func TranslatePoint(p *C.Point, dx, dy C.longlong) {
+	defer guard()
+	p.x += dx
+	p.y += dy
+}
+
+// bridgeHandler serves one function of the generic call bridge. decode fills
+// its argument with the request payload in whatever encoding the caller used;
+// the returned value is encoded the same way.
+type bridgeHandler func(decode func(any) error) (any, error)
+
+// bridgeHandlers lists the functions reachable through the Call* exports. Argument
+// structs carry json tags, which the other codecs fall back to as well.
+var bridgeHandlers = map[string]bridgeHandler{
+	"AddNumbers": func(decode func(any) error) (any, error) {
+		var args struct {
+			A int `json:"a"`
+			B int `json:"b"`
+		}
+		if err := decode(&args); err != nil {
+			return nil, err
+		}
+		return args.A + args.B, nil
+	},
+	"Echo": func(decode func(any) error) (any, error) {
+		var payload any
+		err := decode(&payload)
+		return payload, err
+	},
+	// Negate takes and returns {"value": n}, the shape of the NegateRequest
+	// and NegateResponse messages the proto_bridge build replaces it with.
+	"Negate": func(decode func(any) error) (any, error) {
+		var value struct {
+			Value int64 `json:"value,omitempty"`
+		}
+		if err := decode(&value); err != nil {
+			return nil, err
+		}
+		value.Value = -value.Value
+		return value, nil
+	},
+}
+
+// dispatch runs the bridge handler registered for function.
+func dispatch(function string, decode func(any) error) (any, error) {
+	handler, ok := bridgeHandlers[function]
+	if !ok {
+		return nil, fmt.Errorf("unknown bridge function %q", function)
+	}
+	return handler(decode)
+}
+
+// callBridge runs a bridge function with a payload in one encoding and returns
+// the result in the same encoding, in a buffer allocated with malloc whose
+// length goes to outLen. The caller releases it with FreeGoBytes. On failure
+// it records the error and returns NULL.
+func callBridge(
+	function *C.char,
+	payload *C.uchar,
+	length C.longlong,
+	outLen *C.longlong,
+	unmarshal func([]byte, any) error,
+	marshal func(any) ([]byte, error),
+) unsafe.Pointer {
+	result, err := dispatch(C.GoString(function), func(v any) error {
+		return unmarshal(goBytes(payload, length), v)
+	})
+	if err != nil {
+		setLastError(err)
+		return nil
+	}
+	encoded, err := marshal(result)
+	if err != nil {
+		setLastError(err)
+		return nil
+	}
+	*outLen = C.longlong(len(encoded))
+	return C.CBytes(encoded)
+}
+
+// CallJSON runs a bridge function with a JSON payload; see callBridge.
+//
+//export CallJSON
+func CallJSON(function *C.char, payload *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	return callBridge(function, payload, length, outLen, json.Unmarshal, json.Marshal)
+}
+
+// CallJSONBatch runs several bridge functions in one call. The payload is a
+// JSON array of {"function", "payload"} objects and the result an array of
+// {"result"} or {"error"} objects in the same order; a failing entry does not
+// stop the others. Only a malformed batch records an error and returns NULL.
+//
+//export CallJSONBatch
+func CallJSONBatch(payload *C.uchar, length C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	var calls []struct {
+		Function string          `json:"function"`
+		Payload  json.RawMessage `json:"payload"`
+	}
+	if err := json.Unmarshal(goBytes(payload, length), &calls); err != nil {
+		setLastError(err)
+		return nil
+	}
+	type outcome struct {
+		Result any    `json:"result"`
+		Error  string `json:"error,omitempty"`
+	}
+	outcomes := make([]outcome, len(calls))
+	for i, call := range calls {
+		result, err := dispatch(call.Function, func(v any) error {
+			return json.Unmarshal(call.Payload, v)
+		})
+		if err != nil {
+			outcomes[i].Error = err.Error()
+			continue
+		}
+		outcomes[i].Result = result
+	}
+	encoded, err := json.Marshal(outcomes)
+	if err != nil {
+		setLastError(err)
+		return nil
+	}
+	*outLen = C.longlong(len(encoded))
+	return C.CBytes(encoded)
+}
+
+// apiFunction describes an export with a scalar-only signature for
+// DescribeAPI. Types are "int" (Go int), "int32", "int64" (C.longlong),
+// "uint32", "uint64", "float64" or "void".
+type apiFunction struct {
+	Name   string   `json:"name"`
+	Params []string `json:"params"`
+	Result string   `json:"result"`
+}
+
+// apiDescription lists the exports that may be called by name from Rust.
+// Keep it in sync when adding scalar exports.
+var apiDescription = []apiFunction{
+	{Name: "AddNumbers", Params: []string{"int", "int"}, Result: "int"},
+	{Name: "GetDLLVersion", Params: []string{}, Result: "int64"},
+	{Name: "GoFunction", Params: []string{}, Result: "void"},
+	{Name: "CountWithProgress", Params: []string{"int64"}, Result: "int64"},
+	{Name: "Hypot", Params: []string{"float64", "float64"}, Result: "float64"},
+	{Name: "HalveFloat32", Params: []string{"float32"}, Result: "float32"},
+	{Name: "AddUint64", Params: []string{"uint64", "uint64"}, Result: "uint64"},
+	{Name: "IsEven", Params: []string{"int64"}, Result: "bool"},
+	{Name: "SetGCPercent", Params: []string{"int32"}, Result: "int32"},
+	{Name: "SetMemoryLimit", Params: []string{"int64"}, Result: "int64"},
+}
+
+// DescribeAPI returns the JSON encoded apiDescription. The caller releases
+// it with FreeGoString.
+//
+//export DescribeAPI
+func DescribeAPI() *C.char {
+	defer guard()
+	encoded, err := json.Marshal(map[string]any{"functions": apiDescription})
+	if err != nil {
+		setLastError(err)
+		return nil
+	}
+	return C.CString(string(encoded))
+}
+
+// callbacks holds the Rust callbacks by name. Invocations hold the read lock,
+// so UnregisterCallback waits for running ones before Rust frees the context.
+var callbacks = struct {
+	sync.RWMutex
+	byName map[string]registeredCallback
+}{byName: map[string]registeredCallback{}}
+
+type registeredCallback struct {
+	fn      C.rust_callback
+	context unsafe.Pointer
+}
+
+// RegisterCallback makes fn reachable as name, replacing any previous one.
+//
+//export RegisterCallback
+func RegisterCallback(name *C.char, fn C.rust_callback, context unsafe.Pointer) {
+	defer guard()
+	callbacks.Lock()
+	defer callbacks.Unlock()
+	callbacks.byName[C.GoString(name)] = registeredCallback{fn: fn, context: context}
+}
+
+// UnregisterCallback removes the callback registered as name once no call to
+// it is running. A callback must not unregister itself.
+//
+//export UnregisterCallback
+func UnregisterCallback(name *C.char) {
+	defer guard()
+	callbacks.Lock()
+	defer callbacks.Unlock()
+	delete(callbacks.byName, C.GoString(name))
+}
+
+// invokeCallback passes payload to the callback registered as name, if any.
+func invokeCallback(name string, payload []byte) error {
+	callbacks.RLock()
+	defer callbacks.RUnlock()
+	cb, ok := callbacks.byName[name]
+	if !ok {
+		return nil
+	}
+	var data *C.uchar
+	if len(payload) > 0 {
+		data = (*C.uchar)(unsafe.Pointer(&payload[0]))
+	}
+	if code := C.call_rust_callback(cb.fn, cb.context, data, C.longlong(len(payload))); code != 0 {
+		return fmt.Errorf("callback %s failed with code %d", name, code)
+	}
+	return nil
+}
+
+// CountWithProgress counts to n and reports each step as "i/n" to the
+// "progress" callback. It returns n, or -1 if the callback fails.
+//
+//export CountWithProgress
+func CountWithProgress(n C.longlong) C.longlong {
+	defer guard()
+	for i := C.longlong(1); i <= n; i++ {
+		if err := invokeCallback("progress", []byte(fmt.Sprintf("%d/%d", i, n))); err != nil {
+			setLastError(err)
+			return -1
+		}
+	}
+	return n
+}
+
+// emitEvent sends an event to the Rust side if it subscribed. Events are
+// encoded as the topic, a NUL byte and the payload.
+func emitEvent(topic string, payload []byte) {
+	event := make([]byte, 0, len(topic)+1+len(payload))
+	event = append(append(append(event, topic...), 0), payload...)
+	// The Rust side never fails the event callback; there is nobody to tell.
+	_ = invokeCallback("event", event)
+}
+
+// Log levels, numbered like the Rust log crate's LevelFilter.
+const (
+	logOff   = 0
+	logError = 1
+	logWarn  = 2
+	logInfo  = 3
+	logDebug = 4
+	logTrace = 5
+)
+
+// logLevel is the most verbose level the Rust side forwards. Until it calls
+// SetLogLevel it is logOff and the loggers write to stderr as usual.
+var logLevel atomic.Int32
+
+// stderrLogger is the slog default the library started with.
+var stderrLogger = slog.Default()
+
+// SetLogLevel sends the standard log and slog output up to level to the
+// "log" callback, which must be registered first; logOff sends it back to
+// stderr. Records from log.Print arrive at info level.
+//
+//export SetLogLevel
+func SetLogLevel(level C.int) {
+	defer guard()
+	logLevel.Store(int32(level))
+	if level == logOff {
+		slog.SetDefault(stderrLogger)
+		log.SetOutput(os.Stderr)
+		log.SetFlags(log.LstdFlags)
+		return
+	}
+	// Also routes the standard logger through the handler, without its
+	// timestamps; the Rust logger adds its own.
+	slog.SetDefault(slog.New(logHandler{target: "go"}))
+}
+
+// rustLevel maps a slog level onto the log levels above. slog has no trace
+// level; anything below debug counts as trace.
+func rustLevel(level slog.Level) int32 {
+	switch {
+	case level >= slog.LevelError:
+		return logError
+	case level >= slog.LevelWarn:
+		return logWarn
+	case level >= slog.LevelInfo:
+		return logInfo
+	case level >= slog.LevelDebug:
+		return logDebug
+	default:
+		return logTrace
+	}
+}
+
+// logHandler forwards slog records to the "log" callback, encoded as the
+// level byte, the target, a NUL byte and the message. The target is "go"
+// unless a "target" attribute sets it; other attributes are appended to the
+// message as key=value, with group names as key prefixes.
+type logHandler struct {
+	target string
+	attrs  string
+	group  string
+}
+
+func (h logHandler) Enabled(_ context.Context, level slog.Level) bool {
+	return rustLevel(level) <= logLevel.Load()
+}
+
+func (h logHandler) Handle(_ context.Context, r slog.Record) error {
+	var message strings.Builder
+	message.WriteString(r.Message)
+	message.WriteString(h.attrs)
+	target := h.target
+	r.Attrs(func(a slog.Attr) bool {
+		if a.Key == "target" && h.group == "" {
+			target = a.Value.String()
+		} else {
+			fmt.Fprintf(&message, " %s%s=%v", h.group, a.Key, a.Value)
+		}
+		return true
+	})
+
+	record := make([]byte, 0, 1+len(target)+1+message.Len())
+	record = append(record, byte(rustLevel(r.Level)))
+	record = append(append(record, target...), 0)
+	record = append(record, message.String()...)
+	return invokeCallback("log", record)
+}
+
+func (h logHandler) WithAttrs(attrs []slog.Attr) slog.Handler {
+	for _, a := range attrs {
+		if a.Key == "target" && h.group == "" {
+			h.target = a.Value.String()
+		} else {
+			h.attrs += fmt.Sprintf(" %s%s=%v", h.group, a.Key, a.Value)
+		}
+	}
+	return h
+}
+
+func (h logHandler) WithGroup(name string) slog.Handler {
+	if name == "" {
+		return h
+	}
+	h.group += name + "."
+	return h
+}
+
+// StartTicker emits count "tick" events carrying the tick number, one every
+// intervalMs milliseconds, from a background goroutine.
+//
+//export StartTicker
+func StartTicker(count, intervalMs C.longlong) {
+	defer guard()
+	goBackground(func(ctx context.Context) {
+		for i := 1; i <= int(count); i++ {
+			select {
+			case <-ctx.Done():
+				return
+			case <-time.After(time.Duration(intervalMs) * time.Millisecond):
+			}
+			emitEvent("tick", []byte(strconv.Itoa(i)))
+		}
+	})
+}
+
+// streams holds the open result streams. Each produces its next chunk on
+// demand and returns io.EOF after the last one.
+var streams = struct {
+	sync.Mutex
+	last     int64
+	byHandle map[int64]func() ([]byte, error)
+}{byHandle: map[int64]func() ([]byte, error){}}
+
+func openStream(next func() ([]byte, error)) C.longlong {
+	streams.Lock()
+	defer streams.Unlock()
+	streams.last++
+	streams.byHandle[streams.last] = next
+	return C.longlong(streams.last)
+}
+
+// NextChunk returns the next chunk of a stream and stores its length in
+// outLen. The caller releases it with FreeGoBytes. It returns NULL after the
+// last chunk, or on failure after recording the error.
+//
+//export NextChunk
+func NextChunk(handle C.longlong, outLen *C.longlong) unsafe.Pointer {
+	defer guard()
+	streams.Lock()
+	next, ok := streams.byHandle[int64(handle)]
+	streams.Unlock()
+	if !ok {
+		setLastError(fmt.Errorf("unknown stream %d", handle))
+		return nil
+	}
+	chunk, err := next()
+	if err == io.EOF {
+		return nil
+	}
+	if err != nil {
+		setLastError(err)
+		return nil
+	}
+	*outLen = C.longlong(len(chunk))
+	return C.CBytes(chunk)
+}
+
+// CloseStream releases a stream; closing it twice is harmless.
+//
+//export CloseStream
+func CloseStream(handle C.longlong) {
+	defer guard()
+	streams.Lock()
+	defer streams.Unlock()
+	delete(streams.byHandle, int64(handle))
+}
+
+// OpenCounterStream streams the numbers 0 to count-1, one per line, in chunks
+// of at most chunkSize bytes. It returns the stream handle, or 0 on error.
+//
+//export OpenCounterStream
+func OpenCounterStream(count, chunkSize C.longlong) C.longlong {
+	defer guard()
+	if chunkSize <= 0 {
+		setLastError(fmt.Errorf("chunk size must be positive, got %d", chunkSize))
+		return 0
+	}
+	var i int64
+	var pending []byte
+	return openStream(func() ([]byte, error) {
+		for len(pending) < int(chunkSize) && i < int64(count) {
+			pending = strconv.AppendInt(pending, i, 10)
+			pending = append(pending, '\n')
+			i++
+		}
+		if len(pending) == 0 {
+			return nil, io.EOF
+		}
+		n := min(len(pending), int(chunkSize))
+		chunk := pending[:n]
+		pending = append([]byte(nil), pending[n:]...)
+		return chunk, nil
+	})
+}
+
+// ringHeader mirrors the header the Rust side places in front of a ring
+// buffer's data. head and tail count bytes written and read; each sits on
+// its own cache line. Messages are a little-endian uint32 length followed by
+// the payload, wrapping at the end of the data.
+type ringHeader struct {
+	head     uint64
+	_        [56]byte
+	tail     uint64
+	_        [56]byte
+	capacity uint64
+	closed   uint32 // the writer sends nothing more
+	detached uint32 // the reader is gone
+	_        [48]byte
+}
+
+type ring struct {
+	h    *ringHeader
+	data []byte
+}
+
+func attachRing(p unsafe.Pointer) (ring, error) {
+	h := (*ringHeader)(p)
+	if h.capacity == 0 || h.capacity&(h.capacity-1) != 0 {
+		return ring{}, fmt.Errorf("ring capacity %d is not a power of two", h.capacity)
+	}
+	data := unsafe.Slice((*byte)(unsafe.Add(p, unsafe.Sizeof(ringHeader{}))), h.capacity)
+	return ring{h: h, data: data}, nil
+}
+
+func (r ring) copyIn(pos uint64, b []byte) {
+	start := pos & (r.h.capacity - 1)
+	n := copy(r.data[start:], b)
+	copy(r.data, b[n:])
+}
+
+func (r ring) copyOut(pos uint64, b []byte) {
+	start := pos & (r.h.capacity - 1)
+	n := copy(b, r.data[start:])
+	copy(b[n:], r.data)
+}
+
+// tryRead takes the next message, if one is ready.
+func (r ring) tryRead() ([]byte, bool) {
+	tail := atomic.LoadUint64(&r.h.tail)
+	if atomic.LoadUint64(&r.h.head) == tail {
+		return nil, false
+	}
+	var length [4]byte
+	r.copyOut(tail, length[:])
+	msg := make([]byte, binary.LittleEndian.Uint32(length[:]))
+	r.copyOut(tail+4, msg)
+	atomic.StoreUint64(&r.h.tail, tail+4+uint64(len(msg)))
+	return msg, true
+}
+
+// tryWrite appends msg if there is room for it.
+func (r ring) tryWrite(msg []byte) bool {
+	head := atomic.LoadUint64(&r.h.head)
+	need := 4 + uint64(len(msg))
+	if r.h.capacity-(head-atomic.LoadUint64(&r.h.tail)) < need {
+		return false
+	}
+	var length [4]byte
+	binary.LittleEndian.PutUint32(length[:], uint32(len(msg)))
+	r.copyIn(head, length[:])
+	r.copyIn(head+4, msg)
+	atomic.StoreUint64(&r.h.head, head+need)
+	return true
+}
+
+// idle backs off while a ring has nothing to do.
+func idle(spins *int) {
+	*spins++
+	if *spins < 100 {
+		runtime.Gosched()
+	} else {
+		time.Sleep(50 * time.Microsecond)
+	}
+}
+
+// StartRingEcho starts a goroutine that copies every message from the in
+// ring to the out ring, upper-casing ASCII letters. headerSize is the size of
+// the Rust side's ring header and must match. Once in is closed and drained,
+// or the reader of out is gone, it closes out and finally marks itself
+// detached from in; it touches neither ring after that.
+//
+//export StartRingEcho
+func StartRingEcho(in, out unsafe.Pointer, headerSize C.longlong) {
+	defer guard()
+	if uintptr(headerSize) != unsafe.Sizeof(ringHeader{}) {
+		setLastError(fmt.Errorf("ring header is %d bytes, expected %d", headerSize, unsafe.Sizeof(ringHeader{})))
+		return
+	}
+	src, err := attachRing(in)
+	if err != nil {
+		setLastError(err)
+		return
+	}
+	dst, err := attachRing(out)
+	if err != nil {
+		setLastError(err)
+		return
+	}
+	go func() {
+		defer guardBackground()
+		defer atomic.StoreUint32(&src.h.detached, 1)
+		defer atomic.StoreUint32(&dst.h.closed, 1)
+		spins := 0
+		for atomic.LoadUint32(&dst.h.detached) == 0 {
+			msg, ok := src.tryRead()
+			if !ok {
+				if atomic.LoadUint32(&src.h.closed) != 0 {
+					// Data written before closing is visible by now.
+					if msg, ok = src.tryRead(); !ok {
+						return
+					}
+				} else {
+					idle(&spins)
+					continue
+				}
+			}
+			for i, c := range msg {
+				if 'a' <= c && c <= 'z' {
+					msg[i] = c - ('a' - 'A')
+				}
+			}
+			for !dst.tryWrite(msg) {
+				if atomic.LoadUint32(&dst.h.detached) != 0 {
+					return
+				}
+				idle(&spins)
+			}
+			spins = 0
+		}
+	}()
+}
+
+// FreeGoBytes releases a buffer handed out by this library.
+//
+//export FreeGoBytes
+func FreeGoBytes(p unsafe.Pointer) {
+	defer guard()
+	C.free(p)
+}
+
 //export GoFunction
 func GoFunction() {
+	defer guard()
 	fmt.Println("Hello from Go!")
 }
 
+// AddNumbers uses Go's native int so the generated header declares it with
+// GoInt, which the Rust side maps onto its own GoInt newtype.
+//
 //export AddNumbers
-func AddNumbers(a, b C.longlong) C.longlong {
+func AddNumbers(a, b int) int {
+	defer guard()
 	return a + b
 }
 
+// AddNumbersBatch adds count pairs in one call. pairs holds a0, b0, a1, b1,
+// ... and the sums go to out, which has room for count values.
+//
+//export AddNumbersBatch
+func AddNumbersBatch(pairs *C.longlong, count C.longlong, out *C.longlong) {
+	defer guard()
+	if count <= 0 {
+		return
+	}
+	in := unsafe.Slice(pairs, 2*count)
+	sums := unsafe.Slice(out, count)
+	for i := range sums {
+		sums[i] = in[2*i] + in[2*i+1]
+	}
+}
+
+// Hypot returns sqrt(a*a + b*b) without undue overflow.
+//
+//export Hypot
+func Hypot(a, b C.double) C.double {
+	defer guard()
+	return C.double(math.Hypot(float64(a), float64(b)))
+}
+
+// HalveFloat32 returns x / 2 in single precision.
+//
+//export HalveFloat32
+func HalveFloat32(x C.float) C.float {
+	defer guard()
+	return x / 2
+}
+
+// AddUint64 returns a + b, recording an error if the sum overflows.
+//
+//export AddUint64
+func AddUint64(a, b C.ulonglong) C.ulonglong {
+	defer guard()
+	sum, carry := bits.Add64(uint64(a), uint64(b), 0)
+	if carry != 0 {
+		setLastError(fmt.Errorf("%d + %d overflows uint64", uint64(a), uint64(b)))
+		return 0
+	}
+	return C.ulonglong(sum)
+}
+
+// AddInt128 returns a + b, recording an error if the sum overflows.
+//
+//export AddInt128
+func AddInt128(a, b C.Int128) C.Int128 {
+	defer guard()
+	lo, carry := bits.Add64(uint64(a.lo), uint64(b.lo), 0)
+	hi, _ := bits.Add64(uint64(a.hi), uint64(b.hi), carry)
+	// Two's complement addition overflows when both operands have the same
+	// sign and the result does not.
+	if (a.hi < 0) == (b.hi < 0) && (int64(hi) < 0) != (a.hi < 0) {
+		setLastError(fmt.Errorf("int128 addition overflows"))
+		return C.Int128{}
+	}
+	return C.Int128{lo: C.ulonglong(lo), hi: C.longlong(hi)}
+}
+
+// IsEven reports whether n is even.
+//
+//export IsEven
+func IsEven(n C.longlong) C.bool {
+	defer guard()
+	return C.bool(n%2 == 0)
+}
+
+// cancelContexts holds the contexts behind the Rust side's cancel tokens.
+var cancelContexts = struct {
+	sync.Mutex
+	last int64
+	byID map[int64]cancelContext
+}{byID: map[int64]cancelContext{}}
+
+type cancelContext struct {
+	ctx    context.Context
+	cancel context.CancelFunc
+}
+
+// NewCancelContext creates a cancellable context and returns its token.
+//
+//export NewCancelContext
+func NewCancelContext() C.longlong {
+	defer guard()
+	ctx, cancel := context.WithCancel(context.Background())
+	cancelContexts.Lock()
+	defer cancelContexts.Unlock()
+	cancelContexts.last++
+	cancelContexts.byID[cancelContexts.last] = cancelContext{ctx: ctx, cancel: cancel}
+	return C.longlong(cancelContexts.last)
+}
+
+// CancelContext cancels the context behind token; unknown tokens are ignored.
+//
+//export CancelContext
+func CancelContext(token C.longlong) {
+	defer guard()
+	cancelContexts.Lock()
+	defer cancelContexts.Unlock()
+	if c, ok := cancelContexts.byID[int64(token)]; ok {
+		c.cancel()
+	}
+}
+
+// ReleaseContext cancels and forgets the context behind token.
+//
+//export ReleaseContext
+func ReleaseContext(token C.longlong) {
+	defer guard()
+	cancelContexts.Lock()
+	defer cancelContexts.Unlock()
+	if c, ok := cancelContexts.byID[int64(token)]; ok {
+		c.cancel()
+		delete(cancelContexts.byID, int64(token))
+	}
+}
+
+// contextFor returns the context behind token. Token 0 means the call can't
+// be cancelled.
+func contextFor(token C.longlong) (context.Context, error) {
+	if token == 0 {
+		return context.Background(), nil
+	}
+	cancelContexts.Lock()
+	defer cancelContexts.Unlock()
+	c, ok := cancelContexts.byID[int64(token)]
+	if !ok {
+		return nil, fmt.Errorf("unknown cancel token %d", token)
+	}
+	return c.ctx, nil
+}
+
+// cancelled is what cancellable exports return when their context ends.
+const cancelled = 1
+
+// SleepWithContext waits for millis milliseconds. It returns 0 when done,
+// cancelled if the context behind token ends first, or -1 on failure after
+// recording the error.
+//
+//export SleepWithContext
+func SleepWithContext(token C.longlong, millis C.longlong) C.int {
+	defer guard()
+	ctx, err := contextFor(token)
+	if err != nil {
+		setLastError(err)
+		return -1
+	}
+	timer := time.NewTimer(time.Duration(millis) * time.Millisecond)
+	defer timer.Stop()
+	select {
+	case <-timer.C:
+		return 0
+	case <-ctx.Done():
+		return cancelled
+	}
+}
+
 func main() {} // Required but unused
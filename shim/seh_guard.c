@@ -0,0 +1,39 @@
+// Structured exception handling frame for calls into the Go library.
+//
+// Rust has no __try/__except, so the `guarded` feature routes every call
+// through rgf_guarded_call. Only hardware faults are caught; C++ and Rust
+// panic exceptions keep unwinding as usual.
+
+#include <windows.h>
+
+typedef void (*rgf_thunk)(void *context);
+
+static int rgf_filter(EXCEPTION_POINTERS *info, unsigned long *code, void **address) {
+    switch (info->ExceptionRecord->ExceptionCode) {
+    case EXCEPTION_ACCESS_VIOLATION:
+    case EXCEPTION_IN_PAGE_ERROR:
+    case EXCEPTION_ILLEGAL_INSTRUCTION:
+    case EXCEPTION_PRIV_INSTRUCTION:
+    case EXCEPTION_INT_DIVIDE_BY_ZERO:
+    case EXCEPTION_DATATYPE_MISALIGNMENT:
+    case EXCEPTION_ARRAY_BOUNDS_EXCEEDED:
+        *code = info->ExceptionRecord->ExceptionCode;
+        *address = info->ExceptionRecord->ExceptionAddress;
+        return EXCEPTION_EXECUTE_HANDLER;
+    default:
+        // Stack overflows cannot be recovered from here, and everything else
+        // belongs to someone further up the stack.
+        return EXCEPTION_CONTINUE_SEARCH;
+    }
+}
+
+// Runs thunk(context). Returns 0 on success, or 1 with the exception code and
+// faulting address filled in if a hardware exception was raised.
+int rgf_guarded_call(rgf_thunk thunk, void *context, unsigned long *code, void **address) {
+    __try {
+        thunk(context);
+        return 0;
+    } __except (rgf_filter(GetExceptionInformation(), code, address)) {
+        return 1;
+    }
+}
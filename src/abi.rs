@@ -0,0 +1,76 @@
+//! Handshake on the exported signatures themselves.
+//!
+//! The DLL version only changes when someone bumps it, so a library whose
+//! signatures changed under the same version would be called with the wrong
+//! arguments. `build.rs` hashes the cgo header the bindings are generated
+//! from and stamps the same hash into the library it builds, and
+//! [`abi_check`] compares the two. `initialize` runs the check
+//! after the version handshake.
+
+use log::warn;
+use std::mem;
+
+use crate::library::Library;
+use crate::transfer::{self, TransferKind};
+use crate::{call_export, last_error, poison, DllError};
+
+/// Hex hash of the header the bindings were generated from
+const FINGERPRINT: &str = env!("GO_LIB_ABI_FINGERPRINT");
+
+/// The fingerprint this crate was built against
+pub(crate) fn expected() -> u64 {
+    u64::from_str_radix(FINGERPRINT, 16).unwrap_or_default()
+}
+
+/// Compares the library's fingerprint with [`expected`].
+///
+/// A library that predates the export, or was built without `build.rs`,
+/// can't be checked and only logs a warning.
+pub(crate) fn check(library: &Library) -> Result<(), DllError> {
+    let get_fingerprint = match library.symbol(&library.symbols.get_abi_fingerprint) {
+        Ok(f) => f,
+        Err(DllError::MissingSymbol { .. }) => {
+            warn!("The DLL has no GetABIFingerprint export, skipping the ABI check");
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let found = call_export("GetABIFingerprint", || unsafe { get_fingerprint() })?;
+    transfer::record_out(
+        "GetABIFingerprint",
+        TransferKind::Scalar,
+        mem::size_of_val(&found),
+    );
+    last_error::check(library, "GetABIFingerprint")?;
+
+    if found == 0 {
+        warn!("The DLL was built without an ABI fingerprint, skipping the ABI check");
+        return Ok(());
+    }
+    let expected = expected();
+    if found != expected {
+        return Err(DllError::AbiMismatch { expected, found });
+    }
+    Ok(())
+}
+
+/// Checks that the library was built from the same `go_lib.h` as this
+/// crate's bindings.
+///
+/// Loads the library without initializing it, like
+/// [`get_version`](crate::get_version).
+pub fn abi_check() -> Result<(), DllError> {
+    crate::load_dll()?;
+    let library = crate::current_library()?;
+    check(&library).map_err(poison)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_fingerprint() {
+        assert_eq!(format!("{:016x}", expected()), FINGERPRINT);
+    }
+}
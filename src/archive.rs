@@ -0,0 +1,244 @@
+//! Installs from a vendored archive, for the `installer-archive` feature.
+//!
+//! Air-gapped machines can't download releases, so the library can be
+//! shipped in a `.zip`, `.tar` or `.tar.gz` instead. Next to it, in any
+//! directory of the archive, there has to be a checksum manifest: either
+//! `SHA256SUMS` in `sha256sum` format or `<library>.sha256` holding the
+//! digest alone, e.g. `go_lib.dll.sha256`. The library is only extracted if
+//! its SHA-256 matches.
+
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::search_paths::DLL_FILE_NAME;
+
+/// The manifest listing digests of several files
+const SUMS_FILE_NAME: &str = "SHA256SUMS";
+/// Manifests are small; anything larger isn't one
+const MANIFEST_LIMIT: u64 = 64 * 1024;
+
+/// The files of an archive the installer needs
+#[derive(Default)]
+struct Contents {
+    dll: Option<Vec<u8>>,
+    sums: Option<String>,
+    sidecar: Option<String>,
+}
+
+impl Contents {
+    /// Keeps the entry at `name` if it is one of the files looked for
+    fn keep(&mut self, name: &str, entry: &mut dyn Read) -> io::Result<()> {
+        let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        let read_manifest = |entry: &mut dyn Read| -> io::Result<String> {
+            let mut contents = String::new();
+            entry.take(MANIFEST_LIMIT).read_to_string(&mut contents)?;
+            Ok(contents)
+        };
+        if file_name == DLL_FILE_NAME {
+            let mut dll = Vec::new();
+            entry.read_to_end(&mut dll)?;
+            self.dll = Some(dll);
+        } else if file_name == SUMS_FILE_NAME {
+            self.sums = Some(read_manifest(entry)?);
+        } else if file_name == format!("{}.sha256", DLL_FILE_NAME) {
+            self.sidecar = Some(read_manifest(entry)?);
+        }
+        Ok(())
+    }
+
+    /// The digest the manifest gives for the library, lowercase hex
+    fn expected_digest(&self) -> Option<String> {
+        let digest = match (&self.sidecar, &self.sums) {
+            (Some(sidecar), _) => sidecar.split_whitespace().next()?,
+            (None, Some(sums)) => sums.lines().find_map(|line| {
+                let (digest, name) = line.split_once(char::is_whitespace)?;
+                // `sha256sum` marks binary mode with a leading `*`.
+                let name = name.trim().trim_start_matches('*');
+                let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+                (file_name == DLL_FILE_NAME).then_some(digest)
+            })?,
+            (None, None) => return None,
+        };
+        let digest = digest.to_ascii_lowercase();
+        (digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit())).then_some(digest)
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_zip(file: File, contents: &mut Contents) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(file).map_err(io::Error::from)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::from)?;
+        if entry.is_file() {
+            let name = entry.name().to_string();
+            contents.keep(&name, &mut entry)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_tar(reader: impl Read, contents: &mut Contents) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() {
+            let name = entry.path()?.to_string_lossy().into_owned();
+            contents.keep(&name, &mut entry)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks the library in `archive` against its manifest and writes it to
+/// `dest`, synced to disk
+pub(crate) fn extract_dll(archive: &Path, dest: &Path) -> io::Result<()> {
+    let name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let file = File::open(archive)?;
+    let mut contents = Contents::default();
+    if name.ends_with(".zip") {
+        read_zip(file, &mut contents)?;
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        read_tar(GzDecoder::new(file), &mut contents)?;
+    } else if name.ends_with(".tar") {
+        read_tar(file, &mut contents)?;
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is not a .zip, .tar or .tar.gz archive",
+                archive.display()
+            ),
+        ));
+    }
+
+    let expected = contents.expected_digest().ok_or_else(|| {
+        invalid(format!(
+            "{} has no {} or {}.sha256 with a digest for {}",
+            archive.display(),
+            SUMS_FILE_NAME,
+            DLL_FILE_NAME,
+            DLL_FILE_NAME
+        ))
+    })?;
+    let dll = contents.dll.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} holds no {}", archive.display(), DLL_FILE_NAME),
+        )
+    })?;
+    let digest: String = Sha256::digest(&dll)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if digest != expected {
+        return Err(invalid(format!(
+            "SHA-256 of {} in {} is {}, the manifest says {}",
+            DLL_FILE_NAME,
+            archive.display(),
+            digest,
+            expected
+        )));
+    }
+
+    let mut file = File::create(dest)?;
+    if let Err(e) = file.write_all(&dll).and_then(|()| file.sync_all()) {
+        drop(file);
+        let _ = fs::remove_file(dest);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const DLL: &[u8] = b"not really a library";
+
+    fn digest() -> String {
+        Sha256::digest(DLL)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn write_tar_gz(path: &Path, files: &[(&str, &[u8])]) {
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(path).unwrap(), Default::default());
+        let mut tar = tar::Builder::new(encoder);
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, *data).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_extract_checks_the_manifest() {
+        let dir = env::temp_dir().join(format!("archive_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("staged");
+        let sums = format!("{} *release/{}\n", digest(), DLL_FILE_NAME);
+        let sidecar = format!("{}\n", digest().to_ascii_uppercase());
+        let dll_entry = format!("release/{}", DLL_FILE_NAME);
+        let sidecar_entry = format!("{}.sha256", DLL_FILE_NAME);
+
+        let zip = dir.join("bundle.zip");
+        write_zip(
+            &zip,
+            &[(&dll_entry, DLL), (SUMS_FILE_NAME, sums.as_bytes())],
+        );
+        extract_dll(&zip, &dest).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), DLL);
+
+        let tar_gz = dir.join("bundle.tar.gz");
+        write_tar_gz(
+            &tar_gz,
+            &[(&dll_entry, DLL), (&sidecar_entry, sidecar.as_bytes())],
+        );
+        fs::remove_file(&dest).unwrap();
+        extract_dll(&tar_gz, &dest).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), DLL);
+
+        let tampered = dir.join("tampered.zip");
+        write_zip(
+            &tampered,
+            &[
+                (&dll_entry, b"tampered"),
+                (&sidecar_entry, sidecar.as_bytes()),
+            ],
+        );
+        let error = extract_dll(&tampered, &dir.join("tampered")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.join("tampered").exists());
+
+        let unsigned = dir.join("unsigned.zip");
+        write_zip(&unsigned, &[(&dll_entry, DLL)]);
+        assert!(extract_dll(&unsigned, &dir.join("unsigned")).is_err());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
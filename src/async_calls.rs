@@ -0,0 +1,208 @@
+//! Async versions of the safe wrappers, for use from a tokio runtime.
+//!
+//! Every call into Go blocks its thread, so each wrapper hands the call to
+//! tokio's blocking pool with `spawn_blocking` and awaits the result there,
+//! keeping the reactor free. They must be called from within a runtime.
+//! Under a concurrency limit they wait for a free slot rather than failing
+//! with [`DllError::Busy`].
+//!
+//! Dropping one of these futures doesn't stop the Go call; it finishes on
+//! the blocking pool and its result is discarded. Calls that take a
+//! [`CancelToken`] can be stopped through [`with_cancel_async`], which
+//! cancels the token when its future is dropped.
+
+use log::debug;
+use serde_json::Value;
+use std::panic;
+use std::time::{Duration, Instant};
+
+use crate::bridge::{call_json, call_json_batch};
+use crate::limit;
+use crate::types::{GoBuffer, GoStr, Point};
+use crate::{
+    add_i128, add_numbers, add_numbers_batch, add_u64, count_with_progress, crc32, decode_base64,
+    go_function, go_runtime_version, halve_f32, hypot, is_even, scale_point, sleep_with_cancel,
+    start_ticker, upper_bytes, CancelToken, DllError,
+};
+
+/// Runs `f` on the blocking pool, once the concurrency limit allows, and
+/// logs how long it waited and ran
+async fn run_blocking<T, F>(name: &'static str, f: F) -> Result<T, DllError>
+where
+    F: FnOnce() -> Result<T, DllError> + Send + 'static,
+    T: Send + 'static,
+{
+    let queued = Instant::now();
+    let permit = limit::acquire_async().await;
+    // The blocking pool doesn't inherit the caller's span.
+    #[cfg(feature = "tracing")]
+    let span = tracing::Span::current();
+    let joined = tokio::task::spawn_blocking(move || {
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        let waited = queued.elapsed();
+        let started = Instant::now();
+        let result = limit::hold(permit, f);
+        debug!(
+            "{} waited {:?} for a blocking thread and ran for {:?}",
+            name,
+            waited,
+            started.elapsed()
+        );
+        result
+    })
+    .await;
+    match joined {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+        // Only happens when the runtime shuts down before the call started.
+        Err(_) => Err(DllError::Cancelled {
+            export: name.to_string(),
+        }),
+    }
+}
+
+/// Generates an async wrapper that runs a safe wrapper on the blocking pool.
+///
+/// Arguments must be owned, since they move to another thread.
+macro_rules! async_fn {
+    (
+        $(#[$meta:meta])*
+        pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty => $sync:ident($($call:expr),* $(,)?)
+    ) => {
+        $(#[$meta])*
+        pub async fn $name($($arg: $ty),*) -> Result<$ret, DllError> {
+            run_blocking(stringify!($sync), move || $sync($($call),*)).await
+        }
+    };
+}
+
+async_fn! {
+    /// Async [`add_numbers`]
+    pub fn add_numbers_async(a: i32, b: i32) -> i32 => add_numbers(a, b)
+}
+
+async_fn! {
+    /// Async [`add_numbers_batch`]
+    pub fn add_numbers_batch_async(pairs: Vec<(i32, i32)>) -> Vec<i32> => add_numbers_batch(&pairs)
+}
+
+async_fn! {
+    /// Async [`go_function`]
+    pub fn go_function_async() -> () => go_function()
+}
+
+async_fn! {
+    /// Async [`go_runtime_version`]
+    pub fn go_runtime_version_async() -> GoStr => go_runtime_version()
+}
+
+async_fn! {
+    /// Async [`hypot`]
+    pub fn hypot_async(a: f64, b: f64) -> f64 => hypot(a, b)
+}
+
+async_fn! {
+    /// Async [`halve_f32`]
+    pub fn halve_f32_async(x: f32) -> f32 => halve_f32(x)
+}
+
+async_fn! {
+    /// Async [`add_u64`]
+    pub fn add_u64_async(a: u64, b: u64) -> u64 => add_u64(a, b)
+}
+
+async_fn! {
+    /// Async [`add_i128`]
+    pub fn add_i128_async(a: i128, b: i128) -> i128 => add_i128(a, b)
+}
+
+async_fn! {
+    /// Async [`is_even`]
+    pub fn is_even_async(n: i64) -> bool => is_even(n)
+}
+
+async_fn! {
+    /// Async [`crc32`]
+    pub fn crc32_async(data: Vec<u8>) -> u32 => crc32(&data)
+}
+
+/// Async [`upper_bytes`]; hands the upper-cased buffer back
+pub async fn upper_bytes_async(mut data: Vec<u8>) -> Result<Vec<u8>, DllError> {
+    run_blocking("upper_bytes", move || upper_bytes(&mut data).map(|()| data)).await
+}
+
+async_fn! {
+    /// Async [`decode_base64`]
+    pub fn decode_base64_async(data: Vec<u8>) -> GoBuffer => decode_base64(&data)
+}
+
+async_fn! {
+    /// Async [`scale_point`]
+    pub fn scale_point_async(point: Point, factor: i64) -> Point => scale_point(point, factor)
+}
+
+async_fn! {
+    /// Async [`count_with_progress`]
+    pub fn count_with_progress_async(n: i64) -> i64 => count_with_progress(n)
+}
+
+async_fn! {
+    /// Async [`start_ticker`]
+    pub fn start_ticker_async(count: i64, interval: Duration) -> () => start_ticker(count, interval)
+}
+
+async_fn! {
+    /// Async [`call_json`]
+    pub fn call_json_async(function: String, payload: Value) -> Value => call_json(&function, &payload)
+}
+
+/// Async [`call_json_batch`]
+pub async fn call_json_batch_async(
+    calls: Vec<(String, Value)>,
+) -> Result<Vec<Result<Value, DllError>>, DllError> {
+    run_blocking("call_json_batch", move || {
+        let calls: Vec<(&str, Value)> = calls
+            .iter()
+            .map(|(function, payload)| (function.as_str(), payload.clone()))
+            .collect();
+        call_json_batch(&calls)
+    })
+    .await
+}
+
+/// Cancels the token when the future holding it is dropped early
+struct CancelOnDrop(Option<CancelToken>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(token) = self.0.take() {
+            token.cancel();
+        }
+    }
+}
+
+/// Runs a cancellable call on the blocking pool with a fresh token, which is
+/// cancelled if the returned future is dropped before the call finishes.
+///
+/// ```ignore
+/// let sleep = with_cancel_async(|token| sleep_with_cancel(Duration::from_secs(60), token));
+/// // Cancels the Go side once the timeout drops the future.
+/// tokio::time::timeout(Duration::from_secs(1), sleep).await.ok();
+/// ```
+pub async fn with_cancel_async<T, F>(f: F) -> Result<T, DllError>
+where
+    F: FnOnce(&CancelToken) -> Result<T, DllError> + Send + 'static,
+    T: Send + 'static,
+{
+    let token = run_blocking("CancelToken::new", CancelToken::new).await?;
+    let mut guard = CancelOnDrop(Some(token.clone()));
+    let result = run_blocking("with_cancel_async", move || f(&token)).await;
+    guard.0 = None;
+    result
+}
+
+/// Async [`sleep_with_cancel`]; dropping the future cancels the sleep
+pub async fn sleep_async(duration: Duration) -> Result<(), DllError> {
+    with_cancel_async(move |token| sleep_with_cancel(duration, token)).await
+}
@@ -0,0 +1,175 @@
+//! In-memory log of the most recent calls into the Go library.
+//!
+//! Every call through a wrapper is recorded, including ones a hook or rate
+//! limit refused, so the last few can be inspected after a failure without
+//! Prometheus. The buffer keeps the newest
+//! [`audit_capacity`](crate::FfiConfig::audit_capacity) calls;
+//! [`flush_audit_log`] appends them to a JSON Lines file.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{config, DllError};
+
+/// How a recorded call ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallOutcome {
+    Ok,
+    /// The call failed with the [`DllError`] of this code and message
+    Failed {
+        code: u32,
+        message: String,
+    },
+}
+
+/// One call into the Go library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallRecord {
+    /// Increases by one with every call, so gaps show dropped records
+    pub seq: u64,
+    /// When the call started
+    pub at: SystemTime,
+    pub export: &'static str,
+    pub duration: Duration,
+    pub outcome: CallOutcome,
+}
+
+impl CallRecord {
+    /// The record as one JSON Lines entry, without the newline
+    pub fn to_json(&self) -> String {
+        let at_ms = self
+            .at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis());
+        let mut entry = json!({
+            "seq": self.seq,
+            "at_ms": at_ms as u64,
+            "export": self.export,
+            "duration_us": self.duration.as_micros() as u64,
+            "outcome": "ok",
+        });
+        if let CallOutcome::Failed { code, message } = &self.outcome {
+            entry["outcome"] = json!("failed");
+            entry["code"] = json!(code);
+            entry["message"] = json!(message);
+        }
+        entry.to_string()
+    }
+}
+
+#[derive(Default)]
+struct AuditLog {
+    records: VecDeque<CallRecord>,
+    next_seq: u64,
+    /// First sequence number `flush_audit_log` hasn't written yet
+    flushed: u64,
+}
+
+static LOG: Lazy<Mutex<AuditLog>> = Lazy::new(|| Mutex::new(AuditLog::default()));
+
+/// Adds a call to the buffer, dropping the oldest one if it is full
+pub(crate) fn record(
+    export: &'static str,
+    at: SystemTime,
+    duration: Duration,
+    result: Result<(), &DllError>,
+) {
+    let capacity = config::audit_capacity();
+    if capacity == 0 {
+        return;
+    }
+    let outcome = match result {
+        Ok(()) => CallOutcome::Ok,
+        Err(e) => CallOutcome::Failed {
+            code: e.code(),
+            message: e.to_string(),
+        },
+    };
+
+    let mut log = LOG.lock();
+    let seq = log.next_seq;
+    log.next_seq += 1;
+    while log.records.len() >= capacity {
+        log.records.pop_front();
+    }
+    log.records.push_back(CallRecord {
+        seq,
+        at,
+        export,
+        duration,
+        outcome,
+    });
+}
+
+/// The buffered calls, oldest first
+pub fn recent_calls() -> Vec<CallRecord> {
+    LOG.lock().records.iter().cloned().collect()
+}
+
+/// Appends the calls recorded since the last flush to `path`, one JSON
+/// object per line, and returns how many were written.
+///
+/// Calls that already left the buffer are skipped; their sequence numbers
+/// are missing from the file.
+pub fn flush_audit_log(path: impl AsRef<Path>) -> io::Result<usize> {
+    let pending: Vec<CallRecord> = {
+        let log = LOG.lock();
+        log.records
+            .iter()
+            .filter(|record| record.seq >= log.flushed)
+            .cloned()
+            .collect()
+    };
+    let Some(last) = pending.last() else {
+        return Ok(0);
+    };
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut out = BufWriter::new(file);
+    for record in &pending {
+        writeln!(out, "{}", record.to_json())?;
+    }
+    out.flush()?;
+
+    let mut log = LOG.lock();
+    log.flushed = log.flushed.max(last.seq + 1);
+    Ok(pending.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_json() {
+        let record = CallRecord {
+            seq: 7,
+            at: UNIX_EPOCH + Duration::from_millis(1500),
+            export: "AddNumbers",
+            duration: Duration::from_micros(42),
+            outcome: CallOutcome::Failed {
+                code: 4014,
+                message: "busy".to_string(),
+            },
+        };
+        let entry: serde_json::Value = serde_json::from_str(&record.to_json()).unwrap();
+        assert_eq!(entry["at_ms"], 1500);
+        assert_eq!(entry["duration_us"], 42);
+        assert_eq!(entry["outcome"], "failed");
+        assert_eq!(entry["code"], 4014);
+    }
+
+    #[test]
+    fn test_records_calls() {
+        crate::call_export("AuditTest", || ()).unwrap();
+        assert!(recent_calls()
+            .iter()
+            .any(|call| call.export == "AuditTest" && call.outcome == CallOutcome::Ok));
+    }
+}
@@ -0,0 +1,197 @@
+//! Circuit breaker around the Go library.
+//!
+//! With a [`CircuitBreakerConfig`] set, the breaker counts consecutive calls
+//! that failed inside Go: panics, hardware exceptions, timeouts and errors
+//! the library reported. Once `failure_threshold` of them happened in a row
+//! it opens, and every wrapper fails with [`DllError::CircuitOpen`] without
+//! calling into Go. After `open_for` it lets `half_open_probes` calls
+//! through; the first of them to succeed closes it again, the first to fail
+//! reopens it.
+//!
+//! Errors raised on the Rust side, such as argument checks, don't count.
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::{self, CircuitBreakerConfig};
+use crate::DllError;
+
+/// State of the circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CircuitState {
+    /// Calls go through
+    #[default]
+    Closed,
+    /// Calls fail with [`DllError::CircuitOpen`]
+    Open,
+    /// A limited number of probe calls go through
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// When the breaker last opened or started probing
+    since: Option<Instant>,
+    probes_admitted: u32,
+}
+
+static BREAKER: Lazy<Mutex<Breaker>> = Lazy::new(|| Mutex::new(Breaker::default()));
+
+impl Breaker {
+    fn set_state(&mut self, state: CircuitState, now: Instant) {
+        self.state = state;
+        self.since = Some(now);
+        self.probes_admitted = 0;
+        #[cfg(feature = "metrics-core")]
+        crate::metrics::set_circuit_state(state);
+    }
+
+    fn admit(&mut self, config: &CircuitBreakerConfig, now: Instant) -> Result<(), DllError> {
+        let elapsed = self.since.map_or(Duration::MAX, |since| now - since);
+        match self.state {
+            CircuitState::Closed => return Ok(()),
+            CircuitState::Open if elapsed >= config.open_for => {
+                info!("Circuit breaker half-open, probing the library");
+                self.set_state(CircuitState::HalfOpen, now);
+            }
+            // Probes that never reported back don't block the breaker forever.
+            CircuitState::HalfOpen if elapsed >= config.open_for => {
+                self.set_state(CircuitState::HalfOpen, now);
+            }
+            _ => {}
+        }
+        if self.state == CircuitState::HalfOpen && self.probes_admitted < config.half_open_probes {
+            self.probes_admitted += 1;
+            return Ok(());
+        }
+        #[cfg(feature = "metrics-core")]
+        crate::metrics::increment_circuit_rejections();
+        Err(DllError::CircuitOpen {
+            retry_after: config.open_for.saturating_sub(elapsed),
+        })
+    }
+
+    fn record(&mut self, config: &CircuitBreakerConfig, failed: bool, now: Instant) {
+        if !failed {
+            self.consecutive_failures = 0;
+            // Only a probe closes it; calls that were already running when it
+            // opened, or cleanup, prove nothing.
+            if self.state == CircuitState::HalfOpen {
+                info!("Circuit breaker closed after a successful probe");
+                self.set_state(CircuitState::Closed, now);
+            }
+            return;
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let trips = match self.state {
+            CircuitState::Closed => self.consecutive_failures >= config.failure_threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if trips {
+            warn!(
+                "Circuit breaker open after {} consecutive failures",
+                self.consecutive_failures
+            );
+            self.set_state(CircuitState::Open, now);
+        }
+    }
+}
+
+/// Whether `error` means the library itself failed the call
+fn is_library_failure(error: &DllError) -> bool {
+    matches!(
+        error,
+        DllError::GoPanic { .. }
+            | DllError::HardwareException { .. }
+            | DllError::Timeout { .. }
+            | DllError::CallFailed { .. }
+    )
+}
+
+/// Fails with [`DllError::CircuitOpen`] if calls may not go through now
+pub(crate) fn admit() -> Result<(), DllError> {
+    match config::circuit_breaker() {
+        Some(config) => BREAKER.lock().admit(&config, Instant::now()),
+        None => Ok(()),
+    }
+}
+
+/// Counts a call that returned without error
+pub(crate) fn record_success() {
+    if let Some(config) = config::circuit_breaker() {
+        BREAKER.lock().record(&config, false, Instant::now());
+    }
+}
+
+/// Counts `error` if the library caused it
+pub(crate) fn record_error(error: &DllError) {
+    if !is_library_failure(error) {
+        return;
+    }
+    if let Some(config) = config::circuit_breaker() {
+        BREAKER.lock().record(&config, true, Instant::now());
+    }
+}
+
+/// Current state of the circuit breaker; always closed without one
+pub fn circuit_state() -> CircuitState {
+    BREAKER.lock().state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_probes_and_closes() {
+        let config = CircuitBreakerConfig::new(2, Duration::from_secs(10));
+        let mut breaker = Breaker::default();
+        let start = Instant::now();
+
+        breaker.record(&config, true, start);
+        assert!(breaker.admit(&config, start).is_ok());
+        breaker.record(&config, true, start);
+        assert_eq!(breaker.state, CircuitState::Open);
+        assert!(matches!(
+            breaker.admit(&config, start + Duration::from_secs(4)),
+            Err(DllError::CircuitOpen { retry_after }) if retry_after == Duration::from_secs(6)
+        ));
+
+        let later = start + Duration::from_secs(10);
+        assert!(breaker.admit(&config, later).is_ok());
+        assert_eq!(breaker.state, CircuitState::HalfOpen);
+        assert!(
+            breaker.admit(&config, later).is_err(),
+            "one probe at a time"
+        );
+        breaker.record(&config, false, later);
+        assert_eq!(breaker.state, CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_failed_probe_reopens() {
+        let config = CircuitBreakerConfig::new(1, Duration::from_secs(1));
+        let mut breaker = Breaker::default();
+        let start = Instant::now();
+        breaker.record(&config, true, start);
+        let later = start + Duration::from_secs(1);
+        assert!(breaker.admit(&config, later).is_ok());
+        breaker.record(&config, true, later);
+        assert_eq!(breaker.state, CircuitState::Open);
+        assert!(breaker.admit(&config, later).is_err());
+    }
+
+    #[test]
+    fn test_rust_side_errors_dont_count() {
+        assert!(!is_library_failure(&DllError::NotFound));
+        assert!(is_library_failure(&DllError::CallFailed {
+            export: "DecodeBase64".to_string(),
+            message: "illegal base64 data".to_string(),
+        }));
+    }
+}
@@ -0,0 +1,207 @@
+//! Generic call bridge for payloads the typed wrappers don't cover.
+//!
+//! Arguments are serialized, handed to a single dispatch export together
+//! with the name of the Go function to run, and the encoded response is
+//! parsed back. Any function registered in the Go side's bridge table is
+//! reachable this way without a dedicated export or marshalling code.
+//!
+//! JSON is always available. The `binary-bridge` feature adds MessagePack
+//! and CBOR through the same dispatch table; they serialize straight from and
+//! into the caller's types, reuse a per-thread request buffer and decode the
+//! response in place from Go's memory. The `proto-bridge` feature does the
+//! same for protobuf messages generated from the `.proto` files in `proto/`.
+//!
+//! [`call_json_batch`] sends many JSON calls across the boundary at once, for
+//! small operations where the per-call overhead would dominate.
+
+#[cfg(feature = "binary-bridge")]
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+#[cfg(any(feature = "binary-bridge", feature = "proto-bridge"))]
+use std::cell::RefCell;
+use std::ffi::CString;
+
+use crate::library::{BridgeFn, Symbol, Symbols};
+use crate::transfer::{self, TransferKind};
+use crate::types::{c_len, GoBuffer};
+use crate::{call_export, ensure_initialized, last_error, poison, DllError};
+
+/// Calls the Go function `function` with a JSON payload and returns its
+/// JSON response.
+///
+/// Failures reported by the Go function, including unknown names, surface
+/// as [`DllError::CallFailed`].
+pub fn call_json(function: &str, payload: &Value) -> Result<Value, DllError> {
+    let request = serde_json::to_vec(payload).map_err(|e| codec_error("CallJSON", e))?;
+    let response = call_bridge("CallJSON", |s| &s.call_json, function, &request)?;
+    serde_json::from_slice(&response).map_err(|e| codec_error("CallJSON", e))
+}
+
+/// Runs several bridge functions in one call to the `CallJSONBatch` export.
+///
+/// Every entry succeeds or fails on its own, as [`DllError::CallFailed`];
+/// the outer error only covers the batch as a whole.
+///
+/// ```ignore
+/// let results = call_json_batch(&[
+///     ("AddNumbers", json!({"a": 1, "b": 2})),
+///     ("Echo", json!("hi")),
+/// ])?;
+/// ```
+pub fn call_json_batch(calls: &[(&str, Value)]) -> Result<Vec<Result<Value, DllError>>, DllError> {
+    const EXPORT: &str = "CallJSONBatch";
+    let mut request = vec![b'['];
+    for (i, (function, payload)) in calls.iter().enumerate() {
+        if i > 0 {
+            request.push(b',');
+        }
+        request.extend_from_slice(b"{\"function\":");
+        serde_json::to_writer(&mut request, function).map_err(|e| codec_error(EXPORT, e))?;
+        request.extend_from_slice(b",\"payload\":");
+        serde_json::to_writer(&mut request, payload).map_err(|e| codec_error(EXPORT, e))?;
+        request.push(b'}');
+    }
+    request.push(b']');
+
+    let library = ensure_initialized()?;
+    let batch = library.symbol(&library.symbols.call_json_batch)?;
+    transfer::marshal_in(EXPORT, TransferKind::Payload, request.len())?;
+    let len = c_len(request.len())?;
+    let mut out_len = 0;
+    let raw = call_export(EXPORT, || unsafe {
+        batch(request.as_mut_ptr(), len, &mut out_len)
+    })
+    .map_err(poison)?;
+    // Take ownership first so the buffer is released on every path.
+    let response = unsafe { GoBuffer::from_raw(library.clone(), EXPORT, raw, out_len) };
+    last_error::check(&library, EXPORT).map_err(poison)?;
+
+    let outcomes: Vec<Value> =
+        serde_json::from_slice(&response?).map_err(|e| codec_error(EXPORT, e))?;
+    if outcomes.len() != calls.len() {
+        return Err(codec_error(
+            EXPORT,
+            format!("{} results for {} calls", outcomes.len(), calls.len()),
+        ));
+    }
+    Ok(outcomes
+        .into_iter()
+        .zip(calls)
+        .map(
+            |(mut outcome, (function, _))| match outcome["error"].as_str() {
+                Some(error) => Err(DllError::CallFailed {
+                    export: EXPORT.to_string(),
+                    message: format!("{}: {}", function, error),
+                }),
+                None => Ok(outcome["result"].take()),
+            },
+        )
+        .collect())
+}
+
+/// Calls the Go function `function` with a MessagePack payload.
+///
+/// Structs are encoded as maps keyed by field name, matching the json tags
+/// on the Go side.
+#[cfg(feature = "binary-bridge")]
+pub fn call_msgpack<Req, Resp>(function: &str, request: &Req) -> Result<Resp, DllError>
+where
+    Req: Serialize + ?Sized,
+    Resp: DeserializeOwned,
+{
+    let response = with_request_buffer(|buf| {
+        rmp_serde::encode::write_named(buf, request).map_err(|e| codec_error("CallMsgpack", e))?;
+        call_bridge("CallMsgpack", |s| &s.call_msgpack, function, buf)
+    })?;
+    rmp_serde::from_slice(&response).map_err(|e| codec_error("CallMsgpack", e))
+}
+
+/// Calls the Go function `function` with a CBOR payload
+#[cfg(feature = "binary-bridge")]
+pub fn call_cbor<Req, Resp>(function: &str, request: &Req) -> Result<Resp, DllError>
+where
+    Req: Serialize + ?Sized,
+    Resp: DeserializeOwned,
+{
+    let response = with_request_buffer(|buf| {
+        ciborium::into_writer(request, &mut *buf).map_err(|e| codec_error("CallCBOR", e))?;
+        call_bridge("CallCBOR", |s| &s.call_cbor, function, buf)
+    })?;
+    ciborium::from_reader(&response[..]).map_err(|e| codec_error("CallCBOR", e))
+}
+
+/// Calls the Go function `method` with a protobuf request and decodes the
+/// protobuf response.
+///
+/// Message types come from [`proto`](crate::proto), which the build
+/// generates from the `.proto` files shared with the Go side.
+#[cfg(feature = "proto-bridge")]
+pub fn call_proto<Req, Resp>(method: &str, request: &Req) -> Result<Resp, DllError>
+where
+    Req: prost::Message,
+    Resp: prost::Message + Default,
+{
+    let response = with_request_buffer(|buf| {
+        request
+            .encode(buf)
+            .map_err(|e| codec_error("CallProto", e))?;
+        call_bridge("CallProto", |s| &s.call_proto, method, buf)
+    })?;
+    Resp::decode(&response[..]).map_err(|e| codec_error("CallProto", e))
+}
+
+/// Lends `f` this thread's empty request buffer.
+///
+/// The buffer is taken out for the duration of the call, so a callback that
+/// re-enters the bridge simply gets a fresh one.
+#[cfg(any(feature = "binary-bridge", feature = "proto-bridge"))]
+fn with_request_buffer<T>(f: impl FnOnce(&mut Vec<u8>) -> T) -> T {
+    thread_local! {
+        static REQUEST: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+    let mut buf = REQUEST.with(|r| r.take());
+    buf.clear();
+    let result = f(&mut buf);
+    REQUEST.with(|r| *r.borrow_mut() = buf);
+    result
+}
+
+/// Runs `function` through the bridge export `export` with an encoded
+/// request and returns the encoded response
+fn call_bridge(
+    export: &'static str,
+    symbol: impl FnOnce(&Symbols) -> &Symbol<BridgeFn>,
+    function: &str,
+    request: &[u8],
+) -> Result<GoBuffer, DllError> {
+    let library = ensure_initialized()?;
+    let bridge = library.symbol(symbol(&library.symbols))?;
+    let name = CString::new(function).map_err(|e| codec_error(export, e))?;
+    transfer::marshal_in(export, TransferKind::Payload, request.len())?;
+    let len = c_len(request.len())?;
+
+    let mut out_len = 0;
+    let raw = call_export(export, || unsafe {
+        bridge(
+            name.as_ptr().cast_mut(),
+            request.as_ptr().cast_mut(),
+            len,
+            &mut out_len,
+        )
+    })
+    .map_err(poison)?;
+    // Take ownership first so the buffer is released on every path.
+    let response = unsafe { GoBuffer::from_raw(library.clone(), export, raw, out_len) };
+    last_error::check(&library, export).map_err(poison)?;
+    response
+}
+
+fn codec_error(
+    export: &str,
+    error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> DllError {
+    DllError::Codec {
+        export: export.to_string(),
+        source: error.into(),
+    }
+}
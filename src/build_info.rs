@@ -0,0 +1,172 @@
+//! Provenance of the Go code built into the library.
+//!
+//! The module list is collected by `build.rs` with `go list -m -json all`.
+//! [`dll_build_info`] asks the loaded library itself, which tells a DLL that
+//! was deployed separately from the binary apart.
+
+use serde_json::Value;
+use std::fmt;
+
+use crate::types::GoStr;
+use crate::{call_export, last_error, poison, DllError};
+
+/// A Go module compiled into the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GoModule {
+    pub path: &'static str,
+    /// `None` for the main module
+    pub version: Option<&'static str>,
+    pub main: bool,
+    /// `path@version` of a `replace` directive target, if any
+    pub replaced_by: Option<&'static str>,
+    /// SPDX identifier guessed from the license file
+    pub license: Option<&'static str>,
+    pub license_file: Option<&'static str>,
+}
+
+include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+
+/// Provenance information embedded at build time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Go toolchain that built the library, e.g. `go1.23.4`
+    pub go_version: &'static str,
+    pub modules: &'static [GoModule],
+    /// The same information as a JSON document, for SBOM tooling
+    pub sbom_json: &'static str,
+}
+
+impl BuildInfo {
+    /// Modules whose license could not be identified
+    pub fn unlicensed(&self) -> impl Iterator<Item = &GoModule> {
+        self.modules
+            .iter()
+            .filter(|m| !m.main && m.license.is_none())
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Go toolchain: {}", self.go_version)?;
+        for module in self.modules {
+            writeln!(
+                f,
+                "{:<40} {:<16} {}",
+                module.path,
+                module.version.unwrap_or("(main)"),
+                module.license.unwrap_or("unknown")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the Go modules and licenses that ship inside the library
+pub fn get_build_info() -> BuildInfo {
+    BuildInfo {
+        go_version: GO_VERSION,
+        modules: GO_MODULES,
+        sbom_json: SBOM_JSON,
+    }
+}
+
+/// Build metadata the library reports about itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DllBuildInfo {
+    /// Go toolchain that built the library, e.g. `go1.23.4`
+    pub go_version: String,
+    /// Version of the main Go module; `(devel)` for a local build
+    pub module_version: String,
+    /// VCS commit the library was built from, if known
+    pub commit: Option<String>,
+    /// When the library was built, in RFC 3339, if known
+    pub build_time: Option<String>,
+}
+
+impl DllBuildInfo {
+    fn parse(raw: &str) -> Result<Self, DllError> {
+        let codec = |source: Box<dyn std::error::Error + Send + Sync>| DllError::Codec {
+            export: "GetBuildInfo".to_string(),
+            source,
+        };
+        let value: Value = serde_json::from_str(raw).map_err(|e| codec(e.into()))?;
+        let field = |name: &str| {
+            value[name]
+                .as_str()
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        Ok(Self {
+            go_version: field("go_version").ok_or_else(|| codec("missing go_version".into()))?,
+            module_version: field("module_version").unwrap_or_else(|| "(devel)".to_string()),
+            commit: field("commit"),
+            build_time: field("build_time"),
+        })
+    }
+}
+
+impl fmt::Display for DllBuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} built with {}", self.module_version, self.go_version)?;
+        if let Some(commit) = &self.commit {
+            write!(f, " from {}", commit)?;
+        }
+        if let Some(build_time) = &self.build_time {
+            write!(f, " at {}", build_time)?;
+        }
+        Ok(())
+    }
+}
+
+/// Asks the library which toolchain, module version and commit it was built
+/// from, and when.
+///
+/// Like [`get_version`](crate::get_version) this loads the library without
+/// initializing it, so it also works on a copy that fails the version
+/// handshake.
+pub fn dll_build_info() -> Result<DllBuildInfo, DllError> {
+    crate::load_dll()?;
+    let library = crate::current_library()?;
+    let get_build_info = library.symbol(&library.symbols.get_build_info)?;
+    let raw = call_export("GetBuildInfo", || unsafe { get_build_info() }).map_err(poison)?;
+    let raw = unsafe { GoStr::from_raw(library.clone(), "GetBuildInfo", raw) };
+    last_error::check(&library, "GetBuildInfo").map_err(poison)?;
+    DllBuildInfo::parse(&raw?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dll_build_info() {
+        let info = DllBuildInfo::parse(
+            r#"{"go_version":"go1.23.4","module_version":"","commit":"abc123","build_time":""}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            info,
+            DllBuildInfo {
+                go_version: "go1.23.4".to_string(),
+                module_version: "(devel)".to_string(),
+                commit: Some("abc123".to_string()),
+                build_time: None,
+            }
+        );
+        assert_eq!(info.to_string(), "(devel) built with go1.23.4 from abc123");
+        assert!(DllBuildInfo::parse(r#"{"commit":"abc123"}"#).is_err());
+        assert!(DllBuildInfo::parse("not json").is_err());
+    }
+
+    #[test]
+    fn test_build_info_is_consistent() {
+        let info = get_build_info();
+        assert!(info.modules.iter().filter(|m| m.main).count() <= 1);
+        let sbom: serde_json::Value =
+            serde_json::from_str(info.sbom_json).expect("SBOM should be valid JSON");
+        assert_eq!(
+            sbom["modules"].as_array().map(Vec::len),
+            Some(info.modules.len())
+        );
+    }
+}
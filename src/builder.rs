@@ -0,0 +1,178 @@
+//! One place to set up the FFI layer.
+//!
+//! [`FfiBuilder`] collects the version requirement, search directories and
+//! runtime settings and hands back an initialized [`FfiSession`]. Its
+//! defaults don't depend on cargo features: installing the library,
+//! cleaning up after every [`with_dll`](crate::with_dll) and serving
+//! metrics all stay off unless asked for. Features only decide which of
+//! these can be asked for at all.
+
+use log::debug;
+#[cfg(feature = "installer-core")]
+use log::info;
+use semver::{Version, VersionReq};
+use std::path::PathBuf;
+
+#[cfg(feature = "metrics-core")]
+use crate::config::MetricsExporter;
+use crate::config::{self, FfiConfig, UnloadPolicy};
+use crate::search_paths::search_paths;
+use crate::{DllError, FfiSession, VersionRequirement, DEFAULT_VERSION};
+
+/// Builder for an initialized [`FfiSession`].
+///
+/// ```ignore
+/// let session = FfiBuilder::new()
+///     .version_req(VersionReq::parse(">=0.1, <0.3")?)
+///     .search_path("vendor/go")
+///     .unload_policy(UnloadPolicy::Leak)
+///     .build()?;
+/// let sum = session.add_numbers(2, 3)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct FfiBuilder {
+    version: VersionRequirement,
+    search_paths: Vec<PathBuf>,
+    #[cfg(feature = "installer-core")]
+    auto_install: bool,
+    config: FfiConfig,
+}
+
+impl Default for FfiBuilder {
+    fn default() -> Self {
+        let config = FfiConfig::new().with_auto_cleanup(false);
+        #[cfg(feature = "metrics-core")]
+        let config = config.with_metrics(MetricsExporter::Recorder);
+        Self {
+            version: DEFAULT_VERSION.into(),
+            search_paths: Vec::new(),
+            #[cfg(feature = "installer-core")]
+            auto_install: false,
+            config,
+        }
+    }
+}
+
+impl FfiBuilder {
+    /// Accepts libraries compatible with [`DEFAULT_VERSION`], with default
+    /// settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts libraries compatible with `version`, or only `version`
+    /// itself with [`strict`](Self::strict)
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Accepts libraries matching `requirement`
+    pub fn version_req(mut self, requirement: VersionReq) -> Self {
+        self.version = requirement.into();
+        self
+    }
+
+    /// Whether a [`version`](Self::version) must match exactly
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config = self.config.with_strict_version(strict);
+        self
+    }
+
+    /// Adds a directory checked before the built-in locations; see
+    /// [`SearchPaths::prepend`](crate::SearchPaths::prepend)
+    pub fn search_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.search_paths.push(dir.into());
+        self
+    }
+
+    /// Installs the library first if it can't be found
+    #[cfg(feature = "installer-core")]
+    pub fn auto_install(mut self, auto_install: bool) -> Self {
+        self.auto_install = auto_install;
+        self
+    }
+
+    #[cfg(feature = "metrics-core")]
+    pub fn metrics(mut self, exporter: MetricsExporter) -> Self {
+        self.config = self.config.with_metrics(exporter);
+        self
+    }
+
+    pub fn unload_policy(mut self, policy: UnloadPolicy) -> Self {
+        self.config = self.config.with_unload_policy(policy);
+        self
+    }
+
+    /// Whether [`with_dll`](crate::with_dll) cleans up after its closure
+    /// once the session is gone
+    pub fn auto_cleanup(mut self, auto_cleanup: bool) -> Self {
+        self.config = self.config.with_auto_cleanup(auto_cleanup);
+        self
+    }
+
+    /// Replaces the runtime configuration, including what
+    /// `metrics`, [`unload_policy`](Self::unload_policy),
+    /// [`auto_cleanup`](Self::auto_cleanup) and [`strict`](Self::strict) set
+    /// before
+    pub fn config(mut self, config: FfiConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Applies the settings and initializes the library.
+    ///
+    /// The configuration replaces the process-wide one, and the search
+    /// directories are added to the installed [`SearchPaths`](crate::SearchPaths).
+    /// The library is loaded right away whatever the
+    /// [`LoadMode`](crate::LoadMode).
+    pub fn build(self) -> Result<FfiSession, DllError> {
+        if !self.search_paths.is_empty() {
+            let mut paths = search_paths();
+            for dir in self.search_paths {
+                if !paths.prepended().contains(&dir) {
+                    paths = paths.prepend(dir);
+                }
+            }
+            paths.install();
+        }
+
+        #[cfg(feature = "installer-core")]
+        if self.auto_install && !crate::is_dll_available() {
+            info!("Go library not found, installing it");
+            crate::install_dll()?;
+        }
+
+        debug!("Applying FFI configuration: {:?}", self.config);
+        config::set(self.config);
+        FfiSession::open(self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_ignore_features() {
+        let builder = FfiBuilder::new();
+        assert_eq!(
+            builder.version,
+            VersionRequirement::Version(DEFAULT_VERSION)
+        );
+        assert!(!builder.config.strict_version);
+        assert_eq!(builder.config.auto_cleanup, Some(false));
+        #[cfg(feature = "installer-core")]
+        assert!(!builder.auto_install);
+
+        let builder = builder
+            .search_path("a")
+            .search_path("b")
+            .unload_policy(UnloadPolicy::Leak);
+        assert_eq!(
+            builder.search_paths,
+            [PathBuf::from("a"), PathBuf::from("b")]
+        );
+        assert_eq!(builder.config.unload_policy, UnloadPolicy::Leak);
+    }
+}
@@ -0,0 +1,257 @@
+//! On-disk cache shared by extracted, shadow-copied and downloaded libraries.
+//!
+//! Every artifact lives in `<root>/<kind>/<entry>`, where an entry is either a
+//! single file or a directory holding one version of a library.
+
+use log::debug;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config;
+
+/// Environment variable that overrides the cache root
+pub const CACHE_DIR_ENV: &str = "RUST_GO_FFI_CACHE_DIR";
+
+/// Extracted copies of the embedded library
+pub(crate) const EMBEDDED: &str = "embedded";
+/// Side-by-side copies made by `reload_dll`
+pub(crate) const SHADOW: &str = "shadow";
+
+const KINDS: &[&str] = &[EMBEDDED, SHADOW];
+
+/// What a cache operation removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub entries_removed: u64,
+    pub bytes_freed: u64,
+    /// Entries left in place because they are in use
+    pub entries_skipped: u64,
+}
+
+/// Root directory of the cache.
+///
+/// [`FfiConfig::cache_dir`](crate::FfiConfig) takes precedence over
+/// `RUST_GO_FFI_CACHE_DIR`; the default is the user cache directory, or the
+/// temporary directory where none exists.
+pub fn cache_root() -> PathBuf {
+    if let Some(dir) = config::current().cache_dir {
+        return dir;
+    }
+    if let Some(dir) = std::env::var_os(CACHE_DIR_ENV).filter(|v| !v.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    default_root()
+}
+
+#[cfg(feature = "dirs")]
+fn default_root() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rust_go_ffi")
+}
+
+#[cfg(not(feature = "dirs"))]
+fn default_root() -> PathBuf {
+    std::env::temp_dir().join("rust_go_ffi")
+}
+
+/// Directory for one kind of artifact
+pub(crate) fn dir(kind: &str) -> PathBuf {
+    cache_root().join(kind)
+}
+
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+fn entry_size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|e| entry_size(&e.path()))
+                    .sum()
+            })
+            .unwrap_or(0),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+fn entries(root: &Path) -> Vec<Entry> {
+    let mut found = Vec::new();
+    for kind in KINDS {
+        let Ok(children) = fs::read_dir(root.join(kind)) else {
+            continue;
+        };
+        for child in children.filter_map(Result::ok) {
+            let path = child.path();
+            let modified = child
+                .metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            found.push(Entry {
+                size: entry_size(&path),
+                path,
+                modified,
+            });
+        }
+    }
+    found
+}
+
+/// Whether `entry` holds a library this process still uses
+fn is_in_use(entry: &Path, in_use: &[PathBuf]) -> bool {
+    in_use.iter().any(|p| p.starts_with(entry))
+}
+
+fn remove(entry: &Entry, stats: &mut CacheStats) {
+    let result = if entry.path.is_dir() {
+        fs::remove_dir_all(&entry.path)
+    } else {
+        fs::remove_file(&entry.path)
+    };
+    match result {
+        Ok(()) => {
+            stats.entries_removed += 1;
+            stats.bytes_freed += entry.size;
+        }
+        Err(e) => {
+            // Mapped libraries are locked on Windows.
+            debug!("Could not remove {}: {}", entry.path.display(), e);
+            stats.entries_skipped += 1;
+        }
+    }
+}
+
+fn clear_in(root: &Path, in_use: &[PathBuf]) -> CacheStats {
+    let mut stats = CacheStats::default();
+    for entry in entries(root) {
+        if is_in_use(&entry.path, in_use) {
+            stats.entries_skipped += 1;
+        } else {
+            remove(&entry, &mut stats);
+        }
+    }
+    stats
+}
+
+fn evict_in(root: &Path, max_bytes: u64, in_use: &[PathBuf]) -> CacheStats {
+    let mut stats = CacheStats::default();
+    let mut entries = entries(root);
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+
+    // Oldest first
+    entries.sort_by_key(|e| e.modified);
+    for entry in entries {
+        if total <= max_bytes {
+            break;
+        }
+        if is_in_use(&entry.path, in_use) {
+            continue;
+        }
+        let before = stats.entries_removed;
+        remove(&entry, &mut stats);
+        if stats.entries_removed > before {
+            total -= entry.size;
+        }
+    }
+    stats
+}
+
+/// Removes every cached artifact not used by this process
+pub fn clear_cache() -> io::Result<CacheStats> {
+    let root = cache_root();
+    if !root.exists() {
+        return Ok(CacheStats::default());
+    }
+    Ok(clear_in(&root, &crate::libraries_in_use()))
+}
+
+/// Removes the oldest cached artifacts until the cache fits in `max_bytes`
+pub fn evict_cache(max_bytes: u64) -> io::Result<CacheStats> {
+    let root = cache_root();
+    if !root.exists() {
+        return Ok(CacheStats::default());
+    }
+    Ok(evict_in(&root, max_bytes, &crate::libraries_in_use()))
+}
+
+/// Applies [`FfiConfig::cache_max_bytes`](crate::FfiConfig) after a new artifact was written
+pub(crate) fn enforce_limit(new_entry: &Path) {
+    let Some(max_bytes) = config::current().cache_max_bytes else {
+        return;
+    };
+    let mut in_use = crate::libraries_in_use();
+    in_use.push(new_entry.to_path_buf());
+    let stats = evict_in(&cache_root(), max_bytes, &in_use);
+    if stats.entries_removed > 0 {
+        debug!(
+            "Evicted {} cache entries ({} bytes)",
+            stats.entries_removed, stats.bytes_freed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn populate(root: &Path) {
+        let old = root.join(EMBEDDED).join("old");
+        fs::create_dir_all(&old).unwrap();
+        fs::write(old.join("go_lib.dll"), [0u8; 100]).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+
+        let new = root.join(EMBEDDED).join("new");
+        fs::create_dir_all(&new).unwrap();
+        fs::write(new.join("go_lib.dll"), [0u8; 100]).unwrap();
+
+        fs::create_dir_all(root.join(SHADOW)).unwrap();
+        fs::write(root.join(SHADOW).join("go_lib-1-1.dll"), [0u8; 50]).unwrap();
+    }
+
+    #[test]
+    fn test_clear_skips_in_use() {
+        let root = std::env::temp_dir().join("rust_go_ffi_cache_clear_test");
+        let _ = fs::remove_dir_all(&root);
+        populate(&root);
+
+        let in_use = vec![root.join(EMBEDDED).join("new").join("go_lib.dll")];
+        let stats = clear_in(&root, &in_use);
+        assert_eq!(stats.entries_removed, 2);
+        assert_eq!(stats.bytes_freed, 150);
+        assert_eq!(stats.entries_skipped, 1);
+        assert!(root.join(EMBEDDED).join("new").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_evict_removes_oldest_first() {
+        let root = std::env::temp_dir().join("rust_go_ffi_cache_evict_test");
+        let _ = fs::remove_dir_all(&root);
+        populate(&root);
+
+        let stats = evict_in(&root, 150, &[]);
+        assert_eq!(stats.entries_removed, 1);
+        assert!(!root.join(EMBEDDED).join("old").exists());
+        assert!(root.join(EMBEDDED).join("new").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_env_overrides_root() {
+        temp_env::with_var(CACHE_DIR_ENV, Some("custom_cache"), || {
+            assert_eq!(cache_root(), PathBuf::from("custom_cache"));
+            assert_eq!(dir(SHADOW), PathBuf::from("custom_cache").join(SHADOW));
+        });
+    }
+}
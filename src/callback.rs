@@ -0,0 +1,262 @@
+//! Rust callbacks the Go side can call into.
+//!
+//! [`register_callback`] boxes a closure, hands its address to Go together
+//! with a single `extern "C"` trampoline and returns a [`CallbackHandle`].
+//! The box doesn't move until the handle is dropped, which unregisters the
+//! callback on the Go side before freeing it; Go waits for running
+//! invocations first. The trampoline catches panics so they never unwind
+//! into Go. A failure is parked for the calling thread and turned into
+//! [`DllError::CallbackError`] by the wrapper whose call triggered it.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::ffi::{c_void, CString};
+use std::fmt;
+use std::os::raw::{c_int, c_longlong};
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+use std::sync::Arc;
+
+use log::{debug, warn};
+
+use crate::library::Library;
+use crate::{call_export, ensure_initialized, last_error, poison, DllError};
+
+/// What a callback returns; errors surface as [`DllError::CallbackError`]
+pub type CallbackResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+type CallbackFn = dyn Fn(&[u8]) -> CallbackResult + Send + Sync;
+
+struct Entry {
+    name: String,
+    f: Box<CallbackFn>,
+}
+
+/// Names with a live handle. Go keys callbacks by name, so a second
+/// registration would silently replace the first and be removed by its drop.
+static REGISTERED: Lazy<Mutex<BTreeSet<String>>> = Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+thread_local! {
+    static FAILURE: RefCell<Option<Box<dyn Error + Send + Sync>>> = const { RefCell::new(None) };
+}
+
+/// A panic inside a callback, reported as its error
+#[derive(Debug)]
+struct CallbackPanic {
+    callback: String,
+    message: String,
+}
+
+impl fmt::Display for CallbackPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "callback {} panicked: {}", self.callback, self.message)
+    }
+}
+
+impl Error for CallbackPanic {}
+
+fn callback_error(message: impl Into<String>) -> DllError {
+    DllError::CallbackError(message.into().into())
+}
+
+/// Takes the failure of a callback that ran on this thread, if any
+pub(crate) fn take_failure() -> Option<Box<dyn Error + Send + Sync>> {
+    FAILURE.with(|failure| failure.borrow_mut().take())
+}
+
+/// Records a callback failure for the current thread's next error check
+pub(crate) fn record_failure(error: Box<dyn Error + Send + Sync>) {
+    FAILURE.with(|failure| {
+        let mut failure = failure.borrow_mut();
+        // Keep the first failure; later ones are usually consequences.
+        if failure.is_none() {
+            *failure = Some(error);
+        } else {
+            debug!("Dropping further callback failure: {}", error);
+        }
+    });
+}
+
+unsafe extern "C" fn trampoline(
+    context: *mut c_void,
+    payload: *const u8,
+    length: c_longlong,
+) -> c_int {
+    let entry = &*(context as *const Entry);
+    let payload = if payload.is_null() || length <= 0 {
+        &[][..]
+    } else {
+        slice::from_raw_parts(payload, length as usize)
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| (entry.f)(payload))) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            record_failure(e);
+            1
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            record_failure(Box::new(CallbackPanic {
+                callback: entry.name.clone(),
+                message,
+            }));
+            2
+        }
+    }
+}
+
+/// Keeps a callback registered; dropping it unregisters the callback.
+///
+/// The handle must not be dropped from inside its own callback, since
+/// unregistering waits for running invocations to finish.
+pub struct CallbackHandle {
+    name: CString,
+    entry: *mut Entry,
+    library: Arc<Library>,
+}
+
+// The entry is only shared with the trampoline, which needs `Send + Sync`.
+unsafe impl Send for CallbackHandle {}
+unsafe impl Sync for CallbackHandle {}
+
+impl CallbackHandle {
+    pub fn name(&self) -> &str {
+        unsafe { &(*self.entry).name }
+    }
+
+    /// The copy of the library the callback is registered with
+    pub(crate) fn library(&self) -> &Arc<Library> {
+        &self.library
+    }
+}
+
+impl fmt::Debug for CallbackHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackHandle")
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+impl Drop for CallbackHandle {
+    fn drop(&mut self) {
+        let name = self.name().to_string();
+        let unregistered = self
+            .library
+            .symbol(&self.library.symbols.unregister_callback)
+            .and_then(|unregister| {
+                call_export("UnregisterCallback", || unsafe {
+                    unregister(self.name.as_ptr() as *mut _)
+                })
+            })
+            .and_then(|()| last_error::check(&self.library, "UnregisterCallback"));
+        match unregistered {
+            Ok(()) => {
+                drop(unsafe { Box::from_raw(self.entry) });
+                debug!("Unregistered callback {}", name);
+            }
+            // Go may still call it, so the closure has to outlive us.
+            Err(e) => warn!("Failed to unregister callback {}, leaking it: {}", name, e),
+        }
+        REGISTERED.lock().remove(&name);
+    }
+}
+
+/// Makes `f` callable from Go under `name` until the handle is dropped.
+///
+/// The closure gets the payload Go passes; returning an error or panicking
+/// makes the export that invoked it fail with [`DllError::CallbackError`].
+/// Go may call it from any thread.
+///
+/// ```ignore
+/// let _progress = register_callback("progress", |step| {
+///     println!("{}", String::from_utf8_lossy(step));
+///     Ok(())
+/// })?;
+/// count_with_progress(3)?;
+/// ```
+pub fn register_callback<F>(name: &str, f: F) -> Result<CallbackHandle, DllError>
+where
+    F: Fn(&[u8]) -> CallbackResult + Send + Sync + 'static,
+{
+    let library = ensure_initialized()?;
+    let register = library.symbol(&library.symbols.register_callback)?;
+    let c_name = CString::new(name)
+        .map_err(|_| callback_error(format!("callback name {:?} contains a NUL byte", name)))?;
+    if !REGISTERED.lock().insert(name.to_string()) {
+        return Err(callback_error(format!(
+            "callback {} is already registered",
+            name
+        )));
+    }
+
+    let entry = Box::into_raw(Box::new(Entry {
+        name: name.to_string(),
+        f: Box::new(f),
+    }));
+    let registered = call_export("RegisterCallback", || unsafe {
+        register(
+            c_name.as_ptr() as *mut _,
+            Some(trampoline),
+            entry as *mut c_void,
+        )
+    })
+    .map_err(poison)
+    .and_then(|()| last_error::check(&library, "RegisterCallback").map_err(poison));
+    if let Err(e) = registered {
+        drop(unsafe { Box::from_raw(entry) });
+        REGISTERED.lock().remove(name);
+        return Err(e);
+    }
+    debug!("Registered callback {}", name);
+    Ok(CallbackHandle {
+        name: c_name,
+        entry,
+        library,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    fn entry(f: impl Fn(&[u8]) -> CallbackResult + Send + Sync + 'static) -> Entry {
+        Entry {
+            name: "test".to_string(),
+            f: Box::new(f),
+        }
+    }
+
+    #[test]
+    fn test_trampoline_passes_payload() {
+        let entry = entry(|payload| match payload {
+            b"1/3" => Ok(()),
+            _ => Err("unexpected payload".into()),
+        });
+        let context = &entry as *const Entry as *mut c_void;
+        let code = unsafe { trampoline(context, b"1/3".as_ptr(), 3) };
+        assert_eq!(code, 0);
+        let code = unsafe { trampoline(context, ptr::null(), 0) };
+        assert_eq!(code, 1);
+        assert!(take_failure().is_some());
+    }
+
+    #[test]
+    fn test_trampoline_catches_panics() {
+        let entry = entry(|_| panic!("boom"));
+        let context = &entry as *const Entry as *mut c_void;
+        let code = unsafe { trampoline(context, ptr::null(), 0) };
+        assert_eq!(code, 2);
+        let failure = take_failure().unwrap();
+        assert_eq!(failure.to_string(), "callback test panicked: boom");
+        assert!(take_failure().is_none());
+    }
+}
@@ -0,0 +1,107 @@
+//! Cancellation of long-running Go calls.
+//!
+//! A [`CancelToken`] stands for a `context.Context` on the Go side. Wrappers
+//! of cancellable exports pass the token's id along, and the Go stub looks
+//! the context up and returns early once it ends. [`CancelToken::cancel`]
+//! works from any thread while the call is running, so a hung call can be
+//! abandoned without killing the process.
+
+use log::warn;
+use std::fmt;
+use std::os::raw::c_longlong;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::library::Library;
+use crate::{call_export, ensure_initialized, last_error, poison, DllError};
+
+struct Inner {
+    library: Arc<Library>,
+    id: c_longlong,
+    cancelled: AtomicBool,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let released = self
+            .library
+            .symbol(&self.library.symbols.release_context)
+            .and_then(|release| call_export("ReleaseContext", || unsafe { release(self.id) }))
+            .and_then(|()| last_error::check(&self.library, "ReleaseContext"));
+        if let Err(e) = released {
+            warn!("Failed to release cancel token {}: {}", self.id, e);
+        }
+    }
+}
+
+/// Cancels the Go calls it was passed to; clones share the same context
+#[derive(Clone)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+impl CancelToken {
+    /// Creates a context on the Go side of the active library
+    pub fn new() -> Result<Self, DllError> {
+        let library = ensure_initialized()?;
+        let new_context = library.symbol(&library.symbols.new_cancel_context)?;
+        let id = call_export("NewCancelContext", || unsafe { new_context() }).map_err(poison)?;
+        last_error::check(&library, "NewCancelContext").map_err(poison)?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                library,
+                id,
+                cancelled: AtomicBool::new(false),
+            }),
+        })
+    }
+
+    /// Cancels the context; running and later calls with this token return
+    /// [`DllError::Cancelled`]
+    pub fn cancel(&self) {
+        let inner = &self.inner;
+        if inner.cancelled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let cancelled = inner
+            .library
+            .symbol(&inner.library.symbols.cancel_context)
+            .and_then(|cancel| call_export("CancelContext", || unsafe { cancel(inner.id) }))
+            .and_then(|()| last_error::check(&inner.library, "CancelContext"));
+        if let Err(e) = cancelled {
+            warn!("Failed to cancel token {}: {}", inner.id, e);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// The id to pass to an export of `library`.
+    ///
+    /// Tokens belong to the library copy they were created with; a token
+    /// from before [`reload_dll`](crate::reload_dll) means nothing to the
+    /// new copy.
+    pub(crate) fn id_for(
+        &self,
+        library: &Arc<Library>,
+        export: &'static str,
+    ) -> Result<c_longlong, DllError> {
+        if !Arc::ptr_eq(&self.inner.library, library) {
+            return Err(DllError::CallFailed {
+                export: export.to_string(),
+                message: "the cancel token belongs to a library copy that was replaced".to_string(),
+            });
+        }
+        Ok(self.inner.id)
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("id", &self.inner.id)
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
@@ -0,0 +1,277 @@
+//! Capture of what the process writes to stdout and stderr.
+//!
+//! Go code such as `fmt.Println` in `GoFunction` writes straight to the
+//! process's standard streams, past any Rust logger, and breaks up structured
+//! log output. With
+//! [`FfiConfig::with_output_capture`](crate::FfiConfig::with_output_capture)
+//! both streams are pointed at pipes before the library loads; a thread per
+//! stream splits what arrives into lines and hands them to every
+//! [`subscribe_output`] receiver. Lines nobody subscribed to go to the
+//! original stream. Stderr lines go there in any case, so a Go crash trace
+//! still shows up where it would have without the capture.
+//!
+//! Only what the thread has read makes it anywhere: output still in a pipe
+//! when the process dies, as it does right after Go prints a fatal error, is
+//! lost.
+//!
+//! The redirect is done on the descriptors (and, on Windows, the standard
+//! handles), so it covers the whole process, Rust's own `println!` and
+//! loggers writing to stderr included, and lasts until it exits. The Go
+//! runtime on Windows looks up its standard handles once when it starts, so
+//! there the capture only reaches libraries loaded after it was set up.
+
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::thread;
+
+use crate::DllError;
+
+/// Lines buffered per subscriber before newer ones are dropped
+pub const OUTPUT_CAPACITY: usize = 1024;
+
+/// Which standard stream a line was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    fn fd(self) -> i32 {
+        match self {
+            OutputStream::Stdout => 1,
+            OutputStream::Stderr => 2,
+        }
+    }
+}
+
+/// One line of captured output, without its line ending
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+static SUBSCRIBERS: Lazy<Mutex<Vec<Sender<OutputLine>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+/// Streams redirected so far, so a failed install only retries the rest
+static INSTALLED: Mutex<Vec<OutputStream>> = Mutex::new(Vec::new());
+
+/// Redirects stdout and stderr into the pipes; later calls do nothing
+pub(crate) fn install() -> Result<(), DllError> {
+    let mut installed = INSTALLED.lock();
+    for stream in [OutputStream::Stdout, OutputStream::Stderr] {
+        if installed.contains(&stream) {
+            continue;
+        }
+        let (reader, original) = sys::redirect(stream.fd()).map_err(DllError::OutputCapture)?;
+        let name = match stream {
+            OutputStream::Stdout => "ffi-stdout",
+            OutputStream::Stderr => "ffi-stderr",
+        };
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || pump(stream, reader, original, &SUBSCRIBERS))
+            .map_err(DllError::OutputCapture)?;
+        installed.push(stream);
+    }
+    Ok(())
+}
+
+/// Reads `reader` line by line until it closes, writing the lines to
+/// `original` too if they are stderr or nobody is subscribed.
+///
+/// Must not log: the logger may write to the stream being read.
+fn pump(
+    stream: OutputStream,
+    reader: impl Read,
+    mut original: Option<impl Write>,
+    subscribers: &Mutex<Vec<Sender<OutputLine>>>,
+) {
+    let mut reader = BufReader::new(reader);
+    let mut raw = Vec::new();
+    loop {
+        raw.clear();
+        match reader.read_until(b'\n', &mut raw) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return,
+        }
+        let line = String::from_utf8_lossy(&raw)
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+
+        let mut subscribers = subscribers.lock();
+        // A subscriber that is behind misses the line but stays subscribed.
+        subscribers.retain(|tx| {
+            !matches!(
+                tx.try_send(OutputLine {
+                    stream,
+                    line: line.clone(),
+                }),
+                Err(TrySendError::Disconnected(_))
+            )
+        });
+        if subscribers.is_empty() || stream == OutputStream::Stderr {
+            drop(subscribers);
+            if let Some(original) = &mut original {
+                let _ = original.write_all(&raw).and_then(|()| original.flush());
+            }
+        }
+    }
+}
+
+/// Receives the lines written to stdout and stderr from now on.
+///
+/// Sets up the capture if the configuration didn't already; on Windows that
+/// misses a library that is loaded already. Every receiver has its own
+/// channel of [`OUTPUT_CAPACITY`] lines.
+pub fn subscribe_output() -> Result<Receiver<OutputLine>, DllError> {
+    install()?;
+    let (tx, rx) = crossbeam_channel::bounded(OUTPUT_CAPACITY);
+    SUBSCRIBERS.lock().push(tx);
+    Ok(rx)
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::FromRawFd;
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Points `fd` at a new pipe; returns the read end and a copy of what
+    /// `fd` was before
+    pub fn redirect(fd: i32) -> io::Result<(File, Option<File>)> {
+        let mut pipe = [0; 2];
+        unsafe {
+            check(libc::pipe(pipe.as_mut_ptr()))?;
+            let [read, write] = pipe;
+            let redirected = (|| {
+                // Child processes shouldn't hold the pipe open.
+                check(libc::fcntl(read, libc::F_SETFD, libc::FD_CLOEXEC))?;
+                let original = check(libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0))?;
+                if let Err(e) = check(libc::dup2(write, fd)) {
+                    libc::close(original);
+                    return Err(e);
+                }
+                Ok(original)
+            })();
+            libc::close(write);
+            match redirected {
+                Ok(original) => Ok((File::from_raw_fd(read), Some(File::from_raw_fd(original)))),
+                Err(e) => {
+                    libc::close(read);
+                    Err(e)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::FromRawHandle;
+    use std::ptr;
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::namedpipeapi::CreatePipe;
+    use winapi::um::processenv::{GetStdHandle, SetStdHandle};
+    use winapi::um::winbase::{STD_ERROR_HANDLE, STD_OUTPUT_HANDLE};
+
+    /// Points the standard handle and CRT descriptor `fd` at a new pipe;
+    /// returns the read end and the handle it had before, if any
+    pub fn redirect(fd: i32) -> io::Result<(File, Option<File>)> {
+        let std_handle = if fd == 1 {
+            STD_OUTPUT_HANDLE
+        } else {
+            STD_ERROR_HANDLE
+        };
+        let mut read = ptr::null_mut();
+        let mut write = ptr::null_mut();
+        unsafe {
+            if CreatePipe(&mut read, &mut write, ptr::null_mut(), 0) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let original = GetStdHandle(std_handle);
+            if SetStdHandle(std_handle, write) == 0 {
+                let e = io::Error::last_os_error();
+                CloseHandle(read);
+                CloseHandle(write);
+                return Err(e);
+            }
+            // C code writing through the CRT uses descriptors, not handles.
+            let crt = libc::open_osfhandle(write as libc::intptr_t, 0);
+            if crt >= 0 {
+                libc::dup2(crt, fd);
+            }
+            // A GUI process may have no console to fall back to.
+            let original = (!original.is_null() && original != INVALID_HANDLE_VALUE)
+                .then(|| File::from_raw_handle(original as _));
+            Ok((File::from_raw_handle(read as _), original))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pump_splits_lines() {
+        let subscribers = Mutex::new(Vec::new());
+        let (tx, rx) = crossbeam_channel::bounded(OUTPUT_CAPACITY);
+        subscribers.lock().push(tx);
+        pump(
+            OutputStream::Stdout,
+            &b"Hello from Go!\r\npartial"[..],
+            None::<Vec<u8>>,
+            &subscribers,
+        );
+        let lines: Vec<_> = rx.try_iter().map(|output| output.line).collect();
+        assert_eq!(lines, ["Hello from Go!", "partial"]);
+    }
+
+    #[test]
+    fn test_unsubscribed_lines_reach_original() {
+        let subscribers = Mutex::new(Vec::new());
+        let (tx, rx) = crossbeam_channel::bounded(OUTPUT_CAPACITY);
+        subscribers.lock().push(tx);
+        drop(rx);
+        let mut original = Vec::new();
+        pump(
+            OutputStream::Stderr,
+            &b"one\ntwo\n"[..],
+            Some(&mut original),
+            &subscribers,
+        );
+        assert_eq!(original, b"one\ntwo\n");
+        assert!(subscribers.lock().is_empty());
+    }
+
+    #[test]
+    fn test_stderr_is_teed() {
+        let subscribers = Mutex::new(Vec::new());
+        let (tx, rx) = crossbeam_channel::bounded(OUTPUT_CAPACITY);
+        subscribers.lock().push(tx);
+        let mut original = Vec::new();
+        pump(
+            OutputStream::Stderr,
+            &b"panic: boom\n"[..],
+            Some(&mut original),
+            &subscribers,
+        );
+        assert_eq!(original, b"panic: boom\n");
+        assert_eq!(rx.try_recv().unwrap().line, "panic: boom");
+    }
+}
@@ -0,0 +1,96 @@
+//! SHA-256 check of the library file against the build this crate was
+//! compiled with.
+//!
+//! `build.rs` hashes the DLL it built and bakes the digest into
+//! the crate as its manifest. [`install_dll`](crate::install_dll) refuses to
+//! copy a local build that doesn't match it, and with
+//! [`FfiConfig::with_checksum_verification`](crate::FfiConfig::with_checksum_verification)
+//! every load checks the file it is about to map, so a corrupted or swapped
+//! library fails with [`DllError::ChecksumMismatch`] instead of loading.
+//! A library downloaded by `installer-remote` is checked against the
+//! release's own `.sha256` asset, and only matches the manifest if the
+//! release was built from the same sources.
+
+use log::{debug, warn};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::DllError;
+
+/// Hex SHA-256 of the DLL built alongside the bindings
+const MANIFEST: &str = env!("GO_LIB_DLL_SHA256");
+
+/// The digest a library file must have, if the build recorded one
+pub(crate) fn expected() -> Option<&'static str> {
+    (!MANIFEST.is_empty()).then_some(MANIFEST)
+}
+
+/// Hex SHA-256 of the file at `path`
+pub(crate) fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn compare(path: &Path, expected: &str, found: String) -> Result<(), DllError> {
+    if found != expected {
+        return Err(DllError::ChecksumMismatch {
+            path: path.to_path_buf(),
+            expected: expected.to_string(),
+            found,
+        });
+    }
+    Ok(())
+}
+
+/// Checks the file at `path` against the manifest.
+///
+/// A build without a manifest can't be checked and only logs a warning.
+pub(crate) fn verify(path: &Path) -> Result<(), DllError> {
+    let Some(expected) = expected() else {
+        warn!("No DLL checksum was recorded at build time, skipping the check");
+        return Ok(());
+    };
+    let found =
+        sha256_file(path).map_err(|e| DllError::LoadError(format!("{}: {}", path.display(), e)))?;
+    compare(path, expected, found)?;
+    debug!("SHA-256 of {} matches the manifest", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_sha256_file() {
+        let path = std::env::temp_dir().join(format!("checksum_test_{}", std::process::id()));
+        fs::write(&path, b"abc").unwrap();
+        let digest = sha256_file(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            digest,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        assert!(compare(&path, &digest, digest.clone()).is_ok());
+        let error = compare(&path, &digest, "0".repeat(64)).unwrap_err();
+        assert!(matches!(error, DllError::ChecksumMismatch { .. }));
+        assert_eq!(error.code(), 1006);
+    }
+
+    #[test]
+    fn test_manifest_is_a_digest() {
+        if let Some(expected) = expected() {
+            assert_eq!(expected.len(), 64);
+            assert!(expected.bytes().all(|b| b.is_ascii_hexdigit()));
+        }
+    }
+}
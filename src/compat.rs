@@ -0,0 +1,144 @@
+//! Stable import paths for the global-function API.
+//!
+//! The crate root is going to change as instance contexts, pluggable backends
+//! and a reworked error type land. Code that imports from [`v1`] keeps
+//! compiling across those releases: whenever a root item is redesigned, a shim
+//! with the old signature is added here before the root item changes.
+//!
+//! The root [`DllError`](crate::DllError) already gained variants, so [`v1`]
+//! has its own copy of the original enum, and the functions there that fail
+//! return it.
+//!
+//! The module is enabled by the default `api-v1` feature.
+
+/// The original global-function API
+pub mod v1 {
+    pub use crate::{get_dll_path, is_dll_available, DllSearchPolicy, FfiConfig, SearchPaths};
+
+    use semver::Version;
+    use thiserror::Error;
+
+    /// The original error type.
+    ///
+    /// Its variants are fixed, so matching on them stays exhaustive. Root
+    /// errors it has no variant for become [`LoadError`](DllError::LoadError)
+    /// while locating and loading the library, and
+    /// [`InitializationError`](DllError::InitializationError) otherwise,
+    /// keeping their message.
+    #[derive(Debug, Error)]
+    pub enum DllError {
+        #[error("DLL not found")]
+        NotFound,
+        #[error("Failed to load DLL: {0}")]
+        LoadError(String),
+        #[cfg(feature = "installer-core")]
+        #[error("Failed to install DLL: {0}")]
+        InstallError(#[source] std::io::Error),
+        #[error("Version mismatch: expected {expected}, found {found}")]
+        VersionMismatch { expected: Version, found: Version },
+        #[error("Initialization error: {0}")]
+        InitializationError(String),
+    }
+
+    impl From<crate::DllError> for DllError {
+        fn from(error: crate::DllError) -> Self {
+            match error {
+                crate::DllError::NotFound => DllError::NotFound,
+                crate::DllError::LoadError(message) => DllError::LoadError(message),
+                #[cfg(feature = "installer-core")]
+                crate::DllError::InstallError(e) => DllError::InstallError(e),
+                crate::DllError::VersionMismatch { expected, found } => {
+                    DllError::VersionMismatch { expected, found }
+                }
+                crate::DllError::InitializationError(message) => {
+                    DllError::InitializationError(message)
+                }
+                other if (1000..2000).contains(&other.code()) => {
+                    DllError::LoadError(other.to_string())
+                }
+                other => DllError::InitializationError(other.to_string()),
+            }
+        }
+    }
+
+    impl From<DllError> for crate::DllError {
+        fn from(error: DllError) -> Self {
+            match error {
+                DllError::NotFound => crate::DllError::NotFound,
+                DllError::LoadError(message) => crate::DllError::LoadError(message),
+                #[cfg(feature = "installer-core")]
+                DllError::InstallError(e) => crate::DllError::InstallError(e),
+                DllError::VersionMismatch { expected, found } => {
+                    crate::DllError::VersionMismatch { expected, found }
+                }
+                DllError::InitializationError(message) => {
+                    crate::DllError::InitializationError(message)
+                }
+            }
+        }
+    }
+
+    /// Initializes the FFI system and requires exactly `required_version`.
+    ///
+    /// Any other library version fails with
+    /// [`DllError::VersionMismatch`], whatever
+    /// [`FfiConfig::with_strict_version`] says; the root
+    /// [`initialize`](crate::initialize) accepts compatible versions.
+    pub fn initialize(required_version: Version) -> Result<(), DllError> {
+        Ok(crate::initialize_as(required_version.into(), true)?)
+    }
+
+    /// [`initialize_with_config`](crate::initialize_with_config) with
+    /// strict version matching for this call, as [`initialize`] does.
+    ///
+    /// With [`LoadMode::Lazy`](crate::LoadMode::Lazy) the version is only
+    /// checked on first use, so strict matching is stored in the
+    /// configuration instead and applies to every later load too.
+    pub fn initialize_with_config(
+        required_version: Version,
+        config: FfiConfig,
+    ) -> Result<(), DllError> {
+        if config.load_mode == crate::LoadMode::Lazy {
+            let config = config.with_strict_version(true);
+            return Ok(crate::initialize_with_config(required_version, config)?);
+        }
+        crate::config::set(config);
+        Ok(crate::initialize_as(required_version.into(), true)?)
+    }
+
+    pub fn load_dll() -> Result<(), DllError> {
+        Ok(crate::load_dll()?)
+    }
+
+    pub fn verify_dll() -> Result<(), DllError> {
+        Ok(crate::verify_dll()?)
+    }
+
+    pub fn get_version() -> Result<Version, DllError> {
+        Ok(crate::get_version()?)
+    }
+
+    pub fn add_numbers(a: i32, b: i32) -> Result<i32, DllError> {
+        Ok(crate::add_numbers(a, b)?)
+    }
+
+    pub fn go_function() -> Result<(), DllError> {
+        Ok(crate::go_function()?)
+    }
+
+    pub fn cleanup() -> Result<(), DllError> {
+        Ok(crate::cleanup()?)
+    }
+
+    pub fn with_dll<F, T>(f: F) -> Result<T, DllError>
+    where
+        F: FnOnce() -> Result<T, DllError>,
+    {
+        Ok(crate::with_dll(|| Ok(f()?))?)
+    }
+
+    #[cfg(feature = "installer-core")]
+    pub fn install_dll() -> Result<(), DllError> {
+        Ok(crate::install_dll()?)
+    }
+}
@@ -0,0 +1,144 @@
+//! Compatibility matrix between crate releases and Go library releases.
+//!
+//! The table is generated from `compat.toml` at build time.
+
+use semver::{Version, VersionReq};
+
+use crate::DllError;
+
+/// One row of the compatibility matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompatEntry {
+    /// Crate versions this row applies to
+    pub crate_req: &'static str,
+    /// DLL versions those crate versions support
+    pub dll_req: &'static str,
+    /// ABI revision shared by those releases
+    pub abi: u32,
+}
+
+impl CompatEntry {
+    fn crate_matches(&self, version: &Version) -> bool {
+        VersionReq::parse(self.crate_req).is_ok_and(|req| req.matches(version))
+    }
+
+    fn dll_matches(&self, version: &Version) -> bool {
+        VersionReq::parse(self.dll_req).is_ok_and(|req| req.matches(version))
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/compat_matrix.rs"));
+
+/// Returns the embedded compatibility matrix
+pub fn compatibility_matrix() -> &'static [CompatEntry] {
+    COMPAT_MATRIX
+}
+
+/// Version of this crate
+pub(crate) fn crate_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("crate version is valid semver")
+}
+
+/// Checks a DLL version against the embedded matrix
+pub(crate) fn check(dll_version: &Version) -> Result<(), DllError> {
+    check_against(COMPAT_MATRIX, &crate_version(), dll_version)
+}
+
+fn check_against(
+    matrix: &[CompatEntry],
+    crate_version: &Version,
+    dll_version: &Version,
+) -> Result<(), DllError> {
+    // Rows that know about this DLL release
+    let dll_rows: Vec<&CompatEntry> = matrix
+        .iter()
+        .filter(|e| e.dll_matches(dll_version))
+        .collect();
+    if dll_rows.iter().any(|e| e.crate_matches(crate_version)) {
+        return Ok(());
+    }
+
+    let guidance = if !dll_rows.is_empty() {
+        let required: Vec<&str> = dll_rows.iter().map(|e| e.crate_req).collect();
+        format!(
+            "DLL {} requires crate {}, but this is crate {}",
+            dll_version,
+            required.join(" or "),
+            crate_version
+        )
+    } else {
+        let supported: Vec<&str> = matrix
+            .iter()
+            .filter(|e| e.crate_matches(crate_version))
+            .map(|e| e.dll_req)
+            .collect();
+        if supported.is_empty() {
+            // Nothing is known about either side; leave it to the version check.
+            return Ok(());
+        }
+        format!(
+            "crate {} supports DLL {}, but found DLL {}",
+            crate_version,
+            supported.join(" or "),
+            dll_version
+        )
+    };
+
+    Err(DllError::Incompatible {
+        dll_version: dll_version.clone(),
+        guidance,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATRIX: &[CompatEntry] = &[
+        CompatEntry {
+            crate_req: ">=0.1.0, <0.2.0",
+            dll_req: ">=0.1.0, <0.2.0",
+            abi: 1,
+        },
+        CompatEntry {
+            crate_req: ">=0.2.0",
+            dll_req: ">=0.3.0, <0.4.0",
+            abi: 2,
+        },
+    ];
+
+    #[test]
+    fn test_embedded_matrix_covers_crate() {
+        assert!(compatibility_matrix()
+            .iter()
+            .any(|e| e.crate_matches(&crate_version())));
+    }
+
+    #[test]
+    fn test_compatible_pair() {
+        assert!(check_against(MATRIX, &Version::new(0, 1, 0), &Version::new(0, 1, 3)).is_ok());
+    }
+
+    #[test]
+    fn test_newer_dll_requires_newer_crate() {
+        match check_against(MATRIX, &Version::new(0, 1, 0), &Version::new(0, 3, 1)) {
+            Err(DllError::Incompatible { guidance, .. }) => {
+                assert_eq!(
+                    guidance,
+                    "DLL 0.3.1 requires crate >=0.2.0, but this is crate 0.1.0"
+                );
+            }
+            other => panic!("Expected Incompatible, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unknown_dll() {
+        match check_against(MATRIX, &Version::new(0, 1, 0), &Version::new(9, 0, 0)) {
+            Err(DllError::Incompatible { guidance, .. }) => {
+                assert!(guidance.contains("supports DLL >=0.1.0, <0.2.0"));
+            }
+            other => panic!("Expected Incompatible, got {:?}", other),
+        }
+    }
+}
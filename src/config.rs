@@ -0,0 +1,807 @@
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+#[cfg(feature = "metrics-core")]
+use std::fmt;
+#[cfg(feature = "metrics-core")]
+use std::net::SocketAddr;
+use std::path::PathBuf;
+#[cfg(feature = "metrics-core")]
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "installer-core")]
+use crate::installer::{InstallSource, InstallTarget};
+#[cfg(feature = "installer-remote")]
+use crate::release::DownloadConfig;
+#[cfg(feature = "signed-dll")]
+use crate::signature::DllSignature;
+
+/// Largest string, slice or payload passed into Go in one call unless configured otherwise
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// Calls [`recent_calls`](crate::recent_calls) keeps unless configured
+/// otherwise
+pub const DEFAULT_AUDIT_CAPACITY: usize = 256;
+
+/// Time [`cleanup`](crate::cleanup) gives the library's `GoShutdown` export
+/// unless configured otherwise
+pub const DEFAULT_SHUTDOWN_DEADLINE: Duration = Duration::from_secs(5);
+
+/// How often the `runtime-stats` feature publishes the Go runtime's figures
+/// unless configured otherwise
+#[cfg(feature = "runtime-stats")]
+pub const DEFAULT_RUNTIME_STATS_INTERVAL: Duration = Duration::from_secs(10);
+
+lazy_static::lazy_static! {
+    static ref CONFIG: RwLock<FfiConfig> = RwLock::new(FfiConfig::default());
+}
+
+/// Where the Windows loader may look for the Go DLL and its dependencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DllSearchPolicy {
+    /// Only the DLL's own directory and System32
+    /// (`LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_SYSTEM32`)
+    #[default]
+    Restricted,
+    /// The DLL's own directory plus the application directory, System32 and
+    /// directories registered with `AddDllDirectory`
+    /// (`LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_DEFAULT_DIRS`)
+    SafeDefaults,
+    /// The standard `LoadLibraryW` search order, including the current directory and PATH
+    Legacy,
+}
+
+/// When the Go library is loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadMode {
+    /// [`initialize_with_config`](crate::initialize_with_config) loads and
+    /// initializes the library right away
+    #[default]
+    Eager,
+    /// Nothing is loaded until the first safe wrapper is called, which then
+    /// initializes with the version passed to `initialize_with_config`
+    Lazy,
+}
+
+/// Whether a loaded copy of the Go library may be unmapped again.
+///
+/// The Go runtime starts threads and installs signal handlers that outlive
+/// any call, and it cannot be stopped. Unmapping the library underneath it
+/// crashes the process sooner or later, so by default a copy that has been
+/// called into is kept mapped until the process exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnloadPolicy {
+    /// Always unmap on [`cleanup`](crate::cleanup) and when a reload replaces the copy
+    Unload,
+    /// Never unmap
+    Leak,
+    /// Unmap only copies whose exports were never called
+    #[default]
+    LeakAfterFirstCall,
+}
+
+impl UnloadPolicy {
+    /// Whether a copy may be unmapped, given whether it was ever called into
+    pub(crate) fn allows_unload(self, called: bool) -> bool {
+        match self {
+            UnloadPolicy::Unload => true,
+            UnloadPolicy::Leak => false,
+            UnloadPolicy::LeakAfterFirstCall => !called,
+        }
+    }
+}
+
+/// How wrappers narrow integer results that Go computes in a wider type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerPolicy {
+    /// Fail with [`DllError::ValueOutOfRange`](crate::DllError::ValueOutOfRange)
+    #[default]
+    Checked,
+    /// Keep the low bits like `as`, for callers that know their values fit
+    /// and can't afford the check
+    Wrapping,
+}
+
+/// What the scalar wrappers do with NaN and infinite floats, in arguments
+/// and results alike
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatPolicy {
+    /// Fail with [`DllError::FloatOutOfRange`](crate::DllError::FloatOutOfRange)
+    #[default]
+    Reject,
+    /// Pass them through unchanged
+    Allow,
+}
+
+/// What a call does when every executor thread is busy and the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueuePolicy {
+    /// Wait for a queue slot
+    #[default]
+    Block,
+    /// Fail with [`DllError::ExecutorSaturated`](crate::DllError::ExecutorSaturated)
+    Reject,
+}
+
+/// Where the metrics of the `metrics-core` feature go; see
+/// [`MetricsSink`](crate::MetricsSink)
+#[cfg(feature = "metrics-core")]
+#[derive(Clone)]
+pub enum MetricsExporter {
+    /// The recorder the application installed for the `metrics` facade, if
+    /// any
+    Recorder,
+    /// A Prometheus listener serving them on `addr`
+    #[cfg(feature = "metrics-prometheus")]
+    Prometheus(SocketAddr),
+    /// A StatsD server at `addr`
+    Statsd(SocketAddr),
+    /// An OpenTelemetry collector at this OTLP/gRPC endpoint, which also
+    /// receives a span per call
+    #[cfg(feature = "otel")]
+    Otlp(String),
+    /// Nowhere
+    Noop,
+    /// A sink of the application's own
+    Custom(Arc<dyn crate::MetricsSink>),
+}
+
+#[cfg(feature = "metrics-core")]
+impl fmt::Debug for MetricsExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricsExporter::Recorder => f.write_str("Recorder"),
+            #[cfg(feature = "metrics-prometheus")]
+            MetricsExporter::Prometheus(addr) => f.debug_tuple("Prometheus").field(addr).finish(),
+            MetricsExporter::Statsd(addr) => f.debug_tuple("Statsd").field(addr).finish(),
+            #[cfg(feature = "otel")]
+            MetricsExporter::Otlp(endpoint) => f.debug_tuple("Otlp").field(endpoint).finish(),
+            MetricsExporter::Noop => f.write_str("Noop"),
+            MetricsExporter::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Custom sinks are equal only to themselves.
+#[cfg(feature = "metrics-core")]
+impl PartialEq for MetricsExporter {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MetricsExporter::Recorder, MetricsExporter::Recorder)
+            | (MetricsExporter::Noop, MetricsExporter::Noop) => true,
+            #[cfg(feature = "metrics-prometheus")]
+            (MetricsExporter::Prometheus(a), MetricsExporter::Prometheus(b)) => a == b,
+            (MetricsExporter::Statsd(a), MetricsExporter::Statsd(b)) => a == b,
+            #[cfg(feature = "otel")]
+            (MetricsExporter::Otlp(a), MetricsExporter::Otlp(b)) => a == b,
+            (MetricsExporter::Custom(a), MetricsExporter::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "metrics-core")]
+impl Eq for MetricsExporter {}
+
+/// A fixed pool of threads that makes every call into Go.
+///
+/// The Go runtime parks an OS thread for each blocked cgo call, so calling
+/// from hundreds of threads at once makes it create hundreds more. Routing
+/// calls through the pool bounds both sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutorConfig {
+    pub threads: usize,
+    /// Calls that may wait for a thread before [`QueuePolicy`] applies
+    pub queue_capacity: usize,
+    pub queue_policy: QueuePolicy,
+}
+
+impl ExecutorConfig {
+    /// `threads` threads with a queue of four calls per thread
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        Self {
+            threads,
+            queue_capacity: 4 * threads,
+            queue_policy: QueuePolicy::default(),
+        }
+    }
+
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity;
+        self
+    }
+
+    pub fn with_queue_policy(mut self, policy: QueuePolicy) -> Self {
+        self.queue_policy = policy;
+        self
+    }
+}
+
+/// When the circuit breaker opens and how it recovers; see
+/// [`circuit_state`](crate::circuit_state)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failed calls that open the breaker
+    pub failure_threshold: u32,
+    /// How long it stays open before probing
+    pub open_for: Duration,
+    /// Calls let through while probing
+    pub half_open_probes: u32,
+}
+
+impl CircuitBreakerConfig {
+    pub fn new(failure_threshold: u32, open_for: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            open_for,
+            half_open_probes: 1,
+        }
+    }
+
+    pub fn with_half_open_probes(mut self, probes: u32) -> Self {
+        self.half_open_probes = probes.max(1);
+        self
+    }
+}
+
+/// Token bucket for calls to one export; see
+/// [`FfiConfig::with_rate_limit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Calls allowed per `per` on average
+    pub calls: u32,
+    pub per: Duration,
+    /// Calls allowed back to back after a quiet period
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// `calls` per `per`, all of which may come at once
+    pub fn new(calls: u32, per: Duration) -> Self {
+        let calls = calls.max(1);
+        Self {
+            calls,
+            per,
+            burst: calls,
+        }
+    }
+
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+}
+
+/// How often and how patiently a failing operation is tried again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries in total, the first one included; 1 disables retries
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubles with every further one
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Randomize each wait within its upper half, so callers that failed
+    /// together don't retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Four tries within about a second
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Self::default()
+        }
+    }
+
+    /// A single try
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+}
+
+/// Runtime configuration for the FFI layer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FfiConfig {
+    /// Search policy used when loading the DLL
+    pub search_policy: DllSearchPolicy,
+    /// Directory passed to `SetDllDirectoryW` before loading.
+    ///
+    /// This also removes the current directory from the legacy search order
+    /// used by dependencies the Go runtime loads later on.
+    pub dll_directory: Option<PathBuf>,
+    /// Root of the artifact cache; see [`cache_root`](crate::cache_root)
+    pub cache_dir: Option<PathBuf>,
+    /// Evict the oldest cached artifacts once the cache grows past this size
+    pub cache_max_bytes: Option<u64>,
+    /// String handed to the library's `GoInit` export, if it has one
+    pub go_init_config: Option<String>,
+    /// Deadline [`cleanup`](crate::cleanup) passes to `GoShutdown`;
+    /// [`DEFAULT_SHUTDOWN_DEADLINE`] when unset
+    pub shutdown_deadline: Option<Duration>,
+    /// Whether to load at initialization or on first use
+    pub load_mode: LoadMode,
+    /// Whether released copies of the library are unmapped
+    pub unload_policy: UnloadPolicy,
+    /// Largest non-scalar argument passed into Go in one call;
+    /// [`DEFAULT_MAX_PAYLOAD_BYTES`] when unset
+    pub max_payload_bytes: Option<usize>,
+    /// Per-export overrides of `max_payload_bytes`
+    pub export_payload_limits: BTreeMap<String, usize>,
+    /// Handling of NaN and infinite floats
+    pub float_policy: FloatPolicy,
+    /// Handling of integer results that don't fit the wrapper's return type
+    pub integer_policy: IntegerPolicy,
+    /// Pool that makes the calls; without one every call runs on its
+    /// caller's thread
+    pub executor: Option<ExecutorConfig>,
+    /// Most [`with_dll`](crate::with_dll) closures and async wrapper calls
+    /// running at once; unlimited when unset
+    pub max_concurrent_calls: Option<usize>,
+    /// Stops calling into a library that keeps failing; off when unset
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Per-export token buckets, keyed by Go export name such as `AddNumbers`
+    pub rate_limits: BTreeMap<String, RateLimit>,
+    /// Whether stdout and stderr are captured for
+    /// [`subscribe_output`](crate::subscribe_output) before the library
+    /// loads
+    pub capture_output: bool,
+    /// Where [`install_dll`](crate::install_dll) puts the library, and the
+    /// first installation directory [`get_dll_path`](crate::get_dll_path)
+    /// checks
+    #[cfg(feature = "installer-core")]
+    pub install_target: InstallTarget,
+    /// Where [`install_dll`](crate::install_dll) gets the library from
+    #[cfg(feature = "installer-core")]
+    pub install_source: InstallSource,
+    /// GitHub repository, as `owner/name`, that
+    /// [`install_dll`](crate::install_dll) downloads releases from when there
+    /// is no local build
+    #[cfg(feature = "installer-remote")]
+    pub release_repository: Option<String>,
+    /// URL [`check_for_update`](crate::check_for_update) reads the latest
+    /// release from; the `releases/latest` endpoint of the release
+    /// repository when unset
+    #[cfg(feature = "installer-remote")]
+    pub update_endpoint: Option<String>,
+    /// Proxy, extra root certificates and token of release downloads and
+    /// update checks
+    #[cfg(feature = "installer-remote")]
+    pub download: DownloadConfig,
+    /// Calls kept for [`recent_calls`](crate::recent_calls); 0 turns the
+    /// audit log off, [`DEFAULT_AUDIT_CAPACITY`] when unset
+    pub audit_capacity: Option<usize>,
+    /// Retries of loads that fail with
+    /// [`DllError::LoadError`](crate::DllError::LoadError)
+    pub load_retry: RetryPolicy,
+    /// Whether [`with_dll`](crate::with_dll) cleans up after its closure;
+    /// follows the `auto-cleanup` feature when unset
+    pub auto_cleanup: Option<bool>,
+    /// Whether a [`Version`](semver::Version) passed to
+    /// [`initialize`](crate::initialize) must match the library exactly
+    /// rather than semver-compatibly
+    pub strict_version: bool,
+    /// Whether every load checks the library's SHA-256 against the build
+    /// this crate was compiled with
+    pub verify_checksum: bool,
+    /// Signature every load checks the library for; unsigned libraries load
+    /// when unset
+    #[cfg(feature = "signed-dll")]
+    pub require_signed_dll: Option<DllSignature>,
+    /// Exporter set up at initialization; the application's recorder when
+    /// unset
+    #[cfg(feature = "metrics-core")]
+    pub metrics: Option<MetricsExporter>,
+    /// How often the Go runtime's figures are published;
+    /// [`DEFAULT_RUNTIME_STATS_INTERVAL`] when unset
+    #[cfg(feature = "runtime-stats")]
+    pub runtime_stats_interval: Option<Duration>,
+}
+
+impl FfiConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_search_policy(mut self, policy: DllSearchPolicy) -> Self {
+        self.search_policy = policy;
+        self
+    }
+
+    pub fn with_dll_directory(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dll_directory = Some(dir.into());
+        self
+    }
+
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_cache_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.cache_max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_load_mode(mut self, mode: LoadMode) -> Self {
+        self.load_mode = mode;
+        self
+    }
+
+    pub fn with_unload_policy(mut self, policy: UnloadPolicy) -> Self {
+        self.unload_policy = policy;
+        self
+    }
+
+    pub fn with_max_payload_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_payload_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_export_payload_limit(
+        mut self,
+        export: impl Into<String>,
+        max_bytes: usize,
+    ) -> Self {
+        self.export_payload_limits.insert(export.into(), max_bytes);
+        self
+    }
+
+    /// Largest non-scalar argument `export` accepts in one call
+    pub fn payload_limit(&self, export: &str) -> usize {
+        self.export_payload_limits
+            .get(export)
+            .copied()
+            .or(self.max_payload_bytes)
+            .unwrap_or(DEFAULT_MAX_PAYLOAD_BYTES)
+    }
+
+    pub fn with_float_policy(mut self, policy: FloatPolicy) -> Self {
+        self.float_policy = policy;
+        self
+    }
+
+    pub fn with_integer_policy(mut self, policy: IntegerPolicy) -> Self {
+        self.integer_policy = policy;
+        self
+    }
+
+    pub fn with_executor(mut self, executor: ExecutorConfig) -> Self {
+        self.executor = Some(executor);
+        self
+    }
+
+    pub fn with_max_concurrent_calls(mut self, limit: usize) -> Self {
+        self.max_concurrent_calls = Some(limit);
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Limits calls to `export`; ones over the limit fail with
+    /// [`DllError::RateLimited`](crate::DllError::RateLimited)
+    pub fn with_rate_limit(mut self, export: impl Into<String>, limit: RateLimit) -> Self {
+        self.rate_limits.insert(export.into(), limit);
+        self
+    }
+
+    pub fn with_audit_capacity(mut self, capacity: usize) -> Self {
+        self.audit_capacity = Some(capacity);
+        self
+    }
+
+    /// Captures what the process writes to stdout and stderr, including
+    /// Go's `fmt.Println`, before the library loads; see
+    /// [`subscribe_output`](crate::subscribe_output)
+    pub fn with_output_capture(mut self, capture: bool) -> Self {
+        self.capture_output = capture;
+        self
+    }
+
+    #[cfg(feature = "installer-core")]
+    pub fn with_install_target(mut self, target: InstallTarget) -> Self {
+        self.install_target = target;
+        self
+    }
+
+    #[cfg(feature = "installer-core")]
+    pub fn with_install_source(mut self, source: InstallSource) -> Self {
+        self.install_source = source;
+        self
+    }
+
+    /// Downloads missing libraries from the releases of `repository`
+    /// (`owner/name`) instead of [`DEFAULT_RELEASE_REPOSITORY`](crate::DEFAULT_RELEASE_REPOSITORY)
+    #[cfg(feature = "installer-remote")]
+    pub fn with_release_repository(mut self, repository: impl Into<String>) -> Self {
+        self.release_repository = Some(repository.into());
+        self
+    }
+
+    #[cfg(feature = "installer-remote")]
+    pub fn with_update_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.update_endpoint = Some(url.into());
+        self
+    }
+
+    /// Downloads and checks for updates through a proxy, with extra trusted
+    /// roots or with a token, as `download` says
+    #[cfg(feature = "installer-remote")]
+    pub fn with_download_config(mut self, download: DownloadConfig) -> Self {
+        self.download = download;
+        self
+    }
+
+    pub fn with_load_retry(mut self, policy: RetryPolicy) -> Self {
+        self.load_retry = policy;
+        self
+    }
+
+    pub fn with_strict_version(mut self, strict: bool) -> Self {
+        self.strict_version = strict;
+        self
+    }
+
+    /// Fails loads with
+    /// [`DllError::ChecksumMismatch`](crate::DllError::ChecksumMismatch)
+    /// unless the library file is the one built alongside this crate
+    pub fn with_checksum_verification(mut self, verify: bool) -> Self {
+        self.verify_checksum = verify;
+        self
+    }
+
+    /// Fails loads with
+    /// [`DllError::UntrustedDll`](crate::DllError::UntrustedDll) unless the
+    /// library carries `signature`
+    #[cfg(feature = "signed-dll")]
+    pub fn with_require_signed_dll(mut self, signature: DllSignature) -> Self {
+        self.require_signed_dll = Some(signature);
+        self
+    }
+
+    pub fn with_auto_cleanup(mut self, auto_cleanup: bool) -> Self {
+        self.auto_cleanup = Some(auto_cleanup);
+        self
+    }
+
+    #[cfg(feature = "metrics-core")]
+    pub fn with_metrics(mut self, exporter: MetricsExporter) -> Self {
+        self.metrics = Some(exporter);
+        self
+    }
+
+    pub fn with_go_init_config(mut self, config: impl Into<String>) -> Self {
+        self.go_init_config = Some(config.into());
+        self
+    }
+
+    pub fn with_shutdown_deadline(mut self, deadline: Duration) -> Self {
+        self.shutdown_deadline = Some(deadline);
+        self
+    }
+
+    /// Publishes the Go runtime's figures every `interval`; each read stops
+    /// the Go world briefly
+    #[cfg(feature = "runtime-stats")]
+    pub fn with_runtime_stats_interval(mut self, interval: Duration) -> Self {
+        self.runtime_stats_interval = Some(interval);
+        self
+    }
+}
+
+/// Returns the active float policy without cloning the whole configuration
+pub(crate) fn float_policy() -> FloatPolicy {
+    CONFIG.read().float_policy
+}
+
+/// Returns the active integer policy without cloning the whole configuration
+pub(crate) fn integer_policy() -> IntegerPolicy {
+    CONFIG.read().integer_policy
+}
+
+/// Returns the active executor configuration without cloning the whole
+/// configuration
+pub(crate) fn executor() -> Option<ExecutorConfig> {
+    CONFIG.read().executor
+}
+
+/// Returns the active concurrency limit without cloning the whole
+/// configuration
+pub(crate) fn max_concurrent_calls() -> Option<usize> {
+    CONFIG.read().max_concurrent_calls
+}
+
+/// Returns the active circuit breaker configuration without cloning the
+/// whole configuration
+pub(crate) fn circuit_breaker() -> Option<CircuitBreakerConfig> {
+    CONFIG.read().circuit_breaker
+}
+
+/// Returns the rate limit for `export` without cloning the whole
+/// configuration
+pub(crate) fn rate_limit(export: &str) -> Option<RateLimit> {
+    let config = CONFIG.read();
+    if config.rate_limits.is_empty() {
+        return None;
+    }
+    config.rate_limits.get(export).copied()
+}
+
+/// Returns how many calls the audit log keeps
+pub(crate) fn audit_capacity() -> usize {
+    CONFIG
+        .read()
+        .audit_capacity
+        .unwrap_or(DEFAULT_AUDIT_CAPACITY)
+}
+
+/// Returns the repository to download releases from: the environment
+/// override, then the configuration, then the default
+#[cfg(feature = "installer-remote")]
+pub(crate) fn release_repository() -> String {
+    std::env::var(crate::RELEASE_REPOSITORY_ENV)
+        .ok()
+        .filter(|repository| !repository.is_empty())
+        .or_else(|| CONFIG.read().release_repository.clone())
+        .unwrap_or_else(|| crate::DEFAULT_RELEASE_REPOSITORY.to_string())
+}
+
+/// Returns the URL to read the latest release from
+#[cfg(feature = "installer-remote")]
+pub(crate) fn update_endpoint() -> String {
+    let configured = CONFIG.read().update_endpoint.clone();
+    configured.unwrap_or_else(|| crate::release::latest_endpoint(&release_repository()))
+}
+
+/// Returns how downloads reach the release server
+#[cfg(feature = "installer-remote")]
+pub(crate) fn download_config() -> DownloadConfig {
+    CONFIG.read().download.clone()
+}
+
+/// Returns whether stdout and stderr are captured
+pub(crate) fn capture_output() -> bool {
+    CONFIG.read().capture_output
+}
+
+/// Returns whether versions must match exactly
+pub(crate) fn strict_version() -> bool {
+    CONFIG.read().strict_version
+}
+
+/// Returns where the library is installed
+#[cfg(feature = "installer-core")]
+pub(crate) fn install_target() -> InstallTarget {
+    CONFIG.read().install_target.clone()
+}
+
+#[cfg(feature = "installer-core")]
+pub(crate) fn install_source() -> InstallSource {
+    CONFIG.read().install_source.clone()
+}
+
+/// Returns how often the Go runtime's figures are published
+#[cfg(feature = "runtime-stats")]
+pub(crate) fn runtime_stats_interval() -> Duration {
+    CONFIG
+        .read()
+        .runtime_stats_interval
+        .unwrap_or(DEFAULT_RUNTIME_STATS_INTERVAL)
+}
+
+/// Returns the deadline `cleanup` gives `GoShutdown`
+pub(crate) fn shutdown_deadline() -> Duration {
+    CONFIG
+        .read()
+        .shutdown_deadline
+        .unwrap_or(DEFAULT_SHUTDOWN_DEADLINE)
+}
+
+/// Returns whether `with_dll` cleans up after its closure
+pub(crate) fn auto_cleanup() -> bool {
+    CONFIG
+        .read()
+        .auto_cleanup
+        .unwrap_or(cfg!(feature = "auto-cleanup"))
+}
+
+/// Returns the configured metrics exporter without cloning the whole
+/// configuration
+#[cfg(feature = "metrics-core")]
+pub(crate) fn metrics() -> Option<MetricsExporter> {
+    CONFIG.read().metrics.clone()
+}
+
+/// Returns the active configuration
+pub(crate) fn current() -> FfiConfig {
+    CONFIG.read().clone()
+}
+
+pub(crate) fn set(config: FfiConfig) {
+    *CONFIG.write() = config;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy_is_restricted() {
+        let config = FfiConfig::default();
+        assert_eq!(config.search_policy, DllSearchPolicy::Restricted);
+        assert!(config.dll_directory.is_none());
+        assert!(config.cache_dir.is_none());
+        assert!(config.cache_max_bytes.is_none());
+        assert_eq!(config.load_mode, LoadMode::Eager);
+        assert_eq!(config.unload_policy, UnloadPolicy::LeakAfterFirstCall);
+        assert_eq!(config.load_retry, RetryPolicy::default());
+    }
+
+    #[test]
+    fn test_unload_policy() {
+        assert!(UnloadPolicy::Unload.allows_unload(true));
+        assert!(!UnloadPolicy::Leak.allows_unload(false));
+        assert!(UnloadPolicy::LeakAfterFirstCall.allows_unload(false));
+        assert!(!UnloadPolicy::LeakAfterFirstCall.allows_unload(true));
+    }
+
+    #[test]
+    fn test_payload_limits() {
+        let config = FfiConfig::new();
+        assert_eq!(config.payload_limit("GoInit"), DEFAULT_MAX_PAYLOAD_BYTES);
+
+        let config = config
+            .with_max_payload_bytes(1024)
+            .with_export_payload_limit("GoInit", 16);
+        assert_eq!(config.payload_limit("GoInit"), 16);
+        assert_eq!(config.payload_limit("AddNumbers"), 1024);
+    }
+
+    #[test]
+    fn test_executor_config() {
+        let executor = ExecutorConfig::new(0);
+        assert_eq!(executor.threads, 1);
+        assert_eq!(executor.queue_capacity, 4);
+        assert_eq!(executor.queue_policy, QueuePolicy::Block);
+        assert!(FfiConfig::default().executor.is_none());
+    }
+
+    #[test]
+    fn test_config_builder() {
+        let config = FfiConfig::new()
+            .with_search_policy(DllSearchPolicy::Legacy)
+            .with_dll_directory("deps");
+        assert_eq!(config.search_policy, DllSearchPolicy::Legacy);
+        assert_eq!(config.dll_directory, Some(PathBuf::from("deps")));
+    }
+}
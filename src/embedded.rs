@@ -0,0 +1,107 @@
+use log::debug;
+use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cache;
+use crate::search_paths::DLL_FILE_NAME;
+
+/// The Go library as built alongside this crate
+static EMBEDDED_DLL: &[u8] = include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/go_lib/",
+    env!("GO_LIB_DLL_NAME")
+));
+
+static EXTRACTED: OnceCell<PathBuf> = OnceCell::new();
+
+/// Returns the path of the extracted embedded library, extracting it on first use.
+///
+/// The library is written to `<cache root>/embedded/<sha256 prefix>/`, so
+/// different builds never overwrite each other and an unchanged build is
+/// reused across runs.
+pub(crate) fn extracted_path() -> io::Result<PathBuf> {
+    EXTRACTED
+        .get_or_try_init(|| {
+            let path = extract_to(&cache::dir(cache::EMBEDDED))?;
+            cache::enforce_limit(&path);
+            Ok(path)
+        })
+        .cloned()
+}
+
+/// The extracted library, if it has been extracted
+pub(crate) fn extracted() -> Option<&'static Path> {
+    EXTRACTED.get().map(PathBuf::as_path)
+}
+
+/// Short hex digest identifying the embedded library
+pub(crate) fn embedded_hash() -> String {
+    hex_digest(EMBEDDED_DLL)[..16].to_string()
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Writes the embedded library to `path` and syncs it to disk
+pub(crate) fn write_to(path: &Path) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(EMBEDDED_DLL)?;
+    file.sync_all()
+}
+
+fn extract_to(root: &Path) -> io::Result<PathBuf> {
+    let dir = root.join(embedded_hash());
+    let target = dir.join(DLL_FILE_NAME);
+
+    if fs::metadata(&target).is_ok_and(|m| m.len() == EMBEDDED_DLL.len() as u64) {
+        debug!("Reusing extracted library at {}", target.display());
+        return Ok(target);
+    }
+
+    fs::create_dir_all(&dir)?;
+
+    // Write next to the target and rename so concurrent processes never see a
+    // partially written library.
+    let temp = dir.join(format!("{}.{}.tmp", DLL_FILE_NAME, std::process::id()));
+    write_to(&temp)?;
+    if let Err(e) = fs::rename(&temp, &target) {
+        let _ = fs::remove_file(&temp);
+        // Another process may have won the race while the file was locked.
+        if !target.exists() {
+            return Err(e);
+        }
+    }
+
+    debug!("Extracted embedded library to {}", target.display());
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_is_stable() {
+        assert_eq!(embedded_hash(), embedded_hash());
+        assert_eq!(embedded_hash().len(), 16);
+    }
+
+    #[test]
+    fn test_extract_is_idempotent() {
+        let root = std::env::temp_dir().join("rust_go_ffi_embedded_test");
+        let first = extract_to(&root).expect("Extraction should succeed");
+        let second = extract_to(&root).expect("Re-extraction should succeed");
+
+        assert_eq!(first, second);
+        assert_eq!(fs::read(&first).unwrap(), EMBEDDED_DLL);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
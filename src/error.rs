@@ -0,0 +1,562 @@
+use semver::{Version, VersionReq};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::types::OutOfRange;
+
+/// Error type for DLL operations
+///
+/// Every variant has a stable numeric [`code`](DllError::code) for logs and
+/// for callers on the other side of a process boundary. Codes are grouped by
+/// area and never reused:
+///
+/// | Range | Area |
+/// |-------|------|
+/// | 1xxx  | Locating and loading the library |
+/// | 2xxx  | Version checks |
+/// | 3xxx  | Initialization and lifecycle |
+/// | 4xxx  | Individual calls |
+#[derive(Debug, Error)]
+pub enum DllError {
+    #[error("DLL not found")]
+    NotFound,
+    #[error("Failed to load DLL: {0}")]
+    LoadError(String),
+    #[cfg(feature = "installer-core")]
+    #[error("Failed to install DLL: {0}")]
+    InstallError(#[source] std::io::Error),
+    /// Another process kept the install lock for longer than the timeout
+    #[cfg(feature = "installer-core")]
+    #[error("Another installation holds {} and didn't finish within {timeout:?}", .path.display())]
+    InstallLocked {
+        path: std::path::PathBuf,
+        timeout: Duration,
+    },
+    #[error("Version mismatch: expected {expected}, found {found}")]
+    VersionMismatch { expected: Version, found: Version },
+    /// The library's version doesn't satisfy the requirement given to
+    /// [`FfiBuilder::version_req`](crate::FfiBuilder::version_req)
+    #[error("DLL version {found} does not satisfy {requirement}")]
+    UnsupportedVersion {
+        requirement: VersionReq,
+        found: Version,
+    },
+    /// The library was built from a different `go_lib.h` than the bindings;
+    /// see [`abi_check`](crate::abi_check)
+    #[error(
+        "ABI fingerprint of the DLL is {found:016x}, the bindings expect {expected:016x}; rebuild the DLL and the crate from the same go_lib.go"
+    )]
+    AbiMismatch { expected: u64, found: u64 },
+    #[error("Initialization error: {0}")]
+    InitializationError(String),
+    #[error("Value out of range: {0}")]
+    ValueOutOfRange(#[from] OutOfRange),
+    /// The compatibility matrix rules out this DLL for this crate version
+    #[error("Incompatible DLL: {guidance}")]
+    Incompatible {
+        dll_version: Version,
+        guidance: String,
+    },
+    /// An export the safe wrappers rely on is missing from the library
+    #[error(
+        "Export {name} not found in DLL{}",
+        .dll_version.as_ref().map_or(String::new(), |v| format!(" {}", v))
+    )]
+    MissingSymbol {
+        name: String,
+        /// Version of the DLL that lacks it, if it could be determined
+        dll_version: Option<Version>,
+    },
+    /// Another thread held the context lock for too long
+    #[error("Timed out waiting for the FFI context lock")]
+    LockTimeout,
+    /// An earlier fatal error left the library in an undefined state
+    #[error(
+        "FFI context is poisoned after a fatal error ({reason}); call reset() or reload_dll()"
+    )]
+    Poisoned { reason: String },
+    /// The library's `GoInit` export returned a non-zero code
+    #[error("GoInit failed with code {code}")]
+    GoInitFailed { code: i32 },
+    /// The library's `GoShutdown` export returned a non-zero code
+    #[error("GoShutdown failed with code {code}")]
+    GoShutdownFailed { code: i32 },
+    /// The library was built for a different CPU architecture than this process
+    #[error("Architecture mismatch: this process is {expected} but the DLL is built for {found}")]
+    ArchitectureMismatch { expected: String, found: String },
+    /// The library's SHA-256 differs from the build this crate was compiled
+    /// with; see [`FfiConfig::with_checksum_verification`](crate::FfiConfig::with_checksum_verification)
+    #[error("SHA-256 of {} is {found}, expected {expected}", .path.display())]
+    ChecksumMismatch {
+        path: std::path::PathBuf,
+        expected: String,
+        found: String,
+    },
+    /// The library isn't signed the way
+    /// [`FfiConfig::with_require_signed_dll`](crate::FfiConfig::with_require_signed_dll)
+    /// requires
+    #[cfg(feature = "signed-dll")]
+    #[error("Refusing to load {}: {reason}", .path.display())]
+    UntrustedDll {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+    /// A string, slice or payload exceeded the configured per-call limit
+    #[error(
+        "Payload of {size} bytes for {export} exceeds the {limit} byte limit; split it into smaller calls or raise the limit with FfiConfig::with_max_payload_bytes"
+    )]
+    PayloadTooLarge {
+        export: String,
+        size: usize,
+        limit: usize,
+    },
+    /// A Go export panicked; the panic was recovered on the Go side
+    #[error("Go panicked in {export}: {message}")]
+    GoPanic {
+        export: String,
+        message: String,
+        /// Go stack trace at the point of the panic
+        stack: String,
+    },
+    /// A call did not return within its deadline
+    #[error("{export} did not return within {timeout:?}")]
+    Timeout {
+        export: String,
+        timeout: Duration,
+        /// Set when the call was left running and poisoned the FFI context,
+        /// so calls fail with [`DllError::Poisoned`] until
+        /// [`reset`](crate::reset)
+        poisoned: bool,
+    },
+    /// Go returned a string that is not valid UTF-8
+    #[error("{export} returned invalid UTF-8")]
+    InvalidUtf8 {
+        export: String,
+        #[source]
+        source: std::str::Utf8Error,
+    },
+    /// A Go export reported failure through `TakeThreadGoError`
+    #[error("{export} failed: {message}")]
+    CallFailed { export: String, message: String },
+    /// The DLL raised a hardware exception such as an access violation; only
+    /// caught with the `guarded` feature on Windows
+    #[error("{export} raised hardware exception {code:#010x} at address {address:#x}")]
+    HardwareException {
+        export: String,
+        /// Windows exception code, e.g. `0xc0000005` for an access violation
+        code: u32,
+        address: usize,
+    },
+    /// A bridge payload could not be encoded or decoded
+    #[error("Invalid {export} payload: {source}")]
+    Codec {
+        export: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// An export was called by name with types that don't match the
+    /// signature the library describes
+    #[error("{export} is declared as {expected} but was called as {found}")]
+    SignatureMismatch {
+        export: String,
+        expected: String,
+        found: String,
+    },
+    /// A float was NaN or infinite under [`FloatPolicy::Reject`]
+    ///
+    /// [`FloatPolicy::Reject`]: crate::FloatPolicy::Reject
+    #[error(
+        "{export} cannot take or return {value} as {target}; see FfiConfig::with_float_policy"
+    )]
+    FloatOutOfRange {
+        export: String,
+        value: f64,
+        target: &'static str,
+    },
+    /// The call was cancelled through its [`CancelToken`](crate::CancelToken)
+    #[error("{export} was cancelled")]
+    Cancelled { export: String },
+    /// The executor queue was full and its policy is to reject calls
+    #[error("{export} was rejected: all {queue_capacity} executor queue slots are taken")]
+    ExecutorSaturated {
+        export: String,
+        queue_capacity: usize,
+    },
+    /// All `limit` call slots are taken; see
+    /// [`FfiConfig::with_max_concurrent_calls`](crate::FfiConfig::with_max_concurrent_calls)
+    #[error("All {limit} concurrent call slots are taken")]
+    Busy { limit: usize },
+    /// The circuit breaker is open after repeated failures inside Go
+    #[error("Circuit breaker is open after repeated failures; retry in {retry_after:?}")]
+    CircuitOpen { retry_after: Duration },
+    /// Calls to `export` exceeded its [`RateLimit`](crate::RateLimit)
+    #[error("{export} is rate limited; retry in {retry_after:?}")]
+    RateLimited {
+        export: String,
+        retry_after: Duration,
+    },
+    /// An [`FfiHook`](crate::FfiHook) refused the call
+    #[error("{export} was rejected by a hook: {reason}")]
+    Rejected { export: String, reason: String },
+    /// The configured [`MetricsExporter`](crate::MetricsExporter) couldn't
+    /// be set up, e.g. because its port is taken
+    #[cfg(feature = "metrics-core")]
+    #[error("Failed to set up the {exporter} metrics exporter: {source}")]
+    MetricsExporter {
+        exporter: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Stdout and stderr couldn't be redirected for
+    /// [`subscribe_output`](crate::subscribe_output)
+    #[error("Failed to capture stdout and stderr: {0}")]
+    OutputCapture(#[source] std::io::Error),
+    /// A Rust callback invoked from Go failed
+    #[error("Callback failed: {0}")]
+    CallbackError(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+// Lets `?` pass through conversions that cannot fail, e.g. in `ffi_struct!`.
+impl From<std::convert::Infallible> for DllError {
+    fn from(never: std::convert::Infallible) -> Self {
+        match never {}
+    }
+}
+
+impl DllError {
+    /// Stable numeric code identifying the kind of error
+    pub fn code(&self) -> u32 {
+        match self {
+            DllError::NotFound => 1001,
+            DllError::LoadError(_) => 1002,
+            #[cfg(feature = "installer-core")]
+            DllError::InstallError(_) => 1003,
+            DllError::ArchitectureMismatch { .. } => 1004,
+            DllError::MissingSymbol { .. } => 1005,
+            DllError::ChecksumMismatch { .. } => 1006,
+            #[cfg(feature = "signed-dll")]
+            DllError::UntrustedDll { .. } => 1007,
+            #[cfg(feature = "installer-core")]
+            DllError::InstallLocked { .. } => 1008,
+            DllError::VersionMismatch { .. } => 2001,
+            DllError::Incompatible { .. } => 2002,
+            DllError::UnsupportedVersion { .. } => 2003,
+            DllError::AbiMismatch { .. } => 2004,
+            DllError::InitializationError(_) => 3001,
+            DllError::GoInitFailed { .. } => 3002,
+            DllError::GoShutdownFailed { .. } => 3003,
+            DllError::LockTimeout => 3004,
+            DllError::Poisoned { .. } => 3005,
+            #[cfg(feature = "metrics-core")]
+            DllError::MetricsExporter { .. } => 3006,
+            DllError::OutputCapture(_) => 3007,
+            DllError::ValueOutOfRange(_) => 4001,
+            DllError::PayloadTooLarge { .. } => 4002,
+            DllError::InvalidUtf8 { .. } => 4003,
+            DllError::GoPanic { .. } => 4004,
+            DllError::Timeout { .. } => 4005,
+            DllError::CallbackError(_) => 4006,
+            DllError::CallFailed { .. } => 4007,
+            DllError::HardwareException { .. } => 4008,
+            DllError::Codec { .. } => 4009,
+            DllError::SignatureMismatch { .. } => 4010,
+            DllError::FloatOutOfRange { .. } => 4011,
+            DllError::Cancelled { .. } => 4012,
+            DllError::ExecutorSaturated { .. } => 4013,
+            DllError::Busy { .. } => 4014,
+            DllError::CircuitOpen { .. } => 4015,
+            DllError::Rejected { .. } => 4016,
+            DllError::RateLimited { .. } => 4017,
+        }
+    }
+
+    /// Whether the same operation may succeed if tried again unchanged.
+    ///
+    /// True for contention and deadlines. Errors caused by the library
+    /// itself, by the arguments or by a hook refusing the call will fail the
+    /// same way again, as does a timeout that poisoned the context until it
+    /// is [`reset`](crate::reset).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DllError::Timeout { poisoned, .. } => !poisoned,
+            DllError::LockTimeout
+            | DllError::ExecutorSaturated { .. }
+            | DllError::Busy { .. }
+            | DllError::CircuitOpen { .. }
+            | DllError::RateLimited { .. } => true,
+            #[cfg(feature = "installer-core")]
+            DllError::InstallLocked { .. } => true,
+            #[cfg(feature = "installer-core")]
+            DllError::InstallError(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::WouldBlock
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether the error leaves the library in an undefined state.
+    ///
+    /// A fatal error poisons the FFI context; every later call fails with
+    /// [`DllError::Poisoned`] until [`reset`](crate::reset) or
+    /// [`reload_dll`](crate::reload_dll).
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            DllError::GoPanic { .. } | DllError::HardwareException { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_codes_are_unique() {
+        let errors = [
+            DllError::NotFound,
+            DllError::LoadError(String::new()),
+            #[cfg(feature = "installer-core")]
+            DllError::InstallError(std::io::ErrorKind::Other.into()),
+            #[cfg(feature = "installer-core")]
+            DllError::InstallLocked {
+                path: Default::default(),
+                timeout: Duration::ZERO,
+            },
+            DllError::ArchitectureMismatch {
+                expected: String::new(),
+                found: String::new(),
+            },
+            DllError::MissingSymbol {
+                name: String::new(),
+                dll_version: None,
+            },
+            DllError::ChecksumMismatch {
+                path: Default::default(),
+                expected: String::new(),
+                found: String::new(),
+            },
+            #[cfg(feature = "signed-dll")]
+            DllError::UntrustedDll {
+                path: Default::default(),
+                reason: String::new(),
+            },
+            DllError::VersionMismatch {
+                expected: Version::new(0, 1, 0),
+                found: Version::new(0, 2, 0),
+            },
+            DllError::Incompatible {
+                dll_version: Version::new(0, 2, 0),
+                guidance: String::new(),
+            },
+            DllError::UnsupportedVersion {
+                requirement: VersionReq::STAR,
+                found: Version::new(0, 2, 0),
+            },
+            DllError::AbiMismatch {
+                expected: 1,
+                found: 2,
+            },
+            DllError::InitializationError(String::new()),
+            DllError::GoInitFailed { code: 1 },
+            DllError::GoShutdownFailed { code: 1 },
+            DllError::LockTimeout,
+            DllError::Poisoned {
+                reason: String::new(),
+            },
+            #[cfg(feature = "metrics-core")]
+            DllError::MetricsExporter {
+                exporter: String::new(),
+                source: "bad address".into(),
+            },
+            DllError::OutputCapture(std::io::ErrorKind::Other.into()),
+            DllError::ValueOutOfRange(OutOfRange {
+                value: 0,
+                target: "i32",
+            }),
+            DllError::PayloadTooLarge {
+                export: String::new(),
+                size: 2,
+                limit: 1,
+            },
+            DllError::InvalidUtf8 {
+                export: String::new(),
+                source: String::from_utf8(vec![0xff]).unwrap_err().utf8_error(),
+            },
+            DllError::GoPanic {
+                export: String::new(),
+                message: String::new(),
+                stack: String::new(),
+            },
+            DllError::Timeout {
+                export: String::new(),
+                timeout: Duration::ZERO,
+                poisoned: false,
+            },
+            DllError::CallbackError("failed".into()),
+            DllError::CallFailed {
+                export: String::new(),
+                message: String::new(),
+            },
+            DllError::HardwareException {
+                export: String::new(),
+                code: 0,
+                address: 0,
+            },
+            DllError::Codec {
+                export: String::new(),
+                source: "bad payload".into(),
+            },
+            DllError::SignatureMismatch {
+                export: String::new(),
+                expected: String::new(),
+                found: String::new(),
+            },
+            DllError::FloatOutOfRange {
+                export: String::new(),
+                value: f64::NAN,
+                target: "f64",
+            },
+            DllError::Cancelled {
+                export: String::new(),
+            },
+            DllError::ExecutorSaturated {
+                export: String::new(),
+                queue_capacity: 0,
+            },
+            DllError::Busy { limit: 0 },
+            DllError::CircuitOpen {
+                retry_after: Duration::ZERO,
+            },
+            DllError::Rejected {
+                export: String::new(),
+                reason: String::new(),
+            },
+            DllError::RateLimited {
+                export: String::new(),
+                retry_after: Duration::ZERO,
+            },
+        ];
+        for error in &errors {
+            // Fails to compile once a variant is added, as a reminder to add
+            // it to `errors` too.
+            match error {
+                DllError::NotFound
+                | DllError::LoadError(_)
+                | DllError::ArchitectureMismatch { .. }
+                | DllError::MissingSymbol { .. }
+                | DllError::ChecksumMismatch { .. }
+                | DllError::VersionMismatch { .. }
+                | DllError::Incompatible { .. }
+                | DllError::UnsupportedVersion { .. }
+                | DllError::AbiMismatch { .. }
+                | DllError::InitializationError(_)
+                | DllError::GoInitFailed { .. }
+                | DllError::GoShutdownFailed { .. }
+                | DllError::LockTimeout
+                | DllError::Poisoned { .. }
+                | DllError::OutputCapture(_)
+                | DllError::ValueOutOfRange(_)
+                | DllError::PayloadTooLarge { .. }
+                | DllError::InvalidUtf8 { .. }
+                | DllError::GoPanic { .. }
+                | DllError::Timeout { .. }
+                | DllError::CallbackError(_)
+                | DllError::CallFailed { .. }
+                | DllError::HardwareException { .. }
+                | DllError::Codec { .. }
+                | DllError::SignatureMismatch { .. }
+                | DllError::FloatOutOfRange { .. }
+                | DllError::Cancelled { .. }
+                | DllError::ExecutorSaturated { .. }
+                | DllError::Busy { .. }
+                | DllError::CircuitOpen { .. }
+                | DllError::Rejected { .. }
+                | DllError::RateLimited { .. } => {}
+                #[cfg(feature = "installer-core")]
+                DllError::InstallError(_) | DllError::InstallLocked { .. } => {}
+                #[cfg(feature = "signed-dll")]
+                DllError::UntrustedDll { .. } => {}
+                #[cfg(feature = "metrics-core")]
+                DllError::MetricsExporter { .. } => {}
+            }
+        }
+
+        let mut codes: Vec<u32> = errors.iter().map(DllError::code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+    }
+
+    #[test]
+    fn test_retryable() {
+        assert!(DllError::LockTimeout.is_retryable());
+        assert!(DllError::Timeout {
+            export: "AddNumbers".to_string(),
+            timeout: Duration::from_secs(1),
+            poisoned: false,
+        }
+        .is_retryable());
+        assert!(!DllError::Timeout {
+            export: "AddNumbers".to_string(),
+            timeout: Duration::from_secs(1),
+            poisoned: true,
+        }
+        .is_retryable());
+        assert!(!DllError::NotFound.is_retryable());
+        assert!(!DllError::GoInitFailed { code: 1 }.is_retryable());
+        assert!(!DllError::Rejected {
+            export: "AddNumbers".to_string(),
+            reason: "read-only mode".to_string(),
+        }
+        .is_retryable());
+    }
+
+    #[test]
+    fn test_fatal() {
+        let panic = DllError::GoPanic {
+            export: "AddNumbers".to_string(),
+            message: "runtime error: index out of range".to_string(),
+            stack: String::new(),
+        };
+        assert!(panic.is_fatal());
+        assert!(!panic.is_retryable());
+        assert!(!DllError::CallFailed {
+            export: "AddNumbers".to_string(),
+            message: String::new(),
+        }
+        .is_fatal());
+        assert!(DllError::HardwareException {
+            export: "AddNumbers".to_string(),
+            code: 0xc000_0005,
+            address: 0,
+        }
+        .is_fatal());
+        assert!(!DllError::Poisoned {
+            reason: panic.to_string(),
+        }
+        .is_fatal());
+    }
+
+    #[test]
+    fn test_source_chain() {
+        let utf8 = String::from_utf8(vec![0xff]).unwrap_err().utf8_error();
+        let error = DllError::InvalidUtf8 {
+            export: "GetName".to_string(),
+            source: utf8,
+        };
+        assert_eq!(error.to_string(), "GetName returned invalid UTF-8");
+        assert!(error.source().is_some());
+
+        let error = DllError::from(OutOfRange {
+            value: 1 << 40,
+            target: "i32",
+        });
+        assert_eq!(error.code(), 4001);
+        assert!(error.source().is_some());
+    }
+}
@@ -0,0 +1,155 @@
+//! Events the Go side pushes asynchronously.
+//!
+//! Go emits events through the `"event"` callback, from whatever goroutine
+//! produces them. The first subscription registers that callback, and every
+//! subscriber gets its own bounded channel. Events are delivered without
+//! blocking the Go thread: a subscriber that falls [`EVENT_CAPACITY`] events
+//! behind misses the newer ones, and dropped receivers are pruned on the
+//! next event.
+
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use log::warn;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::callback::{register_callback, CallbackHandle, CallbackResult};
+use crate::{ensure_initialized, DllError};
+
+/// Events buffered per subscriber before newer ones are dropped
+pub const EVENT_CAPACITY: usize = 1024;
+
+const EVENT_CALLBACK: &str = "event";
+
+/// A notification from the Go side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoEvent {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+impl GoEvent {
+    /// Splits the wire format, the topic, a NUL byte and the payload
+    fn decode(raw: &[u8]) -> Option<Self> {
+        let split = raw.iter().position(|&b| b == 0)?;
+        Some(Self {
+            topic: String::from_utf8(raw[..split].to_vec()).ok()?,
+            payload: raw[split + 1..].to_vec(),
+        })
+    }
+}
+
+enum Subscriber {
+    Channel(Sender<GoEvent>),
+    #[cfg(feature = "tokio")]
+    Stream(tokio::sync::mpsc::Sender<GoEvent>),
+}
+
+impl Subscriber {
+    /// Hands `event` over without blocking; `false` once the receiver is gone
+    fn deliver(&self, event: GoEvent) -> bool {
+        let dropped = match self {
+            Subscriber::Channel(tx) => match tx.try_send(event) {
+                Ok(()) => None,
+                Err(TrySendError::Full(event)) => Some(event),
+                Err(TrySendError::Disconnected(_)) => return false,
+            },
+            #[cfg(feature = "tokio")]
+            Subscriber::Stream(tx) => {
+                use tokio::sync::mpsc::error::TrySendError;
+                match tx.try_send(event) {
+                    Ok(()) => None,
+                    Err(TrySendError::Full(event)) => Some(event),
+                    Err(TrySendError::Closed(_)) => return false,
+                }
+            }
+        };
+        if let Some(event) = dropped {
+            warn!(
+                "Dropping {} event for a subscriber that is behind",
+                event.topic
+            );
+        }
+        true
+    }
+}
+
+static SUBSCRIBERS: Lazy<Mutex<Vec<Subscriber>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Kept apart from `SUBSCRIBERS`: dropping the handle waits for running
+/// callbacks, which take that lock.
+static HANDLE: Lazy<Mutex<Option<CallbackHandle>>> = Lazy::new(|| Mutex::new(None));
+
+fn dispatch(raw: &[u8]) -> CallbackResult {
+    let Some(event) = GoEvent::decode(raw) else {
+        warn!("Ignoring malformed event of {} bytes", raw.len());
+        return Ok(());
+    };
+    SUBSCRIBERS
+        .lock()
+        .retain(|subscriber| subscriber.deliver(event.clone()));
+    Ok(())
+}
+
+fn subscribe(subscriber: Subscriber) -> Result<(), DllError> {
+    let library = ensure_initialized()?;
+    let mut handle = HANDLE.lock();
+    if !handle
+        .as_ref()
+        .is_some_and(|h| Arc::ptr_eq(h.library(), &library))
+    {
+        // Leave a copy that `reload_dll` replaced before joining the new one.
+        *handle = None;
+        *handle = Some(register_callback(EVENT_CALLBACK, dispatch)?);
+    }
+    SUBSCRIBERS.lock().push(subscriber);
+    Ok(())
+}
+
+/// Subscribes to the events of the active library.
+///
+/// Subscribing again after [`reload_dll`](crate::reload_dll) moves the
+/// event callback to the new copy.
+pub fn subscribe_events() -> Result<Receiver<GoEvent>, DllError> {
+    let (tx, rx) = crossbeam_channel::bounded(EVENT_CAPACITY);
+    subscribe(Subscriber::Channel(tx))?;
+    Ok(rx)
+}
+
+/// Events as an async [`Stream`](tokio_stream::Stream)
+#[cfg(feature = "tokio")]
+pub type EventStream = tokio_stream::wrappers::ReceiverStream<GoEvent>;
+
+/// Like [`subscribe_events`], for async consumers
+#[cfg(feature = "tokio")]
+pub fn subscribe_event_stream() -> Result<EventStream, DllError> {
+    let (tx, rx) = tokio::sync::mpsc::channel(EVENT_CAPACITY);
+    subscribe(Subscriber::Stream(tx))?;
+    Ok(EventStream::new(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_event() {
+        let event = GoEvent::decode(b"tick\x001").unwrap();
+        assert_eq!(event.topic, "tick");
+        assert_eq!(event.payload, b"1");
+        assert_eq!(GoEvent::decode(b"tick\x00").unwrap().payload, b"");
+        assert!(GoEvent::decode(b"no separator").is_none());
+    }
+
+    #[test]
+    fn test_deliver_never_blocks() {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let subscriber = Subscriber::Channel(tx);
+        let event = GoEvent::decode(b"tick\x001").unwrap();
+        assert!(subscriber.deliver(event.clone()));
+        assert!(subscriber.deliver(event.clone()));
+        assert_eq!(rx.len(), 1);
+        drop(rx);
+        assert!(!subscriber.deliver(event));
+    }
+}
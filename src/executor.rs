@@ -0,0 +1,252 @@
+//! The optional thread pool every call into Go runs on.
+//!
+//! With an [`ExecutorConfig`] set, the safe wrappers hand each call to a
+//! fixed set of worker threads and block until it returns, so at most
+//! `threads` calls are inside Go at once no matter how many threads call the
+//! wrappers. Calls wait in a bounded queue for a free worker; what happens
+//! when the queue is full follows the [`QueuePolicy`]. Without a config
+//! calls run on the caller's thread as before.
+//!
+//! The caller always blocks until its call has finished, which is what lets
+//! calls borrow from the caller's stack. Calls a Go callback makes on a
+//! worker run inline, since waiting for another worker could deadlock. The
+//! worker reports which thread it is, so the errors Go records per thread
+//! are collected from the right one.
+
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use log::debug;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::cell::Cell;
+use std::error::Error;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::callback;
+use crate::config::{self, ExecutorConfig, QueuePolicy};
+use crate::last_error;
+use crate::limit;
+use crate::DllError;
+
+/// Snapshot of the executor's activity since the process started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExecutorStats {
+    /// Worker threads of the active pool; 0 without one
+    pub threads: usize,
+    /// Calls currently waiting for a worker
+    pub queued: usize,
+    pub executed: u64,
+    /// Calls that failed with [`DllError::ExecutorSaturated`]
+    pub rejected: u64,
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// What a worker hands back: the call's result, any callback failure
+/// recorded on the worker during the call, and the worker's OS thread id
+type Outcome<T> = (thread::Result<T>, Option<Box<dyn Error + Send + Sync>>, u64);
+
+/// Moves a value to a worker; sound because the caller blocks until the
+/// worker is done with it
+struct AssertSend<T>(T);
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+impl<T> AssertSend<T> {
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+static EXECUTED: AtomicU64 = AtomicU64::new(0);
+static REJECTED: AtomicU64 = AtomicU64::new(0);
+
+static POOL: Lazy<Mutex<Option<Arc<Pool>>>> = Lazy::new(|| Mutex::new(None));
+
+thread_local! {
+    static IS_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+struct Pool {
+    config: ExecutorConfig,
+    jobs: Sender<Job>,
+}
+
+impl Pool {
+    fn start(config: ExecutorConfig) -> Self {
+        // A zero-capacity channel would be a rendezvous one, so keep a slot.
+        let (jobs, queue) = crossbeam_channel::bounded(config.queue_capacity.max(1));
+        for i in 0..config.threads {
+            let queue: Receiver<Job> = queue.clone();
+            thread::Builder::new()
+                .name(format!("rust-go-ffi-exec-{}", i))
+                .spawn(move || {
+                    IS_WORKER.with(|w| w.set(true));
+                    // Ends once the pool is replaced and the queue drained.
+                    for job in queue {
+                        set_queued(QUEUED.fetch_sub(1, Ordering::AcqRel) - 1);
+                        job();
+                    }
+                })
+                .expect("failed to spawn FFI executor thread");
+        }
+        debug!(
+            "Started FFI executor with {} threads and {} queue slots",
+            config.threads, config.queue_capacity
+        );
+        Self { config, jobs }
+    }
+
+    fn submit(&self, export: &'static str, job: Job) -> Result<(), DllError> {
+        set_queued(QUEUED.fetch_add(1, Ordering::AcqRel) + 1);
+        let sent = match self.config.queue_policy {
+            QueuePolicy::Block => self
+                .jobs
+                .send(job)
+                .map_err(|e| TrySendError::Disconnected(e.0)),
+            QueuePolicy::Reject => self.jobs.try_send(job),
+        };
+        match sent {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                set_queued(QUEUED.fetch_sub(1, Ordering::AcqRel) - 1);
+                REJECTED.fetch_add(1, Ordering::Relaxed);
+                #[cfg(feature = "metrics-core")]
+                crate::metrics::increment_executor_rejections();
+                Err(DllError::ExecutorSaturated {
+                    export: export.to_string(),
+                    queue_capacity: self.config.queue_capacity,
+                })
+            }
+            Err(TrySendError::Disconnected(_)) => unreachable!("workers outlive the pool"),
+        }
+    }
+}
+
+fn set_queued(_depth: usize) {
+    #[cfg(feature = "metrics-core")]
+    crate::metrics::set_executor_queue_depth(_depth);
+}
+
+/// The pool for the active configuration, started or replaced as needed
+fn pool() -> Option<Arc<Pool>> {
+    let config = config::executor();
+    let mut pool = POOL.lock();
+    if pool.as_ref().map(|p| p.config) != config {
+        // Dropping the old pool lets its workers exit after their queue.
+        *pool = config.map(|config| Arc::new(Pool::start(config)));
+    }
+    pool.clone()
+}
+
+/// Runs `f`, on a worker if an executor is configured
+pub(crate) fn run<T, F>(export: &'static str, f: F) -> Result<T, DllError>
+where
+    F: FnOnce() -> Result<T, DllError>,
+{
+    if IS_WORKER.with(Cell::get) {
+        last_error::set_call_thread(None);
+        return f();
+    }
+    let Some(pool) = pool() else {
+        last_error::set_call_thread(None);
+        return f();
+    };
+
+    let (done, outcome) = crossbeam_channel::bounded::<AssertSend<Outcome<Result<T, DllError>>>>(1);
+    let f = AssertSend(f);
+    // Callbacks Go makes on the worker belong to the caller's call chain.
+    let permit = limit::current();
+    #[cfg(feature = "tracing")]
+    let span = tracing::Span::current();
+    let job: Box<dyn FnOnce() + Send + '_> = Box::new(move || {
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        let result = limit::adopt(permit, || {
+            panic::catch_unwind(AssertUnwindSafe(f.into_inner()))
+        });
+        // Callback failures are recorded per thread; hand them back.
+        let failure = callback::take_failure();
+        EXECUTED.fetch_add(1, Ordering::Relaxed);
+        let _ = done.send(AssertSend((result, failure, last_error::os_thread_id())));
+    });
+    // SAFETY: the job only borrows from this frame, and this function doesn't
+    // return before the job has run or been dropped, which drops `done`.
+    let job: Job = unsafe { mem::transmute(job) };
+    pool.submit(export, job)?;
+
+    let (result, failure, thread) = outcome
+        .recv()
+        .expect("executor workers run every queued job")
+        .into_inner();
+    if let Some(failure) = failure {
+        callback::record_failure(failure);
+    }
+    last_error::set_call_thread(Some(thread));
+    result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+/// Activity of the FFI executor
+pub fn executor_stats() -> ExecutorStats {
+    ExecutorStats {
+        threads: POOL.lock().as_ref().map_or(0, |p| p.config.threads),
+        queued: QUEUED.load(Ordering::Acquire),
+        executed: EXECUTED.load(Ordering::Relaxed),
+        rejected: REJECTED.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_on_worker_and_borrows() {
+        let pool = Pool::start(ExecutorConfig::new(2));
+        let mut borrowed = vec![1, 2, 3];
+        let (done, outcome) = crossbeam_channel::bounded(1);
+        let job: Box<dyn FnOnce() + Send + '_> = Box::new(|| {
+            borrowed.push(4);
+            let _ = done.send(IS_WORKER.with(Cell::get));
+        });
+        let job: Job = unsafe { mem::transmute(job) };
+        pool.submit("AddNumbers", job).unwrap();
+        assert!(outcome.recv().unwrap());
+        assert_eq!(borrowed, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_reject_when_full() {
+        let config = ExecutorConfig::new(1)
+            .with_queue_capacity(1)
+            .with_queue_policy(QueuePolicy::Reject);
+        let pool = Pool::start(config);
+        let (release, blocked) = crossbeam_channel::bounded::<()>(0);
+        let (started, running) = crossbeam_channel::bounded::<()>(1);
+        // Occupies the only worker, then the only queue slot.
+        let blocked_again = blocked.clone();
+        let job = move || {
+            let _ = started.send(());
+            let _ = blocked.recv();
+        };
+        pool.submit("AddNumbers", Box::new(job)).unwrap();
+        running.recv().unwrap();
+        let job = move || {
+            let _ = blocked_again.recv();
+        };
+        pool.submit("AddNumbers", Box::new(job)).unwrap();
+        assert!(matches!(
+            pool.submit("AddNumbers", Box::new(|| {})),
+            Err(DllError::ExecutorSaturated {
+                queue_capacity: 1,
+                ..
+            })
+        ));
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+    }
+}
@@ -1,3 +1,23 @@
+//! Raw bindings generated from the cgo header, safe wrappers generated from
+//! it, plus the layer that converts Rust structs to and from their C
+//! representations.
+//!
+//! `build.rs` wraps every export whose arguments and result are scalars, or
+//! whose result is a `char*`, in a function named after it in snake case,
+//! e.g. `AddNumbers` as [`add_numbers`] taking and returning `i64`. The
+//! wrappers convert like the crate's own, fail with a [`DllError`] instead of
+//! truncating, check the Go side's last error and are counted, timed and
+//! traced under the export's name. Exports with pointer or struct arguments
+//! are listed as not wrapped in `$OUT_DIR/wrappers.rs`.
+//!
+//! A Rust type implements [`FfiStruct`] through [`ffi_struct!`](crate::ffi_struct),
+//! which converts field by field with `TryFrom` so narrowing conversions
+//! fail with [`DllError::ValueOutOfRange`] instead of truncating. Types that
+//! mirror a C struct exactly can additionally be checked at compile time
+//! with [`assert_ffi_layout!`](crate::assert_ffi_layout).
+
+use crate::DllError;
+
 mod bindings {
     #![allow(unused)]
     #![allow(non_upper_case_globals)]
@@ -6,4 +26,79 @@ mod bindings {
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
 
+pub use crate::header_wrappers::*;
 pub use bindings::*;
+
+/// A Rust type with a C counterpart that Go exports take by value or pointer
+pub trait FfiStruct: Sized {
+    /// The struct bindgen generated from the header
+    type Repr: Copy;
+
+    fn to_repr(&self) -> Result<Self::Repr, DllError>;
+
+    fn from_repr(repr: Self::Repr) -> Result<Self, DllError>;
+}
+
+/// Implements [`FfiStruct`] for a Rust struct whose fields have the same
+/// names as the C struct's.
+///
+/// ```ignore
+/// pub struct Point {
+///     pub x: i64,
+///     pub y: i64,
+/// }
+///
+/// rust_go_ffi::ffi_struct!(Point => rust_go_ffi::ffi::Point { x, y });
+/// ```
+#[macro_export]
+macro_rules! ffi_struct {
+    ($rust:ident => $repr:path { $($field:ident),* $(,)? }) => {
+        impl $crate::ffi::FfiStruct for $rust {
+            type Repr = $repr;
+
+            fn to_repr(&self) -> ::std::result::Result<$repr, $crate::DllError> {
+                ::std::result::Result::Ok(Self::Repr {
+                    $($field: ::std::convert::TryFrom::try_from(
+                        ::std::clone::Clone::clone(&self.$field),
+                    )?,)*
+                })
+            }
+
+            fn from_repr(repr: $repr) -> ::std::result::Result<Self, $crate::DllError> {
+                ::std::result::Result::Ok($rust {
+                    $($field: ::std::convert::TryFrom::try_from(repr.$field)?,)*
+                })
+            }
+        }
+    };
+}
+
+/// Fails compilation unless two types have the same size and alignment.
+///
+/// Use it for `#[repr(C)]` Rust types that mirror a generated struct, so a
+/// change on the Go side breaks the build instead of corrupting memory.
+#[macro_export]
+macro_rules! assert_ffi_layout {
+    ($rust:ty, $repr:ty) => {
+        const _: () = {
+            assert!(
+                ::std::mem::size_of::<$rust>() == ::std::mem::size_of::<$repr>(),
+                concat!(
+                    "size of ",
+                    stringify!($rust),
+                    " differs from ",
+                    stringify!($repr)
+                )
+            );
+            assert!(
+                ::std::mem::align_of::<$rust>() == ::std::mem::align_of::<$repr>(),
+                concat!(
+                    "alignment of ",
+                    stringify!($rust),
+                    " differs from ",
+                    stringify!($repr)
+                )
+            );
+        };
+    };
+}
@@ -0,0 +1,147 @@
+//! Ownership tracking for memory the Go side hands to Rust.
+//!
+//! Every string and buffer a Go export returns is recorded here when Rust
+//! takes ownership and removed right before it goes back to the library's
+//! allocator. Releasing a pointer that isn't recorded is a double free, and
+//! anything still recorded once nothing references its library copy any
+//! more has leaked, since owners keep their copy alive. Both are bugs in the
+//! wrappers: debug builds panic, release builds log them and skip the free.
+
+use log::error;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use crate::library::Library;
+
+/// A Go allocation Rust currently owns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoAllocation {
+    /// Export that returned it
+    pub export: &'static str,
+    pub address: usize,
+    pub len: usize,
+    pub allocated_at: SystemTime,
+    /// Identifies the library copy that allocated it
+    library: usize,
+}
+
+static LIVE: Lazy<Mutex<HashMap<usize, GoAllocation>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn report(message: String) {
+    if cfg!(debug_assertions) {
+        panic!("{}", message);
+    }
+    error!("{}", message);
+}
+
+fn library_id(library: &Library) -> usize {
+    library as *const Library as usize
+}
+
+/// Records that Rust owns `address`, which `export` of `library` returned
+pub(crate) fn track(library: &Library, export: &'static str, address: usize, len: usize) {
+    track_in(library_id(library), export, address, len);
+}
+
+fn track_in(library: usize, export: &'static str, address: usize, len: usize) {
+    let allocation = GoAllocation {
+        export,
+        address,
+        len,
+        allocated_at: SystemTime::now(),
+        library,
+    };
+    let previous = LIVE.lock().insert(address, allocation);
+    if let Some(previous) = previous {
+        report(format!(
+            "{} returned {:#x}, which {} returned and Rust still owns",
+            export, address, previous.export
+        ));
+    }
+}
+
+/// Forgets `address` before it is freed.
+///
+/// Returns `false`, after reporting it, if Rust doesn't own the address; the
+/// caller must not free it then.
+pub(crate) fn release(address: usize, free_export: &str) -> bool {
+    if LIVE.lock().remove(&address).is_some() {
+        return true;
+    }
+    report(format!(
+        "{} called on {:#x}, which Rust doesn't own; double free?",
+        free_export, address
+    ));
+    false
+}
+
+/// Reports allocations of `library` that are still recorded, as the last
+/// reference to it is released
+pub(crate) fn check_released(library: &Library) {
+    check_released_in(library_id(library));
+}
+
+fn check_released_in(id: usize) {
+    let leaked: Vec<GoAllocation> = LIVE
+        .lock()
+        .values()
+        .filter(|a| a.library == id)
+        .cloned()
+        .collect();
+    if leaked.is_empty() {
+        return;
+    }
+    let summary: Vec<String> = leaked
+        .iter()
+        .map(|a| format!("{} bytes from {} at {:#x}", a.len, a.export, a.address))
+        .collect();
+    report(format!(
+        "{} Go allocation(s) leaked: {}",
+        leaked.len(),
+        summary.join(", ")
+    ));
+}
+
+/// Go allocations Rust currently owns.
+///
+/// Meant for tests: once every string and buffer is dropped this is empty.
+pub fn leaked_allocations() -> Vec<GoAllocation> {
+    let mut live: Vec<GoAllocation> = LIVE.lock().values().cloned().collect();
+    live.sort_by_key(|a| a.allocated_at);
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Neither is ever dereferenced, so any distinct values work.
+    const LIBRARY: usize = 0x11b_0000;
+    const ADDRESS: usize = 0x5eed_0001;
+
+    #[test]
+    fn test_track_and_release() {
+        track_in(LIBRARY, "GoRuntimeVersion", ADDRESS, 8);
+        assert!(leaked_allocations().iter().any(|a| a.address == ADDRESS));
+        assert!(release(ADDRESS, "FreeGoString"));
+        assert!(!leaked_allocations().iter().any(|a| a.address == ADDRESS));
+        check_released_in(LIBRARY);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "leaked")]
+    fn test_leak_panics_in_debug() {
+        track_in(LIBRARY + 1, "DecodeBase64", ADDRESS + 2, 16);
+        check_released_in(LIBRARY + 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "double free")]
+    fn test_double_free_panics_in_debug() {
+        release(ADDRESS + 1, "FreeGoBytes");
+    }
+}
@@ -0,0 +1,117 @@
+//! Log output of the Go side, re-emitted through the `log` facade.
+//!
+//! Go code logs with the standard `log` and `slog` packages, which write to
+//! the host's stderr by default. [`bridge_go_logs`] registers the `"log"`
+//! callback and asks Go for records up to [`log::max_level`]; Go then sends
+//! them here instead. Each record keeps the level Go gave it and its target,
+//! `go` unless the Go logger carries a `target` attribute, so the usual
+//! per-target filters apply. With `tracing`, install `tracing-log`'s
+//! `LogTracer` to receive them as events.
+
+use log::{warn, Level, Record};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::os::raw::c_int;
+use std::sync::Arc;
+
+use crate::callback::{register_callback, CallbackHandle, CallbackResult};
+use crate::{call_export, ensure_initialized, last_error, poison, DllError};
+
+const LOG_CALLBACK: &str = "log";
+
+/// One record from Go
+#[derive(Debug, PartialEq, Eq)]
+struct GoRecord<'a> {
+    level: Level,
+    target: &'a str,
+    message: &'a str,
+}
+
+impl<'a> GoRecord<'a> {
+    /// Splits the wire format: the level byte, the target, a NUL byte and
+    /// the message
+    fn decode(raw: &'a [u8]) -> Option<Self> {
+        let (&level, rest) = raw.split_first()?;
+        let level = match level {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            5 => Level::Trace,
+            _ => return None,
+        };
+        let split = rest.iter().position(|&b| b == 0)?;
+        Some(Self {
+            level,
+            target: std::str::from_utf8(&rest[..split]).ok()?,
+            message: std::str::from_utf8(&rest[split + 1..]).ok()?,
+        })
+    }
+}
+
+/// The callback of the copy the bridge was set up for
+static HANDLE: Lazy<Mutex<Option<CallbackHandle>>> = Lazy::new(|| Mutex::new(None));
+
+fn forward(raw: &[u8]) -> CallbackResult {
+    let Some(record) = GoRecord::decode(raw) else {
+        warn!("Ignoring malformed Go log record of {} bytes", raw.len());
+        return Ok(());
+    };
+    log::logger().log(
+        &Record::builder()
+            .level(record.level)
+            .target(record.target)
+            .args(format_args!("{}", record.message))
+            .build(),
+    );
+    Ok(())
+}
+
+/// Sends the Go library's `log` and `slog` output to the `log` facade.
+///
+/// Records above the current [`log::max_level`] are dropped on the Go side
+/// before they are formatted; call this again after raising the level, or
+/// after [`reload_dll`](crate::reload_dll) to move the bridge to the new
+/// copy. With logging off, Go keeps writing to stderr. Records from
+/// `log.Print` arrive at info level.
+pub fn bridge_go_logs() -> Result<(), DllError> {
+    let library = ensure_initialized()?;
+    let set_log_level = library.symbol(&library.symbols.set_log_level)?;
+    let mut handle = HANDLE.lock();
+    if !handle
+        .as_ref()
+        .is_some_and(|h| Arc::ptr_eq(h.library(), &library))
+    {
+        *handle = None;
+        *handle = Some(register_callback(LOG_CALLBACK, forward)?);
+    }
+
+    let level = log::max_level();
+    call_export("SetLogLevel", || unsafe {
+        set_log_level(level as usize as c_int)
+    })
+    .map_err(poison)?;
+    last_error::check(&library, "SetLogLevel").map_err(poison)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_record() {
+        let record = GoRecord::decode(b"\x02go::lifecycle\x00deadline passed").unwrap();
+        assert_eq!(
+            record,
+            GoRecord {
+                level: Level::Warn,
+                target: "go::lifecycle",
+                message: "deadline passed",
+            }
+        );
+        assert_eq!(GoRecord::decode(b"\x03go\x00").unwrap().message, "");
+        assert!(GoRecord::decode(b"\x07go\x00x").is_none());
+        assert!(GoRecord::decode(b"\x03go").is_none());
+        assert!(GoRecord::decode(b"").is_none());
+    }
+}
@@ -0,0 +1,110 @@
+//! Structured exception handling around calls into the Go library.
+//!
+//! With the `guarded` feature on Windows MSVC targets every call runs inside
+//! the `__try`/`__except` frame of a small C shim, so an access violation or
+//! other hardware fault in the DLL surfaces as
+//! [`DllError::HardwareException`] instead of killing the process. Faults in
+//! Go code are already turned into Go panics by the Go runtime; this catches
+//! the ones in cgo and C code. Elsewhere calls run unguarded.
+
+use crate::DllError;
+
+/// Whether calls are actually wrapped in an exception frame in this build
+pub const GUARDED: bool = cfg!(all(feature = "guarded", windows, target_env = "msvc"));
+
+/// Runs `f`, turning a hardware exception raised inside it into an error
+#[cfg(all(feature = "guarded", windows, target_env = "msvc"))]
+pub(crate) fn guarded<T, F>(export: &str, f: F) -> Result<T, DllError>
+where
+    F: FnOnce() -> T,
+{
+    use std::ffi::c_void;
+    use std::os::raw::{c_int, c_ulong};
+    use std::ptr;
+
+    extern "C" {
+        fn rgf_guarded_call(
+            thunk: unsafe extern "C" fn(*mut c_void),
+            context: *mut c_void,
+            code: *mut c_ulong,
+            address: *mut *mut c_void,
+        ) -> c_int;
+    }
+
+    struct Call<T, F> {
+        f: Option<F>,
+        result: Option<T>,
+    }
+
+    unsafe extern "C" fn thunk<T, F: FnOnce() -> T>(context: *mut c_void) {
+        let call = &mut *context.cast::<Call<T, F>>();
+        if let Some(f) = call.f.take() {
+            call.result = Some(f());
+        }
+    }
+
+    let mut call = Call {
+        f: Some(f),
+        result: None,
+    };
+    let mut code: c_ulong = 0;
+    let mut address: *mut c_void = ptr::null_mut();
+    let faulted = unsafe {
+        rgf_guarded_call(
+            thunk::<T, F>,
+            ptr::addr_of_mut!(call).cast(),
+            &mut code,
+            &mut address,
+        )
+    };
+    match call.result {
+        Some(result) if faulted == 0 => Ok(result),
+        _ => Err(DllError::HardwareException {
+            export: export.to_string(),
+            code,
+            address: address as usize,
+        }),
+    }
+}
+
+/// Runs `f` without an exception frame
+#[cfg(not(all(feature = "guarded", windows, target_env = "msvc")))]
+pub(crate) fn guarded<T, F>(_export: &str, f: F) -> Result<T, DllError>
+where
+    F: FnOnce() -> T,
+{
+    Ok(f())
+}
+
+/// Name of a Windows exception code, if it is one the guard catches
+pub fn exception_name(code: u32) -> Option<&'static str> {
+    Some(match code {
+        0xC000_0005 => "EXCEPTION_ACCESS_VIOLATION",
+        0xC000_0006 => "EXCEPTION_IN_PAGE_ERROR",
+        0xC000_001D => "EXCEPTION_ILLEGAL_INSTRUCTION",
+        0xC000_0096 => "EXCEPTION_PRIV_INSTRUCTION",
+        0xC000_0094 => "EXCEPTION_INT_DIVIDE_BY_ZERO",
+        0x8000_0002 => "EXCEPTION_DATATYPE_MISALIGNMENT",
+        0xC000_008C => "EXCEPTION_ARRAY_BOUNDS_EXCEEDED",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exception_name() {
+        assert_eq!(
+            exception_name(0xC000_0005),
+            Some("EXCEPTION_ACCESS_VIOLATION")
+        );
+        assert_eq!(exception_name(0xE06D_7363), None);
+    }
+
+    #[test]
+    fn test_guarded_passes_result_through() {
+        assert_eq!(guarded("AddNumbers", || 42).unwrap(), 42);
+    }
+}
@@ -0,0 +1,93 @@
+//! Active health check for readiness probes.
+//!
+//! Unlike [`status`](crate::status), [`health_check`] calls into the library:
+//! it round-trips a nonce through the `Ping` export, asks the library for its
+//! version again and reads the Go runtime version, all under one deadline.
+
+use semver::Version;
+use std::mem;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::transfer::{self, TransferKind};
+use crate::{
+    call_export, config, ensure_initialized, get_dll_version, go_runtime_version, last_error,
+    poison, with_timeout, DllError, VersionRequirement, DEFAULT_VERSION, DLL_CONTEXT,
+};
+
+static NONCE: AtomicI64 = AtomicI64::new(1);
+
+/// Outcome of a [`health_check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    /// Round trip of the `Ping` export alone
+    pub latency: Duration,
+    /// Version the library reports now
+    pub version: Version,
+    /// Whether `version` still satisfies the requirement the library was
+    /// initialized with and matches what it reported back then
+    pub version_consistent: bool,
+    /// Version of the Go runtime inside the library, e.g. `go1.22.1`
+    pub go_runtime: String,
+}
+
+impl HealthReport {
+    /// Whether the library should keep receiving traffic
+    pub fn is_ready(&self) -> bool {
+        self.version_consistent
+    }
+}
+
+/// Pings the library and checks its version, failing with
+/// [`DllError::Timeout`] if that takes longer than `timeout`.
+///
+/// Initializes the library if needed. A timeout poisons the context like any
+/// other stuck call, so a hung Go runtime keeps failing the probe until
+/// [`reset`](crate::reset).
+///
+/// ```ignore
+/// let report = health_check(Duration::from_secs(1))?;
+/// assert!(report.is_ready());
+/// ```
+pub fn health_check(timeout: Duration) -> Result<HealthReport, DllError> {
+    with_timeout(timeout, probe)
+}
+
+fn probe() -> Result<HealthReport, DllError> {
+    let library = ensure_initialized()?;
+    let (initialized_with, required) = {
+        let context = DLL_CONTEXT.read()?;
+        let required = context
+            .required_version
+            .clone()
+            .unwrap_or_else(|| VersionRequirement::from(DEFAULT_VERSION));
+        (context.version.clone(), required)
+    };
+
+    let ping = library.symbol(&library.symbols.ping)?;
+    let nonce = NONCE.fetch_add(1, Ordering::Relaxed);
+    transfer::record_in("Ping", TransferKind::Scalar, mem::size_of_val(&nonce));
+    let started = Instant::now();
+    let echoed = call_export("Ping", || unsafe { ping(nonce) }).map_err(poison)?;
+    let latency = started.elapsed();
+    transfer::record_out("Ping", TransferKind::Scalar, mem::size_of_val(&echoed));
+    last_error::check(&library, "Ping").map_err(poison)?;
+    if echoed != nonce {
+        return Err(DllError::CallFailed {
+            export: "Ping".to_string(),
+            message: format!("echoed {} for nonce {}", echoed, nonce),
+        });
+    }
+
+    let version = unsafe { get_dll_version(&library) }.map_err(poison)?;
+    // Statically linked builds are initialized with an assumed version.
+    let version_consistent = required.check(&version, config::strict_version()).is_ok()
+        && (cfg!(feature = "static") || version == initialized_with);
+
+    Ok(HealthReport {
+        latency,
+        version,
+        version_consistent,
+        go_runtime: go_runtime_version()?.to_string(),
+    })
+}
@@ -0,0 +1,217 @@
+//! Interceptors around every call into the Go library.
+//!
+//! Hooks added with [`add_hook`] run on the calling thread around each
+//! export a wrapper calls, in the order they were added. A hook can reject a
+//! call in [`before_call`](FfiHook::before_call), which is how rate limits
+//! plug in; the wrapper then fails with [`DllError::Rejected`] without
+//! calling into Go.
+//!
+//! The argument summary is what the wrapper marshaled for the export on the
+//! same thread just before the call. The result only covers the call itself:
+//! errors the library reports through `TakeThreadGoError` afterwards surface
+//! from the wrapper, not here.
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{DllError, TransferKind};
+
+/// What a wrapper marshaled into Go for one call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgSummary {
+    pub kind: TransferKind,
+    pub bytes: usize,
+}
+
+/// A call into the Go library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallInfo {
+    pub export: &'static str,
+    /// `None` if the wrapper passes no arguments
+    pub args: Option<ArgSummary>,
+}
+
+/// Interceptor for calls into the Go library; see [`add_hook`].
+///
+/// ```ignore
+/// struct Audit;
+///
+/// impl FfiHook for Audit {
+///     fn after_call(&self, call: &CallInfo, duration: Duration, result: Result<(), &DllError>) {
+///         info!("{} took {:?}: {:?}", call.export, duration, result);
+///     }
+/// }
+///
+/// add_hook(Audit);
+/// ```
+pub trait FfiHook: Send + Sync {
+    /// Runs before the call; an error rejects it with `reason`
+    fn before_call(&self, _call: &CallInfo) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Runs after the call, or after a later hook rejected it
+    fn after_call(&self, _call: &CallInfo, _duration: Duration, _result: Result<(), &DllError>) {}
+}
+
+/// Identifies a hook for [`remove_hook`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+type Hooks = Arc<Vec<(HookId, Arc<dyn FfiHook>)>>;
+
+/// Replaced as a whole on every change, so calls never hold the lock while
+/// hooks run and a hook may add or remove hooks.
+static HOOKS: Lazy<RwLock<Hooks>> = Lazy::new(|| RwLock::new(Arc::new(Vec::new())));
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Arguments recorded for the export this thread is about to call
+    static PENDING_ARGS: Cell<Option<(&'static str, ArgSummary)>> = const { Cell::new(None) };
+}
+
+/// Adds `hook` after the ones already installed
+pub fn add_hook(hook: impl FfiHook + 'static) -> HookId {
+    let id = HookId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let mut hooks = HOOKS.write();
+    let mut updated = Vec::clone(&hooks);
+    updated.push((id, Arc::new(hook)));
+    *hooks = Arc::new(updated);
+    id
+}
+
+/// Removes a hook; returns whether it was installed
+pub fn remove_hook(id: HookId) -> bool {
+    let mut hooks = HOOKS.write();
+    if !hooks.iter().any(|(installed, _)| *installed == id) {
+        return false;
+    }
+    let updated = hooks
+        .iter()
+        .filter(|(installed, _)| *installed != id)
+        .cloned()
+        .collect();
+    *hooks = Arc::new(updated);
+    true
+}
+
+/// Notes `bytes` of `kind` marshaled for the next call to `export`
+pub(crate) fn note_args(export: &'static str, kind: TransferKind, bytes: usize) {
+    PENDING_ARGS.with(|pending| pending.set(Some((export, ArgSummary { kind, bytes }))));
+}
+
+/// A call the hooks have let through
+pub(crate) struct ActiveCall {
+    hooks: Hooks,
+    call: CallInfo,
+    started: Instant,
+}
+
+/// Runs every `before_call` for `export`.
+///
+/// `None` when no hooks are installed.
+pub(crate) fn before(export: &'static str) -> Result<Option<ActiveCall>, DllError> {
+    let args = PENDING_ARGS
+        .with(Cell::take)
+        .and_then(|(name, args)| (name == export).then_some(args));
+    let hooks = HOOKS.read().clone();
+    if hooks.is_empty() {
+        return Ok(None);
+    }
+
+    let call = CallInfo { export, args };
+    for (i, (_, hook)) in hooks.iter().enumerate() {
+        if let Err(reason) = hook.before_call(&call) {
+            let error = DllError::Rejected {
+                export: export.to_string(),
+                reason,
+            };
+            for (_, earlier) in &hooks[..i] {
+                earlier.after_call(&call, Duration::ZERO, Err(&error));
+            }
+            return Err(error);
+        }
+    }
+    Ok(Some(ActiveCall {
+        hooks,
+        call,
+        started: Instant::now(),
+    }))
+}
+
+impl ActiveCall {
+    /// Runs every `after_call` with `result`
+    pub(crate) fn finish(self, result: Result<(), &DllError>) {
+        let duration = self.started.elapsed();
+        for (_, hook) in self.hooks.iter() {
+            hook.after_call(&self.call, duration, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    use crate::call_export;
+
+    #[derive(Default)]
+    struct Recorder {
+        export: &'static str,
+        calls: Mutex<Vec<(Option<ArgSummary>, bool)>>,
+    }
+
+    impl FfiHook for Arc<Recorder> {
+        fn before_call(&self, call: &CallInfo) -> Result<(), String> {
+            if call.export == "HookReject" {
+                return Err("not allowed".to_string());
+            }
+            Ok(())
+        }
+
+        fn after_call(&self, call: &CallInfo, _duration: Duration, result: Result<(), &DllError>) {
+            if call.export == self.export {
+                self.calls.lock().push((call.args, result.is_ok()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hooks_see_calls() {
+        let recorder = Arc::new(Recorder {
+            export: "HookTest",
+            ..Default::default()
+        });
+        let id = add_hook(recorder.clone());
+
+        crate::transfer::record_in("HookTest", TransferKind::Slice, 24);
+        assert_eq!(call_export("HookTest", || 42).unwrap(), 42);
+        call_export("HookTest", || ()).unwrap();
+        assert!(remove_hook(id));
+        call_export("HookTest", || ()).unwrap();
+
+        let args = ArgSummary {
+            kind: TransferKind::Slice,
+            bytes: 24,
+        };
+        assert_eq!(*recorder.calls.lock(), [(Some(args), true), (None, true)]);
+        assert!(!remove_hook(id));
+    }
+
+    #[test]
+    fn test_hook_rejects_call() {
+        let id = add_hook(Arc::new(Recorder::default()));
+        let mut called = false;
+        let result = call_export("HookReject", || called = true);
+        remove_hook(id);
+        assert!(
+            matches!(result, Err(DllError::Rejected { reason, .. }) if reason == "not allowed")
+        );
+        assert!(!called);
+    }
+}
@@ -0,0 +1,330 @@
+//! Minimal readers for the headers and export tables of PE and ELF shared
+//! libraries.
+//!
+//! Only what is needed to list exported symbol names and tell the target
+//! architecture is parsed; the file is never mapped or executed.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+const PE_SIGNATURE: &[u8] = b"PE\0\0";
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const SHT_DYNSYM: u32 = 11;
+const STT_FUNC: u8 = 2;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+/// Enough of the file to reach the PE file header or the ELF `e_machine` field
+const HEADER_LEN: u64 = 4096;
+
+/// (PE machine, ELF e_machine, name as in `std::env::consts::ARCH`)
+const MACHINES: &[(u16, u16, &str)] = &[
+    (0x014c, 3, "x86"),
+    (0x8664, 62, "x86_64"),
+    (0x01c4, 40, "arm"),
+    (0xaa64, 183, "aarch64"),
+];
+
+/// Reads `path` and returns the names it exports
+pub(crate) fn exported_symbols(path: &Path) -> Result<Vec<String>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_exports(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub(crate) fn parse_exports(bytes: &[u8]) -> Result<Vec<String>, String> {
+    if bytes.starts_with(b"MZ") {
+        pe_exports(bytes)
+    } else if bytes.starts_with(ELF_MAGIC) {
+        elf_exports(bytes)
+    } else {
+        Err("unrecognized image format".to_string())
+    }
+}
+
+/// Reads the header of `path` and returns the architecture it was built for
+pub(crate) fn architecture_of(path: &Path) -> Result<&'static str, String> {
+    let mut header = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(HEADER_LEN).read_to_end(&mut header))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_architecture(&header).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub(crate) fn parse_architecture(bytes: &[u8]) -> Result<&'static str, String> {
+    let (machine, column) = if bytes.starts_with(b"MZ") {
+        let pe = u32_at(bytes, 0x3c)? as usize;
+        if bytes.get(pe..pe + 4) != Some(PE_SIGNATURE) {
+            return Err("missing PE signature".to_string());
+        }
+        (u16_at(bytes, pe + 4)?, 0)
+    } else if bytes.starts_with(ELF_MAGIC) {
+        if bytes.get(5) != Some(&1) {
+            return Err("big-endian ELF images are not supported".to_string());
+        }
+        (u16_at(bytes, 0x12)?, 1)
+    } else {
+        return Err("unrecognized image format".to_string());
+    };
+
+    MACHINES
+        .iter()
+        .find(|entry| [entry.0, entry.1][column] == machine)
+        .map(|entry| entry.2)
+        .ok_or_else(|| format!("unknown machine type {:#x}", machine))
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, String> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| format!("truncated image at offset {:#x}", offset))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format!("truncated image at offset {:#x}", offset))
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, String> {
+    let low = u64::from(u32_at(bytes, offset)?);
+    let high = u64::from(u32_at(bytes, offset + 4)?);
+    Ok(low | (high << 32))
+}
+
+fn c_str_at(bytes: &[u8], offset: usize) -> Result<String, String> {
+    let tail = bytes
+        .get(offset..)
+        .ok_or_else(|| format!("string offset {:#x} out of bounds", offset))?;
+    let end = tail
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| format!("unterminated string at {:#x}", offset))?;
+    Ok(String::from_utf8_lossy(&tail[..end]).into_owned())
+}
+
+fn pe_exports(bytes: &[u8]) -> Result<Vec<String>, String> {
+    let pe = u32_at(bytes, 0x3c)? as usize;
+    if bytes.get(pe..pe + 4) != Some(PE_SIGNATURE) {
+        return Err("missing PE signature".to_string());
+    }
+    let coff = pe + 4;
+    let section_count = u16_at(bytes, coff + 2)? as usize;
+    let optional_size = u16_at(bytes, coff + 16)? as usize;
+    let optional = coff + 20;
+
+    let directories = match u16_at(bytes, optional)? {
+        PE32_MAGIC => optional + 96,
+        PE32_PLUS_MAGIC => optional + 112,
+        magic => return Err(format!("unknown optional header magic {:#x}", magic)),
+    };
+    // The export table is data directory 0.
+    let export_rva = u32_at(bytes, directories)?;
+    if export_rva == 0 {
+        return Ok(Vec::new());
+    }
+
+    let sections = optional + optional_size;
+    let rva_to_offset = |rva: u32| -> Result<usize, String> {
+        for i in 0..section_count {
+            let header = sections + i * 40;
+            let virtual_size = u32_at(bytes, header + 8)?;
+            let virtual_address = u32_at(bytes, header + 12)?;
+            let raw_size = u32_at(bytes, header + 16)?;
+            let raw_offset = u32_at(bytes, header + 20)?;
+            let size = virtual_size.max(raw_size);
+            if rva >= virtual_address && rva < virtual_address + size {
+                return Ok((rva - virtual_address + raw_offset) as usize);
+            }
+        }
+        Err(format!("RVA {:#x} is not inside any section", rva))
+    };
+
+    let directory = rva_to_offset(export_rva)?;
+    let name_count = u32_at(bytes, directory + 24)? as usize;
+    let names = rva_to_offset(u32_at(bytes, directory + 32)?)?;
+
+    (0..name_count)
+        .map(|i| {
+            let name_rva = u32_at(bytes, names + i * 4)?;
+            c_str_at(bytes, rva_to_offset(name_rva)?)
+        })
+        .collect()
+}
+
+fn elf_exports(bytes: &[u8]) -> Result<Vec<String>, String> {
+    let is_64 = match bytes.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err("invalid ELF class".to_string()),
+    };
+    if bytes.get(5) != Some(&1) {
+        return Err("big-endian ELF images are not supported".to_string());
+    }
+
+    let (section_table, entry_size, section_count) = if is_64 {
+        (
+            u64_at(bytes, 0x28)? as usize,
+            u16_at(bytes, 0x3a)? as usize,
+            u16_at(bytes, 0x3c)? as usize,
+        )
+    } else {
+        (
+            u32_at(bytes, 0x20)? as usize,
+            u16_at(bytes, 0x2e)? as usize,
+            u16_at(bytes, 0x30)? as usize,
+        )
+    };
+
+    // (offset, size, link, entsize) of a section header
+    let section = |index: usize| -> Result<(usize, usize, usize, usize), String> {
+        let header = section_table + index * entry_size;
+        if is_64 {
+            Ok((
+                u64_at(bytes, header + 0x18)? as usize,
+                u64_at(bytes, header + 0x20)? as usize,
+                u32_at(bytes, header + 0x28)? as usize,
+                u64_at(bytes, header + 0x38)? as usize,
+            ))
+        } else {
+            Ok((
+                u32_at(bytes, header + 0x10)? as usize,
+                u32_at(bytes, header + 0x14)? as usize,
+                u32_at(bytes, header + 0x18)? as usize,
+                u32_at(bytes, header + 0x24)? as usize,
+            ))
+        }
+    };
+
+    let mut exports = Vec::new();
+    for index in 0..section_count {
+        let header = section_table + index * entry_size;
+        if u32_at(bytes, header + 4)? != SHT_DYNSYM {
+            continue;
+        }
+        let (offset, size, link, symbol_size) = section(index)?;
+        let (strings, _, _, _) = section(link)?;
+        if symbol_size == 0 {
+            return Err("dynamic symbol table has zero entry size".to_string());
+        }
+
+        for symbol in (offset..offset + size).step_by(symbol_size) {
+            let (name, info, section_index) = if is_64 {
+                (
+                    u32_at(bytes, symbol)?,
+                    *bytes.get(symbol + 4).ok_or("truncated symbol")?,
+                    u16_at(bytes, symbol + 6)?,
+                )
+            } else {
+                (
+                    u32_at(bytes, symbol)?,
+                    *bytes.get(symbol + 12).ok_or("truncated symbol")?,
+                    u16_at(bytes, symbol + 14)?,
+                )
+            };
+            let binding = info >> 4;
+            let kind = info & 0xf;
+            // Undefined symbols are imports, not exports.
+            if section_index == 0
+                || kind != STT_FUNC
+                || !(binding == STB_GLOBAL || binding == STB_WEAK)
+            {
+                continue;
+            }
+            exports.push(c_str_at(bytes, strings + name as usize)?);
+        }
+    }
+    Ok(exports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a PE32+ image with one section holding an export table
+    fn pe_with_exports(names: &[&str]) -> Vec<u8> {
+        const SECTION_RVA: u32 = 0x1000;
+        const SECTION_OFFSET: usize = 0x200;
+
+        let mut image = vec![0u8; SECTION_OFFSET];
+        image[..2].copy_from_slice(b"MZ");
+        image[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        image[0x40..0x44].copy_from_slice(PE_SIGNATURE);
+        let coff = 0x44;
+        image[coff + 2..coff + 4].copy_from_slice(&1u16.to_le_bytes());
+        image[coff + 16..coff + 18].copy_from_slice(&240u16.to_le_bytes());
+        let optional = coff + 20;
+        image[optional..optional + 2].copy_from_slice(&PE32_PLUS_MAGIC.to_le_bytes());
+        image[optional + 112..optional + 116].copy_from_slice(&SECTION_RVA.to_le_bytes());
+
+        // Section: export directory (40 bytes), name pointers, then strings.
+        let mut section = vec![0u8; 40];
+        let pointers = 40;
+        let strings = pointers + names.len() * 4;
+        section[24..28].copy_from_slice(&(names.len() as u32).to_le_bytes());
+        section[32..36].copy_from_slice(&(SECTION_RVA + pointers as u32).to_le_bytes());
+        section.resize(strings, 0);
+        for (i, name) in names.iter().enumerate() {
+            let rva = SECTION_RVA + section.len() as u32;
+            section[pointers + i * 4..pointers + i * 4 + 4].copy_from_slice(&rva.to_le_bytes());
+            section.extend_from_slice(name.as_bytes());
+            section.push(0);
+        }
+
+        let header = optional + 240;
+        let size = section.len() as u32;
+        image[header + 8..header + 12].copy_from_slice(&size.to_le_bytes());
+        image[header + 12..header + 16].copy_from_slice(&SECTION_RVA.to_le_bytes());
+        image[header + 16..header + 20].copy_from_slice(&size.to_le_bytes());
+        image[header + 20..header + 24].copy_from_slice(&(SECTION_OFFSET as u32).to_le_bytes());
+        image.extend_from_slice(&section);
+        image
+    }
+
+    #[test]
+    fn test_pe_exports() {
+        let image = pe_with_exports(&["AddNumbers", "GetDLLVersion", "GoFunction"]);
+        assert_eq!(
+            parse_exports(&image).unwrap(),
+            vec!["AddNumbers", "GetDLLVersion", "GoFunction"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_format() {
+        assert!(parse_exports(b"not a library").is_err());
+        assert!(parse_architecture(b"not a library").is_err());
+    }
+
+    #[test]
+    fn test_pe_architecture() {
+        let mut image = pe_with_exports(&[]);
+        for (machine, _, name) in MACHINES {
+            image[0x44..0x46].copy_from_slice(&machine.to_le_bytes());
+            assert_eq!(parse_architecture(&image).unwrap(), *name);
+        }
+        image[0x44..0x46].copy_from_slice(&0x1234u16.to_le_bytes());
+        assert!(parse_architecture(&image).is_err());
+    }
+
+    #[test]
+    fn test_elf_architecture() {
+        let mut image = vec![0u8; 0x40];
+        image[..4].copy_from_slice(ELF_MAGIC);
+        image[4] = 2;
+        image[5] = 1;
+        for (_, machine, name) in MACHINES {
+            image[0x12..0x14].copy_from_slice(&machine.to_le_bytes());
+            assert_eq!(parse_architecture(&image).unwrap(), *name);
+        }
+    }
+
+    #[test]
+    fn test_rejects_truncated_pe() {
+        let image = pe_with_exports(&["AddNumbers"]);
+        assert!(parse_exports(&image[..0x50]).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+//! Installer progress as JSON lines, for the `installer-json` feature.
+//!
+//! [`JsonProgress`] writes one [`InstallerEvent`] per line, so CI pipelines
+//! and provisioning tools can follow an install and read whether it worked
+//! and where the library went without scraping console output.
+
+use log::warn;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::install_progress::{InstallOperation, InstallProgress, InstallStep, StepStatus};
+
+/// One thing that happened during an install or uninstall, serialized with
+/// its kind in the `event` field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum InstallerEvent {
+    Started {
+        operation: InstallOperation,
+    },
+    Step {
+        /// Name of the [`InstallStep`], e.g. `move_into_place`
+        step: &'static str,
+        /// Path or asset name the step works on
+        target: Option<String>,
+        status: StepStatus,
+    },
+    /// Bytes of the current download received so far
+    Progress {
+        received: u64,
+        total: u64,
+    },
+    Completed {
+        operation: InstallOperation,
+        success: bool,
+        error: Option<String>,
+        /// Where the library was installed or removed from
+        dll_path: Option<PathBuf>,
+    },
+}
+
+impl InstallerEvent {
+    pub fn step(step: InstallStep<'_>, status: StepStatus) -> Self {
+        let path = |path: &std::path::Path| Some(path.display().to_string());
+        let (step, target) = match step {
+            InstallStep::WaitForLock(lock) => ("wait_for_lock", path(lock)),
+            InstallStep::CreateDirectory(dir) => ("create_directory", path(dir)),
+            InstallStep::Copy(to) => ("copy", path(to)),
+            InstallStep::Download(asset) => ("download", Some(asset.to_string())),
+            InstallStep::Extract(archive) => ("extract", path(archive)),
+            InstallStep::WriteEmbedded(to) => ("write_embedded", path(to)),
+            InstallStep::MoveIntoPlace(to) => ("move_into_place", path(to)),
+            InstallStep::Record(record) => ("record", path(record)),
+            InstallStep::Verify(dll) => ("verify", path(dll)),
+            InstallStep::RollBack => ("roll_back", None),
+            InstallStep::RemoveDll(dll) => ("remove_dll", path(dll)),
+        };
+        InstallerEvent::Step {
+            step,
+            target,
+            status,
+        }
+    }
+}
+
+/// Writes every event as a line of JSON to a writer, stdout by default
+pub struct JsonProgress<W: Write> {
+    writer: Mutex<W>,
+    /// The library the last completed move or removal was about
+    dll_path: Mutex<Option<PathBuf>>,
+}
+
+impl JsonProgress<io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> JsonProgress<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            dll_path: Mutex::new(None),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+
+    fn emit(&self, event: &InstallerEvent) {
+        let mut writer = self.writer.lock();
+        let result = serde_json::to_writer(&mut *writer, event)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(writer))
+            .and_then(|()| writer.flush());
+        if let Err(e) = result {
+            warn!("Could not write installer event: {}", e);
+        }
+    }
+}
+
+impl<W: Write> InstallProgress for JsonProgress<W> {
+    fn on_start(&self, operation: InstallOperation) {
+        *self.dll_path.lock() = None;
+        self.emit(&InstallerEvent::Started { operation });
+    }
+
+    fn on_step(&self, step: InstallStep<'_>, status: StepStatus) {
+        match (step, status) {
+            (InstallStep::MoveIntoPlace(path), StepStatus::Done)
+            | (InstallStep::RemoveDll(path), StepStatus::Done) => {
+                *self.dll_path.lock() = Some(path.to_path_buf());
+            }
+            _ => {}
+        }
+        self.emit(&InstallerEvent::step(step, status));
+    }
+
+    fn on_progress(&self, received: u64, total: u64) {
+        self.emit(&InstallerEvent::Progress { received, total });
+    }
+
+    fn on_complete(&self, operation: InstallOperation, result: Result<(), &io::Error>) {
+        self.emit(&InstallerEvent::Completed {
+            operation,
+            success: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+            dll_path: self.dll_path.lock().take(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_events_are_json_lines() {
+        let progress = JsonProgress::new(Vec::new());
+        let dll = Path::new("bin/go_lib-0.1.0.dll");
+        progress.on_start(InstallOperation::Install);
+        progress.on_step(InstallStep::MoveIntoPlace(dll), StepStatus::Started);
+        progress.on_step(InstallStep::MoveIntoPlace(dll), StepStatus::Done);
+        progress.on_complete(InstallOperation::Install, Ok(()));
+
+        let output = String::from_utf8(progress.into_inner()).unwrap();
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            serde_json::json!({"event": "started", "operation": "install"})
+        );
+        assert_eq!(
+            events[2],
+            serde_json::json!({
+                "event": "step",
+                "step": "move_into_place",
+                "target": dll.display().to_string(),
+                "status": "done",
+            })
+        );
+        assert_eq!(
+            events[3],
+            serde_json::json!({
+                "event": "completed",
+                "operation": "install",
+                "success": true,
+                "error": null,
+                "dll_path": dll,
+            })
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Progress reporting of the installer.
+//!
+//! [`install_dll`](crate::install_dll) and
+//! [`uninstall_dll`](crate::uninstall_dll) print colored console output
+//! through [`ConsoleProgress`]. Services and GUIs pass their own
+//! [`InstallProgress`] to [`install_dll_with`](crate::install_dll_with) and
+//! [`uninstall_dll_with`](crate::uninstall_dll_with), or [`QuietProgress`]
+//! to print nothing. With `installer-json`, `JsonProgress` prints JSON lines
+//! for tools to parse.
+
+use colored::*;
+use parking_lot::Mutex;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// What the installer was asked to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "installer-json",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum InstallOperation {
+    Install,
+    Uninstall,
+}
+
+/// One step of an install or uninstall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InstallStep<'a> {
+    /// Waiting for another process that holds the install lock
+    WaitForLock(&'a Path),
+    CreateDirectory(&'a Path),
+    /// Copying the local build to the given staging file
+    Copy(&'a Path),
+    /// Downloading the named release asset; see
+    /// [`on_progress`](InstallProgress::on_progress)
+    Download(&'a str),
+    /// Extracting the library from the given archive
+    Extract(&'a Path),
+    /// Writing the library embedded in the binary to the given staging file
+    WriteEmbedded(&'a Path),
+    /// Renaming the staged DLL to its final path
+    MoveIntoPlace(&'a Path),
+    /// Recording the installation directory in the given file
+    Record(&'a Path),
+    /// Checking that the installed DLL is there and loads
+    Verify(&'a Path),
+    /// Putting back what was there before a failed install
+    RollBack,
+    RemoveDll(&'a Path),
+}
+
+/// Where a step is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "installer-json",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum StepStatus {
+    Started,
+    Done,
+    /// Nothing to do, e.g. the directory already existed
+    Skipped,
+    Failed,
+}
+
+/// Receives the installer's progress.
+///
+/// Every method does nothing by default.
+pub trait InstallProgress {
+    fn on_start(&self, _operation: InstallOperation) {}
+
+    /// Called with [`StepStatus::Started`] when a step begins and once more
+    /// with how it ended
+    fn on_step(&self, _step: InstallStep<'_>, _status: StepStatus) {}
+
+    /// Bytes of the current download received so far, out of `total`
+    fn on_progress(&self, _received: u64, _total: u64) {}
+
+    fn on_complete(&self, _operation: InstallOperation, _result: Result<(), &io::Error>) {}
+}
+
+/// Reports nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuietProgress;
+
+impl InstallProgress for QuietProgress {}
+
+/// Colored console output with a download percentage
+#[derive(Debug, Default)]
+pub struct ConsoleProgress {
+    /// Asset being downloaded and the percentage last drawn
+    download: Mutex<Option<(String, Option<u64>)>>,
+}
+
+impl ConsoleProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl InstallProgress for ConsoleProgress {
+    fn on_start(&self, operation: InstallOperation) {
+        let banner = match operation {
+            InstallOperation::Install => "🚀 Starting installation process...",
+            InstallOperation::Uninstall => "🗑️  Starting uninstallation...",
+        };
+        println!("{}", banner.cyan().bold());
+    }
+
+    fn on_step(&self, step: InstallStep<'_>, status: StepStatus) {
+        if status == StepStatus::Started {
+            match step {
+                InstallStep::WaitForLock(_) => print!(
+                    "{}",
+                    "⏳ Waiting for another installation to finish... ".yellow()
+                ),
+                InstallStep::CreateDirectory(_) => print!("📁 Creating installation directory... "),
+                InstallStep::Copy(path) => print!(
+                    "📦 Copying DLL to: {}... ",
+                    path.display().to_string().blue()
+                ),
+                InstallStep::Download(name) => {
+                    *self.download.lock() = Some((name.to_string(), None));
+                    print!("⬇️  Downloading {}... ", name.blue());
+                }
+                InstallStep::Extract(path) => print!(
+                    "📦 Extracting DLL from: {}... ",
+                    path.display().to_string().blue()
+                ),
+                InstallStep::WriteEmbedded(path) => print!(
+                    "📦 Writing embedded DLL to: {}... ",
+                    path.display().to_string().blue()
+                ),
+                InstallStep::MoveIntoPlace(_) => print!("🔁 Moving DLL into place... "),
+                InstallStep::Record(_) => print!("📝 Recording installation directory... "),
+                InstallStep::Verify(_) => print!("🔍 Verifying installation... "),
+                InstallStep::RollBack => print!("↩️  Rolling back... "),
+                InstallStep::RemoveDll(path) => {
+                    print!("📦 Removing DLL: {}... ", path.display().to_string().blue())
+                }
+            }
+            let _ = io::stdout().flush();
+            return;
+        }
+
+        if let InstallStep::Download(_) = step {
+            *self.download.lock() = None;
+        }
+        let outcome = match (status, step) {
+            (StepStatus::Done, _) => "OK".green().bold(),
+            (StepStatus::Skipped, InstallStep::CreateDirectory(_)) => "EXISTS".blue().bold(),
+            (StepStatus::Skipped, _) => "NOT FOUND".blue().bold(),
+            (_, _) => "FAILED".red().bold(),
+        };
+        println!("{}", outcome);
+    }
+
+    fn on_progress(&self, received: u64, total: u64) {
+        let mut download = self.download.lock();
+        let Some((name, shown)) = download.as_mut() else {
+            return;
+        };
+        let percent = (received * 100).checked_div(total).unwrap_or(100);
+        if *shown != Some(percent) {
+            *shown = Some(percent);
+            print!("\r⬇️  Downloading {}... {:>3}% ", name.blue(), percent);
+            let _ = io::stdout().flush();
+        }
+    }
+
+    fn on_complete(&self, operation: InstallOperation, result: Result<(), &io::Error>) {
+        let message = match (operation, result) {
+            (InstallOperation::Install, Ok(())) => {
+                "✅ Installation completed successfully!".green().bold()
+            }
+            (InstallOperation::Uninstall, Ok(())) => "✅ Uninstalled".green().bold(),
+            (InstallOperation::Install, Err(_)) => "❌ Installation failed".red().bold(),
+            (InstallOperation::Uninstall, Err(_)) => "❌ Uninstallation failed".red().bold(),
+        };
+        println!("{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_tracks_download() {
+        let console = ConsoleProgress::new();
+        console.on_step(InstallStep::Download("go_lib.dll"), StepStatus::Started);
+        console.on_progress(50, 100);
+        assert_eq!(
+            *console.download.lock(),
+            Some(("go_lib.dll".to_string(), Some(50)))
+        );
+        console.on_step(InstallStep::Download("go_lib.dll"), StepStatus::Done);
+        assert_eq!(*console.download.lock(), None);
+    }
+}
@@ -1,136 +1,1154 @@
-use colored::*;
-use std::env;
-use std::fs;
+use semver::Version;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cache::CacheStats;
+use crate::install_progress::{InstallOperation, InstallProgress, InstallStep, StepStatus};
+#[cfg(not(feature = "static"))]
+use crate::library::Library;
+use crate::self_test::{write_steps, Runner, SelfTestStep, StepOutcome};
+use crate::DllError;
+
+#[cfg(feature = "installer-remote")]
+use crate::release::{DownloadConfig, Release};
+use crate::search_paths::DLL_FILE_NAME;
+#[cfg(feature = "installer-remote")]
+use crate::VersionRequirement;
+
+/// What [`uninstall_dll`](crate::uninstall_dll) removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UninstallReport {
+    /// The installed library, if there was one
+    pub dll_removed: Option<PathBuf>,
+    /// Whether the record of the installation directory was removed
+    pub record_removed: bool,
+    /// Whether the installation directory was taken off the user's PATH,
+    /// where installs from before it was recorded put it on Windows
+    pub path_entry_removed: bool,
+    /// Cached copies of the library
+    pub cache: CacheStats,
+}
+
+impl fmt::Display for UninstallReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.dll_removed {
+            Some(path) => writeln!(f, "removed {}", path.display())?,
+            None => writeln!(f, "no installed DLL")?,
+        }
+        if self.record_removed {
+            writeln!(f, "removed the record of the installation directory")?;
+        }
+        if self.path_entry_removed {
+            writeln!(f, "removed the installation directory from PATH")?;
+        }
+        writeln!(
+            f,
+            "removed {} cached entries, freed {} bytes",
+            self.cache.entries_removed, self.cache.bytes_freed
+        )
+    }
+}
+
+/// `path_var` without `dir`, or `None` if it doesn't list `dir`
+#[cfg_attr(not(windows), allow(dead_code))]
+fn without_entry(path_var: &str, dir: &str) -> Option<String> {
+    let normalize = |entry: &str| entry.trim_end_matches(['\\', '/']).to_lowercase();
+    let dir = normalize(dir);
+    let entries: Vec<&str> = path_var.split(';').collect();
+    let kept: Vec<&str> = entries
+        .iter()
+        .copied()
+        .filter(|entry| normalize(entry) != dir)
+        .collect();
+    (kept.len() != entries.len()).then(|| kept.join(";"))
+}
+
+/// Takes `dir` off the user's PATH in `HKCU\Environment`, where installs
+/// from before the directory was recorded in a file put it with `setx`
+#[cfg(windows)]
+fn remove_legacy_path_entry(dir: &Path) -> io::Result<bool> {
+    use std::process::Command;
+
+    let output = Command::new("reg")
+        .args(["query", r"HKCU\Environment", "/v", "PATH"])
+        .output()?;
+    // A user without a PATH of their own has no entry to remove.
+    if !output.status.success() {
+        return Ok(false);
+    }
+    // `    Path    REG_EXPAND_SZ    C:\...;C:\...`
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let user_path = stdout.lines().find_map(|line| {
+        let (kind, value) = line[line.find("REG_")?..].split_once("    ")?;
+        Some((kind.to_string(), value.trim().to_string()))
+    });
+    let Some((kind, path)) = user_path else {
+        return Ok(false);
+    };
+    let Some(path) = without_entry(&path, &dir.to_string_lossy()) else {
+        return Ok(false);
+    };
+    // Unlike `setx`, `reg add` keeps the value's type and doesn't cut it off
+    // at 1024 characters.
+    let status = Command::new("reg")
+        .args(["add", r"HKCU\Environment", "/v", "PATH", "/t", &kind])
+        .args(["/d", &path, "/f"])
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("reg add failed with {}", status),
+        ));
+    }
+    Ok(true)
+}
+
+#[cfg(not(windows))]
+fn remove_legacy_path_entry(_dir: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// What [`verify_installation`](crate::verify_installation) found out about
+/// an installed DLL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationReport {
+    pub path: PathBuf,
+    /// `file`, then with the library loaded `load`, `GetDLLVersion`,
+    /// `AddNumbers` and `Ping`
+    pub checks: Vec<SelfTestStep>,
+}
+
+impl VerificationReport {
+    /// True when no check failed
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestStep> {
+        self.checks
+            .iter()
+            .filter(|s| matches!(s.outcome, StepOutcome::Failed(_)))
+    }
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.path.display())?;
+        write_steps(f, &self.checks)
+    }
+}
+
+/// Calls an export of a library being verified
+#[cfg(not(feature = "static"))]
+fn call_checked<T>(export: &str, f: impl FnOnce() -> T) -> Result<T, String> {
+    crate::guard::guarded(export, f).map_err(|e| e.to_string())
+}
+
+/// Loads a copy of the DLL at `path`, leaving the file itself unlocked, and
+/// calls its exports with known inputs
+#[cfg(not(feature = "static"))]
+fn check_exports(path: &Path, version: &Version, runner: &mut Runner) {
+    let mut loaded = None;
+    runner.step("load", true, || {
+        let library =
+            Library::load_shadow(path, &crate::config::current()).map_err(|e| e.to_string())?;
+        loaded = Some(library);
+        Ok(())
+    });
+    let library = || loaded.as_ref().ok_or_else(|| "not loaded".to_string());
+
+    runner.step("GetDLLVersion", false, || {
+        let library = library()?;
+        let get_version = library
+            .symbol(&library.symbols.get_dll_version)
+            .map_err(|e| e.to_string())?;
+        let raw = call_checked("GetDLLVersion", || unsafe { get_version() })?;
+        let found = crate::negotiation::decode_version(raw).map_err(|e| e.to_string())?;
+        if found != *version {
+            return Err(format!("reports {}, installed as {}", found, version));
+        }
+        Ok(())
+    });
+    runner.step("AddNumbers", false, || {
+        let library = library()?;
+        let add_numbers = library
+            .symbol(&library.symbols.add_numbers)
+            .map_err(|e| e.to_string())?;
+        let two = crate::types::GoInt::from(2i32);
+        match i64::from(call_checked("AddNumbers", || unsafe {
+            add_numbers(two, two)
+        })?) {
+            4 => Ok(()),
+            sum => Err(format!("2 + 2 returned {}", sum)),
+        }
+    });
+    runner.step("Ping", false, || {
+        let library = library()?;
+        let ping = library
+            .symbol(&library.symbols.ping)
+            .map_err(|e| e.to_string())?;
+        let nonce = i64::from(std::process::id());
+        match call_checked("Ping", || unsafe { ping(nonce) })? {
+            echoed if echoed == nonce => Ok(()),
+            echoed => Err(format!("echoed {} for nonce {}", echoed, nonce)),
+        }
+    });
+}
+
+/// A statically linked library is never loaded from the installed file
+#[cfg(feature = "static")]
+fn check_exports(_path: &Path, _version: &Version, runner: &mut Runner) {
+    runner.skip("load", "the library is linked into the binary".to_string());
+}
+
+/// One change [`install_dll`](crate::install_dll) makes, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PlannedAction {
+    CreateDirectory(PathBuf),
+    /// Copying the local build, once its SHA-256 matches the manifest
+    Copy {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Downloading the release asset named `asset`
+    Download {
+        release: String,
+        asset: String,
+        to: PathBuf,
+    },
+    /// Extracting the library from an archive
+    Extract {
+        archive: PathBuf,
+        to: PathBuf,
+    },
+    /// Writing the library embedded in the binary
+    WriteEmbedded(PathBuf),
+    /// Renaming the staged library to its final path; a library already
+    /// there is kept as `<path>.bak` until the install succeeds
+    MoveIntoPlace {
+        from: PathBuf,
+        to: PathBuf,
+        replaces: bool,
+    },
+    /// Loading a copy of the installed library and calling its exports
+    Verify(PathBuf),
+    /// Writing `dir` to the file `get_dll_path` reads the installation
+    /// directory from
+    Record {
+        path: PathBuf,
+        dir: PathBuf,
+    },
+}
+
+impl fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedAction::CreateDirectory(path) => {
+                write!(f, "create directory {}", path.display())
+            }
+            PlannedAction::Copy { from, to } => {
+                write!(f, "copy {} to {}", from.display(), to.display())
+            }
+            PlannedAction::Download { release, asset, to } => {
+                write!(f, "download {} of {} to {}", asset, release, to.display())
+            }
+            PlannedAction::Extract { archive, to } => write!(
+                f,
+                "extract the library from {} to {}",
+                archive.display(),
+                to.display()
+            ),
+            PlannedAction::WriteEmbedded(path) => {
+                write!(f, "write the embedded library to {}", path.display())
+            }
+            PlannedAction::MoveIntoPlace { from, to, replaces } => {
+                write!(f, "rename {} to {}", from.display(), to.display())?;
+                if *replaces {
+                    write!(f, ", replacing the library there")?;
+                }
+                Ok(())
+            }
+            PlannedAction::Record { path, dir } => {
+                write!(f, "write {} to {}", dir.display(), path.display())
+            }
+            PlannedAction::Verify(path) => write!(
+                f,
+                "load a copy of {} and call GetDLLVersion, AddNumbers and Ping",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// What [`install_dll`](crate::install_dll) would do, from
+/// [`plan_install`](crate::plan_install).
+///
+/// Installing never touches `PATH` or the registry, so no plan lists
+/// either.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallPlan {
+    pub version: Version,
+    pub installation_dir: PathBuf,
+    /// Whether the install has to run in an elevated process
+    pub needs_elevation: bool,
+    /// File locked while installing
+    pub lock_path: PathBuf,
+    pub actions: Vec<PlannedAction>,
+}
+
+impl fmt::Display for InstallPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "install version {} into {}",
+            self.version,
+            self.installation_dir.display()
+        )?;
+        if self.needs_elevation {
+            write!(f, " (needs an elevated process)")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  lock {}", self.lock_path.display())?;
+        for (i, action) in self.actions.iter().enumerate() {
+            writeln!(f, "  {}. {}", i + 1, action)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of [`check_for_update`](crate::check_for_update)
+#[cfg(feature = "installer-remote")]
+#[derive(Debug, Clone)]
+pub struct UpdateCheck {
+    /// Newest version installed in the configured target, if any
+    pub installed: Option<Version>,
+    /// Version of the latest release
+    pub latest: Version,
+    release: Release,
+}
+
+#[cfg(feature = "installer-remote")]
+impl UpdateCheck {
+    /// Whether the latest release is newer than everything installed
+    pub fn is_available(&self) -> bool {
+        self.installed
+            .as_ref()
+            .map_or(true, |installed| self.latest > *installed)
+    }
+}
+
+/// What [`update_dll`](crate::update_dll) is doing, for the host to log
+#[cfg(feature = "installer-remote")]
+#[derive(Debug)]
+pub enum UpdateEvent<'a> {
+    /// Reading the latest release from this endpoint
+    Checking(&'a str),
+    /// The installed version is the latest release
+    UpToDate(&'a Version),
+    /// A newer release exists
+    Available {
+        installed: Option<&'a Version>,
+        latest: &'a Version,
+    },
+    /// The newer release is outside the allowed range and stays uninstalled
+    NotAllowed {
+        latest: &'a Version,
+        allowed: &'a VersionRequirement,
+    },
+    /// The release was installed next to the previous versions
+    Updated {
+        version: &'a Version,
+        path: &'a Path,
+    },
+    /// The check or the installation failed; nothing changed
+    Failed(&'a DllError),
+}
+
+/// Where the directory of the last installation is recorded
+fn default_record_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("rust_go_ffi").join("install_dir"))
+}
+
+fn read_record(path: &Path) -> Option<PathBuf> {
+    let contents = fs::read_to_string(path).ok()?;
+    let dir = contents.trim();
+    (!dir.is_empty()).then(|| PathBuf::from(dir))
+}
+
+/// The directory the last installation recorded, if any
+pub(crate) fn recorded_dir() -> Option<PathBuf> {
+    read_record(&default_record_path()?)
+}
+
+/// Longest time an installer waits for another one to finish
+const LOCK_TIMEOUT: Duration = Duration::from_secs(60);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where [`install_dll`](crate::install_dll) puts the library
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum InstallTarget {
+    /// `~/.cargo/bin`, next to the tools cargo installs
+    #[default]
+    Cargo,
+    /// `%LOCALAPPDATA%\rust_go_ffi` on Windows, `~/.local/lib` elsewhere
+    User,
+    /// `%ProgramFiles%\rust_go_ffi` on Windows, `/usr/local/lib` elsewhere;
+    /// needs an elevated process
+    System,
+    Custom(PathBuf),
+}
+
+impl InstallTarget {
+    /// Directory the library goes into; `None` if it can't be determined
+    pub fn dir(&self) -> Option<PathBuf> {
+        match self {
+            InstallTarget::Cargo => Some(dirs::home_dir()?.join(".cargo").join("bin")),
+            #[cfg(windows)]
+            InstallTarget::User => Some(dirs::data_local_dir()?.join("rust_go_ffi")),
+            #[cfg(not(windows))]
+            InstallTarget::User => Some(dirs::home_dir()?.join(".local").join("lib")),
+            #[cfg(windows)]
+            InstallTarget::System => Some(
+                std::env::var_os("ProgramFiles")
+                    .map_or_else(|| PathBuf::from(r"C:\Program Files"), PathBuf::from)
+                    .join("rust_go_ffi"),
+            ),
+            #[cfg(not(windows))]
+            InstallTarget::System => Some(PathBuf::from("/usr/local/lib")),
+            InstallTarget::Custom(dir) => Some(dir.clone()),
+        }
+    }
+}
+
+/// Directories `get_dll_path` looks for installed copies in, in priority
+/// order: the recorded installation, the configured target, then the user,
+/// cargo and system directories
+pub(crate) fn install_dirs(recorded: Option<PathBuf>, configured: &InstallTarget) -> Vec<PathBuf> {
+    let targets = [
+        configured,
+        &InstallTarget::User,
+        &InstallTarget::Cargo,
+        &InstallTarget::System,
+    ];
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for dir in recorded
+        .into_iter()
+        .chain(targets.iter().filter_map(|target| target.dir()))
+    {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// `DLL_FILE_NAME` split around where the version goes, e.g. `go_lib` and
+/// `.dll`
+fn file_name_parts() -> (&'static str, &'static str) {
+    DLL_FILE_NAME
+        .rfind('.')
+        .map_or((DLL_FILE_NAME, ""), |dot| DLL_FILE_NAME.split_at(dot))
+}
+
+/// Name `version` is installed under, e.g. `go_lib-0.1.0.dll`, so that
+/// several versions can sit side by side
+pub(crate) fn versioned_file_name(version: &Version) -> String {
+    let (stem, extension) = file_name_parts();
+    format!("{}-{}{}", stem, version, extension)
+}
+
+/// The version in a [`versioned_file_name`]
+fn parse_versioned(file_name: &str) -> Option<Version> {
+    let (stem, extension) = file_name_parts();
+    let version = file_name
+        .strip_prefix(stem)?
+        .strip_prefix('-')?
+        .strip_suffix(extension)?;
+    Version::parse(version).ok()
+}
+
+/// Versions installed in `dir` and their files
+fn installed_versions(dir: &Path) -> Vec<(Version, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let version = parse_versioned(entry.file_name().to_str()?)?;
+            Some((version, entry.path()))
+        })
+        .filter(|(_, path)| path.is_file())
+        .collect()
+}
+
+/// The newest version installed in `dirs` that `accepts` takes; an equal
+/// version in an earlier directory wins
+pub(crate) fn newest_installed(
+    dirs: &[PathBuf],
+    accepts: impl Fn(&Version) -> bool,
+) -> Option<PathBuf> {
+    let mut newest: Option<(Version, PathBuf)> = None;
+    for (version, path) in dirs.iter().flat_map(|dir| installed_versions(dir)) {
+        if accepts(&version) && newest.as_ref().map_or(true, |(best, _)| version > *best) {
+            newest = Some((version, path));
+        }
+    }
+    newest.map(|(_, path)| path)
+}
+
+/// Where [`install_dll`](crate::install_dll) gets the library from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum InstallSource {
+    /// The build in `go_lib/`; with `installer-remote` the release matching
+    /// the crate version when there is none
+    #[default]
+    LocalBuild,
+    /// The release of the configured repository matching the crate version
+    #[cfg(feature = "installer-remote")]
+    Download,
+    /// A `.zip`, `.tar` or `.tar.gz` holding the library and a checksum
+    /// manifest, for machines without network access
+    #[cfg(feature = "installer-archive")]
+    LocalArchive(PathBuf),
+    /// The library embedded in the binary
+    #[cfg(feature = "embedded")]
+    Embedded,
+}
+
+/// What the installer stages, resolved from an [`InstallSource`]
+enum Source {
+    Copy(PathBuf),
+    #[cfg(feature = "installer-remote")]
+    Download(Release),
+    #[cfg(feature = "installer-archive")]
+    Archive(PathBuf),
+    #[cfg(feature = "embedded")]
+    Embedded,
+}
+
+impl Source {
+    /// The DLL `build.rs` leaves in `go_lib/`
+    fn local_build() -> Self {
+        Source::Copy(Path::new("go_lib").join(DLL_FILE_NAME))
+    }
+}
+
+/// Keeps other processes from installing or uninstalling until dropped
+pub struct InstallLock {
+    _file: File,
+}
 
 pub struct Installer {
-    dll_source: PathBuf,
+    source: Source,
     installation_dir: PathBuf,
+    /// Version the DLL is installed as
+    version: Version,
+    /// File locked while installing; lives in the cache so that it never
+    /// shows up next to the installed DLL
+    lock_path: PathBuf,
+    lock_timeout: Duration,
+    /// Whether writing to `installation_dir` takes an elevated process
+    needs_elevation: bool,
+    /// File `installation_dir` is recorded in for `get_dll_path`
+    record_path: Option<PathBuf>,
+    /// Proxy, roots and token of release downloads and update checks
+    #[cfg(feature = "installer-remote")]
+    download: DownloadConfig,
+    /// Whether verifying loads the installed DLL and calls its exports
+    /// rather than only checking that it is there
+    call_exports: bool,
 }
 
 impl Installer {
+    /// An installer for the configured
+    /// [`InstallTarget`](crate::FfiConfig::with_install_target)
     pub fn new() -> io::Result<Self> {
-        let cargo_home = dirs::home_dir()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))?
-            .join(".cargo");
+        Self::for_target(&crate::config::install_target())
+    }
+
+    pub fn for_target(target: &InstallTarget) -> io::Result<Self> {
+        let installation_dir = target.dir().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No installation directory for {:?}", target),
+            )
+        })?;
 
         Ok(Self {
-            dll_source: PathBuf::from("go_lib/go_lib.dll"),
-            installation_dir: cargo_home.join("bin"),
+            source: Source::local_build(),
+            installation_dir,
+            // The library built from these sources, locally or for the
+            // release matching the crate version.
+            version: crate::DEFAULT_VERSION,
+            lock_path: crate::cache_root().join("install.lock"),
+            lock_timeout: LOCK_TIMEOUT,
+            needs_elevation: *target == InstallTarget::System,
+            record_path: default_record_path(),
+            #[cfg(feature = "installer-remote")]
+            download: crate::config::download_config(),
+            call_exports: true,
         })
     }
 
-    pub fn install(&self) -> io::Result<()> {
-        println!("{}", "🚀 Starting installation process...".cyan().bold());
+    /// Installs from `source` instead of the local build
+    pub fn with_source(self, source: &InstallSource) -> Self {
+        let source = match source {
+            InstallSource::LocalBuild => Source::local_build(),
+            #[cfg(feature = "installer-remote")]
+            InstallSource::Download => {
+                Source::Download(Release::for_crate(&crate::config::release_repository()))
+            }
+            #[cfg(feature = "installer-archive")]
+            InstallSource::LocalArchive(path) => Source::Archive(path.clone()),
+            #[cfg(feature = "embedded")]
+            InstallSource::Embedded => Source::Embedded,
+        };
+        Self { source, ..self }
+    }
 
-        // Create installation directory if it doesn't exist
-        print!("📁 Creating installation directory... ");
-        if !self.installation_dir.exists() {
-            fs::create_dir_all(&self.installation_dir)?;
-            println!("{}", "OK".green().bold());
-        } else {
-            println!("{}", "EXISTS".blue().bold());
+    /// Installs the release asset of `repository` (`owner/name`) matching
+    /// the crate version and target if the local build is missing
+    #[cfg(feature = "installer-remote")]
+    pub fn or_download(mut self, repository: &str) -> Self {
+        if matches!(&self.source, Source::Copy(path) if !path.is_file()) {
+            self.source = Source::Download(Release::for_crate(repository));
         }
+        self
+    }
 
-        // Copy DLL to installation directory
-        let dll_dest = self.installation_dir.join("go_lib.dll");
-        print!(
-            "📦 Copying DLL to: {}... ",
-            dll_dest.display().to_string().blue()
-        );
-        fs::copy(&self.dll_source, &dll_dest)?;
-        println!("{}", "OK".green().bold());
-
-        // Update PATH if necessary
-        self.update_path()?;
+    /// Compares the newest installed version with the release `endpoint`
+    /// serves
+    #[cfg(feature = "installer-remote")]
+    pub fn check_for_update(&self, endpoint: &str) -> io::Result<UpdateCheck> {
+        let (latest, release) = Release::latest(endpoint, &self.download)?;
+        let installed = installed_versions(&self.installation_dir)
+            .into_iter()
+            .map(|(version, _)| version)
+            .max();
+        Ok(UpdateCheck {
+            installed,
+            latest,
+            release,
+        })
+    }
 
-        println!(
-            "{}",
-            "✅ Installation completed successfully!".green().bold()
-        );
-        Ok(())
+    /// Installs the release `check` found next to the installed versions and
+    /// returns where it went
+    #[cfg(feature = "installer-remote")]
+    pub fn update(self, check: UpdateCheck, progress: &dyn InstallProgress) -> io::Result<PathBuf> {
+        let installer = Self {
+            version: check.latest,
+            source: Source::Download(check.release),
+            ..self
+        };
+        installer.install(progress)?;
+        Ok(installer.get_dll_path())
     }
 
-    fn update_path(&self) -> io::Result<()> {
-        let path_var = env::var("PATH").unwrap_or_default();
-        let installation_dir_str = self.installation_dir.to_string_lossy();
+    /// The local build that will be copied; `None` for other sources,
+    /// which carry their own checksums
+    #[cfg(not(feature = "static"))]
+    pub fn local_source(&self) -> Option<&Path> {
+        match &self.source {
+            Source::Copy(path) => Some(path),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
 
-        if !path_var.contains(&*installation_dir_str) {
-            println!("Adding installation directory to PATH...");
+    /// Waits for other processes to finish installing, then keeps them out
+    /// until the returned lock is dropped.
+    ///
+    /// Fails with [`DllError::InstallLocked`] if that takes longer than a
+    /// minute.
+    pub fn lock(&self, progress: &dyn InstallProgress) -> Result<InstallLock, DllError> {
+        if let Some(parent) = self.lock_path.parent() {
+            fs::create_dir_all(parent).map_err(DllError::InstallError)?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.lock_path)
+            .map_err(DllError::InstallError)?;
 
-            #[cfg(windows)]
-            {
-                use std::process::Command;
-                Command::new("setx")
-                    .args(["PATH", &format!("{};%PATH%", installation_dir_str)])
-                    .output()?;
+        let deadline = Instant::now() + self.lock_timeout;
+        let waiting = InstallStep::WaitForLock(&self.lock_path);
+        let mut waited = false;
+        while !sys::try_lock(&file).map_err(DllError::InstallError)? {
+            if Instant::now() >= deadline {
+                progress.on_step(waiting, StepStatus::Failed);
+                return Err(DllError::InstallLocked {
+                    path: self.lock_path.clone(),
+                    timeout: self.lock_timeout,
+                });
             }
-
-            println!("PATH updated. Please restart your terminal for changes to take effect.");
+            if !waited {
+                waited = true;
+                progress.on_step(waiting, StepStatus::Started);
+            }
+            thread::sleep(LOCK_POLL_INTERVAL);
         }
+        if waited {
+            progress.on_step(waiting, StepStatus::Done);
+        }
+        Ok(InstallLock { _file: file })
+    }
 
+    fn check_elevation(&self) -> io::Result<()> {
+        if self.needs_elevation && !sys::is_elevated() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Installing into {} needs an elevated process; run as administrator or root, or pick InstallTarget::User",
+                    self.installation_dir.display()
+                ),
+            ));
+        }
         Ok(())
     }
 
-    pub fn verify_installation(&self) -> io::Result<()> {
-        println!("{}", "\n🔍 Verifying installation...".cyan().bold());
+    /// What [`install`](Self::install) would do, without doing any of it
+    pub fn plan(&self) -> InstallPlan {
+        let dll_path = self.get_dll_path();
+        let staged = with_suffix(&dll_path, ".new");
+        let mut actions = Vec::new();
+        if !self.installation_dir.exists() {
+            actions.push(PlannedAction::CreateDirectory(
+                self.installation_dir.clone(),
+            ));
+        }
+        actions.push(match &self.source {
+            Source::Copy(path) => PlannedAction::Copy {
+                from: path.clone(),
+                to: staged.clone(),
+            },
+            #[cfg(feature = "installer-remote")]
+            Source::Download(release) => PlannedAction::Download {
+                release: release.to_string(),
+                asset: release.asset_name(),
+                to: staged.clone(),
+            },
+            #[cfg(feature = "installer-archive")]
+            Source::Archive(path) => PlannedAction::Extract {
+                archive: path.clone(),
+                to: staged.clone(),
+            },
+            #[cfg(feature = "embedded")]
+            Source::Embedded => PlannedAction::WriteEmbedded(staged.clone()),
+        });
+        actions.push(PlannedAction::MoveIntoPlace {
+            from: staged,
+            to: dll_path.clone(),
+            replaces: dll_path.exists(),
+        });
+        actions.push(PlannedAction::Verify(dll_path));
+        if let Some(record_path) = &self.record_path {
+            actions.push(PlannedAction::Record {
+                path: record_path.clone(),
+                dir: self.installation_dir.clone(),
+            });
+        }
+
+        InstallPlan {
+            version: self.version.clone(),
+            installation_dir: self.installation_dir.clone(),
+            needs_elevation: self.needs_elevation,
+            lock_path: self.lock_path.clone(),
+            actions,
+        }
+    }
 
-        // Check if DLL exists
-        print!("Checking DLL presence... ");
-        let dll_path = self.installation_dir.join("go_lib.dll");
-        if !dll_path.exists() {
-            println!("{}", "NOT FOUND".red().bold());
+    /// Installs as [`install`](Self::install) does, but only if that still
+    /// does exactly what `plan` says
+    pub fn install_planned(
+        &self,
+        plan: &InstallPlan,
+        progress: &dyn InstallProgress,
+    ) -> io::Result<()> {
+        if self.plan() != *plan {
             return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("DLL not found at: {}", dll_path.display()),
+                io::ErrorKind::Other,
+                "The installation no longer matches its plan; plan it again",
             ));
         }
-        println!("{}", "OK".green().bold());
+        self.install(progress)
+    }
 
-        // Try loading the DLL
-        #[cfg(windows)]
-        {
-            use std::os::windows::ffi::OsStrExt;
-            use winapi::um::libloaderapi::{FreeLibrary, LoadLibraryW};
+    /// Installs the DLL as one transaction and checks that it loads.
+    ///
+    /// The new copy is written and synced next to the destination and only
+    /// renamed into place once complete; a previous DLL is kept as a backup
+    /// until the new copy passed verification and the installation directory
+    /// is recorded. Any error before that restores the previous state.
+    pub fn install(&self, progress: &dyn InstallProgress) -> io::Result<()> {
+        progress.on_start(InstallOperation::Install);
+        let result = self.install_steps(progress);
+        progress.on_complete(InstallOperation::Install, result.as_ref().map(|_| ()));
+        result
+    }
 
-            let wide_path: Vec<u16> = dll_path
-                .as_os_str()
-                .encode_wide()
-                .chain(std::iter::once(0))
-                .collect();
+    fn install_steps(&self, progress: &dyn InstallProgress) -> io::Result<()> {
+        self.check_elevation()?;
 
-            unsafe {
-                let handle = LoadLibraryW(wide_path.as_ptr());
-                if handle.is_null() {
-                    return Err(io::Error::new(io::ErrorKind::Other, "Failed to load DLL"));
-                }
-                FreeLibrary(handle);
+        let create = InstallStep::CreateDirectory(&self.installation_dir);
+        let created_dir = !self.installation_dir.exists();
+        if created_dir {
+            report_step(progress, create, || {
+                fs::create_dir_all(&self.installation_dir)
+            })?;
+        } else {
+            progress.on_step(create, StepStatus::Started);
+            progress.on_step(create, StepStatus::Skipped);
+        }
+
+        let dll_dest = self.get_dll_path();
+        let staged = with_suffix(&dll_dest, ".new");
+        let result = self
+            .stage(&staged, progress)
+            .and_then(|()| self.commit(&staged, &dll_dest, progress));
+        if let Err(e) = result {
+            progress.on_step(InstallStep::RollBack, StepStatus::Started);
+            let _ = fs::remove_file(&staged);
+            if created_dir {
+                // Only succeeds while empty.
+                let _ = fs::remove_dir(&self.installation_dir);
+            }
+            progress.on_step(InstallStep::RollBack, StepStatus::Done);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Writes the new DLL to `staged` and syncs it to disk
+    fn stage(&self, staged: &Path, progress: &dyn InstallProgress) -> io::Result<()> {
+        match &self.source {
+            // Copy DLL next to the installation
+            Source::Copy(path) => report_step(progress, InstallStep::Copy(staged), || {
+                fs::copy(path, staged)?;
+                fs::File::open(staged)?.sync_all()
+            }),
+            #[cfg(feature = "installer-remote")]
+            Source::Download(release) => {
+                let name = release.asset_name();
+                report_step(progress, InstallStep::Download(&name), || {
+                    release.download_to(&self.download, staged, |received, total| {
+                        progress.on_progress(received, total)
+                    })
+                })
             }
+            #[cfg(feature = "installer-archive")]
+            Source::Archive(path) => report_step(progress, InstallStep::Extract(path), || {
+                crate::archive::extract_dll(path, staged)
+            }),
+            #[cfg(feature = "embedded")]
+            Source::Embedded => report_step(progress, InstallStep::WriteEmbedded(staged), || {
+                crate::embedded::write_to(staged)
+            }),
         }
+    }
 
-        println!(
-            "{}",
-            "✅ Installation verified successfully!".green().bold()
-        );
+    /// Renames `staged` to `dest`, verifies it and records the installation
+    /// directory, putting back the previous DLL if any of that fails
+    fn commit(&self, staged: &Path, dest: &Path, progress: &dyn InstallProgress) -> io::Result<()> {
+        let backup = with_suffix(dest, ".bak");
+        let had_previous = match fs::rename(dest, &backup) {
+            Ok(()) => true,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e),
+        };
+        let restore = || {
+            if had_previous {
+                let _ = fs::rename(&backup, dest);
+            }
+        };
+
+        if let Err(e) = report_step(progress, InstallStep::MoveIntoPlace(dest), || {
+            fs::rename(staged, dest)
+        }) {
+            restore();
+            return Err(e);
+        }
+
+        if let Err(e) = self
+            .verify_step(progress)
+            .and_then(|()| self.record(progress))
+        {
+            let _ = fs::remove_file(dest);
+            restore();
+            return Err(e);
+        }
+
+        if had_previous {
+            let _ = fs::remove_file(&backup);
+        }
         Ok(())
     }
 
+    /// Records `installation_dir` where `get_dll_path` finds it.
+    ///
+    /// The library is loaded by its full path, so unlike a PATH entry this
+    /// leaves the user's environment alone.
+    fn record(&self, progress: &dyn InstallProgress) -> io::Result<()> {
+        let Some(record_path) = &self.record_path else {
+            return Ok(());
+        };
+        let staged = with_suffix(record_path, ".new");
+        report_step(progress, InstallStep::Record(record_path), || {
+            if let Some(parent) = record_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(
+                &staged,
+                format!("{}\n", self.installation_dir.to_string_lossy()),
+            )?;
+            fs::rename(&staged, record_path)
+        })
+        .map_err(|e| {
+            let _ = fs::remove_file(&staged);
+            e
+        })
+    }
+
+    /// Deletes this version of the installed DLL and, once no other version
+    /// is left and if it points here, the record of the installation
+    /// directory. The PATH entry older installs made on Windows goes too once
+    /// nothing else is left in the directory.
+    ///
+    /// Clearing cached copies is up to the caller.
+    pub fn uninstall(&self, progress: &dyn InstallProgress) -> io::Result<UninstallReport> {
+        progress.on_start(InstallOperation::Uninstall);
+        let result = self.uninstall_steps(progress);
+        progress.on_complete(InstallOperation::Uninstall, result.as_ref().map(|_| ()));
+        result
+    }
+
+    fn uninstall_steps(&self, progress: &dyn InstallProgress) -> io::Result<UninstallReport> {
+        self.check_elevation()?;
+        let mut report = UninstallReport::default();
+
+        let dll_path = self.get_dll_path();
+        let remove = InstallStep::RemoveDll(&dll_path);
+        progress.on_step(remove, StepStatus::Started);
+        match fs::remove_file(&dll_path) {
+            Ok(()) => {
+                progress.on_step(remove, StepStatus::Done);
+                report.dll_removed = Some(dll_path.clone());
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                progress.on_step(remove, StepStatus::Skipped);
+            }
+            Err(e) => {
+                progress.on_step(remove, StepStatus::Failed);
+                return Err(e);
+            }
+        }
+
+        // Other versions installed side by side still need the record.
+        let last_version = installed_versions(&self.installation_dir).is_empty();
+        if let (true, Some(record_path)) = (last_version, &self.record_path) {
+            if read_record(record_path).as_deref() == Some(self.installation_dir.as_path()) {
+                fs::remove_file(record_path)?;
+                report.record_removed = true;
+            }
+        }
+
+        // Older installs put the directory on PATH. It was `~/.cargo/bin`,
+        // so it stays there while anything else, such as cargo, lives in it.
+        let in_use = fs::read_dir(&self.installation_dir)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(false);
+        if !in_use {
+            report.path_entry_removed = remove_legacy_path_entry(&self.installation_dir)?;
+        }
+        Ok(report)
+    }
+
+    /// Reports how the verification of the installed DLL went, as a step
+    fn verify_step(&self, progress: &dyn InstallProgress) -> io::Result<()> {
+        let dll_path = self.get_dll_path();
+        report_step(progress, InstallStep::Verify(&dll_path), || {
+            let report = self.verify_installation();
+            if report.passed() {
+                return Ok(());
+            }
+            let failures: Vec<String> = report
+                .failures()
+                .map(|check| match &check.outcome {
+                    StepOutcome::Failed(reason) => format!("{}: {}", check.name, reason),
+                    _ => check.name.to_string(),
+                })
+                .collect();
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} failed verification: {}",
+                    dll_path.display(),
+                    failures.join("; ")
+                ),
+            ))
+        })
+    }
+
+    /// Checks that the DLL is in place, then loads a copy of it and calls
+    /// `GetDLLVersion`, `AddNumbers` and `Ping`, so that a library that loads
+    /// but is broken or of another version is caught
+    pub fn verify_installation(&self) -> VerificationReport {
+        let path = self.get_dll_path();
+        let mut runner = Runner::new();
+        runner.step("file", true, || {
+            if path.is_file() {
+                Ok(())
+            } else {
+                Err(format!("not found at {}", path.display()))
+            }
+        });
+        if self.call_exports {
+            check_exports(&path, &self.version, &mut runner);
+        }
+        VerificationReport {
+            path,
+            checks: runner.steps,
+        }
+    }
+
+    /// Where this installer's version of the DLL goes
     pub fn get_dll_path(&self) -> PathBuf {
-        self.installation_dir.join("go_lib.dll")
+        self.installation_dir
+            .join(versioned_file_name(&self.version))
     }
 }
 
+#[cfg(unix)]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    pub fn is_elevated() -> bool {
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    /// Takes an exclusive lock on `file`; `false` if someone else holds it.
+    /// Closing the file releases it.
+    pub fn try_lock(file: &File) -> io::Result<bool> {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(true);
+        }
+        let e = io::Error::last_os_error();
+        match e.kind() {
+            io::ErrorKind::WouldBlock => Ok(false),
+            _ => Err(e),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::mem;
+    use std::os::windows::io::AsRawHandle;
+    use std::ptr;
+    use winapi::shared::winerror::ERROR_LOCK_VIOLATION;
+    use winapi::um::fileapi::LockFileEx;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
+    use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+    use winapi::um::securitybaseapi::GetTokenInformation;
+    use winapi::um::winnt::{TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+
+    /// Whether the process runs with an elevated (administrator) token
+    pub fn is_elevated() -> bool {
+        unsafe {
+            let mut token = ptr::null_mut();
+            if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+                return false;
+            }
+            let mut elevation: TOKEN_ELEVATION = mem::zeroed();
+            let mut size = 0;
+            let queried = GetTokenInformation(
+                token,
+                TokenElevation,
+                &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+                mem::size_of::<TOKEN_ELEVATION>() as u32,
+                &mut size,
+            );
+            CloseHandle(token);
+            queried != 0 && elevation.TokenIsElevated != 0
+        }
+    }
+
+    /// Takes an exclusive lock on `file`; `false` if someone else holds it.
+    /// Closing the file releases it.
+    pub fn try_lock(file: &File) -> io::Result<bool> {
+        unsafe {
+            let mut overlapped: OVERLAPPED = mem::zeroed();
+            if LockFileEx(
+                file.as_raw_handle() as _,
+                LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY,
+                0,
+                1,
+                0,
+                &mut overlapped,
+            ) != 0
+            {
+                return Ok(true);
+            }
+        }
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(code) if code == ERROR_LOCK_VIOLATION as i32 => Ok(false),
+            _ => Err(e),
+        }
+    }
+}
+
+/// `path` with `suffix` appended to its file name, e.g. `go_lib.dll.bak`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Runs `f` as `step`, reporting when it starts and how it ended
+fn report_step<T>(
+    progress: &dyn InstallProgress,
+    step: InstallStep<'_>,
+    f: impl FnOnce() -> io::Result<T>,
+) -> io::Result<T> {
+    progress.on_step(step, StepStatus::Started);
+    let result = f();
+    let status = match result {
+        Ok(_) => StepStatus::Done,
+        Err(_) => StepStatus::Failed,
+    };
+    progress.on_step(step, status);
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::install_progress::QuietProgress;
+    use parking_lot::Mutex;
+    use std::env;
 
     #[test]
     fn test_installer_creation() {
         match Installer::new() {
             Ok(installer) => {
                 assert!(installer.installation_dir.ends_with("bin"));
-                assert!(installer.dll_source.ends_with("go_lib.dll"));
+                assert!(
+                    matches!(&installer.source, Source::Copy(path) if path.ends_with(DLL_FILE_NAME))
+                );
             }
             Err(e) => panic!("Failed to create installer: {}", e),
         }
@@ -140,7 +1158,7 @@ mod tests {
     fn test_get_dll_path() {
         if let Ok(installer) = Installer::new() {
             let dll_path = installer.get_dll_path();
-            assert!(dll_path.ends_with("go_lib.dll"));
+            assert!(dll_path.ends_with(versioned_file_name(&crate::DEFAULT_VERSION)));
             assert_eq!(dll_path.parent().unwrap(), installer.installation_dir);
         }
     }
@@ -152,7 +1170,7 @@ mod tests {
             let test_dir = installer.installation_dir.join("test_install");
             let test_installer = Installer {
                 installation_dir: test_dir.clone(),
-                dll_source: installer.dll_source,
+                ..installer
             };
 
             // Test directory creation
@@ -167,6 +1185,386 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_install_rolls_back() {
+        let dir = env::temp_dir().join(format!("installer_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join(versioned_file_name(&crate::DEFAULT_VERSION));
+        fs::write(&dest, b"previous").unwrap();
+
+        let installer = Installer {
+            source: Source::Copy(dir.join("missing").join(DLL_FILE_NAME)),
+            installation_dir: dir.clone(),
+            version: crate::DEFAULT_VERSION,
+            lock_path: dir.join("install.lock"),
+            lock_timeout: LOCK_TIMEOUT,
+            needs_elevation: false,
+            record_path: Some(dir.join("install_dir")),
+            #[cfg(feature = "installer-remote")]
+            download: DownloadConfig::default(),
+            // Test files aren't libraries.
+            call_exports: false,
+        };
+        assert!(installer.install(&QuietProgress).is_err());
+        let left: Vec<_> = fs::read_dir(&dir).unwrap().flatten().collect();
+        assert_eq!(left.len(), 1);
+        assert_eq!(fs::read(&dest).unwrap(), b"previous");
+
+        let source = dir.join("source");
+        fs::write(&source, b"new").unwrap();
+        let installer = Installer {
+            source: Source::Copy(source),
+            ..installer
+        };
+        installer.install(&QuietProgress).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"new");
+        assert!(!with_suffix(&dest, ".bak").exists());
+        assert_eq!(read_record(&dir.join("install_dir")), Some(dir.clone()));
+
+        let report = installer.uninstall(&QuietProgress).unwrap();
+        assert_eq!(report.dll_removed, Some(dest.clone()));
+        assert!(report.record_removed);
+        assert!(!dest.exists());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    /// Records the reported steps by variant name
+    #[derive(Default)]
+    struct RecordingProgress {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl InstallProgress for RecordingProgress {
+        fn on_start(&self, operation: InstallOperation) {
+            self.events.lock().push(format!("{:?}", operation));
+        }
+
+        fn on_step(&self, step: InstallStep<'_>, status: StepStatus) {
+            let step = format!("{:?}", step);
+            let name = step.split('(').next().unwrap_or_default();
+            self.events.lock().push(format!("{} {:?}", name, status));
+        }
+
+        fn on_complete(&self, _operation: InstallOperation, result: Result<(), &io::Error>) {
+            self.events.lock().push(format!("Ok: {}", result.is_ok()));
+        }
+    }
+
+    #[cfg(not(feature = "static"))]
+    #[test]
+    fn test_verification_catches_broken_library() {
+        let dir = env::temp_dir().join(format!("installer_verify_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let installer = Installer {
+            call_exports: true,
+            ..test_installer(&dir, b"not a library")
+        };
+        let dest = installer.get_dll_path();
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::write(&dest, b"previous").unwrap();
+
+        let result = installer.install(&QuietProgress);
+        let previous = fs::read(&dest);
+        let backup = with_suffix(&dest, ".bak").exists();
+        let record = dir.join("install_dir").exists();
+        let report = installer.verify_installation();
+        let _ = fs::remove_dir_all(&dir);
+
+        let error = result.unwrap_err();
+        assert!(error.to_string().contains("load: "), "{}", error);
+        // The previous DLL is back and nothing points at the broken one.
+        assert_eq!(previous.unwrap(), b"previous");
+        assert!(!backup);
+        assert!(!record);
+        let outcomes: Vec<_> = report
+            .checks
+            .iter()
+            .map(|check| (check.name, matches!(check.outcome, StepOutcome::Passed)))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("file", true),
+                ("load", false),
+                ("GetDLLVersion", false),
+                ("AddNumbers", false),
+                ("Ping", false),
+            ]
+        );
+        assert_eq!(report.failures().count(), 1);
+    }
+
+    #[test]
+    fn test_install_plan() {
+        let dir = env::temp_dir().join(format!("installer_plan_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let installer = test_installer(&dir, b"planned");
+        let dll_path = installer.get_dll_path();
+
+        let plan = installer.plan();
+        assert!(!dir.join("bin").exists());
+        assert_eq!(
+            plan.actions,
+            [
+                PlannedAction::CreateDirectory(dir.join("bin")),
+                PlannedAction::Copy {
+                    from: dir.join("source-7"),
+                    to: with_suffix(&dll_path, ".new"),
+                },
+                PlannedAction::MoveIntoPlace {
+                    from: with_suffix(&dll_path, ".new"),
+                    to: dll_path.clone(),
+                    replaces: false,
+                },
+                PlannedAction::Verify(dll_path.clone()),
+                PlannedAction::Record {
+                    path: dir.join("install_dir"),
+                    dir: dir.join("bin"),
+                },
+            ]
+        );
+
+        let result = installer.install_planned(&plan, &QuietProgress);
+        // The directory exists now, so the old plan is stale.
+        let stale = installer.install_planned(&plan, &QuietProgress);
+        let replan = installer.plan();
+        let _ = fs::remove_dir_all(&dir);
+        result.unwrap();
+        assert!(stale.is_err());
+        assert!(matches!(
+            replan.actions[..],
+            [
+                PlannedAction::Copy { .. },
+                PlannedAction::MoveIntoPlace { replaces: true, .. },
+                ..
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_install_reports_steps() {
+        let dir = env::temp_dir().join(format!("installer_progress_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let installer = test_installer(&dir, b"dll");
+
+        let progress = RecordingProgress::default();
+        installer.install(&progress).unwrap();
+        installer.uninstall(&progress).unwrap();
+        assert_eq!(
+            *progress.events.lock(),
+            [
+                "Install",
+                "CreateDirectory Started",
+                "CreateDirectory Done",
+                "Copy Started",
+                "Copy Done",
+                "MoveIntoPlace Started",
+                "MoveIntoPlace Done",
+                "Verify Started",
+                "Verify Done",
+                "Record Started",
+                "Record Done",
+                "Ok: true",
+                "Uninstall",
+                "RemoveDll Started",
+                "RemoveDll Done",
+                "Ok: true",
+            ]
+        );
+
+        let progress = RecordingProgress::default();
+        fs::remove_file(dir.join("source-3")).unwrap();
+        assert!(installer.install(&progress).is_err());
+        assert_eq!(
+            progress.events.lock()[1..],
+            [
+                "CreateDirectory Started",
+                "CreateDirectory Skipped",
+                "Copy Started",
+                "Copy Failed",
+                "RollBack Started",
+                "RollBack Done",
+                "Ok: false",
+            ]
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    fn test_installer(dir: &Path, source: &[u8]) -> Installer {
+        let dll_source = dir.join(format!("source-{}", source.len()));
+        fs::write(&dll_source, source).unwrap();
+        Installer {
+            source: Source::Copy(dll_source),
+            installation_dir: dir.join("bin"),
+            version: crate::DEFAULT_VERSION,
+            lock_path: dir.join("install.lock"),
+            lock_timeout: Duration::from_millis(200),
+            needs_elevation: false,
+            record_path: Some(dir.join("install_dir")),
+            #[cfg(feature = "installer-remote")]
+            download: DownloadConfig::default(),
+            // Test files aren't libraries.
+            call_exports: false,
+        }
+    }
+
+    #[test]
+    fn test_lock_times_out() {
+        let dir = env::temp_dir().join(format!("installer_lock_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = test_installer(&dir, b"first");
+        let second = test_installer(&dir, b"second");
+
+        let held = first.lock(&QuietProgress).unwrap();
+        let error = second.lock(&QuietProgress).err().unwrap();
+        assert!(matches!(error, DllError::InstallLocked { .. }));
+        assert!(error.is_retryable());
+        drop(held);
+        assert!(second.lock(&QuietProgress).is_ok());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_racing_installers() {
+        let dir = env::temp_dir().join(format!("installer_race_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let sources: [&[u8]; 2] = [b"first build", b"second, longer build"];
+        let installers: Vec<Installer> = sources
+            .iter()
+            .map(|source| Installer {
+                lock_timeout: LOCK_TIMEOUT,
+                ..test_installer(&dir, source)
+            })
+            .collect();
+
+        thread::scope(|scope| {
+            for installer in &installers {
+                scope.spawn(move || {
+                    let _lock = installer.lock(&QuietProgress).unwrap();
+                    installer.install(&QuietProgress).unwrap();
+                });
+            }
+        });
+        let installed = fs::read(installers[0].get_dll_path()).unwrap();
+        assert!(sources.contains(&installed.as_slice()));
+        let left: Vec<_> = fs::read_dir(dir.join("bin")).unwrap().flatten().collect();
+        assert_eq!(left.len(), 1);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_install_targets() {
+        let custom = env::temp_dir().join("custom_target");
+        let installer = Installer::for_target(&InstallTarget::Custom(custom.clone())).unwrap();
+        assert_eq!(
+            installer.get_dll_path(),
+            custom.join(versioned_file_name(&crate::DEFAULT_VERSION))
+        );
+        assert!(!installer.needs_elevation);
+        assert!(
+            Installer::for_target(&InstallTarget::System)
+                .unwrap()
+                .needs_elevation
+        );
+
+        let locations = install_dirs(None, &InstallTarget::Custom(custom.clone()));
+        assert_eq!(locations[0], custom);
+        let system = InstallTarget::System.dir().unwrap();
+        assert_eq!(locations.last(), Some(&system));
+        // The configured target isn't listed twice.
+        assert_eq!(
+            install_dirs(None, &InstallTarget::System).len(),
+            locations.len() - 1
+        );
+    }
+
+    #[test]
+    fn test_without_entry() {
+        let bin = r"C:\Users\me\.cargo\bin";
+        assert_eq!(
+            without_entry(r"C:\Windows;C:\Users\me\.cargo\bin\;C:\Tools", bin).as_deref(),
+            Some(r"C:\Windows;C:\Tools")
+        );
+        assert_eq!(without_entry(r"C:\Windows;C:\Tools", bin), None);
+    }
+
+    #[test]
+    fn test_versioned_file_names() {
+        let version = Version::new(0, 2, 0);
+        let name = versioned_file_name(&version);
+        let (stem, extension) = DLL_FILE_NAME.rsplit_once('.').unwrap();
+        assert_eq!(name, format!("{}-0.2.0.{}", stem, extension));
+        assert_eq!(parse_versioned(&name), Some(version));
+        assert_eq!(parse_versioned(DLL_FILE_NAME), None);
+        assert_eq!(parse_versioned(&format!("{}.new", name)), None);
+    }
+
+    #[test]
+    fn test_side_by_side_versions() {
+        let dir = env::temp_dir().join(format!("installer_versions_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let installers: Vec<_> = ["0.1.0", "0.1.2", "0.2.0"]
+            .iter()
+            .map(|version| Installer {
+                version: Version::parse(version).unwrap(),
+                ..test_installer(&dir, version.as_bytes())
+            })
+            .collect();
+        for installer in &installers {
+            installer.install(&QuietProgress).unwrap();
+        }
+
+        let dirs = [dir.join("bin")];
+        let newest = |requirement: &str| {
+            let requirement = semver::VersionReq::parse(requirement).unwrap();
+            newest_installed(&dirs, |v| requirement.matches(v))
+        };
+        assert_eq!(newest("^0.1"), Some(installers[1].get_dll_path()));
+        assert_eq!(newest("*"), Some(installers[2].get_dll_path()));
+        assert_eq!(newest("^0.3"), None);
+
+        // The record stays until the last version is gone.
+        assert!(
+            !installers[2]
+                .uninstall(&QuietProgress)
+                .unwrap()
+                .record_removed
+        );
+        assert_eq!(newest("*"), Some(installers[1].get_dll_path()));
+        assert!(
+            !installers[0]
+                .uninstall(&QuietProgress)
+                .unwrap()
+                .record_removed
+        );
+        assert!(
+            installers[1]
+                .uninstall(&QuietProgress)
+                .unwrap()
+                .record_removed
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[cfg(feature = "installer-remote")]
+    #[test]
+    fn test_update_available() {
+        let check = |installed: Option<Version>| UpdateCheck {
+            installed,
+            latest: Version::new(0, 2, 0),
+            release: Release::for_crate("owner/name"),
+        };
+        assert!(check(None).is_available());
+        assert!(check(Some(Version::new(0, 1, 0))).is_available());
+        assert!(!check(Some(Version::new(0, 2, 0))).is_available());
+        assert!(!check(Some(Version::new(0, 3, 0))).is_available());
+    }
+
     #[test]
     fn test_path_environment_variable() {
         if let Ok(installer) = Installer::new() {
@@ -0,0 +1,181 @@
+//! Error channels from the Go side.
+//!
+//! Go exports that fail signal it through a sentinel return value and record
+//! a message that `TakeThreadGoError` hands out once. Exports also recover
+//! their own panics and park the message and stack trace for
+//! `TakeThreadGoPanic`, so a panic no longer aborts the process. Like errno,
+//! both are kept per OS thread: the Go side files them under the thread that
+//! made the call, and the wrappers collect them for the thread their call ran
+//! on, which is an executor worker when one is configured. Concurrent calls
+//! therefore never see each other's failures. The safe wrappers check both
+//! after every call and return [`DllError::GoPanic`] or
+//! [`DllError::CallFailed`] instead of the zero or sentinel value. Libraries
+//! without these exports never report failures this way. A failed Rust
+//! callback takes precedence over the error Go records for it and becomes
+//! [`DllError::CallbackError`].
+
+use log::warn;
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::breaker;
+use crate::callback;
+use crate::go_alloc;
+use crate::guard;
+use crate::library::Library;
+use crate::stats;
+use crate::transfer::{self, TransferKind};
+use crate::{current_library, DllError};
+
+thread_local! {
+    /// The OS thread this thread's last export call ran on, when that was
+    /// not this thread
+    static CALL_THREAD: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Id of the calling OS thread, the same value the Go side files errors under
+#[cfg(unix)]
+pub(crate) fn os_thread_id() -> u64 {
+    unsafe { libc::pthread_self() as usize as u64 }
+}
+
+/// Id of the calling OS thread, the same value the Go side files errors under
+#[cfg(windows)]
+pub(crate) fn os_thread_id() -> u64 {
+    u64::from(unsafe { winapi::um::processthreadsapi::GetCurrentThreadId() })
+}
+
+/// Records where the caller's export call just ran: on `thread`, or on the
+/// calling thread itself for `None`
+pub(crate) fn set_call_thread(thread: Option<u64>) {
+    CALL_THREAD.with(|t| t.set(thread));
+}
+
+/// The OS thread whose pending errors belong to the caller's last call
+fn call_thread() -> u64 {
+    CALL_THREAD.with(Cell::get).unwrap_or_else(os_thread_id)
+}
+
+/// Takes the pending error message `thread` left in `library`, if any.
+///
+/// Like the other bookkeeping exports here it is called directly, outside
+/// the hooks, limits and counters of the call it belongs to.
+pub(crate) fn take(library: &Library, thread: u64) -> Result<Option<String>, DllError> {
+    let Some(take_error) = library.optional_symbol(&library.symbols.take_thread_go_error) else {
+        return Ok(None);
+    };
+    let raw = guard::guarded("TakeThreadGoError", || unsafe { take_error(thread) })?;
+    if raw.is_null() {
+        return Ok(None);
+    }
+    unsafe { copy_go_string(library, "TakeThreadGoError", raw) }.map(Some)
+}
+
+/// Takes the message and stack trace of a panic the Go side recovered in a
+/// call `thread` made, if any
+fn take_panic(library: &Library, thread: u64) -> Result<Option<(String, String)>, DllError> {
+    let Some(take_go_panic) = library.optional_symbol(&library.symbols.take_thread_go_panic) else {
+        return Ok(None);
+    };
+    let mut message: *mut c_char = ptr::null_mut();
+    let mut stack: *mut c_char = ptr::null_mut();
+    let pending = guard::guarded("TakeThreadGoPanic", || unsafe {
+        take_go_panic(thread, &mut message, &mut stack)
+    })?;
+    if pending == 0 {
+        return Ok(None);
+    }
+    unsafe {
+        Ok(Some((
+            copy_go_string(library, "TakeThreadGoPanic", message)?,
+            copy_go_string(library, "TakeThreadGoPanic", stack)?,
+        )))
+    }
+}
+
+/// Copies a string allocated by the Go side and releases the original.
+///
+/// # Safety
+///
+/// `raw` must be null or a NUL-terminated string allocated by `library`
+/// that nothing else uses afterwards.
+unsafe fn copy_go_string(
+    library: &Library,
+    export: &'static str,
+    raw: *mut c_char,
+) -> Result<String, DllError> {
+    if raw.is_null() {
+        return Ok(String::new());
+    }
+    let copy = CStr::from_ptr(raw).to_string_lossy().into_owned();
+    transfer::record_out(export, TransferKind::String, copy.len() + 1);
+    go_alloc::track(library, export, raw as usize, copy.len() + 1);
+    if !go_alloc::release(raw as usize, "FreeGoString") {
+        return Ok(copy);
+    }
+    match library.optional_symbol(&library.symbols.free_go_string) {
+        Some(free) => guard::guarded("FreeGoString", || free(raw))?,
+        // Leaking is the only safe option without the library's allocator.
+        None => warn!("Library has no FreeGoString export, leaking string"),
+    }
+    Ok(copy)
+}
+
+/// Fails with [`DllError::GoPanic`], [`DllError::CallbackError`] or
+/// [`DllError::CallFailed`] if the call to `export` that just returned
+/// panicked, had a callback fail or recorded an error
+pub(crate) fn check(library: &Library, export: &'static str) -> Result<(), DllError> {
+    let result = reported_failure(library, export);
+    if let Err(_e) = &result {
+        stats::record_error(export);
+        #[cfg(feature = "tracing")]
+        tracing::error!(
+            function = export,
+            code = _e.code(),
+            error = %_e,
+            "Go library reported an error"
+        );
+        #[cfg(feature = "metrics-core")]
+        crate::metrics::record_error(export);
+    }
+    result
+}
+
+fn reported_failure(library: &Library, export: &str) -> Result<(), DllError> {
+    let thread = call_thread();
+    if let Some((message, stack)) = take_panic(library, thread)? {
+        return Err(DllError::GoPanic {
+            export: export.to_string(),
+            message,
+            stack,
+        });
+    }
+    if let Some(failure) = callback::take_failure() {
+        // Drop the error Go recorded for the failed callback.
+        take(library, thread)?;
+        return Err(DllError::CallbackError(failure));
+    }
+    match take(library, thread)? {
+        Some(message) => Err(DllError::CallFailed {
+            export: export.to_string(),
+            message,
+        }),
+        None => {
+            breaker::record_success();
+            Ok(())
+        }
+    }
+}
+
+/// Returns and clears the error message the Go side recorded last for the
+/// calling thread.
+///
+/// The safe wrappers already collect it after each call, so this is mostly
+/// useful after calling the raw exports directly, which run on the calling
+/// thread.
+pub fn last_go_error() -> Option<String> {
+    let library = current_library().ok()?;
+    take(&library, os_thread_id()).ok().flatten()
+}
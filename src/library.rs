@@ -0,0 +1,681 @@
+//! Loaded copies of the Go library and their resolved exports.
+//!
+//! Safe wrappers call through the [`Symbols`] of the library currently stored
+//! in the context rather than through the link-time imports, which is what
+//! allows [`reload_dll`](crate::reload_dll) to swap in a new build at runtime.
+
+// With the `static` feature only the link-time symbols are used.
+#![cfg_attr(feature = "static", allow(dead_code))]
+
+use log::debug;
+use std::ffi::c_void;
+use std::fs;
+use std::os::raw::{c_char, c_int, c_longlong, c_uint, c_ulonglong};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::OnceCell;
+use semver::Version;
+
+use crate::cache;
+#[cfg(not(feature = "static"))]
+use crate::config::FfiConfig;
+use crate::config::UnloadPolicy;
+use crate::ffi;
+use crate::negotiation::Capabilities;
+#[cfg(not(feature = "static"))]
+use crate::retry;
+use crate::types::GoInt;
+use crate::DllError;
+#[cfg(not(feature = "static"))]
+use std::ffi::CString;
+
+/// An export that is looked up the first time a wrapper needs it.
+///
+/// Older DLLs may lack newer exports; resolving per symbol lets the wrappers
+/// that do exist keep working and report [`DllError::MissingSymbol`] for the
+/// ones that don't.
+pub(crate) struct Symbol<T> {
+    name: &'static str,
+    resolved: OnceCell<Option<T>>,
+}
+
+impl<T: Copy> Symbol<T> {
+    fn lazy(name: &'static str) -> Self {
+        Self {
+            name,
+            resolved: OnceCell::new(),
+        }
+    }
+
+    #[allow(dead_code)]
+    fn linked(name: &'static str, f: T) -> Self {
+        Self {
+            name,
+            resolved: OnceCell::with_value(Some(f)),
+        }
+    }
+}
+
+/// Symbols of the wrappers generated from the header
+mod header_symbols {
+    use super::Symbol;
+
+    include!(concat!(env!("OUT_DIR"), "/header_symbols.rs"));
+}
+
+/// Signature shared by the call bridge exports: function name, request
+/// payload and a slot for the response length
+pub(crate) type BridgeFn =
+    unsafe extern "C" fn(*mut c_char, *mut u8, c_longlong, *mut c_longlong) -> *mut c_void;
+
+/// Declares [`Symbols`], its constructors and the names of its exports from
+/// one entry per export
+macro_rules! symbols {
+    (
+        required {
+            $($(#[$attr:meta])* $field:ident: $export:ident => $ty:ty,)*
+        }
+        optional {
+            $($(#[$opt_attr:meta])* $opt_field:ident: $opt_export:ident => $opt_ty:ty,)*
+        }
+    ) => {
+        /// Every export the safe wrappers use
+        pub(crate) struct Symbols {
+            $($(#[$attr])* pub $field: Symbol<$ty>,)*
+            $($(#[$opt_attr])* pub $opt_field: Symbol<$opt_ty>,)*
+            /// Exports of the generated wrappers; see [`ffi`](crate::ffi)
+            pub header: header_symbols::HeaderSymbols,
+        }
+
+        impl Symbols {
+            /// Exports the wrappers that call them fail without
+            pub const REQUIRED: &'static [&'static str] = &[$(stringify!($export)),*];
+            /// Exports older libraries may lack, whose steps are skipped or
+            /// fall back to another export
+            pub const OPTIONAL: &'static [&'static str] = &[$(stringify!($opt_export)),*];
+
+            /// The exports bound at link time
+            #[allow(dead_code)]
+            pub fn linked() -> Self {
+                Self {
+                    $($field: Symbol::linked(stringify!($export), crate::ffi::$export),)*
+                    $($opt_field: Symbol::linked(
+                        stringify!($opt_export),
+                        crate::ffi::$opt_export,
+                    ),)*
+                    header: header_symbols::HeaderSymbols::linked(),
+                }
+            }
+
+            /// Exports resolved from the module on first use
+            fn lazy() -> Self {
+                Self {
+                    $($field: Symbol::lazy(stringify!($export)),)*
+                    $($opt_field: Symbol::lazy(stringify!($opt_export)),)*
+                    header: header_symbols::HeaderSymbols::lazy(),
+                }
+            }
+        }
+    };
+}
+
+symbols! {
+    required {
+        add_numbers: AddNumbers => unsafe extern "C" fn(GoInt, GoInt) -> GoInt,
+        add_numbers_batch: AddNumbersBatch =>
+            unsafe extern "C" fn(*mut c_longlong, c_longlong, *mut c_longlong),
+        hypot: Hypot => unsafe extern "C" fn(f64, f64) -> f64,
+        halve_float32: HalveFloat32 => unsafe extern "C" fn(f32) -> f32,
+        add_uint64: AddUint64 => unsafe extern "C" fn(c_ulonglong, c_ulonglong) -> c_ulonglong,
+        add_int128: AddInt128 => unsafe extern "C" fn(ffi::Int128, ffi::Int128) -> ffi::Int128,
+        is_even: IsEven => unsafe extern "C" fn(c_longlong) -> bool,
+        go_function: GoFunction => unsafe extern "C" fn(),
+        go_runtime_version: GoRuntimeVersion => unsafe extern "C" fn() -> *mut c_char,
+        /// Build metadata; see [`dll_build_info`](crate::dll_build_info)
+        get_build_info: GetBuildInfo => unsafe extern "C" fn() -> *mut c_char,
+        /// Liveness probe; see [`health_check`](crate::health_check)
+        ping: Ping => unsafe extern "C" fn(c_longlong) -> c_longlong,
+        /// Heap and scheduler figures; see [`go_runtime_stats`](crate::go_runtime_stats)
+        read_runtime_stats: ReadRuntimeStats => unsafe extern "C" fn(*mut ffi::RuntimeStats),
+        /// Garbage collector tuning; see [`set_go_gc_percent`](crate::set_go_gc_percent)
+        set_gc_percent: SetGCPercent => unsafe extern "C" fn(c_int) -> c_int,
+        set_memory_limit: SetMemoryLimit => unsafe extern "C" fn(c_longlong) -> c_longlong,
+        crc32: Crc32 => unsafe extern "C" fn(*mut u8, c_longlong) -> c_uint,
+        upper_bytes: UpperBytes => unsafe extern "C" fn(*mut u8, c_longlong),
+        decode_base64: DecodeBase64 =>
+            unsafe extern "C" fn(*mut u8, c_longlong, *mut c_longlong) -> *mut c_void,
+        scale_point: ScalePoint => unsafe extern "C" fn(ffi::Point, c_longlong) -> ffi::Point,
+        translate_point: TranslatePoint =>
+            unsafe extern "C" fn(*mut ffi::Point, c_longlong, c_longlong),
+        /// Generic call bridge; see [`bridge`](crate::bridge)
+        call_json: CallJSON => BridgeFn,
+        #[cfg_attr(not(feature = "binary-bridge"), allow(dead_code))]
+        call_msgpack: CallMsgpack => BridgeFn,
+        #[cfg_attr(not(feature = "binary-bridge"), allow(dead_code))]
+        call_cbor: CallCBOR => BridgeFn,
+        #[cfg_attr(not(feature = "proto-bridge"), allow(dead_code))]
+        call_proto: CallProto => BridgeFn,
+        call_json_batch: CallJSONBatch =>
+            unsafe extern "C" fn(*mut u8, c_longlong, *mut c_longlong) -> *mut c_void,
+        /// Signatures for calls by name; see [`registry`](crate::registry)
+        describe_api: DescribeAPI => unsafe extern "C" fn() -> *mut c_char,
+        /// Rust callbacks; see [`callback`](crate::callback)
+        register_callback: RegisterCallback =>
+            unsafe extern "C" fn(*mut c_char, ffi::rust_callback, *mut c_void),
+        unregister_callback: UnregisterCallback => unsafe extern "C" fn(*mut c_char),
+        count_with_progress: CountWithProgress => unsafe extern "C" fn(c_longlong) -> c_longlong,
+        /// Emits events; see [`events`](crate::events)
+        start_ticker: StartTicker => unsafe extern "C" fn(c_longlong, c_longlong),
+        /// Go log forwarding; see [`go_log`](crate::go_log)
+        set_log_level: SetLogLevel => unsafe extern "C" fn(c_int),
+        /// Chunked results; see [`GoStream`](crate::stream::GoStream)
+        next_chunk: NextChunk => unsafe extern "C" fn(c_longlong, *mut c_longlong) -> *mut c_void,
+        close_stream: CloseStream => unsafe extern "C" fn(c_longlong),
+        open_counter_stream: OpenCounterStream =>
+            unsafe extern "C" fn(c_longlong, c_longlong) -> c_longlong,
+        /// Ring buffer handshake for the `ring-transport` feature
+        #[cfg_attr(not(feature = "ring-transport"), allow(dead_code))]
+        start_ring_echo: StartRingEcho =>
+            unsafe extern "C" fn(*mut c_void, *mut c_void, c_longlong),
+        /// Cancellation; see [`cancel`](crate::cancel)
+        new_cancel_context: NewCancelContext => unsafe extern "C" fn() -> c_longlong,
+        cancel_context: CancelContext => unsafe extern "C" fn(c_longlong),
+        release_context: ReleaseContext => unsafe extern "C" fn(c_longlong),
+        sleep_with_context: SleepWithContext =>
+            unsafe extern "C" fn(c_longlong, c_longlong) -> c_int,
+        get_dll_version: GetDLLVersion => unsafe extern "C" fn() -> c_longlong,
+    }
+    optional {
+        /// Header hash; see [`abi_check`](crate::abi_check)
+        get_abi_fingerprint: GetABIFingerprint => unsafe extern "C" fn() -> c_ulonglong,
+        /// Version and capabilities; see [`negotiation`](crate::negotiation)
+        negotiate_version: NegotiateVersion =>
+            unsafe extern "C" fn(c_longlong, c_longlong, *mut c_ulonglong) -> c_longlong,
+        /// Optional lifecycle hooks; see [`lifecycle`](crate::lifecycle)
+        go_init: GoInit => unsafe extern "C" fn(*mut c_char) -> c_int,
+        go_shutdown: GoShutdown => unsafe extern "C" fn(c_longlong) -> c_int,
+        /// Optional error channel; see [`last_error`](crate::last_error)
+        take_thread_go_error: TakeThreadGoError => unsafe extern "C" fn(c_ulonglong) -> *mut c_char,
+        free_go_string: FreeGoString => unsafe extern "C" fn(*mut c_char),
+        free_go_bytes: FreeGoBytes => unsafe extern "C" fn(*mut c_void),
+        take_thread_go_panic: TakeThreadGoPanic =>
+            unsafe extern "C" fn(c_ulonglong, *mut *mut c_char, *mut *mut c_char) -> c_int,
+    }
+}
+
+/// Every export some wrapper calls, sorted and without duplicates
+pub(crate) fn known_exports() -> Vec<&'static str> {
+    let mut names: Vec<_> = Symbols::REQUIRED
+        .iter()
+        .chain(Symbols::OPTIONAL)
+        .chain(header_symbols::HeaderSymbols::EXPORTS)
+        .copied()
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// Exports a library has to provide for every wrapper to work
+pub(crate) fn required_exports() -> impl Iterator<Item = &'static str> {
+    Symbols::REQUIRED
+        .iter()
+        .chain(header_symbols::HeaderSymbols::EXPORTS)
+        .copied()
+}
+
+/// One mapped copy of the Go library.
+///
+/// The mapping is released when the last `Arc<Library>` is dropped, so calls
+/// that are still running against an old copy finish before it is unloaded.
+pub(crate) struct Library {
+    #[cfg(not(feature = "static"))]
+    handle: ModuleHandle,
+    /// The file the library was copied from
+    source: PathBuf,
+    source_modified: Option<SystemTime>,
+    /// Set when `source` was loaded through a shadow copy
+    shadow: Option<PathBuf>,
+    /// Version reported by this copy, once it has been asked
+    version: OnceCell<Version>,
+    /// Capabilities this copy offered during negotiation
+    capabilities: OnceCell<Capabilities>,
+    unload_policy: UnloadPolicy,
+    /// Set once any export of this copy has been handed out for a call
+    called: AtomicBool,
+    pub symbols: Symbols,
+}
+
+/// A module handle from the platform loader.
+///
+/// Handles are process-wide: any thread may look symbols up in the module or
+/// release it, and the loader synchronizes that itself.
+#[cfg(not(feature = "static"))]
+#[derive(Debug, Clone, Copy)]
+struct ModuleHandle(sys::Handle);
+
+#[cfg(not(feature = "static"))]
+unsafe impl Send for ModuleHandle {}
+#[cfg(not(feature = "static"))]
+unsafe impl Sync for ModuleHandle {}
+
+impl Library {
+    /// The library linked into the binary by the `static` feature
+    #[cfg(feature = "static")]
+    pub fn linked() -> Self {
+        Self {
+            source: PathBuf::new(),
+            source_modified: None,
+            shadow: None,
+            version: OnceCell::new(),
+            capabilities: OnceCell::new(),
+            unload_policy: UnloadPolicy::Leak,
+            called: AtomicBool::new(false),
+            symbols: Symbols::linked(),
+        }
+    }
+
+    /// Loads `path` in place
+    #[cfg(not(feature = "static"))]
+    pub fn load(path: &Path, config: &FfiConfig) -> Result<Self, DllError> {
+        retry::retry(
+            &config.load_retry,
+            "Loading the library",
+            retry::is_transient_load_error,
+            || Self::load_from(path, path, None, config),
+        )
+    }
+
+    /// Loads a private copy of `path`.
+    ///
+    /// Windows hands out the already-mapped module for a path it has seen, and
+    /// keeps the file locked while mapped, so reloads always go through a
+    /// uniquely named copy.
+    #[cfg(not(feature = "static"))]
+    pub fn load_shadow(path: &Path, config: &FfiConfig) -> Result<Self, DllError> {
+        retry::retry(
+            &config.load_retry,
+            "Loading a copy of the library",
+            retry::is_transient_load_error,
+            || {
+                let shadow = shadow_copy(path).map_err(|e| {
+                    DllError::LoadError(format!("Failed to create shadow copy: {}", e))
+                })?;
+                Self::load_from(path, &shadow, Some(shadow.clone()), config).map_err(|e| {
+                    let _ = fs::remove_file(&shadow);
+                    e
+                })
+            },
+        )
+    }
+
+    #[cfg(not(feature = "static"))]
+    fn load_from(
+        source: &Path,
+        load_path: &Path,
+        shadow: Option<PathBuf>,
+        config: &FfiConfig,
+    ) -> Result<Self, DllError> {
+        check_architecture(load_path)?;
+        if config.verify_checksum {
+            crate::checksum::verify(load_path)?;
+        }
+        #[cfg(feature = "signed-dll")]
+        if let Some(signature) = &config.require_signed_dll {
+            crate::signature::verify(load_path, source, signature)?;
+        }
+        let source_modified = fs::metadata(source).and_then(|m| m.modified()).ok();
+        let handle = unsafe { sys::open(load_path, config) }
+            .map_err(|e| DllError::LoadError(format!("{}: {}", load_path.display(), e)))?;
+        Ok(Self {
+            handle: ModuleHandle(handle),
+            source: source.to_path_buf(),
+            source_modified,
+            shadow,
+            version: OnceCell::new(),
+            capabilities: OnceCell::new(),
+            unload_policy: config.unload_policy,
+            called: AtomicBool::new(false),
+            symbols: Symbols::lazy(),
+        })
+    }
+
+    /// Returns the function behind `symbol`, resolving it on first use
+    pub fn symbol<T: Copy>(&self, symbol: &Symbol<T>) -> Result<T, DllError> {
+        self.optional_symbol(symbol)
+            .ok_or_else(|| DllError::MissingSymbol {
+                name: symbol.name.to_string(),
+                dll_version: self.version.get().cloned(),
+            })
+    }
+
+    /// Returns the function behind `symbol`, or `None` if this copy lacks it
+    pub fn optional_symbol<T: Copy>(&self, symbol: &Symbol<T>) -> Option<T> {
+        let resolved = *symbol.resolved.get_or_init(|| self.resolve(symbol.name));
+        if resolved.is_some() {
+            self.called.store(true, Ordering::Release);
+        }
+        resolved
+    }
+
+    #[cfg(not(feature = "static"))]
+    fn resolve<T: Copy>(&self, name: &str) -> Option<T> {
+        assert_eq!(
+            std::mem::size_of::<T>(),
+            std::mem::size_of::<*mut std::ffi::c_void>(),
+            "symbols must be function pointers"
+        );
+        let c_name = CString::new(name).ok()?;
+        let ptr = unsafe { sys::symbol(self.handle.0, &c_name) };
+        if ptr.is_null() {
+            debug!("Export {} not found in {}", name, self.source.display());
+            None
+        } else {
+            Some(unsafe { std::mem::transmute_copy(&ptr) })
+        }
+    }
+
+    /// Looks up an export that has no entry in [`Symbols`], without caching.
+    ///
+    /// Always `None` with the `static` feature, where only the exports known
+    /// at link time exist.
+    pub fn raw_symbol(&self, name: &str) -> Option<*const c_void> {
+        let resolved = self.resolve::<*const c_void>(name);
+        if resolved.is_some() {
+            self.called.store(true, Ordering::Release);
+        }
+        resolved
+    }
+
+    #[cfg(feature = "static")]
+    fn resolve<T: Copy>(&self, _name: &str) -> Option<T> {
+        // Linked symbols are filled in up front.
+        None
+    }
+
+    /// Remembers the version this copy reported, for error messages
+    pub fn set_version(&self, version: Version) {
+        let _ = self.version.set(version);
+    }
+
+    /// The version this copy reported, once it has been asked
+    pub fn version(&self) -> Option<&Version> {
+        self.version.get()
+    }
+
+    pub fn set_capabilities(&self, capabilities: Capabilities) {
+        let _ = self.capabilities.set(capabilities);
+    }
+
+    /// The capabilities this copy offered, once it has negotiated
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities.get().copied()
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// The shadow copy actually mapped, if any
+    pub fn shadow(&self) -> Option<&Path> {
+        self.shadow.as_deref()
+    }
+
+    /// Whether the file on disk has been modified since this copy was loaded
+    pub fn is_outdated(&self) -> bool {
+        let current = fs::metadata(&self.source).and_then(|m| m.modified()).ok();
+        match (current, self.source_modified) {
+            (Some(current), Some(loaded)) => current > loaded,
+            _ => false,
+        }
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        let called = self.called.load(Ordering::Acquire);
+        if !self.unload_policy.allows_unload(called) {
+            debug!(
+                "Keeping library copy of {} mapped ({:?})",
+                self.source.display(),
+                self.unload_policy
+            );
+            return;
+        }
+
+        #[cfg(not(feature = "static"))]
+        unsafe {
+            debug!("Unloading library copy of {}", self.source.display());
+            sys::close(self.handle.0);
+        }
+        if let Some(shadow) = &self.shadow {
+            if let Err(e) = fs::remove_file(shadow) {
+                debug!("Could not remove shadow copy {}: {}", shadow.display(), e);
+            }
+        }
+    }
+}
+
+/// Fails before loading if `path` was built for another architecture.
+///
+/// The loaders only report "not a valid Win32 application" (error 193) or
+/// "wrong ELF class". Headers that can't be read are left for the loader to
+/// reject.
+#[cfg(not(feature = "static"))]
+pub(crate) fn check_architecture(path: &Path) -> Result<(), DllError> {
+    match crate::image::architecture_of(path) {
+        Ok(found) if found != std::env::consts::ARCH => Err(DllError::ArchitectureMismatch {
+            expected: std::env::consts::ARCH.to_string(),
+            found: found.to_string(),
+        }),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            debug!("Could not determine architecture: {}", e);
+            Ok(())
+        }
+    }
+}
+
+fn shadow_copy(path: &Path) -> std::io::Result<PathBuf> {
+    let dir = cache::dir(cache::SHADOW);
+    fs::create_dir_all(&dir)?;
+
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("go_lib");
+    let name = match path.extension().and_then(|s| s.to_str()) {
+        Some(ext) => format!("{}-{}-{}.{}", stem, std::process::id(), stamp, ext),
+        None => format!("{}-{}-{}", stem, std::process::id(), stamp),
+    };
+
+    let shadow = dir.join(name);
+    fs::copy(path, &shadow)?;
+    cache::enforce_limit(&shadow);
+    Ok(shadow)
+}
+
+#[cfg(all(windows, not(feature = "static")))]
+pub(crate) mod sys {
+    use log::debug;
+    use std::ffi::{CStr, OsStr};
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use winapi::um::libloaderapi::{
+        FreeLibrary, GetProcAddress, LoadLibraryExW, LOAD_LIBRARY_SEARCH_DEFAULT_DIRS,
+        LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR, LOAD_LIBRARY_SEARCH_SYSTEM32,
+    };
+    use winapi::um::winbase::SetDllDirectoryW;
+
+    use crate::config::{DllSearchPolicy, FfiConfig};
+    use crate::long_path;
+
+    pub type Handle = winapi::shared::minwindef::HMODULE;
+
+    fn to_wide(s: &OsStr) -> Vec<u16> {
+        s.encode_wide().chain(std::iter::once(0)).collect()
+    }
+
+    /// Loads the library honouring the configured search policy
+    pub unsafe fn open(path: &Path, config: &FfiConfig) -> io::Result<Handle> {
+        if let Some(dir) = &config.dll_directory {
+            let wide_dir = to_wide(dir.as_os_str());
+            if SetDllDirectoryW(wide_dir.as_ptr()) == 0 {
+                debug!("SetDllDirectoryW failed for {}", dir.display());
+            }
+        }
+
+        let flags = match config.search_policy {
+            DllSearchPolicy::Restricted => {
+                LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_SYSTEM32
+            }
+            DllSearchPolicy::SafeDefaults => {
+                LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_DEFAULT_DIRS
+            }
+            DllSearchPolicy::Legacy => 0,
+        };
+
+        // LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR requires a fully qualified path, and
+        // only verbatim paths may exceed MAX_PATH or point at a share reliably.
+        let path = match config.search_policy {
+            // A bare file name asks the loader to search for it.
+            DllSearchPolicy::Legacy if path.parent() == Some(Path::new("")) => path.to_path_buf(),
+            DllSearchPolicy::Legacy => long_path::to_verbatim(path),
+            _ => std::fs::canonicalize(path).unwrap_or_else(|_| long_path::to_verbatim(path)),
+        };
+        debug!("Loading {} with flags {:#x}", path.display(), flags);
+
+        let wide_path = to_wide(path.as_os_str());
+        let handle = LoadLibraryExW(wide_path.as_ptr(), std::ptr::null_mut(), flags);
+        if handle.is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(handle)
+        }
+    }
+
+    pub unsafe fn symbol(handle: Handle, name: &CStr) -> *mut std::ffi::c_void {
+        GetProcAddress(handle, name.as_ptr()).cast()
+    }
+
+    pub unsafe fn close(handle: Handle) {
+        FreeLibrary(handle);
+    }
+}
+
+#[cfg(all(unix, not(feature = "static")))]
+pub(crate) mod sys {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    use crate::config::FfiConfig;
+
+    pub type Handle = *mut libc::c_void;
+
+    /// Loads the library; search policies only apply to the Windows loader
+    pub unsafe fn open(path: &Path, _config: &FfiConfig) -> io::Result<Handle> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let handle = libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if handle.is_null() {
+            let message = libc::dlerror();
+            let message = if message.is_null() {
+                "dlopen failed".to_string()
+            } else {
+                CStr::from_ptr(message).to_string_lossy().into_owned()
+            };
+            Err(io::Error::new(io::ErrorKind::Other, message))
+        } else {
+            Ok(handle)
+        }
+    }
+
+    pub unsafe fn symbol(handle: Handle, name: &CStr) -> *mut std::ffi::c_void {
+        libc::dlsym(handle, name.as_ptr())
+    }
+
+    pub unsafe fn close(handle: Handle) {
+        libc::dlclose(handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shadow_copy_is_unique() {
+        let source = std::env::temp_dir().join("rust_go_ffi_shadow_source.dll");
+        fs::write(&source, b"not a real library").unwrap();
+
+        let first = shadow_copy(&source).unwrap();
+        let second = shadow_copy(&source).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(fs::read(&first).unwrap(), b"not a real library");
+        assert_eq!(first.extension().and_then(|e| e.to_str()), Some("dll"));
+
+        let _ = fs::remove_file(first);
+        let _ = fs::remove_file(second);
+        let _ = fs::remove_file(source);
+    }
+
+    /// Copies the built library into `dir` and loads it from there
+    #[cfg(all(windows, not(feature = "static")))]
+    fn load_copy_in(dir: &Path) -> Result<(), DllError> {
+        let Some(built) = crate::get_dll_path().filter(|p| p.exists()) else {
+            println!("DLL not built, skipping");
+            return Ok(());
+        };
+        fs::create_dir_all(dir).unwrap();
+        let target = dir.join(crate::DLL_FILE_NAME);
+        fs::copy(&built, &target).unwrap();
+
+        let library = Library::load(&target, &FfiConfig::default())?;
+        library.symbol(&library.symbols.get_dll_version)?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(windows, not(feature = "static")))]
+    fn test_load_from_deep_directory() {
+        let root = std::env::temp_dir().join("rust_go_ffi_deep_path_test");
+        let deep = (0..30).fold(root.clone(), |dir, i| dir.join(format!("level_{:02}", i)));
+        assert!(deep.as_os_str().len() > 260);
+
+        let result = load_copy_in(&deep);
+        let _ = fs::remove_dir_all(root);
+        result.expect("Loading from a path longer than MAX_PATH should succeed");
+    }
+
+    #[test]
+    #[cfg(all(windows, not(feature = "static")))]
+    fn test_load_from_unc_path() {
+        // Reach the temp directory through the administrative share of its drive.
+        let temp = std::env::temp_dir();
+        let temp = temp.to_str().unwrap();
+        let (drive, rest) = temp.split_at(2);
+        let unc = PathBuf::from(format!(
+            r"\\localhost\{}$\{}",
+            drive.trim_end_matches(':'),
+            rest.trim_start_matches('\\')
+        ));
+        if !unc.exists() {
+            println!("Administrative share not reachable, skipping");
+            return;
+        }
+
+        let dir = unc.join("rust_go_ffi_unc_test");
+        let result = load_copy_in(&dir);
+        let _ = fs::remove_dir_all(&dir);
+        result.expect("Loading from a UNC path should succeed");
+    }
+}
@@ -0,0 +1,73 @@
+//! Optional two-stage lifecycle hooks exported by the Go side.
+//!
+//! A library may export `GoInit(config *C.char) C.int` and
+//! `GoShutdown(deadlineMs C.longlong) C.int`. [`initialize`](crate::initialize)
+//! calls `GoInit` once the version check has passed, and
+//! [`shutdown`](crate::shutdown) and [`cleanup`](crate::cleanup) call
+//! `GoShutdown` before unloading. [`reload_dll`](crate::reload_dll) does both,
+//! for the new and the replaced copy. Both return zero on success. Libraries built before the hooks existed simply
+//! don't export them, and the steps are skipped.
+
+use log::{debug, info};
+use std::ffi::CString;
+use std::mem;
+use std::time::Duration;
+
+use crate::last_error;
+use crate::library::Library;
+use crate::transfer::{self, TransferKind};
+use crate::{call_export, DllError};
+
+/// Lifecycle exports a library may provide
+pub const LIFECYCLE_EXPORTS: &[&str] = &["GoInit", "GoShutdown"];
+
+/// Runs `GoInit` with `config` if the library exports it.
+///
+/// Returns whether the hook was present.
+pub(crate) fn go_init(library: &Library, config: &str) -> Result<bool, DllError> {
+    let Some(init) = library.optional_symbol(&library.symbols.go_init) else {
+        debug!("Library has no GoInit export, skipping");
+        return Ok(false);
+    };
+
+    let config = CString::new(config).map_err(|_| {
+        DllError::InitializationError("Go init config contains a NUL byte".to_string())
+    })?;
+    transfer::marshal_in(
+        "GoInit",
+        TransferKind::String,
+        config.as_bytes_with_nul().len(),
+    )?;
+    // Go only reads the string; the `char*` in the cgo header is not const.
+    let code = call_export("GoInit", || unsafe { init(config.as_ptr().cast_mut()) })?;
+    last_error::check(library, "GoInit")?;
+    if code != 0 {
+        return Err(DllError::GoInitFailed { code });
+    }
+    info!("Go side initialized");
+    Ok(true)
+}
+
+/// Runs `GoShutdown` with `deadline` if the library exports it.
+///
+/// Returns whether the hook was present.
+pub(crate) fn go_shutdown(library: &Library, deadline: Duration) -> Result<bool, DllError> {
+    let Some(shutdown) = library.optional_symbol(&library.symbols.go_shutdown) else {
+        debug!("Library has no GoShutdown export, skipping");
+        return Ok(false);
+    };
+
+    let deadline_ms = i64::try_from(deadline.as_millis()).unwrap_or(i64::MAX);
+    transfer::record_in(
+        "GoShutdown",
+        TransferKind::Scalar,
+        mem::size_of_val(&deadline_ms),
+    );
+    let code = call_export("GoShutdown", || unsafe { shutdown(deadline_ms) })?;
+    last_error::check(library, "GoShutdown")?;
+    if code != 0 {
+        return Err(DllError::GoShutdownFailed { code });
+    }
+    info!("Go side shut down");
+    Ok(true)
+}
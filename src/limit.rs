@@ -0,0 +1,135 @@
+//! The limit on concurrent calls set by
+//! [`FfiConfig::with_max_concurrent_calls`](crate::FfiConfig::with_max_concurrent_calls).
+//!
+//! Every call into an export takes a permit for its duration, as does
+//! [`with_dll`](crate::with_dll) for its closure, and fails with
+//! [`DllError::Busy`] when none is left; the async wrappers wait for one
+//! instead. A call chain that already holds a permit doesn't take another, so
+//! the exports a `with_dll` closure calls and nested `with_dll` calls count
+//! once. The permit follows the chain onto the threads it continues on, the
+//! worker of [`with_timeout`](crate::with_timeout) and the executor worker
+//! that runs a call and the callbacks Go makes during it.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::config;
+use crate::DllError;
+
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "tokio")]
+static RELEASED: once_cell::sync::Lazy<tokio::sync::Notify> =
+    once_cell::sync::Lazy::new(tokio::sync::Notify::new);
+
+thread_local! {
+    /// The permit of the call chain running on this thread
+    static HOLDING: RefCell<Option<Arc<Permit>>> = const { RefCell::new(None) };
+}
+
+/// One of the limited slots; released on drop
+pub(crate) struct Permit(());
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        ACTIVE.fetch_sub(1, Ordering::AcqRel);
+        #[cfg(feature = "tokio")]
+        RELEASED.notify_waiters();
+    }
+}
+
+/// Takes a slot if fewer than `limit` are taken
+fn try_take(limit: usize) -> Option<Permit> {
+    ACTIVE
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+            (active < limit).then_some(active + 1)
+        })
+        .ok()
+        .map(|_| Permit(()))
+}
+
+/// A permit for this call chain, or `None` if there is no limit or the
+/// chain already holds one
+pub(crate) fn try_acquire() -> Result<Option<Permit>, DllError> {
+    let Some(limit) = config::max_concurrent_calls() else {
+        return Ok(None);
+    };
+    if HOLDING.with(|h| h.borrow().is_some()) {
+        return Ok(None);
+    }
+    try_take(limit).map(Some).ok_or(DllError::Busy { limit })
+}
+
+/// Waits for a permit; `None` if there is no limit
+#[cfg(feature = "tokio")]
+pub(crate) async fn acquire_async() -> Option<Permit> {
+    let limit = config::max_concurrent_calls()?;
+    loop {
+        let mut released = std::pin::pin!(RELEASED.notified());
+        // Register first so a release between the attempt and the wait counts.
+        released.as_mut().enable();
+        if let Some(permit) = try_take(limit) {
+            return Some(permit);
+        }
+        released.await;
+    }
+}
+
+/// Runs `f` with `permit` held by the current thread
+pub(crate) fn hold<T>(permit: Option<Permit>, f: impl FnOnce() -> T) -> T {
+    match permit {
+        Some(permit) => adopt(Some(Arc::new(permit)), f),
+        None => f(),
+    }
+}
+
+/// The permit the call chain on this thread holds, to hand to [`adopt`] on
+/// a thread that continues it
+pub(crate) fn current() -> Option<Arc<Permit>> {
+    HOLDING.with(|h| h.borrow().clone())
+}
+
+/// Runs `f` as part of the call chain that holds `permit`.
+///
+/// The permit is only released once every thread of the chain is done with
+/// it, so a call left running after a timeout keeps its slot.
+pub(crate) fn adopt<T>(permit: Option<Arc<Permit>>, f: impl FnOnce() -> T) -> T {
+    if permit.is_none() {
+        return f();
+    }
+    let previous = HOLDING.with(|h| h.replace(permit));
+    // Restores the previous permit even if `f` panics.
+    struct Restore(Option<Arc<Permit>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            HOLDING.with(|h| *h.borrow_mut() = previous);
+        }
+    }
+    let _restore = Restore(previous);
+    f()
+}
+
+/// Runs `f` under the concurrency limit, failing with [`DllError::Busy`]
+/// if it is reached
+pub(crate) fn run<T>(f: impl FnOnce() -> Result<T, DllError>) -> Result<T, DllError> {
+    hold(try_acquire()?, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_are_bounded() {
+        // Other tests never take permits, since no limit is configured.
+        let first = try_take(2).unwrap();
+        let second = try_take(2).unwrap();
+        assert!(try_take(2).is_none());
+        drop(first);
+        assert!(try_take(2).is_some());
+        drop(second);
+        assert_eq!(ACTIVE.load(Ordering::Acquire), 0);
+    }
+}
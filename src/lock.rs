@@ -0,0 +1,154 @@
+//! Lock around the global [`DllContext`] that survives panics.
+//!
+//! `parking_lot` locks do not poison, so a thread that panics halfway through
+//! updating the context would leave the next caller with half-written state.
+//! Write guards therefore flag a panic on drop, and the next acquisition
+//! resets the bookkeeping fields before handing the context out. Waiting is
+//! bounded so a stuck holder surfaces as [`DllError::LockTimeout`] instead of
+//! hanging every caller.
+
+use log::{error, warn};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::{DllContext, DllError};
+
+/// Longest time a caller waits for another thread to release the context
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub(crate) struct ContextLock {
+    inner: RwLock<DllContext>,
+    /// Set when a thread panicked while holding the write lock
+    poisoned: AtomicBool,
+    recoveries: AtomicU64,
+}
+
+impl ContextLock {
+    pub fn new(context: DllContext) -> Self {
+        Self {
+            inner: RwLock::new(context),
+            poisoned: AtomicBool::new(false),
+            recoveries: AtomicU64::new(0),
+        }
+    }
+
+    pub fn write(&self) -> Result<ContextWriteGuard<'_>, DllError> {
+        let mut guard = self
+            .inner
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or(DllError::LockTimeout)?;
+        self.recover(&mut guard);
+        Ok(ContextWriteGuard {
+            guard,
+            poisoned: &self.poisoned,
+        })
+    }
+
+    pub fn read(&self) -> Result<RwLockReadGuard<'_, DllContext>, DllError> {
+        if self.poisoned.load(Ordering::Acquire) {
+            // Recovery needs exclusive access.
+            drop(self.write()?);
+        }
+        self.inner
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or(DllError::LockTimeout)
+    }
+
+    /// Reads the context if nobody is writing it, without waiting
+    #[cfg(feature = "tracing")]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, DllContext>> {
+        if self.poisoned.load(Ordering::Acquire) {
+            return None;
+        }
+        self.inner.try_read()
+    }
+
+    /// How often the context had to be rebuilt after a panic
+    pub fn recoveries(&self) -> u64 {
+        self.recoveries.load(Ordering::Relaxed)
+    }
+
+    fn recover(&self, context: &mut DllContext) {
+        if !self.poisoned.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let count = self.recoveries.fetch_add(1, Ordering::Relaxed) + 1;
+        warn!(
+            "Rebuilding FFI context after a panic while it was locked (incident {})",
+            count
+        );
+        // The loaded library is swapped in a single assignment and stays
+        // valid; only the initialization bookkeeping may be inconsistent.
+        let defaults = DllContext::default();
+        context.version = defaults.version;
+        context.initialized = false;
+    }
+}
+
+/// Write access to the context that flags panics on drop
+pub(crate) struct ContextWriteGuard<'a> {
+    guard: RwLockWriteGuard<'a, DllContext>,
+    poisoned: &'a AtomicBool,
+}
+
+impl Deref for ContextWriteGuard<'_> {
+    type Target = DllContext;
+
+    fn deref(&self) -> &DllContext {
+        &self.guard
+    }
+}
+
+impl DerefMut for ContextWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut DllContext {
+        &mut self.guard
+    }
+}
+
+impl Drop for ContextWriteGuard<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            error!("Thread panicked while holding the FFI context lock");
+            self.poisoned.store(true, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_recovers_after_panic() {
+        let lock = Arc::new(ContextLock::new(DllContext::default()));
+
+        let panicking = lock.clone();
+        let result = std::thread::spawn(move || {
+            let mut context = panicking.write().unwrap();
+            context.initialized = true;
+            panic!("simulated failure while holding the lock");
+        })
+        .join();
+        assert!(result.is_err());
+
+        let context = lock.read().expect("Lock should be usable after a panic");
+        assert!(!context.initialized);
+        drop(context);
+        assert_eq!(lock.recoveries(), 1);
+
+        // Later acquisitions do not count as new incidents.
+        drop(lock.write().unwrap());
+        assert_eq!(lock.recoveries(), 1);
+    }
+
+    #[test]
+    fn test_normal_use_does_not_recover() {
+        let lock = ContextLock::new(DllContext::default());
+        lock.write().unwrap().initialized = true;
+        assert!(lock.read().unwrap().initialized);
+        assert_eq!(lock.recoveries(), 0);
+    }
+}
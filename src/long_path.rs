@@ -0,0 +1,111 @@
+//! Extended-length (`\\?\`) forms of Windows paths.
+//!
+//! `LoadLibraryExW` is limited to `MAX_PATH` characters unless it is given a
+//! verbatim path, and verbatim paths skip the usual normalization, so `.`,
+//! `..` and forward slashes have to be resolved here first.
+
+use std::path::{Path, PathBuf};
+
+/// Returns the verbatim form of `path`, made absolute against the current directory.
+///
+/// Paths that are not valid UTF-8 are returned unchanged.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn to_verbatim(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        match std::env::current_dir() {
+            Ok(dir) => dir.join(path),
+            Err(_) => return path.to_path_buf(),
+        }
+    };
+    match absolute.to_str() {
+        Some(s) => PathBuf::from(verbatim(s)),
+        None => absolute,
+    }
+}
+
+/// Rewrites an absolute Windows path string into its verbatim form.
+///
+/// `C:\dir\file` becomes `\\?\C:\dir\file` and `\\server\share\file` becomes
+/// `\\?\UNC\server\share\file`. Relative paths are returned unchanged.
+fn verbatim(path: &str) -> String {
+    let path = path.replace('/', "\\");
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return path;
+    }
+    if let Some(rest) = path.strip_prefix(r"\\") {
+        // Server and share are the root and cannot be navigated above.
+        return format!(r"\\?\UNC\{}", normalize(rest, 2));
+    }
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        return format!(r"\\?\{}", normalize(&path, 1));
+    }
+    path
+}
+
+/// Resolves `.` and `..` lexically, never removing the first `root` components
+fn normalize(path: &str, root: usize) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.len() > root {
+                    parts.pop();
+                }
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drive_path() {
+        assert_eq!(
+            verbatim(r"C:\Program Files\app\go_lib.dll"),
+            r"\\?\C:\Program Files\app\go_lib.dll"
+        );
+    }
+
+    #[test]
+    fn test_unc_path() {
+        assert_eq!(
+            verbatim(r"\\fileserver\apps\rust\go_lib.dll"),
+            r"\\?\UNC\fileserver\apps\rust\go_lib.dll"
+        );
+    }
+
+    #[test]
+    fn test_dots_and_slashes_are_resolved() {
+        assert_eq!(
+            verbatim("C:/app/./lib/../go_lib.dll"),
+            r"\\?\C:\app\go_lib.dll"
+        );
+        assert_eq!(
+            verbatim(r"\\server\share\..\..\go_lib.dll"),
+            r"\\?\UNC\server\share\go_lib.dll"
+        );
+    }
+
+    #[test]
+    fn test_verbatim_and_relative_unchanged() {
+        assert_eq!(verbatim(r"\\?\C:\go_lib.dll"), r"\\?\C:\go_lib.dll");
+        assert_eq!(verbatim(r"go_lib\go_lib.dll"), r"go_lib\go_lib.dll");
+    }
+
+    #[test]
+    fn test_deep_path() {
+        let deep = format!(r"C:\{}\go_lib.dll", vec!["segment"; 60].join("\\"));
+        assert!(deep.len() > 260);
+        let long = verbatim(&deep);
+        assert!(long.starts_with(r"\\?\C:\segment"));
+        assert_eq!(long.len(), deep.len() + 4);
+    }
+}
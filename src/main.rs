@@ -1,7 +1,47 @@
 use colored::*;
-use rust_go_ffi::{self, add_numbers, go_function, is_dll_available, verify_dll};
+use rust_go_ffi::{
+    self, add_numbers, cache_root, clear_cache, go_function, is_dll_available, run_self_test,
+    transfer_report, usage_report, verify_dll,
+};
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [command] = args.as_slice() {
+        if command == "verify" {
+            verify();
+            return;
+        }
+        #[cfg(feature = "installer-core")]
+        if command == "install" {
+            install();
+            return;
+        }
+        #[cfg(feature = "installer-core")]
+        if command == "uninstall" {
+            uninstall();
+            return;
+        }
+    }
+    if let [command, action] = args.as_slice() {
+        if command == "cache" && action == "clean" {
+            cache_clean();
+            return;
+        }
+        #[cfg(feature = "installer-core")]
+        if command == "install" && action == "--dry-run" {
+            plan_install();
+            return;
+        }
+        #[cfg(feature = "installer-json")]
+        if command == "install" && action == "--json" {
+            // The events say how it went; only the exit code is left to set.
+            if rust_go_ffi::install_dll_with(&rust_go_ffi::JsonProgress::stdout()).is_err() {
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+
     println!("{}", "Rust-Go FFI Interface".bold().green());
     println!("{}", "===================".green());
 
@@ -47,8 +87,94 @@ fn main() {
         }
     }
 
+    println!("\n{}", "📊 Export usage:".cyan().bold());
+    println!("{}", "---------------".cyan());
+    let report = usage_report();
+    print!("{}", report);
+    for export in report.unused() {
+        println!("{} {}", "⚠️ Never called:".yellow(), export.name);
+    }
+
+    println!("\n{}", "📦 Bytes copied across the boundary:".cyan().bold());
+    println!("{}", "----------------------------------".cyan());
+    print!("{}", transfer_report());
+
     println!(
         "\n{}",
         "✨ All tests completed successfully! ✨".green().bold()
     );
 }
+
+fn verify() {
+    println!("{}", "🔍 Running self-test...".cyan().bold());
+    let report = run_self_test();
+    print!("{}", report);
+    if report.passed() {
+        println!("{}", "✅ Self-test passed".green().bold());
+    } else {
+        eprintln!("{}", "❌ Self-test failed".red().bold());
+        std::process::exit(1);
+    }
+}
+
+fn cache_clean() {
+    println!(
+        "🧹 Cleaning cache at {}",
+        cache_root().display().to_string().blue()
+    );
+    match clear_cache() {
+        Ok(stats) => {
+            println!(
+                "{} removed {} entries, freed {} bytes",
+                "✅".green(),
+                stats.entries_removed,
+                stats.bytes_freed
+            );
+            if stats.entries_skipped > 0 {
+                println!(
+                    "{} {} entries in use were kept",
+                    "⚠️".yellow(),
+                    stats.entries_skipped
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(feature = "installer-core")]
+fn install() {
+    if let Err(e) = rust_go_ffi::install_dll() {
+        eprintln!("{} {}", "Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(feature = "installer-core")]
+fn plan_install() {
+    match rust_go_ffi::plan_install() {
+        Ok(plan) => {
+            print!("{}", plan);
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(feature = "installer-core")]
+fn uninstall() {
+    match rust_go_ffi::uninstall_dll() {
+        Ok(report) => {
+            print!("{}", report);
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Version negotiation and capability flags.
+//!
+//! `initialize` calls the library's `NegotiateVersion` export with the range
+//! of versions the [`VersionRequirement`] accepts, in `GetDLLVersion`'s
+//! `major * 10000 + minor * 100 + patch` encoding. The library replies with
+//! its version and a [`Capabilities`] bitset, so wrappers can feature-detect
+//! with [`capabilities`] instead of inferring support from the version
+//! number. A library without the export falls back to `GetDLLVersion` and
+//! reports no capabilities.
+
+use log::debug;
+use semver::Version;
+use std::fmt;
+use std::mem;
+use std::ops::BitOr;
+
+use crate::library::Library;
+use crate::transfer::{self, TransferKind};
+use crate::types::OutOfRange;
+use crate::{
+    call_export, ensure_initialized, get_dll_version, last_error, DllError, VersionRequirement,
+};
+
+/// Features a library reports through `NegotiateVersion`.
+///
+/// Bits a newer library sets that this crate doesn't know are kept, so they
+/// show up in [`bits`](Capabilities::bits).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities(u64);
+
+impl Capabilities {
+    /// Strings allocated by Go, e.g. [`go_runtime_version`](crate::go_runtime_version)
+    pub const STRINGS: Self = Self(1 << 0);
+    /// Borrowed byte buffers and Go-allocated byte results
+    pub const BYTES: Self = Self(1 << 1);
+    /// Rust callbacks; see [`register_callback`](crate::register_callback)
+    pub const CALLBACKS: Self = Self(1 << 2);
+    /// Go events; see [`subscribe_events`](crate::subscribe_events)
+    pub const EVENTS: Self = Self(1 << 3);
+    /// Chunked results; see [`GoStream`](crate::GoStream)
+    pub const STREAMS: Self = Self(1 << 4);
+    /// The generic call bridge; see [`call_json`](crate::call_json)
+    pub const JSON_BRIDGE: Self = Self(1 << 5);
+    /// See [`bridge_go_logs`](crate::bridge_go_logs)
+    pub const LOG_BRIDGE: Self = Self(1 << 6);
+    /// See [`go_runtime_stats`](crate::go_runtime_stats)
+    pub const RUNTIME_STATS: Self = Self(1 << 7);
+
+    const NAMES: &'static [(Self, &'static str)] = &[
+        (Self::STRINGS, "strings"),
+        (Self::BYTES, "bytes"),
+        (Self::CALLBACKS, "callbacks"),
+        (Self::EVENTS, "events"),
+        (Self::STREAMS, "streams"),
+        (Self::JSON_BRIDGE, "json-bridge"),
+        (Self::LOG_BRIDGE, "log-bridge"),
+        (Self::RUNTIME_STATS, "runtime-stats"),
+    ];
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Whether every flag in `other` is set
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name);
+        let known = Self::NAMES.iter().fold(0, |bits, (flag, _)| bits | flag.0);
+        let unknown = self.0 & !known;
+        match names.next() {
+            Some(first) => {
+                write!(f, "{}", first)?;
+                for name in names {
+                    write!(f, ", {}", name)?;
+                }
+                if unknown != 0 {
+                    write!(f, ", {:#x}", unknown)?;
+                }
+                Ok(())
+            }
+            None if unknown != 0 => write!(f, "{:#x}", unknown),
+            None => write!(f, "none"),
+        }
+    }
+}
+
+/// Decodes `major * 10000 + minor * 100 + patch`
+pub(crate) fn decode_version(raw: i64) -> Result<Version, DllError> {
+    let version_num = u64::try_from(raw).map_err(|_| OutOfRange {
+        value: raw.into(),
+        target: "u64",
+    })?;
+    let major = version_num / 10000;
+    let minor = (version_num % 10000) / 100;
+    let patch = version_num % 100;
+    Ok(Version::new(major, minor, patch))
+}
+
+/// Encodes a version like `GetDLLVersion`; `None` if a component doesn't
+/// fit its two digits
+fn encode_version(version: &Version) -> Option<i64> {
+    if version.minor >= 100 || version.patch >= 100 {
+        return None;
+    }
+    i64::try_from(version.major.checked_mul(10000)? + version.minor * 100 + version.patch).ok()
+}
+
+/// The encoded `[min, max)` range `required` accepts; open-ended where it
+/// can't be expressed, as for a [`VersionRequirement::Req`]
+fn encoded_range(required: &VersionRequirement, strict: bool) -> (i64, i64) {
+    let (min, max) = required.bounds(strict);
+    (
+        min.as_ref().and_then(encode_version).unwrap_or(0),
+        max.as_ref().and_then(encode_version).unwrap_or(i64::MAX),
+    )
+}
+
+/// Asks the library for its version and capabilities, remembering both on
+/// `library`.
+///
+/// # Safety
+///
+/// `library` must be a Go library built from this crate's `go_lib.go`.
+pub(crate) unsafe fn negotiate(
+    library: &Library,
+    required: &VersionRequirement,
+    strict: bool,
+) -> Result<Version, DllError> {
+    let negotiate_version = match library.symbol(&library.symbols.negotiate_version) {
+        Ok(f) => f,
+        Err(DllError::MissingSymbol { .. }) => {
+            debug!("The DLL has no NegotiateVersion export, asking GetDLLVersion");
+            library.set_capabilities(Capabilities::empty());
+            return get_dll_version(library);
+        }
+        Err(e) => return Err(e),
+    };
+    let (min, max) = encoded_range(required, strict);
+    transfer::record_in(
+        "NegotiateVersion",
+        TransferKind::Scalar,
+        mem::size_of_val(&min) + mem::size_of_val(&max),
+    );
+    let mut bits = 0;
+    let raw = call_export("NegotiateVersion", || {
+        negotiate_version(min, max, &mut bits)
+    })?;
+    last_error::check(library, "NegotiateVersion")?;
+    transfer::record_out(
+        "NegotiateVersion",
+        TransferKind::Scalar,
+        mem::size_of_val(&raw) + mem::size_of_val(&bits),
+    );
+
+    let version = decode_version(raw)?;
+    let capabilities = Capabilities::from_bits(bits);
+    debug!("DLL {} offers {}", version, capabilities);
+    library.set_version(version.clone());
+    library.set_capabilities(capabilities);
+    Ok(version)
+}
+
+/// What the initialized library supports, initializing it on first use.
+///
+/// Check this before calling a wrapper whose export an older library may
+/// lack, e.g. `capabilities()?.contains(Capabilities::LOG_BRIDGE)`.
+pub fn capabilities() -> Result<Capabilities, DllError> {
+    let library = ensure_initialized()?;
+    Ok(library.capabilities().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::VersionReq;
+
+    #[test]
+    fn test_version_encoding_round_trips() {
+        let version = Version::new(1, 2, 3);
+        let raw = encode_version(&version).unwrap();
+        assert_eq!(raw, 10203);
+        assert_eq!(decode_version(raw).unwrap(), version);
+        assert_eq!(encode_version(&Version::new(0, 100, 0)), None);
+        assert!(decode_version(-1).is_err());
+    }
+
+    #[test]
+    fn test_encoded_range() {
+        let required = VersionRequirement::from(Version::new(0, 1, 0));
+        assert_eq!(encoded_range(&required, false), (100, 200));
+        assert_eq!(encoded_range(&required, true), (100, 101));
+        let required = VersionRequirement::from(VersionReq::parse(">=0.1, <0.3").unwrap());
+        assert_eq!(encoded_range(&required, false), (0, i64::MAX));
+    }
+
+    #[test]
+    fn test_capabilities_display() {
+        let capabilities = Capabilities::STRINGS | Capabilities::EVENTS;
+        assert!(capabilities.contains(Capabilities::EVENTS));
+        assert!(!capabilities.contains(Capabilities::STRINGS | Capabilities::STREAMS));
+        assert_eq!(capabilities.to_string(), "strings, events");
+        assert_eq!(
+            Capabilities::from_bits(1 | 1 << 40).to_string(),
+            "strings, 0x10000000000"
+        );
+        assert_eq!(Capabilities::empty().to_string(), "none");
+    }
+}
@@ -0,0 +1,111 @@
+//! Per-export rate limits.
+//!
+//! Each export with a [`RateLimit`] in the configuration gets a token bucket
+//! that holds up to `burst` calls and refills at `calls` per `per`. A call
+//! that finds it empty fails with [`DllError::RateLimited`] without calling
+//! into Go. Meant for libraries that talk to quota-bound services; exports
+//! without a limit skip the bookkeeping.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::{self, RateLimit};
+use crate::DllError;
+
+#[derive(Debug)]
+struct Bucket {
+    /// As configured. The fields are public, so `calls` or `burst` may be
+    /// zero; both count as one.
+    limit: RateLimit,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.burst.max(1)),
+            refilled: now,
+        }
+    }
+
+    /// Time one token takes to come back
+    fn interval(&self) -> Duration {
+        self.limit.per / self.limit.calls.max(1)
+    }
+
+    /// Takes a token, or returns how long until one is there
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.refilled);
+        let refill = elapsed.as_secs_f64() / self.interval().as_secs_f64();
+        self.tokens = (self.tokens + refill).min(f64::from(self.limit.burst.max(1)));
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(self.interval().mul_f64(1.0 - self.tokens))
+    }
+}
+
+static BUCKETS: Lazy<Mutex<HashMap<&'static str, Bucket>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Fails with [`DllError::RateLimited`] if `export` is over its limit
+pub(crate) fn admit(export: &'static str) -> Result<(), DllError> {
+    let Some(limit) = config::rate_limit(export) else {
+        return Ok(());
+    };
+    let now = Instant::now();
+    let mut buckets = BUCKETS.lock();
+    let bucket = buckets
+        .entry(export)
+        .or_insert_with(|| Bucket::new(limit, now));
+    // A new configuration starts from a full bucket.
+    if bucket.limit != limit {
+        *bucket = Bucket::new(limit, now);
+    }
+    bucket
+        .take(now)
+        .map_err(|retry_after| DllError::RateLimited {
+            export: export.to_string(),
+            retry_after,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills() {
+        let limit = RateLimit::new(2, Duration::from_secs(1)).with_burst(3);
+        let start = Instant::now();
+        let mut bucket = Bucket::new(limit, start);
+
+        for _ in 0..3 {
+            assert!(bucket.take(start).is_ok());
+        }
+        assert_eq!(bucket.take(start), Err(Duration::from_millis(500)));
+
+        let later = start + Duration::from_millis(750);
+        assert!(bucket.take(later).is_ok());
+        assert_eq!(bucket.take(later), Err(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn test_zero_fields_count_as_one() {
+        let limit = RateLimit {
+            calls: 0,
+            per: Duration::from_secs(1),
+            burst: 0,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket::new(limit, start);
+        assert!(bucket.take(start).is_ok());
+        assert_eq!(bucket.take(start), Err(Duration::from_secs(1)));
+    }
+}
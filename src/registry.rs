@@ -0,0 +1,350 @@
+//! Calls into exports by name, checked against signatures the library
+//! publishes.
+//!
+//! The Go side describes its scalar exports through `DescribeAPI`. A
+//! [`FunctionRegistry`] loads that description once and then calls any listed
+//! export with Rust argument and return types, which are compared with the
+//! description before the call. New Go functions become usable as soon as
+//! they are listed, without regenerating bindings or adding wrappers.
+
+use log::debug;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::c_void;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::library::Library;
+use crate::types::{GoInt, GoStr};
+use crate::{call_export, ensure_initialized, last_error, poison, DllError};
+
+/// A scalar type in an export's signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    /// Go `int`, pointer-sized
+    Int,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Float32,
+    Float64,
+    Bool,
+    /// No value; only valid as a result
+    Void,
+}
+
+impl ValueKind {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "int" => ValueKind::Int,
+            "int32" => ValueKind::Int32,
+            "int64" => ValueKind::Int64,
+            "uint32" => ValueKind::Uint32,
+            "uint64" => ValueKind::Uint64,
+            "float32" => ValueKind::Float32,
+            "float64" => ValueKind::Float64,
+            "bool" => ValueKind::Bool,
+            "void" => ValueKind::Void,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueKind::Int => "int",
+            ValueKind::Int32 => "int32",
+            ValueKind::Int64 => "int64",
+            ValueKind::Uint32 => "uint32",
+            ValueKind::Uint64 => "uint64",
+            ValueKind::Float32 => "float32",
+            ValueKind::Float64 => "float64",
+            ValueKind::Bool => "bool",
+            ValueKind::Void => "void",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parameter and result types of an export
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub params: Vec<ValueKind>,
+    pub result: ValueKind,
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(")?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", param)?;
+        }
+        write!(f, ") -> {}", self.result)
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+    pub trait SealedArgs {}
+}
+
+/// A Rust type that can cross the boundary as a scalar of some [`ValueKind`]
+pub trait FfiValue: sealed::Sealed + Copy {
+    /// The kind this type is reported as in mismatch errors
+    const KIND: ValueKind;
+
+    /// Whether a value declared as `kind` has this type's ABI
+    fn matches(kind: ValueKind) -> bool {
+        kind == Self::KIND
+    }
+}
+
+macro_rules! impl_ffi_value {
+    ($($ty:ty => $kind:ident),* $(,)?) => {
+        $(
+            impl sealed::Sealed for $ty {}
+            impl FfiValue for $ty {
+                const KIND: ValueKind = ValueKind::$kind;
+            }
+        )*
+    };
+}
+
+impl_ffi_value!(
+    i32 => Int32,
+    u32 => Uint32,
+    u64 => Uint64,
+    f32 => Float32,
+    f64 => Float64,
+    bool => Bool,
+    GoInt => Int,
+    () => Void,
+);
+
+impl sealed::Sealed for i64 {}
+impl FfiValue for i64 {
+    const KIND: ValueKind = ValueKind::Int64;
+
+    fn matches(kind: ValueKind) -> bool {
+        kind == ValueKind::Int64 || (kind == ValueKind::Int && cfg!(target_pointer_width = "64"))
+    }
+}
+
+/// A tuple of [`FfiValue`]s passed as an export's arguments
+pub trait FfiArgs: sealed::SealedArgs {
+    fn kinds() -> Vec<ValueKind>;
+
+    /// Whether the tuple has the ABI of `params`
+    fn matches(params: &[ValueKind]) -> bool;
+
+    /// Calls `f` with the tuple's elements as arguments.
+    ///
+    /// # Safety
+    ///
+    /// `f` must point to an `extern "C"` function taking exactly these
+    /// arguments and returning `R`.
+    unsafe fn invoke<R: FfiValue>(self, f: *const c_void) -> R;
+}
+
+macro_rules! impl_ffi_args {
+    ($($arg:ident),*) => {
+        impl<$($arg: FfiValue),*> sealed::SealedArgs for ($($arg,)*) {}
+
+        impl<$($arg: FfiValue),*> FfiArgs for ($($arg,)*) {
+            fn kinds() -> Vec<ValueKind> {
+                vec![$($arg::KIND),*]
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn matches(params: &[ValueKind]) -> bool {
+                let mut params = params.iter();
+                $(
+                    if !params.next().is_some_and(|kind| $arg::matches(*kind)) {
+                        return false;
+                    }
+                )*
+                params.next().is_none()
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn invoke<R: FfiValue>(self, f: *const c_void) -> R {
+                let ($($arg,)*) = self;
+                let f: unsafe extern "C" fn($($arg),*) -> R = std::mem::transmute_copy(&f);
+                f($($arg),*)
+            }
+        }
+    };
+}
+
+impl_ffi_args!();
+impl_ffi_args!(A);
+impl_ffi_args!(A, B);
+impl_ffi_args!(A, B, C);
+impl_ffi_args!(A, B, C, D);
+impl_ffi_args!(A, B, C, D, E);
+impl_ffi_args!(A, B, C, D, E, F);
+
+/// Export names for usage tracking, which keys on `&'static str`. The set
+/// is bounded by the names libraries describe.
+static NAMES: Lazy<Mutex<BTreeSet<&'static str>>> = Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+fn intern(name: &str) -> &'static str {
+    let mut names = NAMES.lock();
+    if let Some(name) = names.get(name) {
+        return name;
+    }
+    let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.insert(name);
+    name
+}
+
+/// Exports of one loaded library that can be called by name
+pub struct FunctionRegistry {
+    library: Arc<Library>,
+    functions: BTreeMap<&'static str, Signature>,
+}
+
+impl FunctionRegistry {
+    /// Reads the signatures the active library describes.
+    ///
+    /// Entries with types the registry doesn't support are skipped. The
+    /// registry keeps using this copy of the library even if
+    /// [`reload_dll`](crate::reload_dll) swaps in another one.
+    pub fn load() -> Result<Self, DllError> {
+        let library = ensure_initialized()?;
+        let describe = library.symbol(&library.symbols.describe_api)?;
+        let raw = call_export("DescribeAPI", || unsafe { describe() }).map_err(poison)?;
+        let description = unsafe { GoStr::from_raw(library.clone(), "DescribeAPI", raw) };
+        last_error::check(&library, "DescribeAPI").map_err(poison)?;
+        let functions = parse_description(&description?)?;
+        Ok(Self { library, functions })
+    }
+
+    /// The callable exports and their signatures
+    pub fn functions(&self) -> impl Iterator<Item = (&str, &Signature)> {
+        self.functions.iter().map(|(name, sig)| (*name, sig))
+    }
+
+    pub fn signature(&self, name: &str) -> Option<&Signature> {
+        self.functions.get(name)
+    }
+
+    /// Calls the export `name` with `args`, after checking that `A` and `R`
+    /// match its described signature.
+    ///
+    /// ```ignore
+    /// let registry = FunctionRegistry::load()?;
+    /// let sum: i64 = registry.call("AddNumbers", (2i64, 40i64))?;
+    /// ```
+    pub fn call<A: FfiArgs, R: FfiValue>(&self, name: &str, args: A) -> Result<R, DllError> {
+        let Some((&export, signature)) = self.functions.get_key_value(name) else {
+            return Err(DllError::MissingSymbol {
+                name: name.to_string(),
+                dll_version: None,
+            });
+        };
+        if !A::matches(&signature.params) || !R::matches(signature.result) {
+            return Err(DllError::SignatureMismatch {
+                export: export.to_string(),
+                expected: signature.to_string(),
+                found: Signature {
+                    params: A::kinds(),
+                    result: R::KIND,
+                }
+                .to_string(),
+            });
+        }
+
+        let f = self
+            .library
+            .raw_symbol(export)
+            .ok_or_else(|| DllError::MissingSymbol {
+                name: export.to_string(),
+                dll_version: None,
+            })?;
+        let result = call_export(export, || unsafe { args.invoke::<R>(f) }).map_err(poison)?;
+        last_error::check(&self.library, export).map_err(poison)?;
+        Ok(result)
+    }
+}
+
+fn parse_description(description: &str) -> Result<BTreeMap<&'static str, Signature>, DllError> {
+    let invalid = |e: &str| DllError::Codec {
+        export: "DescribeAPI".to_string(),
+        source: e.to_string().into(),
+    };
+    let value: Value = serde_json::from_str(description).map_err(|e| DllError::Codec {
+        export: "DescribeAPI".to_string(),
+        source: e.into(),
+    })?;
+    let entries = value["functions"]
+        .as_array()
+        .ok_or_else(|| invalid("missing functions list"))?;
+
+    let mut functions = BTreeMap::new();
+    for entry in entries {
+        let name = entry["name"]
+            .as_str()
+            .ok_or_else(|| invalid("function without a name"))?;
+        let params: Option<Vec<ValueKind>> = entry["params"]
+            .as_array()
+            .map(|params| {
+                params
+                    .iter()
+                    .map(|p| p.as_str().and_then(ValueKind::parse))
+                    .collect()
+            })
+            .unwrap_or(Some(Vec::new()));
+        let result = entry["result"].as_str().and_then(ValueKind::parse);
+        match (params, result) {
+            (Some(params), Some(result)) if !params.contains(&ValueKind::Void) => {
+                functions.insert(intern(name), Signature { params, result });
+            }
+            _ => debug!("Skipping {} with an unsupported signature: {}", name, entry),
+        }
+    }
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_description() {
+        let functions = parse_description(
+            r#"{"functions": [
+                {"name": "AddNumbers", "params": ["int", "int"], "result": "int"},
+                {"name": "GoFunction", "params": [], "result": "void"},
+                {"name": "TakesPointer", "params": ["*char"], "result": "void"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions["AddNumbers"].to_string(), "(int, int) -> int");
+        assert!(!functions.contains_key("TakesPointer"));
+    }
+
+    #[test]
+    fn test_signature_matching() {
+        let params = [ValueKind::Int, ValueKind::Int];
+        assert!(<(GoInt, GoInt)>::matches(&params));
+        assert!(!<(i32, i32)>::matches(&params));
+        assert!(!<(GoInt,)>::matches(&params));
+        assert!(<() as FfiArgs>::matches(&[]));
+        assert!(<() as FfiValue>::matches(ValueKind::Void));
+        assert!(<(f32, bool)>::matches(&[
+            ValueKind::Float32,
+            ValueKind::Bool
+        ]));
+        #[cfg(target_pointer_width = "64")]
+        assert!(<(i64, i64)>::matches(&params));
+    }
+}
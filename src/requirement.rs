@@ -0,0 +1,136 @@
+//! Which library versions [`initialize`](crate::initialize) accepts.
+//!
+//! A plain [`Version`] accepts any semver-compatible library, as `^version`
+//! would, so a 0.1.1 bugfix build satisfies a request for 0.1.0. With
+//! [`FfiConfig::with_strict_version`](crate::FfiConfig::with_strict_version)
+//! it has to match exactly instead. A [`VersionReq`] is always matched as
+//! written.
+
+use semver::{Comparator, Op, Version, VersionReq};
+use std::fmt;
+
+use crate::DllError;
+
+/// Library versions to accept; converts from [`Version`] and [`VersionReq`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionRequirement {
+    /// Compatible with this version, or exactly it in strict mode
+    Version(Version),
+    /// Matching this requirement
+    Req(VersionReq),
+}
+
+impl VersionRequirement {
+    /// The requirement a version stands for outside strict mode
+    fn caret(version: &Version) -> VersionReq {
+        VersionReq {
+            comparators: vec![Comparator {
+                op: Op::Caret,
+                major: version.major,
+                minor: Some(version.minor),
+                patch: Some(version.patch),
+                pre: version.pre.clone(),
+            }],
+        }
+    }
+
+    /// Fails unless `found` is acceptable
+    pub(crate) fn check(&self, found: &Version, strict: bool) -> Result<(), DllError> {
+        let requirement = match self {
+            VersionRequirement::Version(expected) if strict => {
+                if expected == found {
+                    return Ok(());
+                }
+                return Err(DllError::VersionMismatch {
+                    expected: expected.clone(),
+                    found: found.clone(),
+                });
+            }
+            VersionRequirement::Version(version) => Self::caret(version),
+            VersionRequirement::Req(requirement) => requirement.clone(),
+        };
+        if requirement.matches(found) {
+            Ok(())
+        } else {
+            Err(DllError::UnsupportedVersion {
+                requirement,
+                found: found.clone(),
+            })
+        }
+    }
+
+    /// The lowest accepted version and the first version above it that is
+    /// rejected again; `None` where that isn't a single version, as for a
+    /// [`VersionReq`]
+    pub(crate) fn bounds(&self, strict: bool) -> (Option<Version>, Option<Version>) {
+        let VersionRequirement::Version(version) = self else {
+            return (None, None);
+        };
+        let above = if strict || (version.major == 0 && version.minor == 0) {
+            Version::new(version.major, version.minor, version.patch + 1)
+        } else if version.major == 0 {
+            Version::new(0, version.minor + 1, 0)
+        } else {
+            Version::new(version.major + 1, 0, 0)
+        };
+        (Some(version.clone()), Some(above))
+    }
+
+    /// The version to report when there is no library to ask, as with
+    /// static linking
+    #[cfg(feature = "static")]
+    pub(crate) fn assumed(&self) -> Version {
+        match self {
+            VersionRequirement::Version(version) => version.clone(),
+            VersionRequirement::Req(_) => crate::DEFAULT_VERSION,
+        }
+    }
+}
+
+impl From<Version> for VersionRequirement {
+    fn from(version: Version) -> Self {
+        VersionRequirement::Version(version)
+    }
+}
+
+impl From<VersionReq> for VersionRequirement {
+    fn from(requirement: VersionReq) -> Self {
+        VersionRequirement::Req(requirement)
+    }
+}
+
+impl fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionRequirement::Version(version) => write!(f, "{}", version),
+            VersionRequirement::Req(requirement) => write!(f, "{}", requirement),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_accepts_compatible_builds() {
+        let required = VersionRequirement::from(Version::new(0, 1, 0));
+        assert!(required.check(&Version::new(0, 1, 1), false).is_ok());
+        assert!(matches!(
+            required.check(&Version::new(0, 2, 0), false),
+            Err(DllError::UnsupportedVersion { requirement, .. }) if requirement.to_string() == "^0.1.0"
+        ));
+        assert!(matches!(
+            required.check(&Version::new(0, 1, 1), true),
+            Err(DllError::VersionMismatch { .. })
+        ));
+        assert!(required.check(&Version::new(0, 1, 0), true).is_ok());
+    }
+
+    #[test]
+    fn test_req_ignores_strict() {
+        let required = VersionRequirement::from(VersionReq::parse(">=0.1, <0.3").unwrap());
+        assert!(required.check(&Version::new(0, 2, 5), true).is_ok());
+        assert!(required.check(&Version::new(0, 3, 0), false).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! Retries with exponential backoff and jitter.
+//!
+//! Loading retries [`DllError::LoadError`] under
+//! [`FfiConfig::with_load_retry`](crate::FfiConfig::with_load_retry), since
+//! an antivirus scan or an installer that is still copying the file makes
+//! the loader fail for a moment. Calls are only retried when wrapped in
+//! [`with_retry`], and only for errors that are
+//! [`retryable`](DllError::is_retryable). Release downloads retry network
+//! failures the same way.
+
+use log::warn;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::config::RetryPolicy;
+use crate::DllError;
+
+/// Pseudo-random bits for jitter; no need for a real generator here
+fn random() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    hasher.finish()
+}
+
+/// Wait before retry number `retry`, counting from 1.
+///
+/// Doubles from `initial_backoff` up to `max_backoff`; with jitter the wait
+/// is a random point in the upper half of that.
+fn backoff(policy: &RetryPolicy, retry: u32, random: u64) -> Duration {
+    let factor = 1u32
+        .checked_shl(retry.saturating_sub(1))
+        .unwrap_or(u32::MAX);
+    let base = policy
+        .initial_backoff
+        .saturating_mul(factor)
+        .min(policy.max_backoff);
+    if !policy.jitter {
+        return base;
+    }
+    let half = base / 2;
+    let spread = half.as_nanos().min(u64::MAX as u128) as u64;
+    half + Duration::from_nanos(random % (spread + 1))
+}
+
+/// Calls `f` until it succeeds, fails with an error `transient` rejects, or
+/// the policy runs out of attempts
+pub(crate) fn retry<T, E: fmt::Display>(
+    policy: &RetryPolicy,
+    what: &str,
+    transient: impl Fn(&E) -> bool,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) if attempt < policy.max_attempts && transient(&e) => {
+                let wait = backoff(policy, attempt, random());
+                warn!(
+                    "{} failed (attempt {} of {}): {}; retrying in {:?}",
+                    what, attempt, policy.max_attempts, e, wait
+                );
+                thread::sleep(wait);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether a load failure may go away on its own
+#[cfg_attr(feature = "static", allow(dead_code))]
+pub(crate) fn is_transient_load_error(error: &DllError) -> bool {
+    matches!(error, DllError::LoadError(_))
+}
+
+/// Calls `f` again under `policy` while it fails with a
+/// [retryable](DllError::is_retryable) error.
+///
+/// ```ignore
+/// let sum = with_retry(&RetryPolicy::default(), || add_numbers(2, 3))?;
+/// ```
+pub fn with_retry<T, F>(policy: &RetryPolicy, f: F) -> Result<T, DllError>
+where
+    F: FnMut() -> Result<T, DllError>,
+{
+    retry(policy, "Call", DllError::is_retryable, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::new(10)
+            .with_initial_backoff(Duration::from_millis(10))
+            .with_max_backoff(Duration::from_millis(50))
+            .with_jitter(false);
+        let waits: Vec<_> = (1..=5).map(|retry| backoff(&policy, retry, 0)).collect();
+        assert_eq!(
+            waits,
+            [10, 20, 40, 50, 50].map(Duration::from_millis).to_vec()
+        );
+        assert_eq!(backoff(&policy, 100, 0), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_jitter_stays_in_upper_half() {
+        let policy = RetryPolicy::new(3).with_initial_backoff(Duration::from_millis(100));
+        for random in [0, 1, 49_999_999, 50_000_000, u64::MAX] {
+            let wait = backoff(&policy, 1, random);
+            assert!(wait >= Duration::from_millis(50) && wait <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn test_retry_stops_on_permanent_errors() {
+        let policy = RetryPolicy::new(5).with_initial_backoff(Duration::ZERO);
+        let mut calls = 0;
+        let result: Result<(), _> = retry(&policy, "Load", is_transient_load_error, || {
+            calls += 1;
+            Err(DllError::NotFound)
+        });
+        assert!(matches!(result, Err(DllError::NotFound)));
+        assert_eq!(calls, 1);
+
+        let mut calls = 0;
+        let result = retry(&policy, "Load", is_transient_load_error, || {
+            calls += 1;
+            match calls {
+                1 | 2 => Err(DllError::LoadError("file is locked".to_string())),
+                _ => Ok(calls),
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+    }
+}
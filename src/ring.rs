@@ -0,0 +1,354 @@
+//! Shared-memory ring buffers for moving many small messages.
+//!
+//! Each ring is a single-producer single-consumer queue in one allocation:
+//! a header with the byte counters, followed by a power-of-two data area.
+//! Messages are a little-endian `u32` length followed by the payload. The
+//! Go side attaches to a pair of rings once, during the handshake, and from
+//! then on both sides exchange messages through the counters alone, without
+//! an FFI call per message.
+//!
+//! Rust owns the memory. The Go side marks its end of the inbound ring
+//! detached as its very last access, and the memory is only freed once it
+//! did, even if both Rust halves are dropped first.
+
+use std::alloc::{self, Layout};
+use std::hint;
+use std::mem;
+use std::os::raw::c_longlong;
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use log::warn;
+
+use crate::library::Library;
+use crate::{call_export, ensure_initialized, last_error, poison, DllError};
+
+/// Smallest data area; anything smaller can't hold a useful message
+pub const MIN_RING_CAPACITY: usize = 64;
+
+/// Mirrors `ringHeader` on the Go side
+#[repr(C, align(64))]
+struct RingHeader {
+    head: AtomicU64,
+    _pad0: [u8; 56],
+    tail: AtomicU64,
+    _pad1: [u8; 56],
+    capacity: u64,
+    /// The writer sends nothing more
+    closed: AtomicU32,
+    /// The reader is gone
+    detached: AtomicU32,
+    _pad2: [u8; 48],
+}
+
+const HEADER_SIZE: usize = mem::size_of::<RingHeader>();
+const _: () = assert!(HEADER_SIZE == 192);
+
+/// Length prefix of every message
+const PREFIX: usize = mem::size_of::<u32>();
+
+struct Ring {
+    header: NonNull<RingHeader>,
+    capacity: usize,
+}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        debug_assert!(capacity.is_power_of_two());
+        let layout = Self::layout(capacity);
+        let header = unsafe { alloc::alloc_zeroed(layout) } as *mut RingHeader;
+        let Some(header) = NonNull::new(header) else {
+            alloc::handle_alloc_error(layout);
+        };
+        unsafe { ptr::addr_of_mut!((*header.as_ptr()).capacity).write(capacity as u64) };
+        Self { header, capacity }
+    }
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(HEADER_SIZE + capacity, mem::align_of::<RingHeader>())
+            .expect("ring capacity is bounded by the caller")
+    }
+
+    fn header(&self) -> &RingHeader {
+        unsafe { self.header.as_ref() }
+    }
+
+    fn data(&self) -> *mut u8 {
+        unsafe { (self.header.as_ptr() as *mut u8).add(HEADER_SIZE) }
+    }
+
+    fn copy_in(&self, pos: u64, bytes: &[u8]) {
+        let start = (pos as usize) & (self.capacity - 1);
+        let first = bytes.len().min(self.capacity - start);
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.data().add(start), first);
+            ptr::copy_nonoverlapping(bytes[first..].as_ptr(), self.data(), bytes.len() - first);
+        }
+    }
+
+    fn copy_out(&self, pos: u64, bytes: &mut [u8]) {
+        let start = (pos as usize) & (self.capacity - 1);
+        let first = bytes.len().min(self.capacity - start);
+        unsafe {
+            ptr::copy_nonoverlapping(self.data().add(start), bytes.as_mut_ptr(), first);
+            ptr::copy_nonoverlapping(
+                self.data(),
+                bytes[first..].as_mut_ptr(),
+                bytes.len() - first,
+            );
+        }
+    }
+
+    /// Appends `msg` if there is room; only the producer may call this
+    fn try_write(&self, msg: &[u8]) -> bool {
+        let header = self.header();
+        let head = header.head.load(Ordering::Relaxed);
+        let need = (PREFIX + msg.len()) as u64;
+        let used = head - header.tail.load(Ordering::Acquire);
+        if self.capacity as u64 - used < need {
+            return false;
+        }
+        self.copy_in(head, &(msg.len() as u32).to_le_bytes());
+        self.copy_in(head + PREFIX as u64, msg);
+        header.head.store(head + need, Ordering::Release);
+        true
+    }
+
+    /// Takes the next message, if one is ready; only the consumer may call this
+    fn try_read(&self) -> Option<Vec<u8>> {
+        let header = self.header();
+        let tail = header.tail.load(Ordering::Relaxed);
+        if header.head.load(Ordering::Acquire) == tail {
+            return None;
+        }
+        let mut prefix = [0; PREFIX];
+        self.copy_out(tail, &mut prefix);
+        let mut msg = vec![0; u32::from_le_bytes(prefix) as usize];
+        self.copy_out(tail + PREFIX as u64, &mut msg);
+        header
+            .tail
+            .store(tail + (PREFIX + msg.len()) as u64, Ordering::Release);
+        Some(msg)
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.header.as_ptr() as *mut u8, Self::layout(self.capacity)) };
+    }
+}
+
+/// Waits a little longer on every call
+fn backoff(spins: &mut u32) {
+    *spins += 1;
+    if *spins < 64 {
+        hint::spin_loop();
+    } else {
+        thread::yield_now();
+    }
+}
+
+/// The rings both halves share with one Go worker
+struct Channel {
+    inbound: Ring,
+    outbound: Ring,
+    /// Keeps the worker's code loaded until it let go of the rings
+    _library: Arc<Library>,
+}
+
+// The rings are only accessed through their atomics and the SPSC protocol.
+unsafe impl Send for Channel {}
+unsafe impl Sync for Channel {}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        // The halves already signalled the worker; wait until it lets go.
+        let mut spins = 0;
+        while self.inbound.header().detached.load(Ordering::Acquire) == 0 {
+            backoff(&mut spins);
+        }
+    }
+}
+
+/// Sends messages to the Go side; closes the ring on drop
+pub struct RingProducer {
+    channel: Arc<Channel>,
+}
+
+impl RingProducer {
+    /// Largest message that fits
+    pub fn max_message_len(&self) -> usize {
+        self.channel.inbound.capacity - PREFIX
+    }
+
+    /// Sends `msg` if there is room for it right now.
+    ///
+    /// Returns `Ok(false)` if the ring is full.
+    pub fn try_push(&mut self, msg: &[u8]) -> Result<bool, DllError> {
+        let ring = &self.channel.inbound;
+        if msg.len() > self.max_message_len() {
+            return Err(DllError::PayloadTooLarge {
+                export: "StartRingEcho".to_string(),
+                size: msg.len(),
+                limit: self.max_message_len(),
+            });
+        }
+        if ring.header().detached.load(Ordering::Acquire) != 0 {
+            return Err(DllError::CallFailed {
+                export: "StartRingEcho".to_string(),
+                message: "the Go side stopped reading".to_string(),
+            });
+        }
+        Ok(ring.try_write(msg))
+    }
+
+    /// Sends `msg`, waiting for room
+    pub fn push(&mut self, msg: &[u8]) -> Result<(), DllError> {
+        let mut spins = 0;
+        while !self.try_push(msg)? {
+            backoff(&mut spins);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RingProducer {
+    fn drop(&mut self) {
+        self.channel
+            .inbound
+            .header()
+            .closed
+            .store(1, Ordering::Release);
+    }
+}
+
+/// Receives messages from the Go side; detaches from the ring on drop
+pub struct RingConsumer {
+    channel: Arc<Channel>,
+}
+
+impl RingConsumer {
+    /// Takes the next message if one is ready
+    pub fn try_pop(&mut self) -> Option<Vec<u8>> {
+        self.channel.outbound.try_read()
+    }
+
+    /// Waits for the next message; `None` once the Go side closed the ring
+    /// and everything was read
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let ring = &self.channel.outbound;
+        let mut spins = 0;
+        loop {
+            if let Some(msg) = ring.try_read() {
+                return Some(msg);
+            }
+            if ring.header().closed.load(Ordering::Acquire) != 0 {
+                // Messages written before closing are visible by now.
+                return ring.try_read();
+            }
+            backoff(&mut spins);
+        }
+    }
+}
+
+impl Iterator for RingConsumer {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        self.pop()
+    }
+}
+
+impl Drop for RingConsumer {
+    fn drop(&mut self) {
+        self.channel
+            .outbound
+            .header()
+            .detached
+            .store(1, Ordering::Release);
+    }
+}
+
+/// Sets up a pair of rings of `capacity` bytes each, rounded up to a power
+/// of two, and has a Go worker echo every message back upper-cased.
+///
+/// ```ignore
+/// let (mut tx, rx) = ring_echo(1 << 20)?;
+/// tx.push(b"hello")?;
+/// drop(tx);
+/// assert_eq!(rx.collect::<Vec<_>>(), [b"HELLO".to_vec()]);
+/// ```
+pub fn ring_echo(capacity: usize) -> Result<(RingProducer, RingConsumer), DllError> {
+    let library = ensure_initialized()?;
+    let start_ring_echo = library.symbol(&library.symbols.start_ring_echo)?;
+    let capacity = capacity
+        .max(MIN_RING_CAPACITY)
+        .checked_next_power_of_two()
+        .filter(|c| *c <= u32::MAX as usize)
+        .ok_or_else(|| DllError::PayloadTooLarge {
+            export: "StartRingEcho".to_string(),
+            size: capacity,
+            limit: 1 << 31,
+        })?;
+
+    let channel = Arc::new(Channel {
+        inbound: Ring::new(capacity),
+        outbound: Ring::new(capacity),
+        _library: library.clone(),
+    });
+    let started = call_export("StartRingEcho", || unsafe {
+        start_ring_echo(
+            channel.inbound.header.as_ptr().cast(),
+            channel.outbound.header.as_ptr().cast(),
+            HEADER_SIZE as c_longlong,
+        )
+    })
+    .map_err(poison)
+    .and_then(|()| last_error::check(&library, "StartRingEcho").map_err(poison));
+    if let Err(e) = started {
+        // No worker attached, so nobody else will let go of the rings.
+        channel
+            .inbound
+            .header()
+            .detached
+            .store(1, Ordering::Release);
+        warn!("Ring handshake failed: {}", e);
+        return Err(e);
+    }
+
+    Ok((
+        RingProducer {
+            channel: channel.clone(),
+        },
+        RingConsumer { channel },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_wrap_around() {
+        let ring = Ring::new(MIN_RING_CAPACITY);
+        let msg = [7u8; 40];
+        for _ in 0..10 {
+            assert!(ring.try_write(&msg));
+            assert!(!ring.try_write(&msg), "a second message must not fit");
+            assert_eq!(ring.try_read().as_deref(), Some(&msg[..]));
+            assert_eq!(ring.try_read(), None);
+        }
+    }
+
+    #[test]
+    fn test_header_layout() {
+        let ring = Ring::new(MIN_RING_CAPACITY);
+        let base = ring.header.as_ptr() as usize;
+        let header = ring.header();
+        assert_eq!(&header.tail as *const _ as usize - base, 64);
+        assert_eq!(&header.capacity as *const _ as usize - base, 128);
+        assert_eq!(&header.detached as *const _ as usize - base, 140);
+    }
+}
@@ -0,0 +1,89 @@
+//! Figures from the Go runtime inside the library.
+//!
+//! Go's heap and goroutines live outside anything the Rust allocator or
+//! process metrics can attribute, so a leak on the Go side would otherwise go
+//! unnoticed. [`go_runtime_stats`] reads them through the `ReadRuntimeStats`
+//! export. With the `runtime-stats` feature a background thread also
+//! publishes them as the `go.heap_bytes`, `go.heap_objects`, `go.goroutines`,
+//! `go.gc_cycles` and `go.gc_pause_ns` gauges every
+//! `FfiConfig::with_runtime_stats_interval`, ten seconds by default, while
+//! the library is initialized.
+
+use std::mem;
+
+use crate::ffi::FfiStruct;
+use crate::library::Library;
+use crate::transfer::{self, TransferKind};
+use crate::{call_export, ensure_initialized, last_error, poison, DllError};
+
+/// Heap, goroutine and garbage collector figures of the Go runtime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GoRuntimeStats {
+    /// Bytes of live and not yet collected heap objects
+    pub heap_bytes: u64,
+    pub heap_objects: u64,
+    pub goroutines: u64,
+    /// Completed garbage collection cycles
+    pub gc_cycles: u64,
+    /// Time the world was stopped for garbage collection, in total
+    pub gc_pause_total_ns: u64,
+}
+
+crate::ffi_struct!(GoRuntimeStats => crate::ffi::RuntimeStats {
+    heap_bytes,
+    heap_objects,
+    goroutines,
+    gc_cycles,
+    gc_pause_total_ns,
+});
+
+/// Reads the Go runtime's figures; stops the Go world briefly, as
+/// `runtime.ReadMemStats` does
+pub fn go_runtime_stats() -> Result<GoRuntimeStats, DllError> {
+    let library = ensure_initialized()?;
+    read(&library)
+}
+
+fn read(library: &Library) -> Result<GoRuntimeStats, DllError> {
+    let read_runtime_stats = library.symbol(&library.symbols.read_runtime_stats)?;
+    let mut repr = GoRuntimeStats::default().to_repr()?;
+    call_export("ReadRuntimeStats", || unsafe {
+        read_runtime_stats(&mut repr)
+    })
+    .map_err(poison)?;
+    transfer::record_out(
+        "ReadRuntimeStats",
+        TransferKind::Scalar,
+        mem::size_of_val(&repr),
+    );
+    last_error::check(library, "ReadRuntimeStats").map_err(poison)?;
+    GoRuntimeStats::from_repr(repr)
+}
+
+/// Starts the thread that publishes the figures; later calls do nothing
+#[cfg(feature = "runtime-stats")]
+pub(crate) fn start_publishing() {
+    use log::{debug, warn};
+    use std::sync::Once;
+    use std::thread;
+
+    static STARTED: Once = Once::new();
+    STARTED.call_once(|| {
+        let spawned = thread::Builder::new()
+            .name("ffi-runtime-stats".to_string())
+            .spawn(|| loop {
+                thread::sleep(crate::config::runtime_stats_interval());
+                // Never loads the library, and skips a poisoned one.
+                let Some(library) = crate::initialized_library() else {
+                    continue;
+                };
+                match read(&library) {
+                    Ok(stats) => crate::metrics::set_go_runtime_stats(&stats),
+                    Err(e) => debug!("Failed to read Go runtime stats: {}", e),
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("Failed to start publishing Go runtime stats: {}", e);
+        }
+    });
+}
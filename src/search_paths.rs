@@ -0,0 +1,163 @@
+use parking_lot::RwLock;
+use std::env;
+use std::path::PathBuf;
+
+/// Environment variable that overrides DLL discovery.
+///
+/// The value may point either at the DLL itself or at a directory containing it.
+pub const DLL_PATH_ENV: &str = "RUST_GO_FFI_DLL_PATH";
+
+/// File name of the Go shared library built for this platform and
+/// architecture.
+///
+/// `go_lib.dll`, `libgo_lib.so` or `libgo_lib.dylib` on x86_64 and suffixed
+/// with the architecture elsewhere, e.g. `libgo_lib_aarch64.so`. Set by the
+/// build script so it always names the artifact that was built alongside the
+/// crate.
+pub const DLL_FILE_NAME: &str = env!("GO_LIB_DLL_NAME");
+
+lazy_static::lazy_static! {
+    static ref SEARCH_PATHS: RwLock<SearchPaths> = RwLock::new(SearchPaths::new());
+}
+
+/// Builder for the directories checked by [`get_dll_path`](crate::get_dll_path)
+///
+/// Prepended directories are checked before the built-in locations, appended
+/// directories after them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchPaths {
+    prepend: Vec<PathBuf>,
+    append: Vec<PathBuf>,
+}
+
+impl SearchPaths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directory that is checked before the built-in locations
+    pub fn prepend(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.prepend.push(dir.into());
+        self
+    }
+
+    /// Adds a directory that is checked after the built-in locations
+    pub fn append(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.append.push(dir.into());
+        self
+    }
+
+    /// Makes this the process-wide search configuration
+    pub fn install(self) {
+        *SEARCH_PATHS.write() = self;
+    }
+
+    pub fn prepended(&self) -> &[PathBuf] {
+        &self.prepend
+    }
+
+    pub fn appended(&self) -> &[PathBuf] {
+        &self.append
+    }
+}
+
+/// Returns the currently installed search configuration
+pub fn search_paths() -> SearchPaths {
+    SEARCH_PATHS.read().clone()
+}
+
+/// Resolves the `RUST_GO_FFI_DLL_PATH` override, if set.
+///
+/// A directory value is joined with the DLL file name.
+pub(crate) fn env_override() -> Option<PathBuf> {
+    let value = env::var_os(DLL_PATH_ENV).filter(|v| !v.is_empty())?;
+    let path = PathBuf::from(value);
+    if path.is_dir() {
+        Some(path.join(DLL_FILE_NAME))
+    } else {
+        Some(path)
+    }
+}
+
+/// Builds the ordered list of candidate DLL locations.
+///
+/// `builtin` holds the locations checked between the prepended and appended
+/// directories.
+pub(crate) fn candidates(builtin: Vec<PathBuf>) -> Vec<PathBuf> {
+    let paths = SEARCH_PATHS.read();
+    paths
+        .prepend
+        .iter()
+        .map(|dir| dir.join(DLL_FILE_NAME))
+        .chain(builtin)
+        .chain(paths.append.iter().map(|dir| dir.join(DLL_FILE_NAME)))
+        .collect()
+}
+
+/// Returns the first candidate that exists on disk
+pub(crate) fn find_existing(candidates: Vec<PathBuf>) -> Option<PathBuf> {
+    candidates.into_iter().find(|p| p.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_order() {
+        let paths = SearchPaths::new()
+            .prepend("first")
+            .append("last")
+            .prepend("second");
+
+        assert_eq!(
+            paths.prepended(),
+            &[PathBuf::from("first"), PathBuf::from("second")]
+        );
+        assert_eq!(paths.appended(), &[PathBuf::from("last")]);
+    }
+
+    #[test]
+    fn test_env_override_file() {
+        temp_env::with_var(DLL_PATH_ENV, Some("custom/path/go_lib.dll"), || {
+            assert_eq!(
+                env_override(),
+                Some(PathBuf::from("custom/path/go_lib.dll"))
+            );
+        });
+    }
+
+    #[test]
+    fn test_env_override_directory() {
+        let dir = env::temp_dir();
+        temp_env::with_var(DLL_PATH_ENV, Some(&dir), || {
+            assert_eq!(env_override(), Some(dir.join(DLL_FILE_NAME)));
+        });
+    }
+
+    #[test]
+    fn test_dll_file_name_matches_platform() {
+        // The `[output] name` of ffi.toml, go_lib by default
+        let name = env!("GO_LIB_NAME");
+        let stem = if cfg!(target_arch = "x86_64") {
+            name.to_string()
+        } else {
+            format!("{}_{}", name, std::env::consts::ARCH)
+        };
+        let expected = if cfg!(windows) {
+            format!("{}.dll", stem)
+        } else if cfg!(target_os = "macos") {
+            format!("lib{}.dylib", stem)
+        } else {
+            format!("lib{}.so", stem)
+        };
+        assert_eq!(DLL_FILE_NAME, expected);
+    }
+
+    #[test]
+    fn test_env_override_unset() {
+        temp_env::with_var_unset(DLL_PATH_ENV, || {
+            assert_eq!(env_override(), None);
+        });
+    }
+}
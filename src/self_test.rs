@@ -0,0 +1,219 @@
+//! End-to-end smoke test of the Go library through the public API.
+//!
+//! Used by `rust_go_ffi verify` and meant for deployment checks: every step
+//! goes through the same safe wrappers an application would call.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    add_numbers, cleanup, count_with_progress, get_version, go_function, initialize,
+    register_callback, verify_dll,
+};
+
+/// Result of a single self-test step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome {
+    Passed,
+    Failed(String),
+    /// The step could not run, e.g. because an earlier step failed
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestStep {
+    pub name: &'static str,
+    pub outcome: StepOutcome,
+    pub duration: Duration,
+}
+
+/// Pass/fail report produced by [`run_self_test`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    /// True when no step failed; skipped steps don't count as failures
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestStep> {
+        self.steps
+            .iter()
+            .filter(|s| matches!(s.outcome, StepOutcome::Failed(_)))
+    }
+}
+
+/// One line per step with its outcome and duration
+pub(crate) fn write_steps(f: &mut fmt::Formatter<'_>, steps: &[SelfTestStep]) -> fmt::Result {
+    for step in steps {
+        let (status, detail) = match &step.outcome {
+            StepOutcome::Passed => ("ok", ""),
+            StepOutcome::Failed(reason) => ("FAILED", reason.as_str()),
+            StepOutcome::Skipped(reason) => ("skipped", reason.as_str()),
+        };
+        writeln!(
+            f,
+            "{:<16} {:<8} {:>8.2?} {}",
+            step.name, status, step.duration, detail
+        )?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_steps(f, &self.steps)
+    }
+}
+
+/// Collects steps, skipping the rest once a step the others depend on fails
+pub(crate) struct Runner {
+    pub steps: Vec<SelfTestStep>,
+    pub blocked: Option<&'static str>,
+}
+
+impl Runner {
+    pub fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            blocked: None,
+        }
+    }
+
+    pub fn step(
+        &mut self,
+        name: &'static str,
+        required: bool,
+        f: impl FnOnce() -> Result<(), String>,
+    ) {
+        if let Some(failed) = self.blocked {
+            self.skip(name, format!("{} failed", failed));
+            return;
+        }
+        let start = Instant::now();
+        let outcome = match f() {
+            Ok(()) => StepOutcome::Passed,
+            Err(reason) => {
+                if required {
+                    self.blocked = Some(name);
+                }
+                StepOutcome::Failed(reason)
+            }
+        };
+        self.steps.push(SelfTestStep {
+            name,
+            outcome,
+            duration: start.elapsed(),
+        });
+    }
+
+    pub fn skip(&mut self, name: &'static str, reason: String) {
+        self.steps.push(SelfTestStep {
+            name,
+            outcome: StepOutcome::Skipped(reason),
+            duration: Duration::ZERO,
+        });
+    }
+}
+
+fn check_add(a: i32, b: i32) -> Result<(), String> {
+    match add_numbers(a, b) {
+        Ok(sum) if sum == a + b => Ok(()),
+        Ok(sum) => Err(format!("{} + {} returned {}", a, b, sum)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn check_callback() -> Result<(), String> {
+    let steps = Arc::new(AtomicUsize::new(0));
+    let counted = steps.clone();
+    let _handle = register_callback("progress", move |_| {
+        counted.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    count_with_progress(3).map_err(|e| e.to_string())?;
+    match steps.load(Ordering::Relaxed) {
+        3 => Ok(()),
+        n => Err(format!("callback ran {} times instead of 3", n)),
+    }
+}
+
+/// Loads the library, runs the version handshake, calls every wrapped export
+/// with known inputs and cleans up again.
+///
+/// Leaves the FFI system uninitialized; the next wrapper call initializes it
+/// again.
+pub fn run_self_test() -> SelfTestReport {
+    let mut runner = Runner::new();
+
+    runner.step("load", true, || verify_dll().map_err(|e| e.to_string()));
+    runner.step("handshake", true, || {
+        let version = get_version().map_err(|e| e.to_string())?;
+        initialize(version).map_err(|e| e.to_string())
+    });
+    runner.step("AddNumbers", false, || {
+        check_add(2, 3)?;
+        check_add(-7, 3)
+    });
+    runner.step("GoFunction", false, || {
+        go_function().map_err(|e| e.to_string())
+    });
+    runner.step("callback", false, check_callback);
+    // Cleanup runs even after a failure so the process is left in a known state.
+    runner.blocked = None;
+    runner.step("cleanup", false, || cleanup().map_err(|e| e.to_string()));
+
+    SelfTestReport {
+        steps: runner.steps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(name: &'static str, outcome: StepOutcome) -> SelfTestStep {
+        SelfTestStep {
+            name,
+            outcome,
+            duration: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_skipped_steps_do_not_fail_report() {
+        let report = SelfTestReport {
+            steps: vec![
+                step("load", StepOutcome::Passed),
+                step("callback", StepOutcome::Skipped("none".to_string())),
+            ],
+        };
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_required_failure_skips_dependents() {
+        let mut runner = Runner {
+            steps: Vec::new(),
+            blocked: None,
+        };
+        runner.step("load", true, || Err("missing".to_string()));
+        runner.step("AddNumbers", false, || Ok(()));
+
+        let report = SelfTestReport {
+            steps: runner.steps,
+        };
+        assert!(!report.passed());
+        assert_eq!(
+            report.steps[1].outcome,
+            StepOutcome::Skipped("load failed".to_string())
+        );
+        assert_eq!(report.failures().count(), 1);
+    }
+}
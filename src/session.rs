@@ -0,0 +1,124 @@
+//! Scoped use of the Go library.
+//!
+//! An [`FfiSession`] initializes the library when opened and keeps it
+//! initialized until the last open session is dropped, which then runs
+//! [`cleanup`](crate::cleanup). While any session is open, auto cleanup
+//! leaves the library alone after [`with_dll`](crate::with_dll) closures,
+//! so a sequence of calls initializes once instead of once per call.
+//! [`FfiBuilder`](crate::FfiBuilder) opens one with more settings.
+
+use log::warn;
+use semver::Version;
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::types::{GoBuffer, GoStr, Point};
+use crate::{CancelToken, DllError, GoStream, VersionRequirement};
+
+static OPEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether any session is open
+pub(crate) fn active() -> bool {
+    OPEN.load(Ordering::Acquire) > 0
+}
+
+/// Keeps the library initialized while alive.
+///
+/// ```ignore
+/// let session = FfiSession::open(DEFAULT_VERSION)?;
+/// let sum = session.add_numbers(2, 3)?;
+/// session.go_function()?;
+/// // Dropping the last session cleans up.
+/// ```
+#[derive(Debug)]
+pub struct FfiSession {
+    _private: (),
+}
+
+impl FfiSession {
+    /// Initializes the library with `version` and opens a session on it
+    pub fn open(version: impl Into<VersionRequirement>) -> Result<Self, DllError> {
+        // Count first, so a session opened concurrently doesn't clean up
+        // underneath this one.
+        OPEN.fetch_add(1, Ordering::AcqRel);
+        if let Err(e) = crate::initialize(version) {
+            OPEN.fetch_sub(1, Ordering::AcqRel);
+            return Err(e);
+        }
+        Ok(Self { _private: () })
+    }
+
+    /// Version of the loaded library
+    pub fn version(&self) -> Result<Version, DllError> {
+        crate::get_version()
+    }
+}
+
+impl Drop for FfiSession {
+    fn drop(&mut self) {
+        if OPEN.fetch_sub(1, Ordering::AcqRel) == 1 {
+            if let Err(e) = crate::cleanup() {
+                warn!("Cleanup after the last FFI session failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Generates session methods that forward to the free functions
+macro_rules! session_methods {
+    ($(
+        $(#[$meta:meta])*
+        fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty;
+    )*) => {
+        impl FfiSession {
+            $(
+                $(#[$meta])*
+                pub fn $name(&self, $($arg: $ty),*) -> Result<$ret, DllError> {
+                    crate::$name($($arg),*)
+                }
+            )*
+        }
+    };
+}
+
+session_methods! {
+    /// See [`add_numbers`](crate::add_numbers)
+    fn add_numbers(a: i32, b: i32) -> i32;
+    /// See [`add_numbers_batch`](crate::add_numbers_batch)
+    fn add_numbers_batch(pairs: &[(i32, i32)]) -> Vec<i32>;
+    /// See [`go_function`](crate::go_function)
+    fn go_function() -> ();
+    /// See [`go_runtime_version`](crate::go_runtime_version)
+    fn go_runtime_version() -> GoStr;
+    /// See [`hypot`](crate::hypot)
+    fn hypot(a: f64, b: f64) -> f64;
+    /// See [`halve_f32`](crate::halve_f32)
+    fn halve_f32(x: f32) -> f32;
+    /// See [`add_u64`](crate::add_u64)
+    fn add_u64(a: u64, b: u64) -> u64;
+    /// See [`add_i128`](crate::add_i128)
+    fn add_i128(a: i128, b: i128) -> i128;
+    /// See [`is_even`](crate::is_even)
+    fn is_even(n: i64) -> bool;
+    /// See [`crc32`](crate::crc32)
+    fn crc32(data: &[u8]) -> u32;
+    /// See [`upper_bytes`](crate::upper_bytes)
+    fn upper_bytes(data: &mut [u8]) -> ();
+    /// See [`decode_base64`](crate::decode_base64)
+    fn decode_base64(data: &[u8]) -> GoBuffer;
+    /// See [`scale_point`](crate::scale_point)
+    fn scale_point(point: Point, factor: i64) -> Point;
+    /// See [`translate_point`](crate::translate_point)
+    fn translate_point(point: &mut Point, dx: i64, dy: i64) -> ();
+    /// See [`count_with_progress`](crate::count_with_progress)
+    fn count_with_progress(n: i64) -> i64;
+    /// See [`start_ticker`](crate::start_ticker)
+    fn start_ticker(count: i64, interval: Duration) -> ();
+    /// See [`sleep_with_cancel`](crate::sleep_with_cancel)
+    fn sleep_with_cancel(duration: Duration, token: &CancelToken) -> ();
+    /// See [`counter_stream`](crate::counter_stream)
+    fn counter_stream(count: i64, chunk_size: usize) -> GoStream;
+    /// See [`call_json`](crate::call_json)
+    fn call_json(function: &str, payload: &Value) -> Value;
+}
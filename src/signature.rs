@@ -0,0 +1,175 @@
+//! Signature checks on the library before it is loaded, for the `signed-dll`
+//! feature.
+//!
+//! With [`FfiConfig::with_require_signed_dll`](crate::FfiConfig::with_require_signed_dll)
+//! every load checks the file it is about to map and fails with
+//! [`DllError::UntrustedDll`] instead of running unsigned native code. Windows
+//! signatures are checked by `WinVerifyTrust`; on every platform a detached
+//! minisign signature next to the library can be checked against a public
+//! key instead.
+
+// A statically linked library is never loaded from a file.
+#![cfg_attr(feature = "static", allow(dead_code))]
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::DllError;
+
+/// How a library has to be signed to be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DllSignature {
+    /// An Authenticode signature `WinVerifyTrust` accepts; never satisfied
+    /// on other platforms
+    Authenticode,
+    /// A minisign signature in `<library>.minisig`, e.g. `go_lib.dll.minisig`,
+    /// made with the secret key of `public_key`, the base64 line of a
+    /// minisign `.pub` file
+    Minisign { public_key: String },
+}
+
+/// Where minisign puts the signature of `path`
+fn minisig_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".minisig");
+    PathBuf::from(name)
+}
+
+fn verify_minisign(contents: &[u8], public_key: &str, signature: &str) -> Result<(), String> {
+    let public_key = minisign_verify::PublicKey::from_base64(public_key.trim())
+        .map_err(|e| format!("Invalid public key: {}", e))?;
+    let signature = minisign_verify::Signature::decode(signature)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+    // Only prehashed signatures, the default since minisign 0.8.
+    public_key
+        .verify(contents, &signature, false)
+        .map_err(|e| e.to_string())
+}
+
+/// Checks the file at `path` against `signature`.
+///
+/// `source` is the library `path` was copied from, if it is a shadow copy;
+/// a detached signature is looked up next to it.
+pub(crate) fn verify(path: &Path, source: &Path, signature: &DllSignature) -> Result<(), DllError> {
+    let untrusted = |reason: String| DllError::UntrustedDll {
+        path: source.to_path_buf(),
+        reason,
+    };
+    match signature {
+        #[cfg(windows)]
+        DllSignature::Authenticode => authenticode::verify(path).map_err(untrusted),
+        #[cfg(not(windows))]
+        DllSignature::Authenticode => Err(untrusted(
+            "Authenticode signatures can only be checked on Windows".to_string(),
+        )),
+        DllSignature::Minisign { public_key } => {
+            let signature_path = minisig_path(source);
+            let signature = fs::read_to_string(&signature_path)
+                .map_err(|e| untrusted(format!("{}: {}", signature_path.display(), e)))?;
+            let contents = fs::read(path)
+                .map_err(|e| DllError::LoadError(format!("{}: {}", path.display(), e)))?;
+            verify_minisign(&contents, public_key, &signature).map_err(untrusted)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod authenticode {
+    use std::ffi::c_void;
+    use std::iter;
+    use std::mem;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr;
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::softpub::WINTRUST_ACTION_GENERIC_VERIFY_V2;
+    use winapi::um::wintrust::{
+        WinVerifyTrust, WINTRUST_DATA, WINTRUST_FILE_INFO, WTD_CHOICE_FILE, WTD_REVOKE_NONE,
+        WTD_STATEACTION_CLOSE, WTD_STATEACTION_VERIFY, WTD_UI_NONE,
+    };
+
+    /// Asks `WinVerifyTrust` whether the file's embedded signature is valid
+    /// and chains to a trusted root
+    pub fn verify(path: &Path) -> Result<(), String> {
+        let wide_path: Vec<u16> = path
+            .as_os_str()
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        unsafe {
+            let mut file_info: WINTRUST_FILE_INFO = mem::zeroed();
+            file_info.cbStruct = mem::size_of::<WINTRUST_FILE_INFO>() as u32;
+            file_info.pcwszFilePath = wide_path.as_ptr();
+
+            let mut data: WINTRUST_DATA = mem::zeroed();
+            data.cbStruct = mem::size_of::<WINTRUST_DATA>() as u32;
+            data.dwUIChoice = WTD_UI_NONE;
+            data.fdwRevocationChecks = WTD_REVOKE_NONE;
+            data.dwUnionChoice = WTD_CHOICE_FILE;
+            *data.u.pFile_mut() = &mut file_info;
+            data.dwStateAction = WTD_STATEACTION_VERIFY;
+
+            let mut action = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+            let status = WinVerifyTrust(
+                INVALID_HANDLE_VALUE as _,
+                &mut action,
+                &mut data as *mut WINTRUST_DATA as *mut c_void,
+            );
+            // Releases the state the verification allocated.
+            data.dwStateAction = WTD_STATEACTION_CLOSE;
+            WinVerifyTrust(
+                ptr::null_mut(),
+                &mut action,
+                &mut data as *mut WINTRUST_DATA as *mut c_void,
+            );
+
+            match status {
+                0 => Ok(()),
+                status => Err(format!(
+                    "WinVerifyTrust rejected the signature ({:#010x})",
+                    status as u32
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From minisign-verify's own test suite; signs the bytes `test`.
+    const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+
+    #[test]
+    fn test_minisig_path() {
+        assert_eq!(
+            minisig_path(Path::new("go_lib/go_lib.dll")),
+            Path::new("go_lib/go_lib.dll.minisig")
+        );
+    }
+
+    #[test]
+    fn test_verify_minisign() {
+        assert!(verify_minisign(b"test", PUBLIC_KEY, SIGNATURE).is_ok());
+        assert!(verify_minisign(b"tampered", PUBLIC_KEY, SIGNATURE).is_err());
+        assert!(verify_minisign(b"test", "not a key", SIGNATURE).is_err());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_authenticode_needs_windows() {
+        let error = verify(
+            Path::new("go_lib.so"),
+            Path::new("go_lib.so"),
+            &DllSignature::Authenticode,
+        )
+        .unwrap_err();
+        assert_eq!(error.code(), 1007);
+    }
+}
@@ -0,0 +1,554 @@
+//! Where the metrics of the `metrics-core` feature are sent.
+//!
+//! The FFI layer reports every metric to one [`MetricsSink`], chosen with
+//! [`FfiConfig::with_metrics`](crate::FfiConfig::with_metrics) and installed
+//! by [`initialize`](crate::initialize). Nothing listens on a port unless
+//! the configuration asks for it.
+
+use log::warn;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Once;
+use std::time::{Duration, SystemTime};
+
+use crate::DllError;
+
+/// Labels attached to a metric, such as `("export", "AddNumbers")`
+pub type MetricLabels<'a> = &'a [(&'static str, &'static str)];
+
+/// Receives the metrics the FFI layer records.
+///
+/// ```ignore
+/// struct Log;
+///
+/// impl MetricsSink for Log {
+///     fn counter(&self, name: &'static str, _labels: MetricLabels, value: u64) {
+///         debug!("{} += {}", name, value);
+///     }
+///     fn gauge(&self, _name: &'static str, _labels: MetricLabels, _value: f64) {}
+///     fn histogram(&self, _name: &'static str, _labels: MetricLabels, _value: f64) {}
+/// }
+///
+/// let config = FfiConfig::new().with_metrics(MetricsExporter::Custom(Arc::new(Log)));
+/// ```
+pub trait MetricsSink: Send + Sync {
+    /// Adds `value` to a counter
+    fn counter(&self, name: &'static str, labels: MetricLabels, value: u64);
+    /// Sets a gauge to `value`
+    fn gauge(&self, name: &'static str, labels: MetricLabels, value: f64);
+    /// Records one sample; latencies are in milliseconds
+    fn histogram(&self, name: &'static str, labels: MetricLabels, value: f64);
+
+    /// Records a call to `export` as a trace span; most sinks only take
+    /// metrics
+    fn span(
+        &self,
+        _export: &'static str,
+        _start: SystemTime,
+        _duration: Duration,
+        _error: Option<&DllError>,
+    ) {
+    }
+
+    /// Sends anything buffered; runs when the FFI system shuts down
+    fn flush(&self) {}
+}
+
+/// Every metric the FFI layer records, with its unit and description
+const METRICS: &[(&str, Kind, metrics::Unit, &str)] = &[
+    (
+        "ffi.calls",
+        Kind::Counter,
+        metrics::Unit::Count,
+        "FFI function calls, labelled by export and status",
+    ),
+    (
+        "ffi.errors",
+        Kind::Counter,
+        metrics::Unit::Count,
+        "FFI errors, labelled by kind (load or call) and export",
+    ),
+    (
+        "ffi.latency",
+        Kind::Histogram,
+        metrics::Unit::Milliseconds,
+        "Latency of FFI calls, labelled by export",
+    ),
+    (
+        "ffi.dll_loaded",
+        Kind::Gauge,
+        metrics::Unit::Count,
+        "Whether the DLL is currently loaded",
+    ),
+    (
+        "ffi.bytes_copied",
+        Kind::Counter,
+        metrics::Unit::Bytes,
+        "Bytes copied across the FFI boundary",
+    ),
+    (
+        "ffi.executor.queue_depth",
+        Kind::Gauge,
+        metrics::Unit::Count,
+        "Calls waiting for an FFI executor thread",
+    ),
+    (
+        "ffi.executor.rejected",
+        Kind::Counter,
+        metrics::Unit::Count,
+        "Calls the FFI executor rejected because its queue was full",
+    ),
+    (
+        "ffi.circuit_breaker.state",
+        Kind::Gauge,
+        metrics::Unit::Count,
+        "Circuit breaker state: 0 closed, 1 half-open, 2 open",
+    ),
+    (
+        "ffi.circuit_breaker.rejected",
+        Kind::Counter,
+        metrics::Unit::Count,
+        "Calls rejected while the circuit breaker was open",
+    ),
+    (
+        "go.heap_bytes",
+        Kind::Gauge,
+        metrics::Unit::Bytes,
+        "Bytes of heap objects in the Go runtime",
+    ),
+    (
+        "go.heap_objects",
+        Kind::Gauge,
+        metrics::Unit::Count,
+        "Heap objects in the Go runtime",
+    ),
+    (
+        "go.goroutines",
+        Kind::Gauge,
+        metrics::Unit::Count,
+        "Goroutines in the Go runtime",
+    ),
+    (
+        "go.gc_cycles",
+        Kind::Gauge,
+        metrics::Unit::Count,
+        "Garbage collection cycles the Go runtime completed",
+    ),
+    (
+        "go.gc_pause_ns",
+        Kind::Gauge,
+        metrics::Unit::Nanoseconds,
+        "Total time the Go runtime stopped the world for garbage collection",
+    ),
+];
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+fn facade_labels(labels: MetricLabels) -> Vec<metrics::Label> {
+    labels
+        .iter()
+        .map(|&(key, value)| metrics::Label::new(key, value))
+        .collect()
+}
+
+/// Forwards to the recorder installed for the `metrics` facade, if any
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecorderSink;
+
+impl RecorderSink {
+    /// Describes the FFI metrics to the installed recorder; later calls do
+    /// nothing
+    fn describe() {
+        static DESCRIBED: Once = Once::new();
+        DESCRIBED.call_once(|| {
+            for &(name, kind, unit, description) in METRICS {
+                match kind {
+                    Kind::Counter => metrics::describe_counter!(name, unit, description),
+                    Kind::Gauge => metrics::describe_gauge!(name, unit, description),
+                    Kind::Histogram => metrics::describe_histogram!(name, unit, description),
+                }
+            }
+        });
+    }
+}
+
+impl MetricsSink for RecorderSink {
+    fn counter(&self, name: &'static str, labels: MetricLabels, value: u64) {
+        Self::describe();
+        metrics::counter!(name, facade_labels(labels)).increment(value);
+    }
+
+    fn gauge(&self, name: &'static str, labels: MetricLabels, value: f64) {
+        Self::describe();
+        metrics::gauge!(name, facade_labels(labels)).set(value);
+    }
+
+    fn histogram(&self, name: &'static str, labels: MetricLabels, value: f64) {
+        Self::describe();
+        metrics::histogram!(name, facade_labels(labels)).record(value);
+    }
+}
+
+/// Serves the metrics from a Prometheus listener
+#[cfg(feature = "metrics-prometheus")]
+#[derive(Debug, Clone, Copy)]
+pub struct PrometheusSink {
+    pub addr: SocketAddr,
+}
+
+#[cfg(feature = "metrics-prometheus")]
+impl PrometheusSink {
+    /// Installs the Prometheus recorder with a listener on `addr`.
+    ///
+    /// Fails if the address is taken or another recorder is already
+    /// installed.
+    pub fn install(addr: SocketAddr) -> Result<Self, metrics_exporter_prometheus::BuildError> {
+        metrics_exporter_prometheus::PrometheusBuilder::new()
+            .with_http_listener(addr)
+            .install()?;
+        Ok(Self { addr })
+    }
+}
+
+#[cfg(feature = "metrics-prometheus")]
+impl MetricsSink for PrometheusSink {
+    fn counter(&self, name: &'static str, labels: MetricLabels, value: u64) {
+        RecorderSink.counter(name, labels, value);
+    }
+
+    fn gauge(&self, name: &'static str, labels: MetricLabels, value: f64) {
+        RecorderSink.gauge(name, labels, value);
+    }
+
+    fn histogram(&self, name: &'static str, labels: MetricLabels, value: f64) {
+        RecorderSink.histogram(name, labels, value);
+    }
+}
+
+/// Sends the metrics as StatsD datagrams over UDP.
+///
+/// Labels become DogStatsD tags (`|#export:AddNumbers`); plain StatsD
+/// servers ignore them. Histograms are sent as timers (`|ms`). Send errors
+/// are dropped, as is usual for StatsD.
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+}
+
+impl StatsdSink {
+    /// Sends to the StatsD server at `addr`
+    pub fn new(addr: SocketAddr) -> io::Result<Self> {
+        let local: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        Ok(Self { socket })
+    }
+
+    fn send(&self, name: &str, value: impl fmt::Display, kind: &str, labels: MetricLabels) {
+        let mut line = format!("{}:{}|{}", name, value, kind);
+        for (i, (key, value)) in labels.iter().enumerate() {
+            line.push_str(if i == 0 { "|#" } else { "," });
+            line.push_str(key);
+            line.push(':');
+            line.push_str(value);
+        }
+        // Warns once at most; a missing server shouldn't flood the log.
+        if let Err(e) = self.socket.send(line.as_bytes()) {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| warn!("Failed to send StatsD metrics: {}", e));
+        }
+    }
+}
+
+impl MetricsSink for StatsdSink {
+    fn counter(&self, name: &'static str, labels: MetricLabels, value: u64) {
+        self.send(name, value, "c", labels);
+    }
+
+    fn gauge(&self, name: &'static str, labels: MetricLabels, value: f64) {
+        self.send(name, value, "g", labels);
+    }
+
+    fn histogram(&self, name: &'static str, labels: MetricLabels, value: f64) {
+        self.send(name, value, "ms", labels);
+    }
+}
+
+/// Exports the metrics, and a span per call, over OTLP/gRPC.
+///
+/// Batches are sent from a background thread of its own, so no Tokio
+/// runtime is needed. The service name comes from `OTEL_SERVICE_NAME`, as
+/// with other OpenTelemetry SDKs.
+#[cfg(feature = "otel")]
+pub struct OtlpSink {
+    endpoint: String,
+    meters: opentelemetry_sdk::metrics::SdkMeterProvider,
+    meter: opentelemetry::metrics::Meter,
+    tracers: opentelemetry_sdk::trace::TracerProvider,
+    tracer: opentelemetry_sdk::trace::Tracer,
+    instruments: parking_lot::RwLock<std::collections::HashMap<&'static str, Instrument>>,
+    /// Runs the exporters; `None` once dropped
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+#[cfg(feature = "otel")]
+#[derive(Clone)]
+enum Instrument {
+    Counter(opentelemetry::metrics::Counter<u64>),
+    Gauge(opentelemetry::metrics::Gauge<f64>),
+    Histogram(opentelemetry::metrics::Histogram<f64>),
+}
+
+#[cfg(feature = "otel")]
+fn otel_attributes(labels: MetricLabels) -> Vec<opentelemetry::KeyValue> {
+    labels
+        .iter()
+        .map(|&(key, value)| opentelemetry::KeyValue::new(key, value))
+        .collect()
+}
+
+#[cfg(feature = "otel")]
+impl OtlpSink {
+    /// Exports to the collector at `endpoint`, e.g. `http://localhost:4317`.
+    ///
+    /// Fails if the endpoint isn't a valid URI or the export thread can't
+    /// start; an unreachable collector only shows up as failed exports.
+    pub fn install(endpoint: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        use opentelemetry::metrics::MeterProvider as _;
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry_otlp::WithExportConfig;
+        use opentelemetry_sdk::runtime::Tokio;
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("ffi-otlp")
+            .enable_all()
+            .build()?;
+        // The exporters spawn their tasks onto the current runtime.
+        let _entered = runtime.enter();
+
+        let metric_exporter = opentelemetry_otlp::MetricExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let meters = opentelemetry_sdk::metrics::SdkMeterProvider::builder()
+            .with_reader(
+                opentelemetry_sdk::metrics::PeriodicReader::builder(metric_exporter, Tokio).build(),
+            )
+            .build();
+
+        let span_exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let tracers = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_batch_exporter(span_exporter, Tokio)
+            .build();
+
+        Ok(Self {
+            endpoint: endpoint.to_string(),
+            meter: meters.meter("rust_go_ffi"),
+            meters,
+            tracer: tracers.tracer("rust_go_ffi"),
+            tracers,
+            instruments: Default::default(),
+            runtime: Some(runtime),
+        })
+    }
+
+    /// The instrument for `name`, created with the unit and description
+    /// from [`METRICS`] on first use
+    fn instrument(&self, name: &'static str, kind: Kind) -> Instrument {
+        if let Some(instrument) = self.instruments.read().get(name) {
+            return instrument.clone();
+        }
+        let (unit, description) = METRICS
+            .iter()
+            .find(|metric| metric.0 == name)
+            .map_or(("", ""), |metric| (metric.2.as_canonical_label(), metric.3));
+        let instrument = match kind {
+            Kind::Counter => Instrument::Counter(
+                self.meter
+                    .u64_counter(name)
+                    .with_unit(unit)
+                    .with_description(description)
+                    .build(),
+            ),
+            Kind::Gauge => Instrument::Gauge(
+                self.meter
+                    .f64_gauge(name)
+                    .with_unit(unit)
+                    .with_description(description)
+                    .build(),
+            ),
+            Kind::Histogram => Instrument::Histogram(
+                self.meter
+                    .f64_histogram(name)
+                    .with_unit(unit)
+                    .with_description(description)
+                    .build(),
+            ),
+        };
+        self.instruments
+            .write()
+            .entry(name)
+            .or_insert(instrument)
+            .clone()
+    }
+}
+
+#[cfg(feature = "otel")]
+impl fmt::Debug for OtlpSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OtlpSink")
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "otel")]
+impl MetricsSink for OtlpSink {
+    fn counter(&self, name: &'static str, labels: MetricLabels, value: u64) {
+        if let Instrument::Counter(counter) = self.instrument(name, Kind::Counter) {
+            counter.add(value, &otel_attributes(labels));
+        }
+    }
+
+    fn gauge(&self, name: &'static str, labels: MetricLabels, value: f64) {
+        if let Instrument::Gauge(gauge) = self.instrument(name, Kind::Gauge) {
+            gauge.record(value, &otel_attributes(labels));
+        }
+    }
+
+    fn histogram(&self, name: &'static str, labels: MetricLabels, value: f64) {
+        if let Instrument::Histogram(histogram) = self.instrument(name, Kind::Histogram) {
+            histogram.record(value, &otel_attributes(labels));
+        }
+    }
+
+    fn span(
+        &self,
+        export: &'static str,
+        start: SystemTime,
+        duration: Duration,
+        error: Option<&DllError>,
+    ) {
+        use opentelemetry::trace::{Span as _, SpanKind, Status, Tracer as _};
+        use opentelemetry::KeyValue;
+
+        let mut span = self
+            .tracer
+            .span_builder(export)
+            .with_kind(SpanKind::Client)
+            .with_start_time(start)
+            .with_attributes([KeyValue::new("ffi.export", export)])
+            .start(&self.tracer);
+        if let Some(e) = error {
+            span.set_attribute(KeyValue::new("ffi.error_code", i64::from(e.code())));
+            span.set_status(Status::error(e.to_string()));
+        }
+        span.end_with_timestamp(start + duration);
+    }
+
+    fn flush(&self) {
+        if let Err(e) = self.meters.force_flush() {
+            warn!("Failed to export metrics to {}: {}", self.endpoint, e);
+        }
+        if let Some(Err(e)) = self.tracers.force_flush().into_iter().find(Result::is_err) {
+            warn!("Failed to export spans to {}: {}", self.endpoint, e);
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+impl Drop for OtlpSink {
+    fn drop(&mut self) {
+        let _ = self.meters.shutdown();
+        let _ = self.tracers.shutdown();
+        // Dropping a runtime blocks, which panics inside async code.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Drops every metric
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopSink;
+
+impl MetricsSink for NoopSink {
+    fn counter(&self, _name: &'static str, _labels: MetricLabels, _value: u64) {}
+
+    fn gauge(&self, _name: &'static str, _labels: MetricLabels, _value: f64) {}
+
+    fn histogram(&self, _name: &'static str, _labels: MetricLabels, _value: f64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statsd_lines() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let sink = StatsdSink::new(server.local_addr().unwrap()).unwrap();
+
+        sink.counter(
+            "ffi.bytes_copied",
+            &[("export", "AddNumbers"), ("direction", "in")],
+            16,
+        );
+        sink.gauge("ffi.dll_loaded", &[], 1.0);
+
+        let mut buf = [0u8; 256];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"ffi.bytes_copied:16|c|#export:AddNumbers,direction:in"
+        );
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"ffi.dll_loaded:1|g");
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_otlp_sink() {
+        assert!(OtlpSink::install("not a uri").is_err());
+
+        // Nothing listens there; exports fail without disturbing the calls.
+        let sink = OtlpSink::install("http://127.0.0.1:9").unwrap();
+        sink.counter(
+            "ffi.calls",
+            &[("export", "AddNumbers"), ("status", "ok")],
+            1,
+        );
+        sink.histogram("ffi.latency", &[("export", "AddNumbers")], 0.5);
+        sink.span(
+            "AddNumbers",
+            SystemTime::now(),
+            Duration::from_micros(500),
+            Some(&DllError::Busy { limit: 1 }),
+        );
+        drop(sink);
+    }
+
+    #[cfg(feature = "metrics-prometheus")]
+    #[test]
+    fn test_prometheus_port_taken() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(PrometheusSink::install(taken.local_addr().unwrap()).is_err());
+    }
+}
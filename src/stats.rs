@@ -0,0 +1,202 @@
+//! Call statistics that don't need the `metrics` features.
+//!
+//! Counters and latency histograms are plain atomics updated on every call,
+//! so [`stats`] works in builds that can't run an exporter. Latencies go
+//! into power-of-two microsecond buckets; percentiles report the upper bound
+//! of their bucket and so overestimate by less than a factor of two.
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Buckets up to 2^39 µs, about six days; slower calls land in the last one
+const BUCKETS: usize = 40;
+
+#[derive(Debug)]
+struct Histogram([AtomicU64; BUCKETS]);
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self(std::array::from_fn(|_| AtomicU64::new(0)))
+    }
+}
+
+impl Histogram {
+    fn record(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        self.0[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Upper bound of the bucket holding the `quantile` of recorded calls
+    fn percentile(&self, quantile: f64) -> Duration {
+        let counts: Vec<u64> = self.0.iter().map(|c| c.load(Ordering::Relaxed)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((total as f64 * quantile).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(1 << bucket);
+            }
+        }
+        Duration::from_micros(1 << (BUCKETS - 1))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    latency: Histogram,
+}
+
+impl Counters {
+    fn snapshot(&self) -> (u64, u64, Duration, Duration) {
+        (
+            self.calls.load(Ordering::Relaxed),
+            self.errors.load(Ordering::Relaxed),
+            self.latency.percentile(0.5),
+            self.latency.percentile(0.95),
+        )
+    }
+}
+
+static TOTAL: Lazy<Counters> = Lazy::new(Counters::default);
+static EXPORTS: Lazy<RwLock<HashMap<&'static str, Arc<Counters>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+fn counters(export: &'static str) -> Arc<Counters> {
+    if let Some(counters) = EXPORTS.read().get(export) {
+        return counters.clone();
+    }
+    EXPORTS.write().entry(export).or_default().clone()
+}
+
+/// Counts a call to `export` that took `duration`
+pub(crate) fn record_call(export: &'static str, duration: Duration, failed: bool) {
+    for counters in [&*TOTAL, &*counters(export)] {
+        counters.calls.fetch_add(1, Ordering::Relaxed);
+        counters.latency.record(duration);
+        if failed {
+            counters.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Counts an error the library reported after a call to `export` returned
+pub(crate) fn record_error(export: &str) {
+    TOTAL.errors.fetch_add(1, Ordering::Relaxed);
+    if let Some(counters) = EXPORTS.read().get(export) {
+        counters.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Statistics of one export
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionStats {
+    pub name: &'static str,
+    pub calls: u64,
+    pub errors: u64,
+    pub p50: Duration,
+    pub p95: Duration,
+}
+
+/// Calls into the library since the process started
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfiStats {
+    pub calls: u64,
+    /// Calls that failed, including ones a hook or rate limit refused and
+    /// ones the library reported an error for
+    pub errors: u64,
+    pub p50: Duration,
+    pub p95: Duration,
+    /// Exports that were called, by name
+    pub functions: Vec<FunctionStats>,
+}
+
+impl fmt::Display for FfiStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>10} calls {:>8} errors  p50 {:?}  p95 {:?}",
+            "total", self.calls, self.errors, self.p50, self.p95
+        )?;
+        for function in &self.functions {
+            writeln!(
+                f,
+                "{:<24} {:>10} calls {:>8} errors  p50 {:?}  p95 {:?}",
+                function.name, function.calls, function.errors, function.p50, function.p95
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns call counts, error counts and latency percentiles, in total and
+/// per export
+pub fn stats() -> FfiStats {
+    let mut functions: Vec<FunctionStats> = EXPORTS
+        .read()
+        .iter()
+        .map(|(name, counters)| {
+            let (calls, errors, p50, p95) = counters.snapshot();
+            FunctionStats {
+                name,
+                calls,
+                errors,
+                p50,
+                p95,
+            }
+        })
+        .collect();
+    functions.sort_by_key(|function| function.name);
+
+    let (calls, errors, p50, p95) = TOTAL.snapshot();
+    FfiStats {
+        calls,
+        errors,
+        p50,
+        p95,
+        functions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let histogram = Histogram::default();
+        assert_eq!(histogram.percentile(0.5), Duration::ZERO);
+        for _ in 0..9 {
+            histogram.record(Duration::from_micros(3));
+        }
+        histogram.record(Duration::from_millis(1));
+        assert_eq!(histogram.percentile(0.5), Duration::from_micros(4));
+        assert_eq!(histogram.percentile(0.95), Duration::from_micros(1024));
+    }
+
+    #[test]
+    fn test_counts_per_export() {
+        record_call("StatsTest", Duration::from_micros(10), false);
+        record_call("StatsTest", Duration::from_micros(10), true);
+        record_error("StatsTest");
+        let stats = stats();
+        let function = stats
+            .functions
+            .iter()
+            .find(|f| f.name == "StatsTest")
+            .unwrap();
+        assert_eq!((function.calls, function.errors), (2, 2));
+        assert!(stats.calls >= 2 && stats.errors >= 2);
+    }
+}
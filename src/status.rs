@@ -0,0 +1,124 @@
+//! Cheap snapshots of the FFI layer for health endpoints.
+//!
+//! [`status`] and [`dll_info`] only read state that is already there: they
+//! never load the library or call into it, so polling them is safe even
+//! while the library is misbehaving.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use semver::Version;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::{circuit_state, usage_report, CircuitState, DllError, DLL_CONTEXT};
+
+/// The library file behind the FFI layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DllInfo {
+    /// Where the library was loaded from, or would be
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Version the loaded copy reported; `None` until it has been asked
+    pub version: Option<Version>,
+}
+
+/// The most recent error a call into the library ended with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastError {
+    /// [`DllError::code`] of the error
+    pub code: u32,
+    pub message: String,
+    pub at: SystemTime,
+}
+
+/// Snapshot of the FFI layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfiStatus {
+    pub initialized: bool,
+    /// When the library was last initialized; cleared by cleanup
+    pub initialized_at: Option<SystemTime>,
+    pub dll: Option<DllInfo>,
+    /// Why the context is poisoned, if it is
+    pub poisoned: Option<String>,
+    /// Calls into the library since the process started
+    pub total_calls: u64,
+    pub last_error: Option<LastError>,
+    pub circuit: CircuitState,
+}
+
+impl FfiStatus {
+    /// Whether calls can be expected to succeed: initialized, not poisoned
+    /// and with the circuit breaker closed
+    pub fn is_healthy(&self) -> bool {
+        self.initialized && self.poisoned.is_none() && self.circuit == CircuitState::Closed
+    }
+}
+
+static LAST_ERROR: Lazy<Mutex<Option<LastError>>> = Lazy::new(|| Mutex::new(None));
+
+/// Remembers `error` as the last one
+pub(crate) fn record_error(error: &DllError) {
+    *LAST_ERROR.lock() = Some(LastError {
+        code: error.code(),
+        message: error.to_string(),
+        at: SystemTime::now(),
+    });
+}
+
+/// Describes the library file: the loaded copy's source if there is one,
+/// otherwise the file [`get_dll_path`](crate::get_dll_path) resolves to.
+///
+/// `None` if no file was found, and always with the `static` feature.
+pub fn dll_info() -> Option<DllInfo> {
+    if cfg!(feature = "static") {
+        return None;
+    }
+    let loaded = DLL_CONTEXT.read().ok()?.library.clone();
+    let (path, version) = match loaded {
+        Some(library) => (library.source().to_path_buf(), library.version().cloned()),
+        None => (crate::get_dll_path()?, None),
+    };
+    let metadata = fs::metadata(&path).ok()?;
+    Some(DllInfo {
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        path,
+        version,
+    })
+}
+
+/// Current state of the FFI layer
+pub fn status() -> FfiStatus {
+    let (initialized, initialized_at, poisoned) = match DLL_CONTEXT.read() {
+        Ok(context) => (
+            context.initialized,
+            context.initialized_at,
+            context.poisoned.clone(),
+        ),
+        Err(e) => (false, None, Some(e.to_string())),
+    };
+    FfiStatus {
+        initialized,
+        initialized_at,
+        dll: dll_info(),
+        poisoned,
+        total_calls: usage_report().total_calls(),
+        last_error: LAST_ERROR.lock().clone(),
+        circuit: circuit_state(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_last_error() {
+        record_error(&DllError::Busy { limit: 2 });
+        let last = status().last_error.unwrap();
+        // Other tests may record errors concurrently.
+        assert!(last.code > 0 && !last.message.is_empty());
+    }
+}
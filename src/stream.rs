@@ -0,0 +1,104 @@
+//! Results the Go side produces in chunks.
+//!
+//! A Go export opens a stream and returns its handle; [`GoStream`] then pulls
+//! one chunk per `NextChunk` call until Go signals the end, so a large result
+//! never has to fit into a single buffer on either side. Chunks are handed
+//! out as [`Bytes`] backed by the Go allocation, without copying.
+
+use bytes::Bytes;
+use std::fmt;
+use std::os::raw::c_longlong;
+use std::sync::Arc;
+
+use log::warn;
+
+use crate::library::Library;
+use crate::types::GoBuffer;
+use crate::{call_export, last_error, poison, DllError};
+
+/// A chunked result of a Go export; closed on drop
+pub struct GoStream {
+    library: Arc<Library>,
+    handle: c_longlong,
+    done: bool,
+}
+
+impl GoStream {
+    /// Wraps the stream `handle` an export of `library` opened
+    pub(crate) fn new(library: Arc<Library>, handle: c_longlong) -> Self {
+        Self {
+            library,
+            handle,
+            done: false,
+        }
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Bytes>, DllError> {
+        let next_chunk = self.library.symbol(&self.library.symbols.next_chunk)?;
+        let mut len = 0;
+        let raw = call_export("NextChunk", || unsafe { next_chunk(self.handle, &mut len) })
+            .map_err(poison)?;
+        // Take ownership first so the chunk is released on every path.
+        let chunk = unsafe { GoBuffer::from_raw(self.library.clone(), "NextChunk", raw, len) };
+        last_error::check(&self.library, "NextChunk").map_err(poison)?;
+        if raw.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(Bytes::from_owner(chunk?)))
+    }
+
+    /// Reads the rest of the stream into one buffer
+    pub fn collect_bytes(self) -> Result<Vec<u8>, DllError> {
+        let mut all = Vec::new();
+        for chunk in self {
+            all.extend_from_slice(&chunk?);
+        }
+        Ok(all)
+    }
+}
+
+impl Iterator for GoStream {
+    type Item = Result<Bytes, DllError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_chunk() {
+            Ok(Some(chunk)) => Some(Ok(chunk)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            // The stream can't be resumed after a failure.
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for GoStream {}
+
+impl fmt::Debug for GoStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GoStream")
+            .field("handle", &self.handle)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl Drop for GoStream {
+    fn drop(&mut self) {
+        let closed = self
+            .library
+            .symbol(&self.library.symbols.close_stream)
+            .and_then(|close| call_export("CloseStream", || unsafe { close(self.handle) }))
+            .and_then(|()| last_error::check(&self.library, "CloseStream"));
+        if let Err(e) = closed {
+            warn!("Failed to close stream {}: {}", self.handle, e);
+        }
+    }
+}
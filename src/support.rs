@@ -0,0 +1,174 @@
+//! Diagnostics for bug reports: a startup banner and a support bundle.
+//!
+//! The bundle is a zip archive of text files so it can be attached to an
+//! issue as is; it needs the `support-bundle` feature.
+
+#[cfg(feature = "support-bundle")]
+use std::fmt::Write as _;
+#[cfg(feature = "support-bundle")]
+use std::fs::File;
+#[cfg(feature = "support-bundle")]
+use std::io::{self, Write as _};
+#[cfg(feature = "support-bundle")]
+use std::path::Path;
+
+#[cfg(feature = "support-bundle")]
+use crate::{
+    abi_check, config, dll_build_info, is_dll_available, list_exports, recent_calls, search_paths,
+    transfer_report, usage_report, verify_dll, DLL_CONTEXT,
+};
+use crate::{get_build_info, get_dll_path};
+
+/// One line describing this build and where the library comes from
+pub fn banner() -> String {
+    format!(
+        "rust_go_ffi {} ({} {}), Go {}, library {}",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        get_build_info().go_version,
+        get_dll_path().map_or("not found".to_string(), |p| p.display().to_string()),
+    )
+}
+
+/// Writes a zip archive with everything needed to diagnose a problem.
+///
+/// Contains the crate and platform versions, a doctor report of the library
+/// on disk, the state of the FFI context, the effective configuration, the
+/// Go build info, the call and transfer counters and the recent calls from
+/// the audit log. Collecting it loads the
+/// library if necessary but never calls into it.
+#[cfg(feature = "support-bundle")]
+pub fn support_bundle(path: impl AsRef<Path>) -> io::Result<()> {
+    let entries = [
+        ("environment.txt", environment()),
+        ("doctor.txt", doctor()),
+        ("context.txt", context()),
+        (
+            "config.txt",
+            format!("{:#?}\n{:#?}\n", config::current(), search_paths()),
+        ),
+        ("build_info.json", get_build_info().sbom_json.to_string()),
+        ("usage.txt", usage_report().to_string()),
+        ("transfers.txt", transfer_report().to_string()),
+        ("recent_calls.jsonl", recent_calls_jsonl()),
+    ];
+
+    write_zip(path.as_ref(), &entries)
+}
+
+/// Writes `entries` of file name and contents as a deflated zip archive
+#[cfg(feature = "support-bundle")]
+fn write_zip(path: &Path, entries: &[(&str, String)]) -> io::Result<()> {
+    let mut archive = zip::ZipWriter::new(File::create(path)?);
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, contents) in entries {
+        archive.start_file(*name, options)?;
+        archive.write_all(contents.as_bytes())?;
+    }
+    archive.finish()?;
+    Ok(())
+}
+
+#[cfg(feature = "support-bundle")]
+fn recent_calls_jsonl() -> String {
+    recent_calls()
+        .iter()
+        .map(|call| call.to_json() + "\n")
+        .collect()
+}
+
+#[cfg(feature = "support-bundle")]
+fn environment() -> String {
+    let mut out = String::new();
+    writeln!(out, "{}", banner()).unwrap();
+    writeln!(out, "crate version: {}", env!("CARGO_PKG_VERSION")).unwrap();
+    writeln!(out, "os: {}", std::env::consts::OS).unwrap();
+    writeln!(out, "arch: {}", std::env::consts::ARCH).unwrap();
+    for (key, value) in std::env::vars_os() {
+        let key = key.to_string_lossy();
+        if key.starts_with("RUST_GO_FFI_") || key == "RUST_LOG" {
+            writeln!(out, "{}={}", key, value.to_string_lossy()).unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(feature = "support-bundle")]
+fn doctor() -> String {
+    let mut out = String::new();
+    match get_dll_path() {
+        Some(path) => writeln!(out, "library path: {}", path.display()).unwrap(),
+        None => writeln!(out, "library path: not found").unwrap(),
+    }
+    writeln!(out, "available: {}", is_dll_available()).unwrap();
+    match verify_dll() {
+        Ok(()) => writeln!(out, "verify: ok").unwrap(),
+        Err(e) => writeln!(out, "verify: {} (code {})", e, e.code()).unwrap(),
+    }
+    match abi_check() {
+        Ok(()) => writeln!(out, "abi: ok").unwrap(),
+        Err(e) => writeln!(out, "abi: {} (code {})", e, e.code()).unwrap(),
+    }
+    match dll_build_info() {
+        Ok(info) => writeln!(out, "library build: {}", info).unwrap(),
+        Err(e) => writeln!(out, "library build: {}", e).unwrap(),
+    }
+    match list_exports() {
+        Ok(exports) => writeln!(out, "exports: {}", exports.join(", ")).unwrap(),
+        Err(e) => writeln!(out, "exports: {}", e).unwrap(),
+    }
+    out
+}
+
+#[cfg(feature = "support-bundle")]
+fn context() -> String {
+    let mut out = String::new();
+    writeln!(out, "lock recoveries: {}", DLL_CONTEXT.recoveries()).unwrap();
+    match DLL_CONTEXT.read() {
+        Ok(context) => {
+            writeln!(out, "initialized: {}", context.initialized).unwrap();
+            writeln!(out, "version: {}", context.version).unwrap();
+            if let Some(reason) = &context.poisoned {
+                writeln!(out, "poisoned: {}", reason).unwrap();
+            }
+            if let Some(library) = &context.library {
+                writeln!(out, "loaded from: {}", library.source().display()).unwrap();
+                if let Some(capabilities) = library.capabilities() {
+                    writeln!(out, "capabilities: {}", capabilities).unwrap();
+                }
+                if let Some(shadow) = library.shadow() {
+                    writeln!(out, "shadow copy: {}", shadow.display()).unwrap();
+                }
+            }
+        }
+        Err(e) => writeln!(out, "context unavailable: {}", e).unwrap(),
+    }
+    out
+}
+
+#[cfg(all(test, feature = "support-bundle"))]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_write_zip() {
+        let path = std::env::temp_dir().join(format!("support_zip_{}.zip", std::process::id()));
+        let entries = [("a.txt", "hello".to_string()), ("b.txt", String::new())];
+        write_zip(&path, &entries).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut contents = String::new();
+        archive
+            .by_name("a.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello");
+        assert_eq!(archive.by_name("b.txt").unwrap().size(), 0);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,174 @@
+//! Bounded latency for calls into the Go library.
+//!
+//! [`with_timeout`] runs a call on a worker thread and stops waiting for it
+//! once its deadline passes. Rust can't interrupt a call that is stuck inside
+//! Go, so the worker is left behind and the FFI context is poisoned, since
+//! the library's state is unknown while it runs. [`with_cancel_timeout`]
+//! hands the call a [`CancelToken`] and cancels it at the deadline instead;
+//! only a call that ignores the cancellation for [`CANCEL_GRACE`] poisons
+//! the context.
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::limit;
+use crate::{poison_stuck, CancelToken, DllError};
+
+/// How long a cancelled call may take to return before the context is
+/// poisoned anyway
+pub const CANCEL_GRACE: Duration = Duration::from_secs(1);
+
+/// The export a worker entered last
+type Entered = Arc<Mutex<Option<&'static str>>>;
+
+type Outcome<T> = thread::Result<Result<T, DllError>>;
+
+thread_local! {
+    /// Set on worker threads only
+    static ENTERED: RefCell<Option<Entered>> = const { RefCell::new(None) };
+}
+
+/// Notes that the current thread calls `export`, if a watchdog waits for it
+pub(crate) fn enter(export: &'static str) {
+    ENTERED.with(|entered| {
+        if let Some(entered) = &*entered.borrow() {
+            *entered.lock() = Some(export);
+        }
+    });
+}
+
+fn spawn_worker<T, F>(f: F) -> (Receiver<Outcome<T>>, Entered)
+where
+    F: FnOnce() -> Result<T, DllError> + Send + 'static,
+    T: Send + 'static,
+{
+    let entered = Entered::default();
+    let worker_entered = entered.clone();
+    // The worker continues the caller's call chain under its permit.
+    let permit = limit::current();
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    thread::Builder::new()
+        .name("rust-go-ffi-call".to_string())
+        .spawn(move || {
+            ENTERED.with(|entered| *entered.borrow_mut() = Some(worker_entered));
+            let outcome = limit::adopt(permit, || panic::catch_unwind(AssertUnwindSafe(f)));
+            // Nobody is listening any more if the call timed out.
+            let _ = tx.send(outcome);
+        })
+        .expect("failed to spawn FFI call thread");
+    (rx, entered)
+}
+
+fn finish<T>(outcome: Outcome<T>) -> Result<T, DllError> {
+    outcome.unwrap_or_else(|payload: Box<dyn Any + Send>| panic::resume_unwind(payload))
+}
+
+fn timed_out(entered: &Entered, timeout: Duration, poisoned: bool) -> DllError {
+    let error = DllError::Timeout {
+        // A call that never reached an export was stuck in initialization.
+        export: entered.lock().unwrap_or("initialize").to_string(),
+        timeout,
+        poisoned,
+    };
+    if poisoned {
+        poison_stuck(error)
+    } else {
+        error
+    }
+}
+
+/// Runs `f` on a worker thread and returns [`DllError::Timeout`] if it takes
+/// longer than `timeout`.
+///
+/// The call keeps running after a timeout and its result is discarded. The
+/// FFI context is poisoned then, so later calls fail with
+/// [`DllError::Poisoned`] until [`reset`](crate::reset) or
+/// [`reload_dll`](crate::reload_dll), and the timeout is not
+/// [retryable](DllError::is_retryable). A panic in `f` is resumed on the
+/// calling thread.
+///
+/// ```ignore
+/// let sum = with_timeout(Duration::from_millis(100), || add_numbers(2, 3))?;
+/// ```
+pub fn with_timeout<T, F>(timeout: Duration, f: F) -> Result<T, DllError>
+where
+    F: FnOnce() -> Result<T, DllError> + Send + 'static,
+    T: Send + 'static,
+{
+    let (rx, entered) = spawn_worker(f);
+    match rx.recv_timeout(timeout) {
+        Ok(outcome) => finish(outcome),
+        Err(RecvTimeoutError::Timeout) => Err(timed_out(&entered, timeout, true)),
+        Err(RecvTimeoutError::Disconnected) => unreachable!("the worker always reports back"),
+    }
+}
+
+/// Like [`with_timeout`], but cancels the token `f` receives at the deadline.
+///
+/// The context is only poisoned if the call doesn't return within
+/// [`CANCEL_GRACE`] after that; either way the result is
+/// [`DllError::Timeout`].
+///
+/// ```ignore
+/// with_cancel_timeout(Duration::from_secs(1), |token| {
+///     sleep_with_cancel(Duration::from_secs(60), token)
+/// })?;
+/// ```
+pub fn with_cancel_timeout<T, F>(timeout: Duration, f: F) -> Result<T, DllError>
+where
+    F: FnOnce(&CancelToken) -> Result<T, DllError> + Send + 'static,
+    T: Send + 'static,
+{
+    let token = CancelToken::new()?;
+    let worker_token = token.clone();
+    let (rx, entered) = spawn_worker(move || f(&worker_token));
+    match rx.recv_timeout(timeout) {
+        Ok(outcome) => finish(outcome),
+        Err(RecvTimeoutError::Timeout) => {
+            token.cancel();
+            let stuck = rx.recv_timeout(CANCEL_GRACE).is_err();
+            Err(timed_out(&entered, timeout, stuck))
+        }
+        Err(RecvTimeoutError::Disconnected) => unreachable!("the worker always reports back"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_within_deadline() {
+        let result = with_timeout(Duration::from_secs(5), || Ok(42));
+        assert_eq!(result.unwrap(), 42);
+        let result: Result<(), _> =
+            with_timeout(Duration::from_secs(5), || Err(DllError::NotFound));
+        assert!(matches!(result, Err(DllError::NotFound)));
+    }
+
+    #[test]
+    #[should_panic(expected = "inside the worker")]
+    fn test_panic_is_resumed() {
+        let _ = with_timeout(Duration::from_secs(5), || -> Result<(), DllError> {
+            panic!("inside the worker")
+        });
+    }
+
+    #[test]
+    fn test_entered_is_per_worker() {
+        // Not a worker, so nothing is recorded.
+        enter("GetDLLVersion");
+        let (rx, entered) = spawn_worker(|| {
+            enter("AddNumbers");
+            Ok(())
+        });
+        finish(rx.recv().unwrap()).unwrap();
+        assert_eq!(*entered.lock(), Some("AddNumbers"));
+    }
+}
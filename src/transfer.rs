@@ -0,0 +1,221 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::config::{self, FfiConfig};
+use crate::DllError;
+
+/// What kind of value crossed the FFI boundary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TransferKind {
+    /// Fixed-size values passed by value
+    Scalar,
+    /// UTF-8 strings copied into or out of Go memory
+    String,
+    /// Slices and byte buffers
+    Slice,
+    /// Serialized request/response payloads
+    Payload,
+}
+
+impl fmt::Display for TransferKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TransferKind::Scalar => "scalar",
+            TransferKind::String => "string",
+            TransferKind::Slice => "slice",
+            TransferKind::Payload => "payload",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counter {
+    bytes_in: u64,
+    bytes_out: u64,
+    copies_in: u64,
+    copies_out: u64,
+}
+
+static TRANSFERS: Lazy<Mutex<BTreeMap<(&'static str, TransferKind), Counter>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Bytes copied across the boundary for one export and value kind.
+///
+/// "In" is Rust to Go, "out" is Go to Rust.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferStats {
+    pub name: &'static str,
+    pub kind: TransferKind,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub copies_in: u64,
+    pub copies_out: u64,
+}
+
+/// Snapshot of marshaling traffic since process start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferReport {
+    pub entries: Vec<TransferStats>,
+}
+
+impl TransferReport {
+    /// Entries recorded for `name`
+    pub fn for_export<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a TransferStats> {
+        self.entries.iter().filter(move |e| e.name == name)
+    }
+
+    pub fn total_bytes_in(&self) -> u64 {
+        self.entries.iter().map(|e| e.bytes_in).sum()
+    }
+
+    pub fn total_bytes_out(&self) -> u64 {
+        self.entries.iter().map(|e| e.bytes_out).sum()
+    }
+}
+
+impl fmt::Display for TransferReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{:<24} {:<8} {:>12} B in {:>12} B out",
+                entry.name, entry.kind, entry.bytes_in, entry.bytes_out
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks a non-scalar argument against the configured payload limit, then
+/// records it like [`record_in`].
+///
+/// cgo copies or pins such arguments as a whole, and very large ones can run
+/// into runtime limits inside Go; rejecting them here turns a crash into
+/// [`DllError::PayloadTooLarge`].
+pub(crate) fn marshal_in(
+    name: &'static str,
+    kind: TransferKind,
+    bytes: usize,
+) -> Result<(), DllError> {
+    check_size(name, kind, bytes, &config::current())?;
+    record_in(name, kind, bytes);
+    Ok(())
+}
+
+fn check_size(
+    name: &str,
+    kind: TransferKind,
+    bytes: usize,
+    config: &FfiConfig,
+) -> Result<(), DllError> {
+    // Scalars have a fixed size known at compile time.
+    if kind == TransferKind::Scalar {
+        return Ok(());
+    }
+    let limit = config.payload_limit(name);
+    if bytes > limit {
+        return Err(DllError::PayloadTooLarge {
+            export: name.to_string(),
+            size: bytes,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// Records `bytes` copied from Rust into Go for `name`
+pub(crate) fn record_in(name: &'static str, kind: TransferKind, bytes: usize) {
+    crate::hooks::note_args(name, kind, bytes);
+    let bytes = bytes as u64;
+    {
+        let mut transfers = TRANSFERS.lock();
+        let counter = transfers.entry((name, kind)).or_default();
+        counter.bytes_in += bytes;
+        counter.copies_in += 1;
+    }
+
+    #[cfg(feature = "metrics-core")]
+    crate::metrics::record_transfer(name, "in", bytes);
+}
+
+/// Records `bytes` copied from Go back into Rust for `name`
+pub(crate) fn record_out(name: &'static str, kind: TransferKind, bytes: usize) {
+    let bytes = bytes as u64;
+    {
+        let mut transfers = TRANSFERS.lock();
+        let counter = transfers.entry((name, kind)).or_default();
+        counter.bytes_out += bytes;
+        counter.copies_out += 1;
+    }
+
+    #[cfg(feature = "metrics-core")]
+    crate::metrics::record_transfer(name, "out", bytes);
+}
+
+/// Returns how many bytes each export has copied across the boundary.
+///
+/// Useful for quantifying marshaling overhead before moving hot paths to
+/// shared memory or a zero-copy format.
+pub fn transfer_report() -> TransferReport {
+    let transfers = TRANSFERS.lock();
+    TransferReport {
+        entries: transfers
+            .iter()
+            .map(|((name, kind), counter)| TransferStats {
+                name,
+                kind: *kind,
+                bytes_in: counter.bytes_in,
+                bytes_out: counter.bytes_out,
+                copies_in: counter.copies_in,
+                copies_out: counter.copies_out,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_accumulates() {
+        record_in("test_transfer", TransferKind::String, 10);
+        record_in("test_transfer", TransferKind::String, 5);
+        record_out("test_transfer", TransferKind::String, 7);
+        record_in("test_transfer", TransferKind::Scalar, 8);
+
+        let report = transfer_report();
+        let string = report
+            .for_export("test_transfer")
+            .find(|e| e.kind == TransferKind::String)
+            .expect("String transfers should be reported");
+        assert_eq!(string.bytes_in, 15);
+        assert_eq!(string.copies_in, 2);
+        assert_eq!(string.bytes_out, 7);
+        assert_eq!(string.copies_out, 1);
+        assert_eq!(report.for_export("test_transfer").count(), 2);
+        assert!(report.total_bytes_in() >= 23);
+    }
+
+    #[test]
+    fn test_check_size() {
+        let config = FfiConfig::new().with_max_payload_bytes(8);
+        assert!(check_size("test_limit", TransferKind::Slice, 8, &config).is_ok());
+        assert!(check_size("test_limit", TransferKind::Scalar, 64, &config).is_ok());
+        match check_size("test_limit", TransferKind::Payload, 9, &config) {
+            Err(DllError::PayloadTooLarge {
+                export,
+                size,
+                limit,
+            }) => {
+                assert_eq!(export, "test_limit");
+                assert_eq!(size, 9);
+                assert_eq!(limit, 8);
+            }
+            other => panic!("Expected PayloadTooLarge, got {:?}", other),
+        }
+    }
+}
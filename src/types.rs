@@ -0,0 +1,703 @@
+//! Rust counterparts of the types cgo emits in `go_lib.h`.
+//!
+//! cgo sizes `GoInt`/`GoUint` after the target's pointer width, so the
+//! newtypes do the same. They are `#[repr(transparent)]` and therefore
+//! ABI-compatible with the generated signatures. Strings and buffers the Go
+//! side allocates come back as [`GoStr`] and [`GoBuffer`]; Rust buffers are
+//! lent to Go as [`GoBytes`] and [`GoBytesMut`].
+
+use log::{error, warn};
+use std::ffi::{c_void, CStr};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::os::raw::{c_char, c_longlong};
+use std::sync::Arc;
+
+use crate::config::{FloatPolicy, IntegerPolicy};
+use crate::ffi;
+use crate::go_alloc;
+use crate::library::Library;
+use crate::transfer::{self, TransferKind};
+use crate::{call_export, DllError};
+
+#[cfg(target_pointer_width = "64")]
+type GoIntRepr = i64;
+#[cfg(target_pointer_width = "32")]
+type GoIntRepr = i32;
+
+#[cfg(target_pointer_width = "64")]
+type GoUintRepr = u64;
+#[cfg(target_pointer_width = "32")]
+type GoUintRepr = u32;
+
+/// Go `int` as seen through cgo
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GoInt(pub GoIntRepr);
+
+/// Go `uint` as seen through cgo
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GoUint(pub GoUintRepr);
+
+/// Go `float64` as seen through cgo
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct GoFloat64(pub f64);
+
+/// A value did not fit into the requested integer type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRange {
+    pub value: i128,
+    pub target: &'static str,
+}
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "value {} does not fit into {}", self.value, self.target)
+    }
+}
+
+impl std::error::Error for OutOfRange {}
+
+// Lossless conversions into the Go types
+macro_rules! impl_from_native {
+    ($go:ident: $($native:ty),*) => {
+        $(
+            impl From<$native> for $go {
+                fn from(value: $native) -> Self {
+                    Self(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_native!(GoInt: i8, i16, i32, u8, u16);
+impl_from_native!(GoUint: u8, u16, u32);
+impl_from_native!(GoFloat64: f32, f64, i8, i16, i32, u8, u16, u32);
+
+#[cfg(target_pointer_width = "64")]
+impl_from_native!(GoInt: i64, u32);
+#[cfg(target_pointer_width = "64")]
+impl_from_native!(GoUint: u64);
+
+// Checked conversions in both directions
+macro_rules! impl_try_from {
+    ($go:ident: $($native:ty),*) => {
+        $(
+            impl TryFrom<$go> for $native {
+                type Error = OutOfRange;
+
+                fn try_from(value: $go) -> Result<Self, Self::Error> {
+                    <$native>::try_from(value.0).map_err(|_| OutOfRange {
+                        value: value.0 as i128,
+                        target: stringify!($native),
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from!(GoInt: i8, i16, i32, u8, u16, u32, u64, usize, isize);
+impl_try_from!(GoUint: i8, i16, i32, i64, u8, u16, u32, usize, isize);
+
+// The conversions below are the identity on 64-bit targets.
+impl From<GoInt> for i64 {
+    #[allow(clippy::useless_conversion)]
+    fn from(value: GoInt) -> Self {
+        value.0.into()
+    }
+}
+
+impl From<GoUint> for u64 {
+    #[allow(clippy::useless_conversion)]
+    fn from(value: GoUint) -> Self {
+        value.0.into()
+    }
+}
+
+impl From<GoFloat64> for f64 {
+    fn from(value: GoFloat64) -> Self {
+        value.0
+    }
+}
+
+#[cfg(target_pointer_width = "32")]
+impl TryFrom<i64> for GoInt {
+    type Error = OutOfRange;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        GoIntRepr::try_from(value)
+            .map(Self)
+            .map_err(|_| OutOfRange {
+                value: value.into(),
+                target: "GoInt",
+            })
+    }
+}
+
+#[cfg(target_pointer_width = "32")]
+impl TryFrom<u64> for GoUint {
+    type Error = OutOfRange;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        GoUintRepr::try_from(value)
+            .map(Self)
+            .map_err(|_| OutOfRange {
+                value: value.into(),
+                target: "GoUint",
+            })
+    }
+}
+
+/// Narrows an integer result to `i32` according to `policy`
+pub(crate) fn narrow_i32(value: i64, policy: IntegerPolicy) -> Result<i32, OutOfRange> {
+    match policy {
+        IntegerPolicy::Checked => i32::try_from(value).map_err(|_| OutOfRange {
+            value: value.into(),
+            target: "i32",
+        }),
+        IntegerPolicy::Wrapping => Ok(value as i32),
+    }
+}
+
+/// A Rust scalar the generated wrappers convert to and from its C type.
+///
+/// Conversions fail instead of truncating, and floats are checked against
+/// the [`FloatPolicy`].
+pub(crate) trait FfiScalar: Sized {
+    type Ffi: Copy;
+
+    fn to_ffi(self, export: &'static str, policy: FloatPolicy) -> Result<Self::Ffi, DllError>;
+
+    fn from_ffi(
+        value: Self::Ffi,
+        export: &'static str,
+        policy: FloatPolicy,
+    ) -> Result<Self, DllError>;
+}
+
+macro_rules! impl_ffi_scalar_identity {
+    ($($ty:ty),*) => {
+        $(
+            impl FfiScalar for $ty {
+                type Ffi = $ty;
+
+                fn to_ffi(self, _: &'static str, _: FloatPolicy) -> Result<$ty, DllError> {
+                    Ok(self)
+                }
+
+                fn from_ffi(value: $ty, _: &'static str, _: FloatPolicy) -> Result<$ty, DllError> {
+                    Ok(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_ffi_scalar_identity!(i8, u8, i16, u16, i32, u32, i64, u64, bool);
+
+/// A type of the bindings that carries the [`FfiScalar`] type `T` across.
+///
+/// The header's typedefs are plain aliases and carry themselves; the
+/// newtypes carry their representation.
+pub(crate) trait HeaderType<T>: Copy {
+    fn wrap(value: T) -> Self;
+
+    fn unwrap(self) -> T;
+}
+
+impl<T: Copy> HeaderType<T> for T {
+    fn wrap(value: T) -> Self {
+        value
+    }
+
+    fn unwrap(self) -> T {
+        self
+    }
+}
+
+macro_rules! impl_header_type_newtype {
+    ($($go:ident($repr:ty)),*) => {
+        $(
+            impl HeaderType<$repr> for $go {
+                fn wrap(value: $repr) -> Self {
+                    Self(value)
+                }
+
+                fn unwrap(self) -> $repr {
+                    self.0
+                }
+            }
+        )*
+    };
+}
+
+impl_header_type_newtype!(GoInt(GoIntRepr), GoUint(GoUintRepr), GoFloat64(f64));
+
+macro_rules! impl_ffi_scalar_float {
+    ($($ty:ty),*) => {
+        $(
+            impl FfiScalar for $ty {
+                type Ffi = $ty;
+
+                fn to_ffi(self, export: &'static str, policy: FloatPolicy) -> Result<$ty, DllError> {
+                    check_float(export, self.into(), stringify!($ty), policy)?;
+                    Ok(self)
+                }
+
+                fn from_ffi(
+                    value: $ty,
+                    export: &'static str,
+                    policy: FloatPolicy,
+                ) -> Result<$ty, DllError> {
+                    check_float(export, value.into(), stringify!($ty), policy)?;
+                    Ok(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_ffi_scalar_float!(f32, f64);
+
+fn check_float(
+    export: &str,
+    value: f64,
+    target: &'static str,
+    policy: FloatPolicy,
+) -> Result<(), DllError> {
+    if policy == FloatPolicy::Reject && !value.is_finite() {
+        return Err(DllError::FloatOutOfRange {
+            export: export.to_string(),
+            value,
+            target,
+        });
+    }
+    Ok(())
+}
+
+/// Crosses as two 64-bit halves, since C has no portable 128-bit integer
+impl FfiScalar for i128 {
+    type Ffi = ffi::Int128;
+
+    fn to_ffi(self, _: &'static str, _: FloatPolicy) -> Result<ffi::Int128, DllError> {
+        Ok(ffi::Int128 {
+            lo: self as u64,
+            hi: (self >> 64) as i64,
+        })
+    }
+
+    fn from_ffi(value: ffi::Int128, _: &'static str, _: FloatPolicy) -> Result<i128, DllError> {
+        Ok(((value.hi as i128) << 64) | value.lo as i128)
+    }
+}
+
+/// A string returned by a Go export, owned until dropped.
+///
+/// Borrows as `&str` without copying; the UTF-8 check happens once when it
+/// is received. Dropping it hands the memory back through the library's
+/// `FreeGoString` export, and it keeps that copy of the library loaded until
+/// then.
+pub struct GoStr {
+    library: Arc<Library>,
+    raw: *mut c_char,
+    len: usize,
+}
+
+// The string is never mutated, and FreeGoString may be called from any thread.
+unsafe impl Send for GoStr {}
+unsafe impl Sync for GoStr {}
+
+impl GoStr {
+    /// Takes ownership of a string `export` returned.
+    ///
+    /// A null pointer becomes the empty string. The string is released even
+    /// when it turns out not to be valid UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be null or a NUL-terminated string allocated by `library`
+    /// that nothing else frees.
+    pub(crate) unsafe fn from_raw(
+        library: Arc<Library>,
+        export: &'static str,
+        raw: *mut c_char,
+    ) -> Result<Self, DllError> {
+        let len = if raw.is_null() {
+            0
+        } else {
+            CStr::from_ptr(raw).to_bytes().len()
+        };
+        if !raw.is_null() {
+            go_alloc::track(&library, export, raw as usize, len + 1);
+        }
+        let string = Self { library, raw, len };
+        transfer::record_out(export, TransferKind::String, len + 1);
+        std::str::from_utf8(string.as_bytes()).map_err(|source| DllError::InvalidUtf8 {
+            export: export.to_string(),
+            source,
+        })?;
+        Ok(string)
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        if self.raw.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.raw.cast::<u8>(), self.len) }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Validated in `from_raw`.
+        unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
+    }
+}
+
+impl Deref for GoStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for GoStr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for GoStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for GoStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<str> for GoStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for GoStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Drop for GoStr {
+    fn drop(&mut self) {
+        if self.raw.is_null() || !go_alloc::release(self.raw as usize, "FreeGoString") {
+            return;
+        }
+        let library = &self.library;
+        match library.optional_symbol(&library.symbols.free_go_string) {
+            Some(free) => {
+                let raw = self.raw;
+                if let Err(e) = call_export("FreeGoString", || unsafe { free(raw) }) {
+                    error!("Failed to release Go string: {}", e);
+                }
+            }
+            // Leaking is the only safe option without the library's allocator.
+            None => warn!("Library has no FreeGoString export, leaking string"),
+        }
+    }
+}
+
+/// A point with 64-bit coordinates, as taken by `ScalePoint`/`TranslatePoint`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+crate::ffi_struct!(Point => crate::ffi::Point { x, y });
+crate::assert_ffi_layout!(Point, crate::ffi::Point);
+
+/// A Rust byte slice lent to a Go export as pointer and length.
+///
+/// Nothing is copied; Go reads the caller's memory directly and must not
+/// keep the pointer after the call returns.
+#[derive(Debug, Clone, Copy)]
+pub struct GoBytes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> GoBytes<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Pointer and length as the export expects them, after checking the
+    /// payload limit for `export`. The pointer is `*mut` only because cgo
+    /// headers carry no `const`; Go does not write through it.
+    pub(crate) fn marshal(self, export: &'static str) -> Result<(*mut u8, c_longlong), DllError> {
+        transfer::marshal_in(export, TransferKind::Slice, self.data.len())?;
+        Ok((self.data.as_ptr().cast_mut(), c_len(self.data.len())?))
+    }
+}
+
+impl<'a> From<&'a [u8]> for GoBytes<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        Self::new(data)
+    }
+}
+
+/// A mutable Rust byte slice lent to a Go export, which may write into it
+/// in place.
+#[derive(Debug)]
+pub struct GoBytesMut<'a> {
+    data: &'a mut [u8],
+}
+
+impl<'a> GoBytesMut<'a> {
+    pub fn new(data: &'a mut [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Pointer and length as the export expects them, after checking the
+    /// payload limit for `export`
+    pub(crate) fn marshal(
+        &mut self,
+        export: &'static str,
+    ) -> Result<(*mut u8, c_longlong), DllError> {
+        transfer::marshal_in(export, TransferKind::Slice, self.data.len())?;
+        Ok((self.data.as_mut_ptr(), c_len(self.data.len())?))
+    }
+}
+
+impl<'a> From<&'a mut [u8]> for GoBytesMut<'a> {
+    fn from(data: &'a mut [u8]) -> Self {
+        Self::new(data)
+    }
+}
+
+pub(crate) fn c_len(len: usize) -> Result<c_longlong, OutOfRange> {
+    c_longlong::try_from(len).map_err(|_| OutOfRange {
+        value: len as i128,
+        target: "c_longlong",
+    })
+}
+
+/// A byte buffer returned by a Go export, owned until dropped.
+///
+/// Derefs to `&[u8]` without copying. Dropping it hands the memory back
+/// through the library's `FreeGoBytes` export.
+pub struct GoBuffer {
+    library: Arc<Library>,
+    raw: *mut c_void,
+    len: usize,
+    _owned: PhantomData<[u8]>,
+}
+
+// The buffer is never mutated, and FreeGoBytes may be called from any thread.
+unsafe impl Send for GoBuffer {}
+unsafe impl Sync for GoBuffer {}
+
+impl GoBuffer {
+    /// Takes ownership of a buffer of `len` bytes that `export` returned.
+    ///
+    /// A null pointer becomes the empty buffer.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be null or point to `len` bytes allocated by `library` that
+    /// nothing else frees.
+    pub(crate) unsafe fn from_raw(
+        library: Arc<Library>,
+        export: &'static str,
+        raw: *mut c_void,
+        len: c_longlong,
+    ) -> Result<Self, DllError> {
+        if !raw.is_null() {
+            go_alloc::track(&library, export, raw as usize, len.max(0) as usize);
+        }
+        let mut buffer = Self {
+            library,
+            raw,
+            len: 0,
+            _owned: PhantomData,
+        };
+        if !raw.is_null() {
+            buffer.len = usize::try_from(len).map_err(|_| OutOfRange {
+                value: len.into(),
+                target: "usize",
+            })?;
+        }
+        transfer::record_out(export, TransferKind::Slice, buffer.len);
+        Ok(buffer)
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        if self.raw.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.raw.cast::<u8>(), self.len) }
+    }
+}
+
+impl Deref for GoBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for GoBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl fmt::Debug for GoBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GoBuffer").field("len", &self.len).finish()
+    }
+}
+
+impl Drop for GoBuffer {
+    fn drop(&mut self) {
+        if self.raw.is_null() || !go_alloc::release(self.raw as usize, "FreeGoBytes") {
+            return;
+        }
+        let library = &self.library;
+        match library.optional_symbol(&library.symbols.free_go_bytes) {
+            Some(free) => {
+                let raw = self.raw;
+                if let Err(e) = call_export("FreeGoBytes", || unsafe { free(raw) }) {
+                    error!("Failed to release Go buffer: {}", e);
+                }
+            }
+            // Leaking is the only safe option without the library's allocator.
+            None => warn!("Library has no FreeGoBytes export, leaking buffer"),
+        }
+    }
+}
+
+impl fmt::Display for GoInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for GoUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for GoFloat64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_matches_cgo() {
+        assert_eq!(
+            std::mem::size_of::<GoInt>(),
+            std::mem::size_of::<*const ()>()
+        );
+        assert_eq!(
+            std::mem::size_of::<GoUint>(),
+            std::mem::size_of::<*const ()>()
+        );
+        assert_eq!(std::mem::size_of::<GoFloat64>(), 8);
+    }
+
+    #[test]
+    fn test_checked_narrowing() {
+        assert_eq!(i32::try_from(GoInt::from(42)), Ok(42));
+
+        let err = i8::try_from(GoInt::from(300)).unwrap_err();
+        assert_eq!(err.value, 300);
+        assert_eq!(err.target, "i8");
+        assert_eq!(err.to_string(), "value 300 does not fit into i8");
+    }
+
+    #[test]
+    fn test_integer_policy() {
+        let big = i64::from(i32::MAX) + 1;
+        let err = narrow_i32(big, IntegerPolicy::Checked).unwrap_err();
+        assert_eq!(err.target, "i32");
+        assert_eq!(narrow_i32(big, IntegerPolicy::Wrapping), Ok(i32::MIN));
+        assert_eq!(narrow_i32(-7, IntegerPolicy::Checked), Ok(-7));
+    }
+
+    #[test]
+    fn test_i128_split() {
+        for value in [0, -1, 1 << 64, i128::MIN, i128::MAX, -(1 << 70) + 5] {
+            let split = value.to_ffi("AddInt128", FloatPolicy::Reject).unwrap();
+            let joined = i128::from_ffi(split, "AddInt128", FloatPolicy::Reject).unwrap();
+            assert_eq!(joined, value);
+        }
+    }
+
+    #[test]
+    fn test_header_type_newtypes() {
+        let int = <GoInt as HeaderType<GoIntRepr>>::wrap(-7);
+        assert_eq!(int, GoInt(-7));
+        assert_eq!(HeaderType::<GoIntRepr>::unwrap(int), -7);
+        assert_eq!(<GoFloat64 as HeaderType<f64>>::wrap(0.5), GoFloat64(0.5));
+        assert_eq!(<i64 as HeaderType<i64>>::wrap(3), 3);
+    }
+
+    #[test]
+    fn test_float_policy() {
+        assert!(f64::NAN.to_ffi("Hypot", FloatPolicy::Reject).is_err());
+        assert!(f32::INFINITY.to_ffi("Hypot", FloatPolicy::Reject).is_err());
+        assert!(f64::NAN
+            .to_ffi("Hypot", FloatPolicy::Allow)
+            .unwrap()
+            .is_nan());
+        assert_eq!(1.5f64.to_ffi("Hypot", FloatPolicy::Reject).unwrap(), 1.5);
+    }
+
+    #[test]
+    fn test_point_round_trip() {
+        use crate::ffi::FfiStruct;
+
+        let point = Point { x: -3, y: 7 };
+        let repr = point.to_repr().unwrap();
+        assert_eq!((repr.x, repr.y), (-3, 7));
+        assert_eq!(Point::from_repr(repr).unwrap(), point);
+    }
+
+    #[test]
+    fn test_bytes_marshal() {
+        let data = *b"hello";
+        let (ptr, len) = GoBytes::new(&data).marshal("Crc32").unwrap();
+        assert_eq!(ptr.cast_const(), data.as_ptr());
+        assert_eq!(len, 5);
+
+        let mut data = *b"hello";
+        let expected = data.as_mut_ptr();
+        let (ptr, len) = GoBytesMut::from(&mut data[..])
+            .marshal("UpperBytes")
+            .unwrap();
+        assert_eq!(ptr, expected);
+        assert_eq!(len, 5);
+    }
+
+    #[test]
+    fn test_negative_into_unsigned() {
+        assert!(u32::try_from(GoInt::from(-1)).is_err());
+        assert!(i32::try_from(GoUint::from(u32::MAX)).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::SystemTime;
+
+/// Exports the safe wrappers know how to call, including the ones
+/// generated from the header
+pub static KNOWN_EXPORTS: Lazy<Vec<&'static str>> = Lazy::new(crate::library::known_exports);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counter {
+    calls: u64,
+    last_called: Option<SystemTime>,
+}
+
+static USAGE: Lazy<Mutex<BTreeMap<&'static str, Counter>>> = Lazy::new(|| {
+    Mutex::new(
+        KNOWN_EXPORTS
+            .iter()
+            .map(|name| (*name, Counter::default()))
+            .collect(),
+    )
+});
+
+/// Usage of a single export since process start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportUsage {
+    pub name: &'static str,
+    pub calls: u64,
+    pub last_called: Option<SystemTime>,
+}
+
+/// Snapshot of which exports have been invoked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageReport {
+    pub exports: Vec<ExportUsage>,
+}
+
+impl UsageReport {
+    /// Exports that were never called
+    pub fn unused(&self) -> impl Iterator<Item = &ExportUsage> {
+        self.exports.iter().filter(|e| e.calls == 0)
+    }
+
+    pub fn total_calls(&self) -> u64 {
+        self.exports.iter().map(|e| e.calls).sum()
+    }
+}
+
+impl fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for export in &self.exports {
+            writeln!(f, "{:<24} {:>10} calls", export.name, export.calls)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records one invocation of `name`
+pub(crate) fn record(name: &'static str) {
+    let mut usage = USAGE.lock();
+    let counter = usage.entry(name).or_default();
+    counter.calls += 1;
+    counter.last_called = Some(SystemTime::now());
+}
+
+/// Returns how often each export has been invoked.
+///
+/// All known exports are listed, including ones that were never called, so
+/// the report can be used to find wrappers that are candidates for removal.
+pub fn usage_report() -> UsageReport {
+    let usage = USAGE.lock();
+    UsageReport {
+        exports: usage
+            .iter()
+            .map(|(name, counter)| ExportUsage {
+                name,
+                calls: counter.calls,
+                last_called: counter.last_called,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_exports_listed() {
+        let report = usage_report();
+        for name in KNOWN_EXPORTS.iter() {
+            assert!(report.exports.iter().any(|e| e.name == *name));
+        }
+    }
+
+    #[test]
+    fn test_record_increments() {
+        let before = usage_report()
+            .exports
+            .into_iter()
+            .find(|e| e.name == "test_export")
+            .map_or(0, |e| e.calls);
+
+        record("test_export");
+        record("test_export");
+
+        let report = usage_report();
+        let entry = report
+            .exports
+            .iter()
+            .find(|e| e.name == "test_export")
+            .expect("Recorded export should be reported");
+        assert_eq!(entry.calls, before + 2);
+        assert!(entry.last_called.is_some());
+    }
+}
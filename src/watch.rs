@@ -0,0 +1,174 @@
+//! File-watcher driven reloads of the Go library.
+
+use log::{debug, info, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::DllError;
+
+/// Builds usually write the DLL in several steps; wait for writes to settle.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Progress of a watcher-triggered reload
+#[derive(Debug)]
+pub enum WatchEvent<'a> {
+    /// The file changed and a reload is about to start; pause traffic here
+    BeforeSwap(&'a Path),
+    /// The reload finished; `true` if a new copy was swapped in
+    AfterSwap(bool),
+    /// The reload failed and the previous copy is still active
+    Failed(&'a DllError),
+}
+
+/// Watches the DLL and reloads it when the file changes.
+///
+/// Dropping the watcher stops the background thread.
+pub struct DllWatcher {
+    watcher: Option<RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DllWatcher {
+    /// Starts watching the currently resolved DLL path.
+    ///
+    /// `hook` is called on the watcher thread for every reload attempt.
+    pub fn start<F>(hook: F) -> Result<Self, DllError>
+    where
+        F: FnMut(WatchEvent<'_>) + Send + 'static,
+    {
+        let path = crate::get_dll_path().ok_or(DllError::NotFound)?;
+        Self::start_at(path, hook)
+    }
+
+    fn start_at<F>(path: PathBuf, mut hook: F) -> Result<Self, DllError>
+    where
+        F: FnMut(WatchEvent<'_>) + Send + 'static,
+    {
+        let watch_error =
+            |e: notify::Error| DllError::LoadError(format!("Failed to watch DLL: {}", e));
+
+        // Watch the directory: builds often replace the file instead of
+        // writing it in place, which ends a watch on the file itself.
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file_name = path.file_name().map(|n| n.to_os_string());
+
+        let (tx, rx) = mpsc::channel::<notify::Result<notify::Event>>();
+        let mut watcher = notify::recommended_watcher(tx).map_err(watch_error)?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+        info!("Watching {} for changes", path.display());
+
+        let thread = thread::Builder::new()
+            .name("rust_go_ffi-watch".to_string())
+            .spawn(move || {
+                let is_ours = |event: &notify::Event| {
+                    (event.kind.is_create() || event.kind.is_modify())
+                        && event
+                            .paths
+                            .iter()
+                            .any(|p| p.file_name() == file_name.as_deref())
+                };
+                let mut pending = false;
+                loop {
+                    match rx.recv_timeout(DEBOUNCE) {
+                        Ok(Ok(event)) => {
+                            if is_ours(&event) {
+                                pending = true;
+                            }
+                        }
+                        Ok(Err(e)) => warn!("DLL watch error: {}", e),
+                        Err(RecvTimeoutError::Timeout) => {
+                            if pending {
+                                pending = false;
+                                reload(&path, &mut hook);
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                debug!("DLL watcher stopped");
+            })
+            .map_err(|e| DllError::LoadError(format!("Failed to start watcher: {}", e)))?;
+
+        Ok(Self {
+            watcher: Some(watcher),
+            thread: Some(thread),
+        })
+    }
+}
+
+fn reload<F>(path: &Path, hook: &mut F)
+where
+    F: FnMut(WatchEvent<'_>),
+{
+    hook(WatchEvent::BeforeSwap(path));
+    match crate::reload_dll() {
+        Ok(swapped) => hook(WatchEvent::AfterSwap(swapped)),
+        Err(e) => {
+            warn!("Reloading {} failed: {}", path.display(), e);
+            hook(WatchEvent::Failed(&e));
+        }
+    }
+}
+
+impl Drop for DllWatcher {
+    fn drop(&mut self) {
+        // Dropping the watcher closes the channel, which ends the thread.
+        self.watcher.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Starts a [`DllWatcher`] for the currently resolved DLL path
+pub fn watch_dll<F>(hook: F) -> Result<DllWatcher, DllError>
+where
+    F: FnMut(WatchEvent<'_>) + Send + 'static,
+{
+    DllWatcher::start(hook)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    #[test]
+    fn test_watcher_reports_change() {
+        let dir = std::env::temp_dir().join("rust_go_ffi_watch_test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("go_lib.dll");
+        fs::write(&path, b"v1").unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let watcher = DllWatcher::start_at(path.clone(), move |event| {
+            if let WatchEvent::BeforeSwap(p) = event {
+                recorded.lock().unwrap().push(p.to_path_buf());
+            }
+        })
+        .expect("Watcher should start");
+
+        fs::write(&path, b"v2").unwrap();
+        let deadline = Instant::now() + DEBOUNCE * 40;
+        while events.lock().unwrap().is_empty() && Instant::now() < deadline {
+            thread::sleep(DEBOUNCE);
+        }
+        drop(watcher);
+
+        let events = events.lock().unwrap();
+        assert!(!events.is_empty(), "The change should trigger a reload");
+        assert!(events.iter().all(|p| p == &path));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
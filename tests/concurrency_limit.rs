@@ -0,0 +1,59 @@
+//! `FfiConfig::with_max_concurrent_calls` is process-wide, so these tests
+//! run in their own binary instead of next to the ones in integration_test.rs.
+
+use rust_go_ffi::{
+    add_numbers, count_with_progress, initialize_with_config, is_dll_available, register_callback,
+    with_dll, with_timeout, DllError, ExecutorConfig, FfiConfig,
+};
+use semver::Version;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_concurrency_limit() {
+    if !is_dll_available() {
+        println!("Skipping concurrency limit test as DLL is not available");
+        return;
+    }
+    let config = FfiConfig::default()
+        .with_max_concurrent_calls(1)
+        .with_executor(ExecutorConfig::new(2));
+    initialize_with_config(Version::new(0, 1, 0), config).unwrap();
+
+    // One call chain takes one slot, whichever threads it runs on.
+    let sum = with_dll(|| with_timeout(Duration::from_secs(10), || add_numbers(1, 2))).unwrap();
+    assert_eq!(sum, 3);
+
+    // A callback runs inline on the executor worker of the call it is part of.
+    let sums = Arc::new(Mutex::new(Vec::new()));
+    let seen = sums.clone();
+    let handle = register_callback("progress", move |_| {
+        seen.lock().unwrap().push(add_numbers(2, 2)?);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(count_with_progress(2).unwrap(), 2);
+    assert_eq!(*sums.lock().unwrap(), [4, 4]);
+    drop(handle);
+
+    // Another chain is turned away while the only slot is held.
+    let (entered_tx, entered) = mpsc::channel();
+    let (release, release_rx) = mpsc::channel::<()>();
+    let holder = thread::spawn(move || {
+        with_dll(|| {
+            entered_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+            add_numbers(2, 3)
+        })
+    });
+    entered.recv().unwrap();
+    assert!(matches!(
+        add_numbers(1, 2),
+        Err(DllError::Busy { limit: 1 })
+    ));
+    release.send(()).unwrap();
+    assert_eq!(holder.join().unwrap().unwrap(), 5);
+    assert_eq!(add_numbers(1, 2).unwrap(), 3);
+}
@@ -1,4 +1,11 @@
-use rust_go_ffi::{self, add_numbers, go_function, is_dll_available, verify_dll};
+use rust_go_ffi::{
+    self, add_numbers, go_function, go_runtime_version, is_dll_available, run_self_test, verify_dll,
+};
+use std::sync::Mutex;
+
+/// Held by tests that register the "progress" callback, which the self-test
+/// also uses.
+static PROGRESS_CALLBACK: Mutex<()> = Mutex::new(());
 
 #[test]
 fn test_full_dll_workflow() {
@@ -21,6 +28,398 @@ fn test_full_dll_workflow() {
     }
 }
 
+#[test]
+fn test_self_test_passes() {
+    if !is_dll_available() {
+        println!("Skipping self-test as DLL is not available");
+        return;
+    }
+    let _progress = PROGRESS_CALLBACK.lock().unwrap_or_else(|e| e.into_inner());
+    let report = run_self_test();
+    println!("{}", report);
+    assert!(report.passed(), "Self-test should pass: {:?}", report);
+}
+
+#[test]
+fn test_go_allocations_are_released() {
+    use rust_go_ffi::{decode_base64, leaked_allocations};
+
+    if !is_dll_available() {
+        println!("Skipping allocation test as DLL is not available");
+        return;
+    }
+    let decoded = decode_base64(b"aGVsbG8=").unwrap();
+    let address = decoded.as_ptr() as usize;
+    let allocation = leaked_allocations()
+        .into_iter()
+        .find(|a| a.address == address)
+        .expect("the buffer should be recorded");
+    assert_eq!(allocation.export, "DecodeBase64");
+    drop(decoded);
+    // Another test may get the same address from Go again, but not at the same time.
+    assert!(!leaked_allocations().contains(&allocation));
+}
+
+#[test]
+fn test_string_return() {
+    if !is_dll_available() {
+        println!("Skipping string test as DLL is not available");
+        return;
+    }
+    let version = go_runtime_version().expect("GoRuntimeVersion should return a string");
+    assert!(
+        version.starts_with("go"),
+        "Unexpected Go version {}",
+        version
+    );
+}
+
+#[test]
+fn test_byte_slices() {
+    use rust_go_ffi::{crc32, decode_base64, upper_bytes, DllError};
+
+    if !is_dll_available() {
+        println!("Skipping byte slice test as DLL is not available");
+        return;
+    }
+    assert_eq!(crc32(b"123456789").unwrap(), 0xcbf4_3926);
+
+    let mut data = *b"hello, go";
+    upper_bytes(&mut data).unwrap();
+    assert_eq!(&data, b"HELLO, GO");
+
+    assert_eq!(&*decode_base64(b"aGVsbG8=").unwrap(), b"hello");
+    assert!(matches!(
+        decode_base64(b"not base64!"),
+        Err(DllError::CallFailed { .. })
+    ));
+}
+
+#[test]
+fn test_struct_passing() {
+    use rust_go_ffi::types::Point;
+    use rust_go_ffi::{scale_point, translate_point};
+
+    if !is_dll_available() {
+        println!("Skipping struct test as DLL is not available");
+        return;
+    }
+    let point = scale_point(Point { x: 2, y: -3 }, 4).unwrap();
+    assert_eq!(point, Point { x: 8, y: -12 });
+
+    let mut point = Point { x: 1, y: 1 };
+    translate_point(&mut point, 10, -1).unwrap();
+    assert_eq!(point, Point { x: 11, y: 0 });
+}
+
+#[test]
+fn test_json_bridge() {
+    use rust_go_ffi::{call_json, DllError};
+    use serde_json::json;
+
+    if !is_dll_available() {
+        println!("Skipping JSON bridge test as DLL is not available");
+        return;
+    }
+    assert_eq!(
+        call_json("AddNumbers", &json!({ "a": 2, "b": 40 })).unwrap(),
+        json!(42)
+    );
+    let payload = json!({ "name": "gopher", "tags": ["a", "b"] });
+    assert_eq!(call_json("Echo", &payload).unwrap(), payload);
+    assert!(matches!(
+        call_json("NoSuchFunction", &json!(null)),
+        Err(DllError::CallFailed { .. })
+    ));
+}
+
+#[test]
+#[cfg(feature = "binary-bridge")]
+fn test_binary_bridge() {
+    use rust_go_ffi::{call_cbor, call_msgpack};
+    use std::collections::BTreeMap;
+
+    if !is_dll_available() {
+        println!("Skipping binary bridge test as DLL is not available");
+        return;
+    }
+    let args = BTreeMap::from([("a", 20), ("b", 22)]);
+    let sum: i64 = call_msgpack("AddNumbers", &args).unwrap();
+    assert_eq!(sum, 42);
+    let sum: i64 = call_cbor("AddNumbers", &args).unwrap();
+    assert_eq!(sum, 42);
+
+    let echoed: Vec<String> = call_cbor("Echo", &["a", "b"]).unwrap();
+    assert_eq!(echoed, ["a", "b"]);
+}
+
+#[test]
+#[cfg(feature = "proto-bridge")]
+fn test_proto_bridge() {
+    use rust_go_ffi::call_proto;
+    use rust_go_ffi::proto::bridge::{NegateRequest, NegateResponse};
+
+    if !is_dll_available() {
+        println!("Skipping proto bridge test as DLL is not available");
+        return;
+    }
+    let negated: NegateResponse = call_proto("Negate", &NegateRequest { value: 41 }).unwrap();
+    assert_eq!(negated.value, -41);
+}
+
+#[test]
+fn test_scalar_types() {
+    use rust_go_ffi::{add_i128, add_u64, halve_f32, hypot, is_even, DllError};
+
+    if !is_dll_available() {
+        println!("Skipping scalar test as DLL is not available");
+        return;
+    }
+    assert_eq!(hypot(3.0, 4.0).unwrap(), 5.0);
+    assert!(matches!(
+        hypot(f64::NAN, 1.0),
+        Err(DllError::FloatOutOfRange { .. })
+    ));
+    assert_eq!(halve_f32(3.0).unwrap(), 1.5);
+    assert_eq!(add_u64(u64::MAX - 1, 1).unwrap(), u64::MAX);
+    assert!(add_u64(u64::MAX, 1).is_err());
+    assert_eq!(add_i128(u64::MAX as i128, 1).unwrap(), 1 << 64);
+    assert_eq!(add_i128(-5, 3).unwrap(), -2);
+    assert!(add_i128(i128::MAX, 1).is_err());
+    assert!(is_even(4).unwrap());
+    assert!(!is_even(-3).unwrap());
+}
+
+#[test]
+fn test_batch_calls() {
+    use rust_go_ffi::{add_numbers_batch, call_json_batch};
+    use serde_json::json;
+
+    if !is_dll_available() {
+        println!("Skipping batch test as DLL is not available");
+        return;
+    }
+    let pairs: Vec<(i32, i32)> = (0..1000).map(|i| (i, 2 * i)).collect();
+    let sums = add_numbers_batch(&pairs).unwrap();
+    assert_eq!(sums.len(), 1000);
+    assert_eq!(sums[999], 2997);
+    assert!(add_numbers_batch(&[]).unwrap().is_empty());
+    assert!(add_numbers_batch(&[(i32::MAX, 1)]).is_err());
+
+    let results = call_json_batch(&[
+        ("AddNumbers", json!({"a": 1, "b": 2})),
+        ("NoSuchFunction", json!(null)),
+        ("Echo", json!("hi")),
+    ])
+    .unwrap();
+    assert_eq!(results[0].as_ref().unwrap(), &json!(3));
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().unwrap(), &json!("hi"));
+}
+
+#[test]
+fn test_call_by_name() {
+    use rust_go_ffi::{types::GoInt, DllError, FunctionRegistry};
+
+    if !is_dll_available() {
+        println!("Skipping registry test as DLL is not available");
+        return;
+    }
+    let registry = FunctionRegistry::load().expect("DescribeAPI should succeed");
+    let sum: GoInt = registry
+        .call("AddNumbers", (GoInt::from(2), GoInt::from(40)))
+        .unwrap();
+    assert_eq!(sum, GoInt::from(42));
+    registry.call::<(), ()>("GoFunction", ()).unwrap();
+
+    assert!(matches!(
+        registry.call::<(i32,), i32>("AddNumbers", (1,)),
+        Err(DllError::SignatureMismatch { .. })
+    ));
+    assert!(matches!(
+        registry.call::<(), ()>("NoSuchFunction", ()),
+        Err(DllError::MissingSymbol { .. })
+    ));
+}
+
+#[test]
+fn test_callback_round_trip() {
+    use rust_go_ffi::{count_with_progress, register_callback, DllError};
+    use std::sync::Arc;
+
+    if !is_dll_available() {
+        println!("Skipping callback test as DLL is not available");
+        return;
+    }
+    let _progress = PROGRESS_CALLBACK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let steps = Arc::new(Mutex::new(Vec::new()));
+    let seen = steps.clone();
+    let handle = register_callback("progress", move |step| {
+        seen.lock()
+            .unwrap()
+            .push(String::from_utf8_lossy(step).into_owned());
+        Ok(())
+    })
+    .unwrap();
+    assert!(register_callback("progress", |_| Ok(())).is_err());
+    assert_eq!(count_with_progress(3).unwrap(), 3);
+    assert_eq!(*steps.lock().unwrap(), ["1/3", "2/3", "3/3"]);
+    drop(handle);
+
+    // Without a registered callback Go just counts.
+    assert_eq!(count_with_progress(2).unwrap(), 2);
+
+    let _handle = register_callback("progress", |_| Err("stop".into())).unwrap();
+    assert!(matches!(
+        count_with_progress(2),
+        Err(DllError::CallbackError(e)) if e.to_string() == "stop"
+    ));
+}
+
+#[test]
+fn test_go_log_bridge() {
+    use rust_go_ffi::bridge_go_logs;
+
+    if !is_dll_available() {
+        println!("Skipping log bridge test as DLL is not available");
+        return;
+    }
+    bridge_go_logs().unwrap();
+    // Bridging again keeps the registered callback.
+    bridge_go_logs().unwrap();
+}
+
+#[test]
+fn test_capabilities() {
+    use rust_go_ffi::{capabilities, Capabilities};
+
+    if !is_dll_available() {
+        println!("Skipping capabilities test as DLL is not available");
+        return;
+    }
+    let capabilities = capabilities().unwrap();
+    assert!(capabilities.contains(Capabilities::STRINGS | Capabilities::CALLBACKS));
+}
+
+#[test]
+fn test_abi_check() {
+    use rust_go_ffi::abi_check;
+
+    if !is_dll_available() {
+        println!("Skipping ABI check test as DLL is not available");
+        return;
+    }
+    abi_check().unwrap();
+}
+
+#[test]
+fn test_dll_build_info() {
+    use rust_go_ffi::{dll_build_info, go_runtime_version};
+
+    if !is_dll_available() {
+        println!("Skipping build info test as DLL is not available");
+        return;
+    }
+    let info = dll_build_info().unwrap();
+    assert_eq!(info.go_version, go_runtime_version().unwrap().as_str());
+}
+
+#[test]
+fn test_go_runtime_stats() {
+    use rust_go_ffi::go_runtime_stats;
+
+    if !is_dll_available() {
+        println!("Skipping runtime stats test as DLL is not available");
+        return;
+    }
+    let stats = go_runtime_stats().unwrap();
+    assert!(stats.goroutines > 0);
+    assert!(stats.heap_bytes > 0);
+}
+
+#[test]
+fn test_go_gc_tuning() {
+    use rust_go_ffi::{set_go_gc_percent, set_go_memory_limit};
+
+    if !is_dll_available() {
+        println!("Skipping GC tuning test as DLL is not available");
+        return;
+    }
+    let percent = set_go_gc_percent(50).unwrap();
+    assert_eq!(set_go_gc_percent(percent).unwrap(), 50);
+
+    let limit = set_go_memory_limit(1 << 30).unwrap();
+    assert_eq!(set_go_memory_limit(-1).unwrap(), 1 << 30);
+    set_go_memory_limit(limit).unwrap();
+}
+
+#[test]
+fn test_event_subscription() {
+    use rust_go_ffi::{start_ticker, subscribe_events};
+    use std::time::Duration;
+
+    if !is_dll_available() {
+        println!("Skipping event test as DLL is not available");
+        return;
+    }
+    let events = subscribe_events().unwrap();
+    start_ticker(3, Duration::from_millis(10)).unwrap();
+    let ticks: Vec<_> = (0..3)
+        .map(|_| events.recv_timeout(Duration::from_secs(5)).unwrap())
+        .filter(|event| event.topic == "tick")
+        .map(|event| event.payload)
+        .collect();
+    assert_eq!(ticks, [b"1", b"2", b"3"]);
+}
+
+#[test]
+fn test_streaming_results() {
+    use rust_go_ffi::counter_stream;
+
+    if !is_dll_available() {
+        println!("Skipping stream test as DLL is not available");
+        return;
+    }
+    let chunks: Vec<_> = counter_stream(1000, 64)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|chunk| chunk.len() <= 64));
+    let text = String::from_utf8(chunks.concat()).unwrap();
+    assert_eq!(text.lines().count(), 1000);
+    assert_eq!(text.lines().last(), Some("999"));
+
+    // Dropping a stream early closes it.
+    let mut stream = counter_stream(1000, 16).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+    drop(stream);
+
+    assert!(counter_stream(10, 0).is_err());
+}
+
+#[test]
+#[cfg(feature = "ring-transport")]
+fn test_ring_transport() {
+    use rust_go_ffi::ring_echo;
+
+    if !is_dll_available() {
+        println!("Skipping ring test as DLL is not available");
+        return;
+    }
+    let (mut tx, rx) = ring_echo(4096).unwrap();
+    let sender = std::thread::spawn(move || {
+        for i in 0..10_000 {
+            tx.push(format!("msg {}", i).as_bytes()).unwrap();
+        }
+    });
+    let received: Vec<_> = rx.collect();
+    sender.join().unwrap();
+    assert_eq!(received.len(), 10_000);
+    assert_eq!(received[42], b"MSG 42");
+}
+
 #[test]
 #[cfg(feature = "auto-install")]
 fn test_auto_installation() {
@@ -42,9 +441,188 @@ fn test_auto_installation() {
 
 #[test]
 fn test_error_handling() {
-    // Test with invalid numbers (if your Go function has bounds checking)
+    // Go adds in a wider int; the sum doesn't fit back into i32.
     match add_numbers(i32::MAX, 1) {
-        Ok(_) => println!("Operation succeeded (might be expected)"),
-        Err(e) => println!("Expected error occurred: {:?}", e),
+        Ok(sum) => panic!("Overflowing sum {} should not be returned", sum),
+        Err(rust_go_ffi::DllError::ValueOutOfRange(_)) => {}
+        Err(e) if !is_dll_available() => println!("Expected error occurred: {:?}", e),
+        Err(e) => panic!("Unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+#[cfg(feature = "api-v1")]
+fn test_compat_v1_paths() {
+    use rust_go_ffi::compat::v1;
+
+    // The v1 paths must keep resolving to the same functions as the crate root.
+    let path = v1::get_dll_path();
+    assert_eq!(path, rust_go_ffi::get_dll_path());
+
+    if v1::is_dll_available() {
+        assert_eq!(v1::add_numbers(2, 2).expect("Addition should work"), 4);
+    }
+
+    // Errors the original enum has no variant for keep their message.
+    let busy = rust_go_ffi::DllError::Busy { limit: 1 };
+    let message = busy.to_string();
+    match v1::DllError::from(busy) {
+        v1::DllError::InitializationError(m) => assert_eq!(m, message),
+        other => panic!("Unexpected v1 error: {:?}", other),
+    }
+    assert!(matches!(
+        v1::DllError::from(rust_go_ffi::DllError::NotFound),
+        v1::DllError::NotFound
+    ));
+}
+
+#[test]
+fn test_cancel_token() {
+    use rust_go_ffi::{sleep_with_cancel, CancelToken, DllError};
+    use std::time::{Duration, Instant};
+
+    if !is_dll_available() {
+        println!("Skipping cancellation test as DLL is not available");
+        return;
+    }
+    let token = CancelToken::new().unwrap();
+    let canceller = token.clone();
+    let started = Instant::now();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        canceller.cancel();
+    });
+    let result = sleep_with_cancel(Duration::from_secs(30), &token);
+    handle.join().unwrap();
+    assert!(matches!(result, Err(DllError::Cancelled { .. })));
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(token.is_cancelled());
+
+    let token = CancelToken::new().unwrap();
+    sleep_with_cancel(Duration::from_millis(1), &token).unwrap();
+}
+
+#[test]
+fn test_call_timeout() {
+    use rust_go_ffi::{
+        poisoned_reason, sleep_with_cancel, with_cancel_timeout, with_timeout, DllError,
+    };
+    use std::time::Duration;
+
+    if !is_dll_available() {
+        println!("Skipping timeout test as DLL is not available");
+        return;
+    }
+    assert_eq!(
+        with_timeout(Duration::from_secs(10), || add_numbers(2, 3)).unwrap(),
+        5
+    );
+    let result = with_cancel_timeout(Duration::from_millis(50), |token| {
+        sleep_with_cancel(Duration::from_secs(30), token)
+    });
+    assert!(matches!(
+        result,
+        Err(DllError::Timeout { export, .. }) if export == "SleepWithContext"
+    ));
+    assert_eq!(poisoned_reason(), None);
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn test_async_wrappers() {
+    use rust_go_ffi::{add_numbers_async, crc32_async, go_function_async};
+
+    if !is_dll_available() {
+        println!("Skipping async test as DLL is not available");
+        return;
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        assert_eq!(add_numbers_async(2, 3).await.unwrap(), 5);
+        go_function_async().await.unwrap();
+        assert_eq!(crc32_async(b"hello".to_vec()).await.unwrap(), 0x3610_a686);
+    });
+}
+
+#[test]
+fn test_session() {
+    use rust_go_ffi::types::Point;
+    use rust_go_ffi::{FfiSession, DEFAULT_VERSION};
+
+    if !is_dll_available() {
+        println!("Skipping session test as DLL is not available");
+        return;
+    }
+    let session = FfiSession::open(DEFAULT_VERSION).unwrap();
+    assert!(session.version().unwrap() >= DEFAULT_VERSION);
+    assert_eq!(session.add_numbers(2, 3).unwrap(), 5);
+    assert_eq!(
+        session.add_numbers_batch(&[(1, 1), (2, 2)]).unwrap(),
+        [2, 4]
+    );
+    let mut point = Point { x: 1, y: 2 };
+    session.translate_point(&mut point, 1, 1).unwrap();
+    assert_eq!(point, Point { x: 2, y: 3 });
+}
+
+#[test]
+fn test_builder() {
+    use rust_go_ffi::{DllError, FfiBuilder, UnloadPolicy};
+    use semver::VersionReq;
+
+    if !is_dll_available() {
+        println!("Skipping builder test as DLL is not available");
+        return;
+    }
+    let session = FfiBuilder::new()
+        .version_req(VersionReq::parse(">=0.1.0, <1.0.0").unwrap())
+        .unload_policy(UnloadPolicy::Leak)
+        .build()
+        .unwrap();
+    assert_eq!(session.add_numbers(2, 3).unwrap(), 5);
+    drop(session);
+
+    let result = FfiBuilder::new()
+        .version_req(VersionReq::parse(">=99.0.0").unwrap())
+        .build();
+    assert!(matches!(result, Err(DllError::UnsupportedVersion { .. })));
+}
+
+#[test]
+fn test_status() {
+    use rust_go_ffi::{dll_info, status, FfiSession, DEFAULT_VERSION};
+
+    if !is_dll_available() {
+        println!("Skipping status test as DLL is not available");
+        return;
+    }
+    // Keeps sessions other tests drop from cleaning up underneath this one.
+    let session = FfiSession::open(DEFAULT_VERSION).unwrap();
+    session.add_numbers(1, 2).unwrap();
+    let status = status();
+    assert!(status.initialized);
+    assert!(status.initialized_at.is_some());
+    assert!(status.total_calls > 0);
+
+    let info = dll_info().unwrap();
+    assert!(info.size > 0);
+    assert_eq!(info.version, Some(DEFAULT_VERSION));
+}
+
+#[test]
+fn test_health_check() {
+    use rust_go_ffi::{health_check, FfiSession, DEFAULT_VERSION};
+    use std::time::Duration;
+
+    if !is_dll_available() {
+        println!("Skipping health check test as DLL is not available");
+        return;
     }
+    let _session = FfiSession::open(DEFAULT_VERSION).unwrap();
+    let report = health_check(Duration::from_secs(5)).unwrap();
+    assert!(report.is_ready());
+    assert_eq!(report.version, DEFAULT_VERSION);
+    assert!(report.go_runtime.starts_with("go"));
 }